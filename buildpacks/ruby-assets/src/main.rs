@@ -0,0 +1,258 @@
+//! Compiles application assets after `heroku/ruby` has installed Ruby and gems
+//!
+//! This buildpack requires `ruby_assets` in the build plan. When it's part of a build,
+//! `heroku/ruby` skips asset compilation and leaves it to this buildpack. Without it,
+//! `heroku/ruby` compiles assets itself.
+use bullet_stream::state::{Bullet, SubBullet};
+use bullet_stream::{style, Print};
+use commons::assets::{self, AssetPipeline};
+use commons::cache::CacheError;
+use commons::gemfile_lock::locked_gems;
+use commons::rake_task_detect::RakeDetect;
+use fs_err::PathExt;
+use fun_run::{CmdError, CommandWithName};
+use indoc::formatdoc;
+use libcnb::build::{BuildContext, BuildResult, BuildResultBuilder};
+use libcnb::data::build_plan::BuildPlanBuilder;
+use libcnb::detect::{DetectContext, DetectResult, DetectResultBuilder};
+use libcnb::generic::{GenericMetadata, GenericPlatform};
+use libcnb::{buildpack_main, Buildpack, Env, Platform};
+use std::io::{stdout, Stdout};
+use std::process::Command;
+
+struct RubyAssetsBuildpack;
+
+#[derive(Debug, thiserror::Error)]
+enum RubyAssetsBuildpackError {
+    #[error("Cannot read {0}")]
+    DetectError(std::io::Error),
+
+    #[error("Error detecting rake tasks: {0}")]
+    RakeDetectError(CmdError),
+
+    #[error("Error compiling assets: {0}")]
+    RakeAssetsPrecompileFailed(CmdError),
+
+    #[error("Error caching assets: {0}")]
+    InAppDirCacheError(CacheError),
+}
+
+impl From<RubyAssetsBuildpackError> for libcnb::Error<RubyAssetsBuildpackError> {
+    fn from(error: RubyAssetsBuildpackError) -> Self {
+        Self::BuildpackError(error)
+    }
+}
+
+impl Buildpack for RubyAssetsBuildpack {
+    type Platform = GenericPlatform;
+    type Metadata = GenericMetadata;
+    type Error = RubyAssetsBuildpackError;
+
+    fn detect(&self, context: DetectContext<Self>) -> libcnb::Result<DetectResult, Self::Error> {
+        let has_file = |name: &str| {
+            context
+                .app_dir
+                .join(name)
+                .fs_err_try_exists()
+                .map_err(RubyAssetsBuildpackError::DetectError)
+        };
+
+        let mut has_rakefile = false;
+        for name in RAKEFILES {
+            has_rakefile = has_rakefile || has_file(name)?;
+        }
+
+        if has_rakefile && has_file("Gemfile.lock")? {
+            DetectResultBuilder::pass()
+                .build_plan(
+                    BuildPlanBuilder::new()
+                        .requires("ruby")
+                        .requires("ruby_assets")
+                        .build(),
+                )
+                .build()
+        } else {
+            DetectResultBuilder::fail().build()
+        }
+    }
+
+    fn build(&self, context: BuildContext<Self>) -> libcnb::Result<BuildResult, Self::Error> {
+        let build_output = Print::new(stdout()).h2("Heroku Ruby Assets Buildpack");
+
+        // Ruby, Bundler, and gems are put on the environment by `heroku/ruby`
+        let mut env = Env::from_current();
+        for (key, value) in context.platform.env() {
+            env.insert(key, value);
+        }
+
+        let mut cmd = Command::new("rake");
+        cmd.args(["-P", "--trace"]).env_clear().envs(&env);
+        let timer = build_output
+            .bullet("Rake assets install")
+            .start_timer(format!("Running {}", style::command(cmd.name())));
+        let output = cmd
+            .named_output()
+            .map_err(|error| fun_run::map_which_problem(error, &mut cmd, env.get("PATH").cloned()))
+            .map_err(RubyAssetsBuildpackError::RakeDetectError)?;
+        let tasks = RakeDetect::from_rake_output(&output.stdout_lossy());
+
+        let bullet = timer.done();
+        let bullet = if tasks.has_task("assets:precompile") {
+            rake_assets_install(bullet, &context, &env, &tasks)?
+        } else {
+            bullet.sub_bullet(format!(
+                "Skipping (task {} not found via {})",
+                style::value("assets:precompile"),
+                style::value("rake -P")
+            ))
+        };
+        bullet.done().done();
+
+        BuildResultBuilder::new().build()
+    }
+
+    fn on_error(&self, error: libcnb::Error<Self::Error>) {
+        on_error(Print::new(stdout()).without_header(), error);
+    }
+}
+
+/// Rakefile names that rake will load, in the order rake looks for them
+const RAKEFILES: [&str; 4] = ["Rakefile", "rakefile", "Rakefile.rb", "rakefile.rb"];
+
+/// Compiles assets with the same pipeline detection, caches, and manifest check as `heroku/ruby`
+fn rake_assets_install(
+    mut bullet: Print<SubBullet<Stdout>>,
+    context: &BuildContext<RubyAssetsBuildpack>,
+    env: &Env,
+    tasks: &RakeDetect,
+) -> Result<Print<SubBullet<Stdout>>, RubyAssetsBuildpackError> {
+    let gems = fs_err::read_to_string(context.app_dir.join("Gemfile.lock"))
+        .map(|contents| locked_gems(&contents))
+        .map_err(RubyAssetsBuildpackError::DetectError)?;
+    let pipeline = AssetPipeline::detect(|gem| gems.contains_key(gem));
+    bullet = AssetPipeline::print(pipeline, bullet);
+    let build_caches = pipeline
+        .unwrap_or(AssetPipeline::Sprockets)
+        .build_caches(&context.app_dir);
+
+    let mut cmd = Command::new("rake");
+    cmd.env_clear().envs(env);
+    let cache_config = if tasks.has_task("assets:clean") {
+        cmd.args(["assets:precompile", "assets:clean", "--trace"]);
+        assets::output_cache_configs(&context.app_dir, |gem| gems.contains_key(gem))
+    } else {
+        cmd.args(["assets:precompile", "--trace"]);
+        bullet = bullet.sub_bullet(format!(
+            "Compiling assets without {} cache (task {} not found)",
+            style::value("public/assets"),
+            style::value("assets:clean")
+        ));
+        // Without `assets:clean` a cached `public/assets` would grow forever
+        Vec::new()
+    };
+    let mut cmd = cmd.named_fn(|cmd| fun_run::display_with_env_keys(cmd, env, ["RAILS_ENV"]));
+
+    assets::with_caches(
+        bullet,
+        context,
+        cache_config.into_iter().chain(build_caches),
+        RubyAssetsBuildpackError::InAppDirCacheError,
+        |mut bullet| {
+            bullet
+                .stream_with(
+                    format!("Running {}", style::command(cmd.name())),
+                    |stdout, stderr| cmd.stream_output(stdout, stderr),
+                )
+                .map_err(|error| {
+                    fun_run::map_which_problem(error, cmd.mut_cmd(), env.get("PATH").cloned())
+                })
+                .map_err(RubyAssetsBuildpackError::RakeAssetsPrecompileFailed)?;
+
+            let public_assets = context.app_dir.join("public").join("assets");
+            Ok(
+                match pipeline.map(|pipeline| pipeline.manifest(&public_assets)) {
+                    Some(Ok(manifest)) => manifest.print(bullet, &context.app_dir),
+                    Some(Err(error)) => bullet.sub_bullet(format!(
+                        "{} {error}, pages that reference assets may render with broken links",
+                        style::important("WARNING")
+                    )),
+                    None => bullet,
+                },
+            )
+        },
+    )
+}
+
+fn on_error(output: Print<Bullet<Stdout>>, error: libcnb::Error<RubyAssetsBuildpackError>) {
+    let debug_info = style::important("Debug info");
+    match error {
+        libcnb::Error::BuildpackError(error) => {
+            let (title, detail) = match &error {
+                RubyAssetsBuildpackError::DetectError(_) => (
+                    "Error detecting application files",
+                    "A file needed to detect the application could not be read.",
+                ),
+                RubyAssetsBuildpackError::RakeDetectError(_) => (
+                    "Error detecting rake tasks",
+                    "The `rake -P` command failed. Ensure it succeeds locally with the \
+                     production environment.",
+                ),
+                RubyAssetsBuildpackError::RakeAssetsPrecompileFailed(_) => (
+                    "Error compiling assets",
+                    "An error occured while compiling assets via rake command.",
+                ),
+                RubyAssetsBuildpackError::InAppDirCacheError(_) => (
+                    "Error caching assets",
+                    "An error occured while loading or storing the asset cache.",
+                ),
+            };
+            output
+                .bullet(&debug_info)
+                .sub_bullet(error.to_string())
+                .done()
+                .error(formatdoc! {"
+                    {title}
+
+                    {detail}
+
+                    Use the information above to debug further.
+                "});
+        }
+        error => {
+            output
+                .bullet(&debug_info)
+                .sub_bullet(error.to_string())
+                .done()
+                .error(formatdoc! {"
+                    Error: heroku/ruby-assets internal buildpack error
+
+                    The framework used by this buildpack encountered an unexpected error.
+                "});
+        }
+    }
+}
+
+buildpack_main!(RubyAssetsBuildpack);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rake_detect_from_rake_output() {
+        let tasks = RakeDetect::from_rake_output(indoc::indoc! {"
+            rake about
+                environment
+            rake assets:clean
+                environment
+            rake assets:precompile
+                environment
+                yarn:install
+        "});
+
+        assert!(tasks.has_task("assets:precompile"));
+        assert!(tasks.has_task("assets:clean"));
+        assert!(!tasks.has_task("environment"));
+        assert!(!tasks.has_task("assets"));
+    }
+}
@@ -0,0 +1,216 @@
+//! Heroku Ruby Doctor
+//!
+//! Prints information about the Ruby environment of a running container. When
+//! `HEROKU_RUBY_FEATURE_DOCTOR=1` is set the buildpack installs it on the `PATH` as
+//! `heroku-ruby-doctor` so it can be invoked when debugging an application:
+//!
+//! ```shell
+//! $ heroku-ruby-doctor
+//! ```
+//!
+//! It does not modify anything, it only reports versions, environment variables, the Ruby
+//! load path, and the order in which executables are resolved from the `PATH`.
+
+// Required due to: https://github.com/rust-lang/rust/issues/95513
+#![allow(unused_crate_dependencies)]
+
+use std::ffi::OsStr;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Environment variables that affect how Ruby and bundler load code
+const ENV_KEYS: &[&str] = &[
+    "BUNDLE_BIN",
+    "BUNDLE_GEMFILE",
+    "BUNDLE_PATH",
+    "BUNDLE_WITHOUT",
+    "GEM_HOME",
+    "GEM_PATH",
+    "RACK_ENV",
+    "RAILS_ENV",
+];
+
+/// Executables that are commonly resolved from the wrong location
+const EXECUTABLES: &[&str] = &["ruby", "gem", "bundle", "rake", "rails"];
+
+const RAILS_VERSION_SCRIPT: &str = r#"
+begin
+  require "bundler/setup"
+  spec = Gem.loaded_specs["railties"]
+  puts spec ? "Rails #{spec.version}" : "Rails not found in bundle"
+rescue Exception => e
+  puts "Could not load bundle: #{e.class}: #{e.message}"
+end
+"#;
+
+fn main() {
+    println!("## Heroku Ruby Doctor");
+    println!();
+
+    println!("### Versions");
+    print_command(Command::new("ruby").arg("--version"));
+    print_command(Command::new("bundle").arg("--version"));
+    print_command(Command::new("ruby").args(["-e", RAILS_VERSION_SCRIPT]));
+    println!();
+
+    println!("### Environment");
+    for key in ENV_KEYS {
+        match std::env::var_os(key) {
+            Some(value) => println!("- {key}={}", value.to_string_lossy()),
+            None => println!("- {key} (not set)"),
+        }
+    }
+    println!();
+
+    println!("### GEM_PATH health");
+    let gem_path = std::env::var_os("GEM_PATH").unwrap_or_default();
+    print_path_health(std::env::split_paths(&gem_path));
+    println!();
+
+    println!("### Load path health");
+    match Command::new("ruby")
+        .args(["-e", "puts $LOAD_PATH"])
+        .output()
+    {
+        Ok(output) if output.status.success() => {
+            print_path_health(
+                String::from_utf8_lossy(&output.stdout)
+                    .lines()
+                    .map(PathBuf::from),
+            );
+        }
+        Ok(output) => println!(
+            "- Could not read load path: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ),
+        Err(error) => println!("- Could not read load path: {error}"),
+    }
+    println!();
+
+    println!("### Executable resolution order");
+    let path = std::env::var_os("PATH").unwrap_or_default();
+    for name in EXECUTABLES {
+        let found = which_all(name, &path);
+        if found.is_empty() {
+            println!("- {name} (not found on PATH)");
+        } else {
+            println!("- {name}");
+            for (index, path) in found.iter().enumerate() {
+                println!("  {}. {}", index + 1, path.display());
+            }
+        }
+    }
+}
+
+/// Runs a command and prints its name along with the output
+fn print_command(cmd: &mut Command) {
+    let name = fun_run::display(cmd);
+    match cmd.output() {
+        Ok(output) => {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let text = if stdout.trim().is_empty() {
+                stderr.trim().to_string()
+            } else {
+                stdout.trim().to_string()
+            };
+            println!("- `{name}`: {text}");
+        }
+        Err(error) => println!("- `{name}` could not be run: {error}"),
+    }
+}
+
+/// Prints each path and notes the ones that do not exist on disk
+fn print_path_health(paths: impl IntoIterator<Item = PathBuf>) {
+    let paths = paths.into_iter().collect::<Vec<_>>();
+    if paths.is_empty() {
+        println!("- (empty)");
+    }
+
+    for path in &paths {
+        if path.exists() {
+            println!("- {}", path.display());
+        } else {
+            println!("- {} (missing)", path.display());
+        }
+    }
+
+    let missing = missing_paths(&paths);
+    if !missing.is_empty() {
+        println!(
+            "- WARNING: {} of {} paths do not exist",
+            missing.len(),
+            paths.len()
+        );
+    }
+}
+
+fn missing_paths(paths: &[PathBuf]) -> Vec<&PathBuf> {
+    paths.iter().filter(|path| !path.exists()).collect()
+}
+
+/// Returns every executable file with the given name in `PATH` order
+///
+/// The first entry is what a shell would execute.
+fn which_all(name: &str, path: &OsStr) -> Vec<PathBuf> {
+    std::env::split_paths(path)
+        .map(|dir| dir.join(name))
+        .filter(|candidate| is_executable(candidate))
+        .collect()
+}
+
+fn is_executable(path: &Path) -> bool {
+    path.metadata()
+        .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::ffi::OsString;
+
+    fn touch_executable(path: &Path) {
+        fs_err::create_dir_all(path.parent().unwrap()).unwrap();
+        fs_err::write(path, "").unwrap();
+        let mut perms = fs_err::metadata(path).unwrap().permissions();
+        perms.set_mode(0o755);
+        fs_err::set_permissions(path, perms).unwrap();
+    }
+
+    #[test]
+    fn which_all_preserves_path_order() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let first = tmpdir.path().join("first");
+        let second = tmpdir.path().join("second");
+        let empty = tmpdir.path().join("empty");
+        touch_executable(&second.join("rake"));
+        touch_executable(&first.join("rake"));
+
+        let path = std::env::join_paths([&first, &empty, &second]).unwrap();
+
+        assert_eq!(
+            which_all("rake", &path),
+            vec![first.join("rake"), second.join("rake")]
+        );
+        assert!(which_all("rails", &path).is_empty());
+        assert!(which_all("rake", &OsString::new()).is_empty());
+    }
+
+    #[test]
+    fn which_all_ignores_non_executable_files() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        fs_err::write(tmpdir.path().join("ruby"), "").unwrap();
+
+        assert!(which_all("ruby", tmpdir.path().as_os_str()).is_empty());
+    }
+
+    #[test]
+    fn missing_paths_reports_only_missing() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let exists = tmpdir.path().to_path_buf();
+        let missing = tmpdir.path().join("missing");
+
+        assert_eq!(missing_paths(&[exists, missing.clone()]), vec![&missing]);
+    }
+}
@@ -0,0 +1,321 @@
+//! Read requirements that other buildpacks place in the build plan
+//!
+//! A buildpack that runs before this one (for example a meta-buildpack) can require `ruby`
+//! with a `version` metadata entry:
+//!
+//! ```toml
+//! [[requires]]
+//! name = "ruby"
+//!
+//! [requires.metadata]
+//! version = "3.3.5"
+//! ```
+//!
+//! These versions are treated as additional constraints on top of the `Gemfile.lock`.
+//!
+//! The buildpack also provides `ruby_assets`. When a later buildpack requires it, that
+//! buildpack compiles the application's assets and this buildpack skips doing so.
+use crate::ruby_version_file::RubyVersionPin;
+use commons::gemfile_lock::{GemfileLock, ResolvedRubyVersion, RubyVersion};
+use commons::requirements;
+use libcnb::data::buildpack_plan::BuildpackPlan;
+
+/// Build plan entry that delegates asset compilation to another buildpack
+pub(crate) const RUBY_ASSETS_PLAN_NAME: &str = "ruby_assets";
+
+/// Returns true when a later buildpack requires `ruby_assets`
+pub(crate) fn assets_delegated(plan: &BuildpackPlan) -> bool {
+    plan.entries
+        .iter()
+        .any(|entry| entry.name == RUBY_ASSETS_PLAN_NAME)
+}
+
+/// Returns all `ruby` version requirements found in the buildpack plan
+pub(crate) fn ruby_version_requirements(plan: &BuildpackPlan) -> Vec<String> {
+    plan.entries
+        .iter()
+        .filter(|entry| entry.name == "ruby")
+        .filter_map(|entry| entry.metadata.get("version"))
+        .filter_map(toml::Value::as_str)
+        .map(|version| version.trim().to_string())
+        .filter(|version| !version.is_empty())
+        .collect()
+}
+
+/// Resolve the Ruby version from the `Gemfile.lock`, a version manager file, and any build plan
+/// requirements
+///
+/// Returns the version along with a human readable source of where it came from.
+///
+/// - When the `Gemfile.lock` has an explicit version, every requirement must match it.
+/// - When a version manager file (such as `.ruby-version`) pins a version, every requirement
+///   must match it.
+/// - When the `Gemfile.lock` does not specify a version, the most specific plain version
+///   requirement is used and every other requirement must match it.
+/// - Otherwise the default version is used, and every requirement must match it.
+///
+/// A requirement matches a version if it is equal to it, or if it's a prefix of whole
+/// version segments i.e. `3.3` matches `3.3.5` but not `3.30.0`. Operators such as
+/// `>= 3.2` and `~> 3.3.0` are also supported, see [`commons::requirements`].
+///
+/// # Errors
+///
+/// Returns an error when the requirements cannot all be satisfied.
+pub(crate) fn resolve_ruby(
+    gemfile_lock: &GemfileLock,
+    pin: Option<&RubyVersionPin>,
+    requirements: &[String],
+    default: &str,
+) -> Result<(ResolvedRubyVersion, String), BuildPlanError> {
+    match &gemfile_lock.ruby_version {
+        RubyVersion::Explicit(version) => {
+            if let Some(requested) = requirements
+                .iter()
+                .find(|requested| !version_matches(version, requested))
+            {
+                Err(BuildPlanError::RubyVersionConflict {
+                    version: version.clone(),
+                    origin: gemfile_lock.ruby_source(),
+                    requested: requested.clone(),
+                })
+            } else {
+                Ok((
+                    gemfile_lock.resolve_ruby(default),
+                    gemfile_lock.ruby_source(),
+                ))
+            }
+        }
+        RubyVersion::Default => {
+            if let Some(pin) = pin {
+                if let Some(requested) = requirements
+                    .iter()
+                    .find(|requested| !version_matches(&pin.version, requested))
+                {
+                    Err(BuildPlanError::RubyVersionConflict {
+                        version: pin.version.clone(),
+                        origin: String::from(pin.source),
+                        requested: requested.clone(),
+                    })
+                } else {
+                    Ok((
+                        ResolvedRubyVersion(pin.version.clone()),
+                        String::from(pin.source),
+                    ))
+                }
+            } else {
+                // Operator requirements such as `>= 3.2` can't be installed, only checked
+                let (version, origin) = match requirements
+                    .iter()
+                    .filter(|requested| requested.starts_with(|c: char| c.is_ascii_digit()))
+                    .filter(|requested| !requested.contains(','))
+                    .max_by_key(|requested| requested.len())
+                {
+                    Some(version) => (version.clone(), String::from(BUILD_PLAN_SOURCE)),
+                    None => (
+                        gemfile_lock.resolve_ruby(default).0,
+                        gemfile_lock.ruby_source(),
+                    ),
+                };
+                if let Some(requested) = requirements
+                    .iter()
+                    .find(|requested| !version_matches(&version, requested))
+                {
+                    Err(BuildPlanError::RubyVersionConflict {
+                        version,
+                        origin,
+                        requested: requested.clone(),
+                    })
+                } else {
+                    Ok((ResolvedRubyVersion(version), origin))
+                }
+            }
+        }
+    }
+}
+
+const BUILD_PLAN_SOURCE: &str = "buildpack plan";
+
+fn version_matches(version: &str, requested: &str) -> bool {
+    requirements::parse_requirements(requested)
+        .is_ok_and(|requirements| requirements::matches_all(&requirements, version))
+}
+
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum BuildPlanError {
+    #[error("Ruby version `{version}` from `{origin}` does not satisfy the `ruby` version `{requested}` required by another buildpack")]
+    RubyVersionConflict {
+        version: String,
+        origin: String,
+        requested: String,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::str::FromStr;
+    use libcnb::data::buildpack_plan::Entry;
+
+    fn lockfile(ruby: &str) -> GemfileLock {
+        GemfileLock::from_str(&format!(
+            r"
+RUBY VERSION
+   ruby {ruby}
+
+BUNDLED WITH
+   2.3.4
+"
+        ))
+        .unwrap()
+    }
+
+    fn entry(name: &str, version: Option<&str>) -> Entry {
+        let mut metadata = toml::Table::new();
+        if let Some(version) = version {
+            metadata.insert(
+                String::from("version"),
+                toml::Value::String(version.to_string()),
+            );
+        }
+        Entry {
+            name: name.to_string(),
+            metadata,
+        }
+    }
+
+    #[test]
+    fn test_ruby_version_requirements() {
+        let plan = BuildpackPlan {
+            entries: vec![
+                entry("ruby", Some("3.3.5")),
+                entry("ruby", None),
+                entry("node", Some("20")),
+            ],
+        };
+
+        assert_eq!(
+            ruby_version_requirements(&plan),
+            vec![String::from("3.3.5")]
+        );
+    }
+
+    #[test]
+    fn test_assets_delegated() {
+        assert!(!assets_delegated(&BuildpackPlan {
+            entries: vec![entry("ruby", None)],
+        }));
+        assert!(assets_delegated(&BuildpackPlan {
+            entries: vec![entry("ruby", None), entry("ruby_assets", None)],
+        }));
+    }
+
+    #[test]
+    fn test_no_requirements_uses_lockfile() {
+        let (version, source) = resolve_ruby(&lockfile("3.3.5"), None, &[], "3.2.6").unwrap();
+        assert_eq!(version, ResolvedRubyVersion(String::from("3.3.5")));
+        assert_eq!(source, "Gemfile.lock");
+
+        let (version, source) =
+            resolve_ruby(&GemfileLock::from_str("").unwrap(), None, &[], "3.2.6").unwrap();
+        assert_eq!(version, ResolvedRubyVersion(String::from("3.2.6")));
+        assert_eq!(source, "default");
+    }
+
+    #[test]
+    fn test_requirement_matches_lockfile() {
+        let (version, source) =
+            resolve_ruby(&lockfile("3.3.5"), None, &[String::from("3.3")], "3.2.6").unwrap();
+        assert_eq!(version, ResolvedRubyVersion(String::from("3.3.5")));
+        assert_eq!(source, "Gemfile.lock");
+    }
+
+    #[test]
+    fn test_requirement_conflicts_with_lockfile() {
+        let result = resolve_ruby(&lockfile("3.30.0"), None, &[String::from("3.3")], "3.2.6");
+        assert!(matches!(
+            result,
+            Err(BuildPlanError::RubyVersionConflict { .. })
+        ));
+    }
+
+    #[test]
+    fn test_requirement_replaces_default() {
+        let (version, source) = resolve_ruby(
+            &GemfileLock::from_str("").unwrap(),
+            None,
+            &[String::from("3.3"), String::from("3.3.5")],
+            "3.2.6",
+        )
+        .unwrap();
+        assert_eq!(version, ResolvedRubyVersion(String::from("3.3.5")));
+        assert_eq!(source, "buildpack plan");
+
+        let result = resolve_ruby(
+            &GemfileLock::from_str("").unwrap(),
+            None,
+            &[String::from("3.2"), String::from("3.3.5")],
+            "3.2.6",
+        );
+        assert!(matches!(
+            result,
+            Err(BuildPlanError::RubyVersionConflict { .. })
+        ));
+    }
+
+    #[test]
+    fn test_operator_requirement_checks_default() {
+        let (version, source) = resolve_ruby(
+            &GemfileLock::from_str("").unwrap(),
+            None,
+            &[String::from(">= 3.2")],
+            "3.2.6",
+        )
+        .unwrap();
+        assert_eq!(version, ResolvedRubyVersion(String::from("3.2.6")));
+        assert_eq!(source, "default");
+
+        let result = resolve_ruby(
+            &GemfileLock::from_str("").unwrap(),
+            None,
+            &[String::from("~> 3.3.0")],
+            "3.2.6",
+        );
+        assert!(matches!(
+            result,
+            Err(BuildPlanError::RubyVersionConflict { .. })
+        ));
+    }
+
+    #[test]
+    fn test_version_file_pin() {
+        let pin = RubyVersionPin {
+            version: String::from("3.3.5"),
+            source: ".tool-versions",
+        };
+
+        let (version, source) = resolve_ruby(&lockfile("3.2.6"), Some(&pin), &[], "3.1.6").unwrap();
+        assert_eq!(version, ResolvedRubyVersion(String::from("3.2.6")));
+        assert_eq!(source, "Gemfile.lock");
+
+        let (version, source) = resolve_ruby(
+            &GemfileLock::from_str("").unwrap(),
+            Some(&pin),
+            &[String::from("3.3")],
+            "3.1.6",
+        )
+        .unwrap();
+        assert_eq!(version, ResolvedRubyVersion(String::from("3.3.5")));
+        assert_eq!(source, ".tool-versions");
+
+        let result = resolve_ruby(
+            &GemfileLock::from_str("").unwrap(),
+            Some(&pin),
+            &[String::from("3.2")],
+            "3.1.6",
+        );
+        assert!(matches!(
+            result,
+            Err(BuildPlanError::RubyVersionConflict { .. })
+        ));
+    }
+}
@@ -0,0 +1,241 @@
+//! Select which steps of the build run
+//!
+//! The build is an ordered list of named steps. Steps can be skipped with a comma separated
+//! list in `HEROKU_RUBY_SKIP_STEPS`, or with `skip_steps` in `project.toml`:
+//!
+//! ```toml
+//! [com.heroku.buildpacks.ruby]
+//! skip_steps = ["assets"]
+//! ```
+//!
+//! The environment variable takes precedence over `project.toml`. A step can't be skipped
+//! while a step that depends on it still runs, for example skipping `bundler` also requires
+//! skipping `bundle_install`, `gem_detect`, and `assets`.
+use crate::project_toml;
+use bullet_stream::style;
+use libcnb::Env;
+use std::fmt;
+use std::path::Path;
+
+pub(crate) const SKIP_STEPS_ENV_KEY: &str = "HEROKU_RUBY_SKIP_STEPS";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum BuildStep {
+    RubyInstall,
+    Bundler,
+    BundleInstall,
+    GemDetect,
+    Assets,
+}
+
+impl BuildStep {
+    /// Every step, in the order they run
+    pub(crate) const ALL: [BuildStep; 5] = [
+        BuildStep::RubyInstall,
+        BuildStep::Bundler,
+        BuildStep::BundleInstall,
+        BuildStep::GemDetect,
+        BuildStep::Assets,
+    ];
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            BuildStep::RubyInstall => "ruby_install",
+            BuildStep::Bundler => "bundler",
+            BuildStep::BundleInstall => "bundle_install",
+            BuildStep::GemDetect => "gem_detect",
+            BuildStep::Assets => "assets",
+        }
+    }
+
+    /// Steps that must run for this step to work
+    fn requires(self) -> &'static [BuildStep] {
+        match self {
+            BuildStep::RubyInstall => &[],
+            BuildStep::Bundler => &[BuildStep::RubyInstall],
+            BuildStep::BundleInstall => &[BuildStep::Bundler],
+            BuildStep::GemDetect => &[BuildStep::BundleInstall],
+            BuildStep::Assets => &[BuildStep::GemDetect],
+        }
+    }
+}
+
+impl fmt::Display for BuildStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub(crate) enum BuildStepsError {
+    #[error("Unknown build step `{name}` in {origin}, valid steps are: {valid}")]
+    UnknownStep {
+        name: String,
+        origin: String,
+        valid: String,
+    },
+
+    #[error("Cannot skip `{skipped}` from {origin} because `{required_by}` requires it")]
+    MissingPrerequisite {
+        skipped: BuildStep,
+        required_by: BuildStep,
+        origin: String,
+    },
+
+    #[error("Cannot parse project.toml: {0}")]
+    ProjectToml(String),
+}
+
+/// The steps selected to run
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct BuildSteps {
+    skip: Vec<BuildStep>,
+    origin: String,
+}
+
+impl BuildSteps {
+    /// Reads `HEROKU_RUBY_SKIP_STEPS` from the platform env, or `skip_steps` from `project.toml`
+    ///
+    /// # Errors
+    ///
+    /// Errors when a step name is unknown, when a skipped step is required by a step that
+    /// still runs, or when `project.toml` cannot be read.
+    pub(crate) fn from_env_or_project_toml(
+        platform_env: &Env,
+        app_dir: &Path,
+    ) -> Result<Self, BuildStepsError> {
+        if let Some(value) = platform_env.get(SKIP_STEPS_ENV_KEY) {
+            let value = value.to_string_lossy();
+            return Self::from_names(value.split(','), SKIP_STEPS_ENV_KEY);
+        }
+
+        project_toml::ruby_setting::<Vec<String>>(app_dir, "skip_steps")
+            .map_err(|error| BuildStepsError::ProjectToml(error.to_string()))?
+            .map_or_else(
+                || Ok(Self::default()),
+                |names| Self::from_names(names.iter().map(String::as_str), "project.toml"),
+            )
+    }
+
+    fn from_names<'a>(
+        names: impl IntoIterator<Item = &'a str>,
+        origin: &str,
+    ) -> Result<Self, BuildStepsError> {
+        let mut skip = Vec::new();
+        for name in names.into_iter().map(str::trim).filter(|n| !n.is_empty()) {
+            let step = BuildStep::ALL
+                .into_iter()
+                .find(|step| step.name() == name)
+                .ok_or_else(|| BuildStepsError::UnknownStep {
+                    name: name.to_string(),
+                    origin: origin.to_string(),
+                    valid: BuildStep::ALL.map(BuildStep::name).join(", "),
+                })?;
+            if !skip.contains(&step) {
+                skip.push(step);
+            }
+        }
+
+        for step in BuildStep::ALL
+            .into_iter()
+            .filter(|step| !skip.contains(step))
+        {
+            if let Some(skipped) = step.requires().iter().find(|req| skip.contains(req)) {
+                return Err(BuildStepsError::MissingPrerequisite {
+                    skipped: *skipped,
+                    required_by: step,
+                    origin: origin.to_string(),
+                });
+            }
+        }
+
+        Ok(Self {
+            skip,
+            origin: origin.to_string(),
+        })
+    }
+
+    pub(crate) fn enabled(&self, step: BuildStep) -> bool {
+        !self.skip.contains(&step)
+    }
+
+    /// Explains why a step did not run
+    pub(crate) fn skip_reason(&self, step: BuildStep) -> String {
+        format!(
+            "Skipping ({} listed in {})",
+            style::value(step.name()),
+            style::value(&self.origin)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_env() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut env = Env::new();
+        assert_eq!(
+            BuildSteps::from_env_or_project_toml(&env, dir.path()).unwrap(),
+            BuildSteps::default()
+        );
+
+        env.insert(SKIP_STEPS_ENV_KEY, " gem_detect, assets,");
+        let steps = BuildSteps::from_env_or_project_toml(&env, dir.path()).unwrap();
+        assert!(steps.enabled(BuildStep::BundleInstall));
+        assert!(!steps.enabled(BuildStep::GemDetect));
+        assert!(!steps.enabled(BuildStep::Assets));
+    }
+
+    #[test]
+    fn test_from_project_toml() {
+        let dir = tempfile::tempdir().unwrap();
+        fs_err::write(
+            dir.path().join("project.toml"),
+            "[com.heroku.buildpacks.ruby]\nskip_steps = [\"assets\"]\n",
+        )
+        .unwrap();
+
+        let steps = BuildSteps::from_env_or_project_toml(&Env::new(), dir.path()).unwrap();
+        assert!(steps.enabled(BuildStep::GemDetect));
+        assert!(!steps.enabled(BuildStep::Assets));
+
+        let mut env = Env::new();
+        env.insert(SKIP_STEPS_ENV_KEY, "");
+        let steps = BuildSteps::from_env_or_project_toml(&env, dir.path()).unwrap();
+        assert!(steps.enabled(BuildStep::Assets));
+
+        fs_err::write(
+            dir.path().join("project.toml"),
+            "[com.heroku.buildpacks.ruby]\nskip_steps = [\"assets\", 1]\n",
+        )
+        .unwrap();
+        assert!(matches!(
+            BuildSteps::from_env_or_project_toml(&Env::new(), dir.path()),
+            Err(BuildStepsError::ProjectToml(_))
+        ));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(matches!(
+            BuildSteps::from_names(["compile"], SKIP_STEPS_ENV_KEY),
+            Err(BuildStepsError::UnknownStep { .. })
+        ));
+        assert_eq!(
+            BuildSteps::from_names(["bundler"], SKIP_STEPS_ENV_KEY),
+            Err(BuildStepsError::MissingPrerequisite {
+                skipped: BuildStep::Bundler,
+                required_by: BuildStep::BundleInstall,
+                origin: SKIP_STEPS_ENV_KEY.to_string(),
+            })
+        );
+        assert!(BuildSteps::from_names(
+            ["bundler", "bundle_install", "gem_detect", "assets"],
+            SKIP_STEPS_ENV_KEY
+        )
+        .is_ok());
+    }
+}
@@ -0,0 +1,170 @@
+//! Attribute `bundle install` time to downloading gems or compiling native extensions
+//!
+//! Bundler prints a line before it starts each unit of work, for example:
+//!
+//! ```text
+//! Fetching nokogiri 1.16.0
+//! Installing nokogiri 1.16.0 with native extensions
+//! Installing rack 3.0.8
+//! ```
+//!
+//! The time between one line and the next is attributed to the work the first line announced.
+//! Bundler installs gems in parallel so the numbers are an approximation, but they're enough
+//! to tell whether a build is dominated by network time or by compiling native extensions.
+use crate::step_timings::format_duration;
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    Download,
+    Compile,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct BundlePhases {
+    download: Duration,
+    compile: Duration,
+    current: Option<(Phase, Instant)>,
+    partial_line: Vec<u8>,
+}
+
+impl BundlePhases {
+    /// Wraps a writer so every line written through it is observed
+    pub(crate) fn writer<W: Write>(&mut self, inner: W) -> PhaseWriter<'_, W> {
+        PhaseWriter {
+            inner,
+            phases: self,
+        }
+    }
+
+    /// Stops the phase that's currently running
+    pub(crate) fn finish(&mut self, now: Instant) {
+        self.observe_line("", now);
+    }
+
+    /// A short summary, returns `None` if nothing was downloaded or compiled
+    pub(crate) fn summary(&self) -> Option<String> {
+        if self.download.is_zero() && self.compile.is_zero() {
+            None
+        } else {
+            Some(format!(
+                "Approximate time downloading gems {download}, compiling native extensions {compile}",
+                download = format_duration(self.download),
+                compile = format_duration(self.compile),
+            ))
+        }
+    }
+
+    fn observe_line(&mut self, line: &str, now: Instant) {
+        if let Some((phase, started)) = self.current.take() {
+            let elapsed = now.duration_since(started);
+            match phase {
+                Phase::Download => self.download += elapsed,
+                Phase::Compile => self.compile += elapsed,
+            }
+        }
+        self.current = phase_for(line).map(|phase| (phase, now));
+    }
+
+    fn observe_bytes(&mut self, bytes: &[u8]) {
+        self.partial_line.extend_from_slice(bytes);
+        while let Some(index) = self.partial_line.iter().position(|byte| *byte == b'\n') {
+            let line = self.partial_line.drain(..=index).collect::<Vec<_>>();
+            self.observe_line(&String::from_utf8_lossy(&line), Instant::now());
+        }
+    }
+}
+
+fn phase_for(line: &str) -> Option<Phase> {
+    let line = line.trim();
+    if line.starts_with("Fetching ") {
+        Some(Phase::Download)
+    } else if line.starts_with("Installing ") && line.ends_with("with native extensions") {
+        Some(Phase::Compile)
+    } else {
+        None
+    }
+}
+
+pub(crate) struct PhaseWriter<'a, W> {
+    inner: W,
+    phases: &'a mut BundlePhases,
+}
+
+impl<W: Write> Write for PhaseWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.phases.observe_bytes(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_phase_for() {
+        assert_eq!(phase_for("Fetching rack 3.0.8"), Some(Phase::Download));
+        assert_eq!(
+            phase_for("Installing nokogiri 1.16.0 with native extensions\n"),
+            Some(Phase::Compile)
+        );
+        assert_eq!(phase_for("Installing rack 3.0.8"), None);
+        assert_eq!(phase_for("Bundle complete!"), None);
+    }
+
+    #[test]
+    fn test_time_is_attributed_to_previous_line() {
+        let start = Instant::now();
+        let mut phases = BundlePhases::default();
+
+        phases.observe_line("Fetching nokogiri 1.16.0", start);
+        phases.observe_line(
+            "Installing nokogiri 1.16.0 with native extensions",
+            start + Duration::from_secs(2),
+        );
+        phases.observe_line("Installing rack 3.0.8", start + Duration::from_secs(7));
+        phases.finish(start + Duration::from_secs(8));
+
+        assert_eq!(phases.download, Duration::from_secs(2));
+        assert_eq!(phases.compile, Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_writer_passes_through_and_splits_lines() {
+        let mut phases = BundlePhases::default();
+        let mut out = Vec::new();
+        {
+            let mut writer = phases.writer(&mut out);
+            writer.write_all(b"Fetch").unwrap();
+            writer.write_all(b"ing rack 3.0.8\nInst").unwrap();
+        }
+
+        assert_eq!(out, b"Fetching rack 3.0.8\nInst");
+        assert!(matches!(phases.current, Some((Phase::Download, _))));
+        assert_eq!(phases.partial_line, b"Inst");
+    }
+
+    #[test]
+    fn test_summary() {
+        assert_eq!(BundlePhases::default().summary(), None);
+
+        let phases = BundlePhases {
+            download: Duration::from_millis(1_500),
+            compile: Duration::from_secs(30),
+            ..BundlePhases::default()
+        };
+        assert_eq!(
+            phases.summary(),
+            Some(String::from(
+                "Approximate time downloading gems 1.50s, compiling native extensions 30.00s"
+            ))
+        );
+    }
+}
@@ -0,0 +1,79 @@
+//! Explain what `detect` looked at, so a failed detection can be debugged
+//!
+//! When no buildpack requires `ruby` the build fails with "No buildpack groups passed detection"
+//! and no other information. Setting `BP_LOG_LEVEL=debug` prints the files this buildpack checked,
+//! whether they exist, and which requirements it emitted.
+use bullet_stream::{style, Print};
+use libcnb::Env;
+use std::io::stderr;
+
+pub(crate) const DETECT_EXPLAIN_ENV_KEY: &str = "BP_LOG_LEVEL";
+
+/// Returns true when `BP_LOG_LEVEL=debug` is set in the platform or process environment
+pub(crate) fn detect_explain_enabled(env: &Env) -> bool {
+    env.get(DETECT_EXPLAIN_ENV_KEY)
+        .map(|value| value.to_string_lossy().to_string())
+        .or_else(|| std::env::var(DETECT_EXPLAIN_ENV_KEY).ok())
+        .is_some_and(|value| value.trim().eq_ignore_ascii_case("debug"))
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct DetectExplain {
+    files: Vec<(String, bool, &'static str)>,
+}
+
+impl DetectExplain {
+    /// Record a file that was checked and what finding it means
+    pub(crate) fn file(&mut self, name: impl Into<String>, exists: bool, effect: &'static str) {
+        self.files.push((name.into(), exists, effect));
+    }
+
+    /// Print the explanation to stderr
+    pub(crate) fn print(&self, requires: &[&str]) {
+        let mut bullet = Print::new(stderr())
+            .h2("Heroku Ruby Buildpack (detect)")
+            .bullet("Files checked");
+        for (name, exists, effect) in &self.files {
+            bullet = bullet.sub_bullet(if *exists {
+                format!("{} found ({effect})", style::value(name))
+            } else {
+                format!("{} not found", style::value(name))
+            });
+        }
+
+        let bullet = bullet.done().bullet("Build plan");
+        let bullet = if requires.is_empty() {
+            bullet.sub_bullet(format!(
+                "Provides {} but requires nothing. Another buildpack must require {} or detection will fail",
+                style::value("ruby"),
+                style::value("ruby")
+            ))
+        } else {
+            bullet.sub_bullet(format!(
+                "Provides {} and requires {}",
+                style::value("ruby"),
+                requires
+                    .iter()
+                    .map(|name| style::value(*name))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))
+        };
+        bullet.done().done();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_explain_enabled() {
+        let mut env = Env::new();
+        env.insert(DETECT_EXPLAIN_ENV_KEY, "DEBUG");
+        assert!(detect_explain_enabled(&env));
+
+        env.insert(DETECT_EXPLAIN_ENV_KEY, "info");
+        assert!(!detect_explain_enabled(&env));
+    }
+}
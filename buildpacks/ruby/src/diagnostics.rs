@@ -0,0 +1,310 @@
+//! Capture system diagnostic information when a key step fails
+//!
+//! Failures to list gems, install gems, or detect rake tasks are often caused by the environment
+//! rather than the application. When one of those steps fails, a fixed set of commands that
+//! describe the Ruby installation runs with the same environment as the failed step. Their
+//! output is stored on the error and printed below the error details, so support requests
+//! include it without a second build.
+//!
+//! Diagnostic commands are run when the error is created, errors are reported without access
+//! to the platform env. They must never make things worse, each one (including every process
+//! of a pipeline) is killed after [`COMMAND_TIMEOUT`] and a failure is recorded as its output.
+//! The [`Feature::DisableDiagnostics`] feature (`HEROKU_RUBY_FEATURE_DISABLE_DIAGNOSTICS=1`) skips them
+//! entirely, for environments where paths and environment values must not appear in build
+//! output.
+use crate::features::{Feature, Features};
+use crate::pipeline::NamedPipeline;
+use fun_run::CommandWithName;
+use libcnb::Env;
+use std::io::Read;
+use std::path::Path;
+use std::process::{Child, Command, ExitStatus, Output, Stdio};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The output of each diagnostic command, in the order they ran
+#[derive(Debug, Default)]
+pub(crate) struct Diagnostics {
+    entries: Vec<(String, String)>,
+}
+
+impl Diagnostics {
+    /// Runs the diagnostic commands, a command that fails records its error instead
+    ///
+    /// Nothing runs when diagnostics are disabled.
+    pub(crate) fn capture(features: &Features, env: &Env, app_dir: &Path) -> Self {
+        if features.enabled(Feature::DisableDiagnostics) {
+            return Self::default();
+        }
+
+        let command = |program: &str, args: &[&str]| {
+            let mut cmd = Command::new(program);
+            cmd.args(args)
+                .current_dir(app_dir)
+                .env_clear()
+                .envs(env)
+                .stdin(Stdio::null());
+            cmd
+        };
+        let mut entries = [
+            command("ruby", &["-v"]),
+            command("gem", &["env"]),
+            command("ls", &["-la", &app_dir.to_string_lossy()]),
+        ]
+        .into_iter()
+        .map(|mut cmd| (cmd.name(), run(&mut cmd)))
+        .collect::<Vec<_>>();
+
+        // The Gemfile and Gemfile.lock sections repeat files from the app
+        let mut bundle_env = NamedPipeline::new(
+            command("bundle", &["env"]),
+            command("sed", &["/^## Gemfile$/,$d"]),
+        );
+        let name = bundle_env.name();
+        let output = bundle_env
+            .spawn()
+            .and_then(|(first, second)| wait_with_timeout(vec![first, second], COMMAND_TIMEOUT));
+        entries.push((name.clone(), describe_timeout(&name, output)));
+
+        if let Some(bundle_path) = env.get("BUNDLE_PATH") {
+            let mut ls = command("ls", &["-la", &bundle_path.to_string_lossy()]);
+            entries.push((ls.name(), run(&mut ls)));
+        }
+
+        Self { entries }
+    }
+
+    /// Runs a single command that describes the failure, when diagnostics are enabled
+    pub(crate) fn command(features: &Features, cmd: &mut Command) -> Self {
+        if features.enabled(Feature::DisableDiagnostics) {
+            return Self::default();
+        }
+
+        Self {
+            entries: vec![(cmd.name(), run(cmd))],
+        }
+    }
+
+    /// Lists the directory of a file that could not be read
+    pub(crate) fn list_parent(features: &Features, path: &Path) -> Self {
+        path.parent().map_or_else(Self::default, |dir| {
+            Self::command(features, Command::new("ls").arg("-la").arg(dir))
+        })
+    }
+
+    /// Command names paired with their output, empty when diagnostics are disabled
+    pub(crate) fn entries(&self) -> &[(String, String)] {
+        &self.entries
+    }
+}
+
+/// Runs a diagnostic command, returning its output or why it failed
+///
+/// The command is killed when it runs longer than [`COMMAND_TIMEOUT`].
+fn run(cmd: &mut Command) -> String {
+    let name = cmd.name();
+    describe_timeout(&name, output_with_timeout(cmd, COMMAND_TIMEOUT))
+}
+
+fn describe_timeout(name: &str, result: std::io::Result<Output>) -> String {
+    describe(result.map_err(|error| {
+        if error.kind() == std::io::ErrorKind::TimedOut {
+            timed_out()
+        } else {
+            format!("Could not run {name}: {error}")
+        }
+    }))
+}
+
+fn output_with_timeout(cmd: &mut Command, timeout: Duration) -> std::io::Result<Output> {
+    let child = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    wait_with_timeout(vec![child], timeout)
+}
+
+/// Waits for every child, all of them are killed once the timeout passes
+///
+/// Stdout and stderr are combined in order, like `set -o pipefail` the first failed status is
+/// returned.
+pub(crate) fn wait_with_timeout(
+    mut children: Vec<Child>,
+    timeout: Duration,
+) -> std::io::Result<Output> {
+    let readers = children
+        .iter_mut()
+        .map(|child| {
+            (
+                read_in_background(child.stdout.take()),
+                read_in_background(child.stderr.take()),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let deadline = Instant::now() + timeout;
+    let mut statuses: Vec<Option<ExitStatus>> = vec![None; children.len()];
+    while statuses.iter().any(Option::is_none) {
+        for (child, status) in children.iter_mut().zip(statuses.iter_mut()) {
+            if status.is_none() {
+                match child.try_wait() {
+                    Ok(exited) => *status = exited,
+                    Err(error) => {
+                        kill_all(&mut children);
+                        return Err(error);
+                    }
+                }
+            }
+        }
+        if statuses.iter().all(Option::is_some) {
+            break;
+        }
+        if Instant::now() >= deadline {
+            kill_all(&mut children);
+            return Err(std::io::Error::from(std::io::ErrorKind::TimedOut));
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    let statuses = statuses.into_iter().flatten().collect::<Vec<_>>();
+
+    let mut stdout = Vec::new();
+    let mut stderr = Vec::new();
+    for (out, err) in readers {
+        stdout.extend(out.join().unwrap_or_default());
+        stderr.extend(err.join().unwrap_or_default());
+    }
+    Ok(Output {
+        status: statuses
+            .iter()
+            .find(|status| !status.success())
+            .or(statuses.last())
+            .copied()
+            .unwrap_or_default(),
+        stdout,
+        stderr,
+    })
+}
+
+fn kill_all(children: &mut [Child]) {
+    for child in children {
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+}
+
+/// Reads a pipe on another thread so a full pipe never blocks the command
+fn read_in_background(pipe: Option<impl Read + Send + 'static>) -> JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut buffer = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buffer);
+        }
+        buffer
+    })
+}
+
+fn timed_out() -> String {
+    format!("Timed out after {} seconds", COMMAND_TIMEOUT.as_secs())
+}
+
+fn describe(result: Result<Output, String>) -> String {
+    match result {
+        Ok(output) => {
+            let text = format!(
+                "{}{}",
+                String::from_utf8_lossy(&output.stdout),
+                String::from_utf8_lossy(&output.stderr)
+            );
+            if output.status.success() {
+                text.trim_end().to_string()
+            } else {
+                format!("{}\nExited with {}", text.trim_end(), output.status)
+            }
+        }
+        Err(error) => error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capture_records_failures() {
+        let dir = tempfile::tempdir().unwrap();
+        fs_err::write(dir.path().join("Gemfile"), "").unwrap();
+        let mut env = Env::new();
+        env.insert("PATH", "/usr/bin:/bin");
+
+        let diagnostics = Diagnostics::capture(&Features::default(), &env, dir.path());
+        let (name, output) = diagnostics
+            .entries()
+            .iter()
+            .find(|(name, _)| name.starts_with("ls"))
+            .unwrap();
+        assert_eq!(name, &format!("ls -la {}", dir.path().display()));
+        assert!(output.contains("Gemfile"));
+        assert!(diagnostics
+            .entries()
+            .iter()
+            .any(|(name, _)| name.starts_with("bundle env | sed")));
+    }
+
+    #[test]
+    fn test_output_with_timeout() {
+        let error = output_with_timeout(Command::new("sleep").arg("5"), Duration::from_millis(100))
+            .unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::TimedOut);
+
+        assert!(
+            run(&mut Command::new("does-not-exist")).starts_with("Could not run does-not-exist")
+        );
+        assert!(run(&mut Command::new("false")).contains("Exited with"));
+    }
+
+    #[test]
+    fn test_pipeline_with_timeout() {
+        let pipeline = |first: &str, second: &str, timeout| {
+            let command = |line: &str| {
+                let mut parts = line.split_whitespace();
+                let mut cmd = Command::new(parts.next().unwrap());
+                cmd.args(parts);
+                cmd
+            };
+            NamedPipeline::new(command(first), command(second))
+                .spawn()
+                .and_then(|(first, second)| wait_with_timeout(vec![first, second], timeout))
+        };
+
+        let output = pipeline("echo hello", "tr a-z A-Z", Duration::from_secs(5)).unwrap();
+        assert_eq!(output.stdout, b"HELLO\n");
+        assert!(!pipeline("false", "cat", Duration::from_secs(5))
+            .unwrap()
+            .status
+            .success());
+        assert_eq!(
+            pipeline("sleep 5", "cat", Duration::from_millis(100))
+                .unwrap_err()
+                .kind(),
+            std::io::ErrorKind::TimedOut
+        );
+    }
+
+    #[test]
+    fn test_disabled() {
+        let mut env = Env::new();
+        env.insert(Feature::DisableDiagnostics.env_key(), "1");
+        let features = Features::from_env(&env);
+
+        assert!(Diagnostics::capture(&features, &env, Path::new("."))
+            .entries()
+            .is_empty());
+        assert!(Diagnostics::command(&features, &mut Command::new("true"))
+            .entries()
+            .is_empty());
+    }
+}
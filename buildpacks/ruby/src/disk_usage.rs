@@ -0,0 +1,68 @@
+//! Measure files on disk for build output
+//!
+//! Symlinks are counted by their own size and never followed, so a link to a large directory
+//! outside the application is not reported as part of it.
+use std::path::Path;
+
+/// The size of a file, or of every file in a directory, in bytes
+///
+/// Entries that can't be read are skipped, sizes are only used for reporting.
+pub(crate) fn disk_size(path: &Path) -> u64 {
+    let Ok(metadata) = fs_err::symlink_metadata(path) else {
+        return 0;
+    };
+    if metadata.is_dir() {
+        fs_err::read_dir(path).map_or(0, |entries| {
+            entries
+                .filter_map(Result::ok)
+                .map(|entry| disk_size(&entry.path()))
+                .sum()
+        })
+    } else {
+        metadata.len()
+    }
+}
+
+/// Formats bytes with a binary unit, for example `48.2 MiB`
+#[allow(clippy::cast_precision_loss)]
+pub(crate) fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut size = bytes as f64;
+    let mut unit = "B";
+    for next in UNITS {
+        if size < 1024.0 {
+            break;
+        }
+        size /= 1024.0;
+        unit = next;
+    }
+    format!("{size:.1} {unit}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disk_size() {
+        let dir = tempfile::tempdir().unwrap();
+        fs_err::create_dir_all(dir.path().join("a/b")).unwrap();
+        fs_err::write(dir.path().join("a/one"), "1").unwrap();
+        fs_err::write(dir.path().join("a/b/two"), "22").unwrap();
+        std::os::unix::fs::symlink(dir.path().join("a"), dir.path().join("link")).unwrap();
+
+        assert_eq!(disk_size(&dir.path().join("a")), 3);
+        assert_eq!(disk_size(&dir.path().join("missing")), 0);
+    }
+
+    #[test]
+    fn test_human_size() {
+        assert_eq!(human_size(10), "10 B");
+        assert_eq!(human_size(1536), "1.5 KiB");
+        assert_eq!(human_size(48 * 1024 * 1024 + 200 * 1024), "48.2 MiB");
+        assert_eq!(human_size(3 * 1024 * 1024 * 1024), "3.0 GiB");
+    }
+}
@@ -0,0 +1,232 @@
+//! Explain which layer set each environment variable
+//!
+//! Layers modify the environment at build and at launch, by overriding a value, setting a
+//! default, or prepending and appending to a list such as `PATH`. When it's unclear why a
+//! variable has a value at runtime, set `HEROKU_RUBY_FEATURE_PRINT_ENV_PLAN=1` (or `BP_LOG_LEVEL=debug`)
+//! to print every variable the layers of this buildpack set, the value they produce, and which
+//! layers contributed to it.
+//!
+//! Layers are applied in alphabetical order, the same order as the CNB lifecycle. A layer's
+//! `bin` directory is prepended to `PATH` before its env files are applied. Values come only
+//! from layers, so a prepended `PATH` does not include the system directories it's added to.
+//! Values of variables that look like secrets are redacted.
+use crate::features::{Feature, Features};
+use bullet_stream::state::SubBullet;
+use bullet_stream::{style, Print};
+use libcnb::layer_env::{LayerEnv, Scope};
+use libcnb::Env;
+use std::collections::BTreeMap;
+use std::io::Stdout;
+use std::path::Path;
+
+/// Stands in for an existing value to tell how a layer modified a variable
+const EXISTING: &str = "\u{0}";
+
+/// Returns true when the `PRINT_ENV_PLAN` feature is enabled or `BP_LOG_LEVEL=debug` is set
+pub(crate) fn enabled(features: &Features, platform_env: &Env) -> bool {
+    features.enabled(Feature::PrintEnvPlan)
+        || crate::detect_explain::detect_explain_enabled(platform_env)
+}
+
+/// A variable's value after every layer is applied, and how each layer changed it
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Entry {
+    value: String,
+    contributions: Vec<(String, &'static str)>,
+}
+
+pub(crate) fn print_env_plan(
+    mut bullet: Print<SubBullet<Stdout>>,
+    layers_dir: &Path,
+) -> Print<SubBullet<Stdout>> {
+    for (name, scope, layer_type) in [
+        ("Build", Scope::Build, "build"),
+        ("Launch", Scope::Launch, "launch"),
+    ] {
+        let plan = plan(layers_dir, &scope, layer_type);
+        if plan.is_empty() {
+            bullet = bullet.sub_bullet(format!("{name}: no variables set"));
+            continue;
+        }
+        bullet = bullet.sub_bullet(format!("{name}:"));
+        for (key, entry) in plan {
+            bullet = bullet.sub_bullet(format!(
+                "{} ({})",
+                style::value(format!("{key}={}", redact(&key, &entry.value))),
+                entry
+                    .contributions
+                    .iter()
+                    .map(|(layer, behavior)| format!("{behavior} from {layer}"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+    }
+    bullet
+}
+
+/// Applies the env of every layer with the given type in `<layer>.toml`, in order
+fn plan(layers_dir: &Path, scope: &Scope, layer_type: &str) -> BTreeMap<String, Entry> {
+    let Ok(entries) = fs_err::read_dir(layers_dir) else {
+        return BTreeMap::new();
+    };
+    let mut layers = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .filter(|path| {
+            fs_err::read_to_string(path.with_extension("toml"))
+                .ok()
+                .and_then(|contents| contents.parse::<toml::Table>().ok())
+                .and_then(|table| table.get("types")?.get(layer_type)?.as_bool())
+                .unwrap_or(false)
+        })
+        .collect::<Vec<_>>();
+    layers.sort();
+
+    let mut env = Env::new();
+    let mut plan = BTreeMap::<String, Entry>::new();
+    for layer in layers {
+        let name = layer
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        // Reading the layer also prepends its `bin` directory to `PATH`
+        let Ok(layer_env) = LayerEnv::read_from_layer_dir(&layer) else {
+            continue;
+        };
+        for (key, behavior) in behaviors(&layer_env, scope) {
+            plan.entry(key)
+                .or_default()
+                .contributions
+                .push((name.clone(), behavior));
+        }
+        env = layer_env.apply(scope.clone(), &env);
+    }
+    for (key, entry) in &mut plan {
+        entry.value = env
+            .get(key)
+            .map(|value| value.to_string_lossy().to_string())
+            .unwrap_or_default();
+    }
+    plan
+}
+
+/// The variables a layer env sets and how, one of `override`, `default`, `prepend`, or `append`
+fn behaviors(layer_env: &LayerEnv, scope: &Scope) -> Vec<(String, &'static str)> {
+    layer_env
+        .apply(scope.clone(), &Env::new())
+        .iter()
+        .map(|(key, _)| {
+            let mut existing = Env::new();
+            existing.insert(key, EXISTING);
+            let value = layer_env
+                .apply(scope.clone(), &existing)
+                .get(key)
+                .map(|value| value.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let behavior = if value == EXISTING {
+                "default"
+            } else if value.ends_with(EXISTING) {
+                "prepend"
+            } else if value.starts_with(EXISTING) {
+                "append"
+            } else {
+                "override"
+            };
+            (key.to_string_lossy().to_string(), behavior)
+        })
+        .collect()
+}
+
+fn redact(key: &str, value: &str) -> String {
+    let key = key.to_ascii_uppercase();
+    if ["SECRET", "TOKEN", "PASSWORD", "KEY_BASE"]
+        .iter()
+        .any(|word| key.contains(word))
+    {
+        String::from("[REDACTED]")
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libcnb::layer_env::ModificationBehavior;
+
+    #[test]
+    fn test_plan() {
+        let dir = tempfile::tempdir().unwrap();
+        let layers_dir = dir.path();
+        for (name, types) in [
+            ("bundler", "build = true\nlaunch = true"),
+            ("gems", "build = true\nlaunch = true"),
+            ("build_only", "build = true"),
+        ] {
+            fs_err::create_dir_all(layers_dir.join(name)).unwrap();
+            fs_err::write(
+                layers_dir.join(format!("{name}.toml")),
+                format!("[types]\n{types}\n"),
+            )
+            .unwrap();
+        }
+        fs_err::create_dir_all(layers_dir.join("bundler/bin")).unwrap();
+        LayerEnv::new()
+            .chainable_insert(Scope::All, ModificationBehavior::Delimiter, "GEM_PATH", ":")
+            .chainable_insert(Scope::All, ModificationBehavior::Prepend, "GEM_PATH", "/b")
+            .write_to_layer_dir(layers_dir.join("bundler"))
+            .unwrap();
+        LayerEnv::new()
+            .chainable_insert(Scope::All, ModificationBehavior::Delimiter, "GEM_PATH", ":")
+            .chainable_insert(Scope::All, ModificationBehavior::Prepend, "GEM_PATH", "/g")
+            .chainable_insert(
+                Scope::All,
+                ModificationBehavior::Default,
+                "BUNDLE_WITHOUT",
+                "test",
+            )
+            .write_to_layer_dir(layers_dir.join("gems"))
+            .unwrap();
+        LayerEnv::new()
+            .chainable_insert(Scope::Build, ModificationBehavior::Override, "CI", "1")
+            .write_to_layer_dir(layers_dir.join("build_only"))
+            .unwrap();
+
+        let launch = plan(layers_dir, &Scope::Launch, "launch");
+        assert_eq!(
+            launch.get("GEM_PATH"),
+            Some(&Entry {
+                value: String::from("/g:/b"),
+                contributions: vec![
+                    (String::from("bundler"), "prepend"),
+                    (String::from("gems"), "prepend")
+                ]
+            })
+        );
+        assert_eq!(
+            launch
+                .get("BUNDLE_WITHOUT")
+                .map(|entry| &entry.contributions),
+            Some(&vec![(String::from("gems"), "default")])
+        );
+        assert_eq!(
+            launch.get("PATH").map(|entry| &entry.value),
+            Some(&layers_dir.join("bundler/bin").display().to_string())
+        );
+        assert!(!launch.contains_key("CI"));
+
+        let build = plan(layers_dir, &Scope::Build, "build");
+        assert_eq!(
+            build.get("CI").map(|entry| &entry.contributions),
+            Some(&vec![(String::from("build_only"), "override")])
+        );
+    }
+
+    #[test]
+    fn test_redact() {
+        assert_eq!(redact("SECRET_KEY_BASE", "abc"), "[REDACTED]");
+        assert_eq!(redact("GEM_PATH", "/layers"), "/layers");
+    }
+}
@@ -0,0 +1,168 @@
+//! Stable identifiers for build failures
+//!
+//! Error messages are written for people and change between releases. Tooling that needs to
+//! tell failures apart (retry logic, support dashboards, log processors) should use the code of
+//! an [`ErrorKind`] instead. It is printed as `Error code` above every error message and is
+//! listed in `docs/application_contract.md`. A code is never renamed or reused once released.
+//! New kinds may be added in any release, so consumers must handle codes they don't recognize.
+//!
+//! ## Exit behavior
+//!
+//! Every error exits the current phase with status `1`:
+//!
+//! - Kinds starting with `detect_` happen during detect. The application could not be read, so
+//!   the buildpack errors instead of reporting that it does not apply (status `100`).
+//! - All other kinds happen during build and stop the build. Layers written before the error
+//!   are discarded.
+//! - `framework_error` is an error from the buildpack framework rather than the application.
+use crate::{DetectError, RubyBuildpackError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub(crate) enum ErrorKind {
+    DetectGemfile,
+    DetectGemfileLock,
+    DetectPackageJson,
+    DetectYarnLock,
+    BuildPlan,
+    BuildSteps,
+    CacheDirs,
+    AssetTasks,
+    RubyVersionFile,
+    RakeDetect,
+    GemList,
+    RubyInstall,
+    UnsupportedTarget,
+    MetricsAgent,
+    MissingGemfileLock,
+    InAppDirCache,
+    BootsnapCache,
+    BundleInstallDigest,
+    AssetsDigest,
+    BundleInstall,
+    NativeExtension,
+    BundleAddPlatform,
+    RakeAssetsPrecompile,
+    AssetManifest,
+    JekyllBuild,
+    BootCheck,
+    BuildHook,
+    JrubyWarmup,
+    JrubyWarmupDigest,
+    ZeitwerkCheck,
+    GemInstallBundler,
+    DoctorInstall,
+    BundleCheckInstall,
+    PathCheckInstall,
+    WritableTmp,
+    FrameworkError,
+}
+
+impl ErrorKind {
+    /// The stable code printed with the error
+    pub(crate) fn code(self) -> &'static str {
+        match self {
+            ErrorKind::DetectGemfile => "detect_gemfile",
+            ErrorKind::DetectGemfileLock => "detect_gemfile_lock",
+            ErrorKind::DetectPackageJson => "detect_package_json",
+            ErrorKind::DetectYarnLock => "detect_yarn_lock",
+            ErrorKind::BuildPlan => "build_plan",
+            ErrorKind::BuildSteps => "build_steps",
+            ErrorKind::CacheDirs => "cache_dirs",
+            ErrorKind::AssetTasks => "asset_tasks",
+            ErrorKind::RubyVersionFile => "ruby_version_file",
+            ErrorKind::RakeDetect => "rake_detect",
+            ErrorKind::GemList => "gem_list",
+            ErrorKind::RubyInstall => "ruby_install",
+            ErrorKind::UnsupportedTarget => "unsupported_target",
+            ErrorKind::MetricsAgent => "metrics_agent",
+            ErrorKind::MissingGemfileLock => "missing_gemfile_lock",
+            ErrorKind::InAppDirCache => "in_app_dir_cache",
+            ErrorKind::BootsnapCache => "bootsnap_cache",
+            ErrorKind::BundleInstallDigest => "bundle_install_digest",
+            ErrorKind::AssetsDigest => "assets_digest",
+            ErrorKind::BundleInstall => "bundle_install",
+            ErrorKind::NativeExtension => "native_extension",
+            ErrorKind::BundleAddPlatform => "bundle_add_platform",
+            ErrorKind::RakeAssetsPrecompile => "rake_assets_precompile",
+            ErrorKind::AssetManifest => "asset_manifest",
+            ErrorKind::JekyllBuild => "jekyll_build",
+            ErrorKind::BootCheck => "boot_check",
+            ErrorKind::BuildHook => "build_hook",
+            ErrorKind::JrubyWarmup => "jruby_warmup",
+            ErrorKind::JrubyWarmupDigest => "jruby_warmup_digest",
+            ErrorKind::ZeitwerkCheck => "zeitwerk_check",
+            ErrorKind::GemInstallBundler => "gem_install_bundler",
+            ErrorKind::DoctorInstall => "doctor_install",
+            ErrorKind::BundleCheckInstall => "bundle_check_install",
+            ErrorKind::PathCheckInstall => "path_check_install",
+            ErrorKind::WritableTmp => "writable_tmp",
+            ErrorKind::FrameworkError => "framework_error",
+        }
+    }
+}
+
+impl RubyBuildpackError {
+    pub(crate) fn kind(&self) -> ErrorKind {
+        match self {
+            RubyBuildpackError::BuildpackDetectionError(error) => match error {
+                DetectError::Gemfile(_) => ErrorKind::DetectGemfile,
+                DetectError::GemfileLock(_) => ErrorKind::DetectGemfileLock,
+                DetectError::PackageJson(_) => ErrorKind::DetectPackageJson,
+                DetectError::YarnLock(_) => ErrorKind::DetectYarnLock,
+            },
+            RubyBuildpackError::BuildPlanError(_) => ErrorKind::BuildPlan,
+            RubyBuildpackError::BuildStepsError(_) => ErrorKind::BuildSteps,
+            RubyBuildpackError::CacheDirsError(_) => ErrorKind::CacheDirs,
+            RubyBuildpackError::AssetTasksError(_) => ErrorKind::AssetTasks,
+            RubyBuildpackError::RubyVersionFileError(_) => ErrorKind::RubyVersionFile,
+            RubyBuildpackError::RakeDetectError(_, _) => ErrorKind::RakeDetect,
+            RubyBuildpackError::GemListGetError(_, _) => ErrorKind::GemList,
+            RubyBuildpackError::RubyInstallError(_) => ErrorKind::RubyInstall,
+            RubyBuildpackError::UnsupportedTarget(_) => ErrorKind::UnsupportedTarget,
+            RubyBuildpackError::MetricsAgentError(_) => ErrorKind::MetricsAgent,
+            RubyBuildpackError::MissingGemfileLock(..) => ErrorKind::MissingGemfileLock,
+            RubyBuildpackError::InAppDirCacheError(_) => ErrorKind::InAppDirCache,
+            RubyBuildpackError::BootsnapCacheError(_) => ErrorKind::BootsnapCache,
+            RubyBuildpackError::BundleInstallDigestError(..) => ErrorKind::BundleInstallDigest,
+            RubyBuildpackError::AssetsDigestError(_, _) => ErrorKind::AssetsDigest,
+            RubyBuildpackError::BundleInstallCommandError(_, _) => ErrorKind::BundleInstall,
+            RubyBuildpackError::NativeExtensionError(_, _, _) => ErrorKind::NativeExtension,
+            RubyBuildpackError::BundleAddPlatformError(_) => ErrorKind::BundleAddPlatform,
+            RubyBuildpackError::RakeAssetsPrecompileFailed(_) => ErrorKind::RakeAssetsPrecompile,
+            RubyBuildpackError::AssetManifestError(_) => ErrorKind::AssetManifest,
+            RubyBuildpackError::JekyllBuildFailed(_) => ErrorKind::JekyllBuild,
+            RubyBuildpackError::BootCheckFailed(_) => ErrorKind::BootCheck,
+            RubyBuildpackError::BuildHookFailed(_) => ErrorKind::BuildHook,
+            RubyBuildpackError::JrubyWarmupFailed(_) => ErrorKind::JrubyWarmup,
+            RubyBuildpackError::JrubyWarmupDigestError(_, _) => ErrorKind::JrubyWarmupDigest,
+            RubyBuildpackError::ZeitwerkCheckFailed(_) => ErrorKind::ZeitwerkCheck,
+            RubyBuildpackError::GemInstallBundlerCommandError(..) => ErrorKind::GemInstallBundler,
+            RubyBuildpackError::DoctorInstallError(_) => ErrorKind::DoctorInstall,
+            RubyBuildpackError::BundleCheckInstallError(_) => ErrorKind::BundleCheckInstall,
+            RubyBuildpackError::PathCheckInstallError(_) => ErrorKind::PathCheckInstall,
+            RubyBuildpackError::WritableTmpError(_) => ErrorKind::WritableTmp,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_codes() {
+        let error = RubyBuildpackError::BuildpackDetectionError(DetectError::GemfileLock(
+            std::io::Error::from(std::io::ErrorKind::PermissionDenied),
+        ));
+        assert_eq!(error.kind().code(), "detect_gemfile_lock");
+        assert_eq!(
+            RubyBuildpackError::DoctorInstallError(std::io::Error::from(
+                std::io::ErrorKind::NotFound
+            ))
+            .kind()
+            .code(),
+            "doctor_install"
+        );
+    }
+}
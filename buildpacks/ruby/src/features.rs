@@ -0,0 +1,229 @@
+//! Opt-in behavior configured through the platform environment
+//!
+//! Every feature is enabled with `HEROKU_RUBY_FEATURE_<NAME>=1` (for example
+//! `HEROKU_RUBY_FEATURE_BOOT_CHECK=1`). Features that existed before this prefix also read
+//! their released environment variable, such as `HEROKU_BUNDLE_ADD_PLATFORM=1`. Values of `1`
+//! or `true` enable a feature, `0` or `false` disable it. When both variables are set the
+//! `HEROKU_RUBY_FEATURE_` one wins.
+//!
+//! Features are read once at the start of the build. `HEROKU_RUBY_FEATURE_` variables that don't
+//! match a known feature produce a warning, as they're usually a typo.
+use crate::layers::bundle_install_layer::ADD_PLATFORM_ENV_KEY;
+use crate::layers::ruby_install_layer::BUILD_FROM_SOURCE_ENV_KEY;
+use crate::warning::Warning;
+use bullet_stream::style;
+use indoc::formatdoc;
+use libcnb::Env;
+use std::collections::BTreeSet;
+
+pub(crate) const FEATURE_ENV_PREFIX: &str = "HEROKU_RUBY_FEATURE_";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Feature {
+    AddPlatform,
+    BootCheck,
+    BuildFromSource,
+    BundleCheckAtLaunch,
+    DisableDiagnostics,
+    Doctor,
+    JrubyWarmup,
+    KeepGitDirs,
+    PrintEnvPlan,
+    ReadOnlyWorkspace,
+    SkipAssetManifestCheck,
+    VendorBundle,
+}
+
+impl Feature {
+    const ALL: [Feature; 12] = [
+        Feature::AddPlatform,
+        Feature::BootCheck,
+        Feature::BuildFromSource,
+        Feature::BundleCheckAtLaunch,
+        Feature::DisableDiagnostics,
+        Feature::Doctor,
+        Feature::JrubyWarmup,
+        Feature::KeepGitDirs,
+        Feature::PrintEnvPlan,
+        Feature::ReadOnlyWorkspace,
+        Feature::SkipAssetManifestCheck,
+        Feature::VendorBundle,
+    ];
+
+    /// Suffix of the `HEROKU_RUBY_FEATURE_` environment variable
+    fn name(self) -> &'static str {
+        match self {
+            Feature::AddPlatform => "ADD_PLATFORM",
+            Feature::BootCheck => "BOOT_CHECK",
+            Feature::BuildFromSource => "BUILD_FROM_SOURCE",
+            Feature::BundleCheckAtLaunch => "BUNDLE_CHECK_AT_LAUNCH",
+            Feature::DisableDiagnostics => "DISABLE_DIAGNOSTICS",
+            Feature::Doctor => "DOCTOR",
+            Feature::JrubyWarmup => "JRUBY_WARMUP",
+            Feature::KeepGitDirs => "KEEP_GIT_DIRS",
+            Feature::PrintEnvPlan => "PRINT_ENV_PLAN",
+            Feature::ReadOnlyWorkspace => "READ_ONLY_WORKSPACE",
+            Feature::SkipAssetManifestCheck => "SKIP_ASSET_MANIFEST_CHECK",
+            Feature::VendorBundle => "VENDOR_BUNDLE",
+        }
+    }
+
+    /// The environment variable that enables the feature, for example
+    /// `HEROKU_RUBY_FEATURE_BOOT_CHECK`
+    pub(crate) fn env_key(self) -> String {
+        format!("{FEATURE_ENV_PREFIX}{}", self.name())
+    }
+
+    /// The environment variable of a feature released before `HEROKU_RUBY_FEATURE_` existed
+    fn legacy_env_key(self) -> Option<&'static str> {
+        match self {
+            Feature::AddPlatform => Some(ADD_PLATFORM_ENV_KEY),
+            Feature::BuildFromSource => Some(BUILD_FROM_SOURCE_ENV_KEY),
+            Feature::BootCheck
+            | Feature::BundleCheckAtLaunch
+            | Feature::DisableDiagnostics
+            | Feature::Doctor
+            | Feature::JrubyWarmup
+            | Feature::KeepGitDirs
+            | Feature::PrintEnvPlan
+            | Feature::ReadOnlyWorkspace
+            | Feature::SkipAssetManifestCheck
+            | Feature::VendorBundle => None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct Features {
+    enabled: BTreeSet<Feature>,
+    unknown: Vec<String>,
+}
+
+impl Features {
+    pub(crate) fn from_env(platform_env: &Env) -> Self {
+        let value = |key: &str| {
+            platform_env
+                .get(key)
+                .and_then(|value| match value.to_string_lossy().trim() {
+                    "1" | "true" => Some(true),
+                    "0" | "false" => Some(false),
+                    _ => None,
+                })
+        };
+        let enabled = Feature::ALL
+            .into_iter()
+            .filter(|feature| {
+                value(&feature.env_key())
+                    .or_else(|| feature.legacy_env_key().and_then(value))
+                    .unwrap_or(false)
+            })
+            .collect();
+        let unknown = platform_env
+            .iter()
+            .filter_map(|(key, _)| {
+                key.to_string_lossy()
+                    .strip_prefix(FEATURE_ENV_PREFIX)
+                    .map(String::from)
+            })
+            .filter(|name| !Feature::ALL.iter().any(|feature| feature.name() == name))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+
+        Self { enabled, unknown }
+    }
+
+    pub(crate) fn enabled(&self, feature: Feature) -> bool {
+        self.enabled.contains(&feature)
+    }
+
+    /// Warns about `HEROKU_RUBY_FEATURE_` variables that don't match a known feature
+    pub(crate) fn unknown_warning(&self) -> Option<Warning> {
+        if self.unknown.is_empty() {
+            return None;
+        }
+
+        Some(Warning {
+            code: "unknown_feature",
+            title: "Unknown feature flag",
+            body: formatdoc! {"
+                These environment variables do not match a known feature and are ignored:
+
+                {unknown}
+
+                Known features are:
+
+                {known}
+                ",
+                unknown = self
+                    .unknown
+                    .iter()
+                    .map(|name| format!("- {}", style::value(format!("{FEATURE_ENV_PREFIX}{name}"))))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+                known = Feature::ALL
+                    .iter()
+                    .map(|feature| format!("- {}", style::value(feature.env_key())))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            },
+            url: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn features(vars: &[(&str, &str)]) -> Features {
+        let mut env = Env::new();
+        for (key, value) in vars {
+            env.insert(key, value);
+        }
+        Features::from_env(&env)
+    }
+
+    #[test]
+    fn test_defaults() {
+        let features = features(&[]);
+        assert!(Feature::ALL
+            .iter()
+            .all(|feature| !features.enabled(*feature)));
+        assert_eq!(features.unknown_warning(), None);
+    }
+
+    #[test]
+    fn test_feature_and_legacy_env() {
+        assert!(features(&[("HEROKU_RUBY_FEATURE_BOOT_CHECK", "1")]).enabled(Feature::BootCheck));
+        assert!(!features(&[("HEROKU_RUBY_BOOT_CHECK", "1")]).enabled(Feature::BootCheck));
+        assert!(features(&[(BUILD_FROM_SOURCE_ENV_KEY, "true")]).enabled(Feature::BuildFromSource));
+        assert!(!features(&[(BUILD_FROM_SOURCE_ENV_KEY, "0")]).enabled(Feature::BuildFromSource));
+        assert!(!features(&[(BUILD_FROM_SOURCE_ENV_KEY, "yes")]).enabled(Feature::BuildFromSource));
+        assert!(
+            features(&[("HEROKU_RUBY_FEATURE_SKIP_ASSET_MANIFEST_CHECK", "true")])
+                .enabled(Feature::SkipAssetManifestCheck)
+        );
+
+        let both = features(&[
+            ("HEROKU_RUBY_FEATURE_ADD_PLATFORM", "0"),
+            (ADD_PLATFORM_ENV_KEY, "1"),
+        ]);
+        assert!(!both.enabled(Feature::AddPlatform));
+    }
+
+    #[test]
+    fn test_unknown_warning() {
+        let features = features(&[
+            ("HEROKU_RUBY_FEATURE_BOOT_CHEK", "1"),
+            ("HEROKU_RUBY_FEATURE_JRUBY_WARMUP", "1"),
+        ]);
+        assert!(features.enabled(Feature::JrubyWarmup));
+
+        let body = bullet_stream::strip_ansi(features.unknown_warning().unwrap().body);
+        let (unknown, known) = body.split_once("Known features").unwrap();
+        assert!(unknown.contains("- `HEROKU_RUBY_FEATURE_BOOT_CHEK`"));
+        assert!(!unknown.contains("JRUBY_WARMUP"));
+        assert!(known.contains("- `HEROKU_RUBY_FEATURE_BOOT_CHECK`"));
+    }
+}
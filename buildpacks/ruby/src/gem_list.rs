@@ -3,20 +3,45 @@ use commons::gem_version::GemVersion;
 use core::str::FromStr;
 use fun_run::{CmdError, CommandWithName};
 use regex::Regex;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::ffi::OsStr;
 use std::io::Stdout;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
 /// ## Gets list of an application's dependencies
 ///
 /// Requires `ruby` and `bundle` to be installed and on the PATH
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub(crate) struct GemList {
-    pub(crate) gems: HashMap<String, GemVersion>,
+    pub(crate) gems: HashMap<String, GemEntry>,
+    /// Gems locked at more than one version in the `Gemfile.lock`, and those versions
+    duplicates: BTreeMap<String, BTreeSet<String>>,
 }
 
-/// Calls `bundle list` and returns a `GemList` struct
+/// An installed gem
+#[derive(Debug, Default)]
+pub(crate) struct GemEntry {
+    pub(crate) version: GemVersion,
+    pub(crate) source: GemSource,
+    /// Platforms the gem is locked for, empty when it's only locked for `ruby`
+    pub(crate) platforms: Vec<String>,
+    /// Where the gem is installed, when `bundle list --paths` reported it
+    pub(crate) path: Option<PathBuf>,
+}
+
+/// The section of the `Gemfile.lock` that provides a gem
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum GemSource {
+    #[default]
+    Rubygems,
+    Git,
+    Path,
+}
+
+/// Calls `bundle list` and `bundle list --paths` and returns a `GemList` struct
+///
+/// Sources, platforms, and duplicate versions are read from the `Gemfile.lock` contents.
 ///
 /// # Errors
 ///
@@ -24,21 +49,54 @@ pub(crate) struct GemList {
 pub(crate) fn bundle_list<T, K, V>(
     bullet: Print<SubBullet<Stdout>>,
     envs: T,
+    lockfile: &str,
 ) -> Result<(Print<SubBullet<Stdout>>, GemList), CmdError>
 where
-    T: IntoIterator<Item = (K, V)>,
+    T: IntoIterator<Item = (K, V)> + Clone,
     K: AsRef<OsStr>,
     V: AsRef<OsStr>,
 {
     let mut cmd = Command::new("bundle");
-    cmd.arg("list").env_clear().envs(envs);
+    cmd.arg("list").env_clear().envs(envs.clone());
 
     let timer = bullet.start_timer(format!("Running {}", style::command(cmd.name())));
-    let gem_list = cmd
+    let mut gem_list = cmd
         .named_output()
         .map(|output| output.stdout_lossy())
         .and_then(|output| GemList::from_str(&output))?;
-    Ok((timer.done(), gem_list))
+
+    let mut paths = Command::new("bundle");
+    paths.args(["list", "--paths"]).env_clear().envs(envs);
+    // Paths are informational, a failure here is not worth failing the build
+    if let Ok(output) = paths.named_output() {
+        gem_list.add_paths(&output.stdout_lossy());
+    }
+    gem_list.add_lockfile(lockfile);
+
+    let mut bullet = timer.done();
+    let mut local = gem_list
+        .gems
+        .iter()
+        .filter(|(_, gem)| gem.source != GemSource::Rubygems)
+        .collect::<Vec<_>>();
+    local.sort_by_key(|(name, _)| *name);
+    for (name, gem) in local {
+        bullet = bullet.sub_bullet(format!(
+            "Using {} {} from {}{}",
+            style::value(name),
+            gem.version,
+            match gem.source {
+                GemSource::Git => "git",
+                GemSource::Path | GemSource::Rubygems => "a path",
+            },
+            gem.path
+                .as_ref()
+                .map(|path| format!(" ({})", path.display()))
+                .unwrap_or_default()
+        ));
+    }
+
+    Ok((bullet, gem_list))
 }
 
 /// Converts the output of `$ gem list` into a data structure that can be inspected and compared
@@ -81,6 +139,109 @@ impl GemList {
     pub(crate) fn has(&self, str: &str) -> bool {
         self.gems.contains_key(&str.trim().to_lowercase())
     }
+
+    #[must_use]
+    pub(crate) fn version_for(&self, str: &str) -> Option<&GemVersion> {
+        self.gems
+            .get(&str.trim().to_lowercase())
+            .map(|gem| &gem.version)
+    }
+
+    /// Gems locked for a specific platform, usually because they ship a precompiled extension
+    pub(crate) fn platform_specific(&self) -> usize {
+        self.gems
+            .values()
+            .filter(|gem| !gem.platforms.is_empty())
+            .count()
+    }
+
+    /// Gems locked at more than one version, and those versions
+    pub(crate) fn duplicates(&self) -> &BTreeMap<String, BTreeSet<String>> {
+        &self.duplicates
+    }
+
+    /// Matches the output of `bundle list --paths` to installed gems
+    ///
+    /// Gems from rubygems and git install to `<name>-<version>[-<platform>]` and
+    /// `<name>-<revision>` directories. A gem from a path is matched by the directory name.
+    fn add_paths(&mut self, output: &str) {
+        for path in output
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+        {
+            let path = Path::new(path);
+            let Some(dir) = path.file_name().map(|name| name.to_string_lossy()) else {
+                continue;
+            };
+            // Longest name first so `rails-html-sanitizer-1.6.0` isn't matched to `rails`
+            let Some(entry) = self
+                .gems
+                .iter_mut()
+                .filter(|(name, _)| {
+                    dir.strip_prefix(name.as_str()).is_some_and(|rest| {
+                        rest.is_empty()
+                            || rest.strip_prefix('-').is_some_and(|rest| {
+                                rest.starts_with(|c: char| c.is_ascii_alphanumeric())
+                            })
+                    })
+                })
+                .max_by_key(|(name, _)| name.len())
+                .map(|(_, entry)| entry)
+            else {
+                continue;
+            };
+            entry.path = Some(path.to_path_buf());
+        }
+    }
+
+    /// Reads the source and platforms of each gem from `Gemfile.lock` contents
+    ///
+    /// Specs are indented four spaces under a `GEM`, `GIT`, or `PATH` section, platform specific
+    /// versions end in `-<platform>`.
+    pub(crate) fn add_lockfile(&mut self, lockfile: &str) {
+        let mut locked = BTreeMap::<String, BTreeSet<String>>::new();
+        let mut source = None;
+        for line in lockfile.lines() {
+            match line {
+                "GEM" => source = Some(GemSource::Rubygems),
+                "GIT" => source = Some(GemSource::Git),
+                "PATH" => source = Some(GemSource::Path),
+                _ if !line.starts_with(' ') => source = None,
+                _ => {}
+            }
+            let Some(source) = source else {
+                continue;
+            };
+            let Some((name, version)) = line
+                .strip_prefix("    ")
+                .filter(|spec| !spec.starts_with(' '))
+                .and_then(|spec| spec.strip_suffix(')'))
+                .and_then(|spec| spec.split_once(" ("))
+            else {
+                continue;
+            };
+            let (version, platform) = version
+                .split_once('-')
+                .map_or((version, None), |(version, platform)| {
+                    (version, Some(platform))
+                });
+            locked
+                .entry(name.to_lowercase())
+                .or_default()
+                .insert(version.to_string());
+            if let Some(entry) = self.gems.get_mut(&name.to_lowercase()) {
+                entry.source = source;
+                if let Some(platform) = platform {
+                    entry.platforms.push(platform.to_string());
+                }
+            }
+        }
+        self.duplicates = locked
+            .into_iter()
+            .filter(|(_, versions)| versions.len() > 1)
+            .collect();
+    }
 }
 
 impl FromStr for GemList {
@@ -88,7 +249,8 @@ impl FromStr for GemList {
 
     fn from_str(string: &str) -> Result<Self, Self::Err> {
         // https://regex101.com/r/EIJe5G/1
-        let gem_entry_re = Regex::new("  \\* (\\S+) \\(([a-zA-Z0-9\\.]+)\\)")
+        // Gems from git end in a revision, `  * rails (8.0.0.alpha 1a2b3c4)`
+        let gem_entry_re = Regex::new("  \\* (\\S+) \\(([a-zA-Z0-9\\.]+)( [0-9a-f]+)?\\)")
             .expect("Internal error: invalid regex");
 
         let gems = gem_entry_re
@@ -106,13 +268,24 @@ impl FromStr for GemList {
                     };
                     (
                         name.to_string().to_lowercase(),
-                        GemVersion::from_str(version).unwrap_or_default(),
+                        GemEntry {
+                            version: GemVersion::from_str(version).unwrap_or_default(),
+                            source: if capture.get(3).is_some() {
+                                GemSource::Git
+                            } else {
+                                GemSource::Rubygems
+                            },
+                            ..GemEntry::default()
+                        },
                     )
                 }, //
             )
-            .collect::<HashMap<String, GemVersion>>();
+            .collect::<HashMap<String, GemEntry>>();
 
-        Ok(GemList { gems })
+        Ok(GemList {
+            gems,
+            duplicates: BTreeMap::new(),
+        })
     }
 }
 
@@ -149,4 +322,101 @@ Use `bundle info` to print more detailed information about a gem
 
         assert_eq!(gem_list.gems.len(), 14);
     }
+
+    #[test]
+    fn test_git_gems() {
+        let gem_list = GemList::from_str(
+            r"
+Gems included by the bundle:
+  * rack (3.1.8)
+  * rails (8.0.0.alpha 1a2b3c4)
+            ",
+        )
+        .unwrap();
+
+        assert_eq!(gem_list.gems["rack"].source, GemSource::Rubygems);
+        assert_eq!(gem_list.gems["rails"].source, GemSource::Git);
+        assert_eq!(
+            gem_list.version_for("rails").unwrap(),
+            &GemVersion::from_str("8.0.0.alpha").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_add_paths() {
+        let mut gem_list = GemList::from_str(
+            r"
+  * rails (7.2.1)
+  * rails-html-sanitizer (1.6.0)
+  * nokogiri (1.16.7)
+  * my_engine (0.1.0)
+            ",
+        )
+        .unwrap();
+        gem_list.add_paths(
+            "/layers/heroku_ruby/gems/ruby/3.3.0/gems/rails-7.2.1
+/layers/heroku_ruby/gems/ruby/3.3.0/gems/rails-html-sanitizer-1.6.0
+/layers/heroku_ruby/gems/ruby/3.3.0/gems/nokogiri-1.16.7-x86_64-linux
+/workspace/engines/my_engine
+",
+        );
+
+        let path = |name: &str| gem_list.gems[name].path.clone().unwrap();
+        assert!(path("rails").ends_with("rails-7.2.1"));
+        assert!(path("rails-html-sanitizer").ends_with("rails-html-sanitizer-1.6.0"));
+        assert!(path("nokogiri").ends_with("nokogiri-1.16.7-x86_64-linux"));
+        assert_eq!(
+            path("my_engine"),
+            PathBuf::from("/workspace/engines/my_engine")
+        );
+    }
+
+    #[test]
+    fn test_add_lockfile() {
+        let mut gem_list = GemList::from_str(
+            r"
+  * my_engine (0.1.0)
+  * nokogiri (1.16.7)
+  * rack (3.1.8)
+            ",
+        )
+        .unwrap();
+        gem_list.add_lockfile(
+            "PATH
+  remote: engines/my_engine
+  specs:
+    my_engine (0.1.0)
+      rack
+
+GEM
+  remote: https://rubygems.org/
+  specs:
+    nokogiri (1.16.7-aarch64-linux)
+      racc (~> 1.4)
+    nokogiri (1.15.6-x86_64-linux)
+      racc (~> 1.4)
+    rack (3.1.8)
+
+PLATFORMS
+  aarch64-linux
+  x86_64-linux
+
+BUNDLED WITH
+   2.5.6
+",
+        );
+
+        assert_eq!(gem_list.gems["my_engine"].source, GemSource::Path);
+        assert_eq!(gem_list.gems["rack"].source, GemSource::Rubygems);
+        assert!(gem_list.gems["rack"].platforms.is_empty());
+        assert_eq!(gem_list.platform_specific(), 1);
+        assert_eq!(
+            gem_list.gems["nokogiri"].platforms,
+            vec!["aarch64-linux", "x86_64-linux"]
+        );
+        assert_eq!(
+            gem_list.duplicates().keys().collect::<Vec<_>>(),
+            vec!["nokogiri"]
+        );
+    }
 }
@@ -0,0 +1,201 @@
+//! Decide which Gemfile and lockfile the application uses
+//!
+//! Bundler reads `Gemfile` and `Gemfile.lock` from the application root by default. Apps doing
+//! a dual-boot upgrade (for example to a new Rails version) keep a second Gemfile such as
+//! `Gemfile_next` and select it with `BUNDLE_GEMFILE`. When `BUNDLE_GEMFILE` is set in the
+//! platform environment it's honored during detect and build, and the lockfile is found the
+//! same way bundler finds it.
+//!
+//! The Gemfile may be in a subdirectory (`gemfiles/rails_next.gemfile`), and either file may be
+//! a symlink. The paths here are the ones bundler is given, symlinks are followed when files are
+//! read, so the same paths are used for detect, cache digests, and the `BUNDLE_GEMFILE` env var.
+use libcnb::Env;
+use std::path::{Path, PathBuf};
+
+pub(crate) const BUNDLE_GEMFILE_ENV_KEY: &str = "BUNDLE_GEMFILE";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct GemfilePaths {
+    gemfile: PathBuf,
+    lockfile: PathBuf,
+    app_dir: PathBuf,
+    /// Set when the Gemfile came from `BUNDLE_GEMFILE`
+    configured: bool,
+}
+
+impl GemfilePaths {
+    /// Reads `BUNDLE_GEMFILE` from the platform env, relative paths are relative to the app
+    pub(crate) fn from_env(platform_env: &Env, app_dir: &Path) -> Self {
+        match platform_env
+            .get(BUNDLE_GEMFILE_ENV_KEY)
+            .map(|value| value.to_string_lossy().trim().to_string())
+            .filter(|value| !value.is_empty())
+        {
+            Some(value) => {
+                let gemfile = app_dir.join(value);
+                Self {
+                    lockfile: lockfile_for(&gemfile),
+                    gemfile,
+                    app_dir: app_dir.to_path_buf(),
+                    configured: true,
+                }
+            }
+            None => Self {
+                gemfile: app_dir.join("Gemfile"),
+                lockfile: app_dir.join("Gemfile.lock"),
+                app_dir: app_dir.to_path_buf(),
+                configured: false,
+            },
+        }
+    }
+
+    pub(crate) fn gemfile(&self) -> &Path {
+        &self.gemfile
+    }
+
+    pub(crate) fn lockfile(&self) -> &Path {
+        &self.lockfile
+    }
+
+    /// True when the Gemfile came from `BUNDLE_GEMFILE`
+    pub(crate) fn configured(&self) -> bool {
+        self.configured
+    }
+
+    /// Files whose contents decide which gems are installed
+    pub(crate) fn digest_files(&self) -> [&Path; 2] {
+        [&self.gemfile, &self.lockfile]
+    }
+
+    /// A path relative to the application for display, for example `gemfiles/rails.gemfile.lock`
+    pub(crate) fn display(&self, path: &Path) -> String {
+        path.strip_prefix(&self.app_dir)
+            .unwrap_or(path)
+            .display()
+            .to_string()
+    }
+
+    /// True when the lockfile exists, following symlinks
+    ///
+    /// # Errors
+    ///
+    /// Errors when the lockfile is a symlink to a file that does not exist, a missing lockfile
+    /// would otherwise be reported and hide the broken link.
+    pub(crate) fn lockfile_exists(&self) -> Result<bool, std::io::Error> {
+        match fs_err::symlink_metadata(&self.lockfile) {
+            Ok(metadata) if metadata.is_symlink() => {
+                if self.lockfile.exists() {
+                    Ok(true)
+                } else {
+                    Err(std::io::Error::new(
+                        std::io::ErrorKind::NotFound,
+                        format!(
+                            "{} is a symlink to {}, which does not exist",
+                            self.display(&self.lockfile),
+                            fs_err::read_link(&self.lockfile)?.display()
+                        ),
+                    ))
+                }
+            }
+            Ok(_) => Ok(true),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Where the lockfile points when it is a symlink, relative to the application when inside it
+    pub(crate) fn lockfile_symlink_target(&self) -> Option<String> {
+        fs_err::symlink_metadata(&self.lockfile)
+            .ok()
+            .filter(std::fs::Metadata::is_symlink)
+            .and_then(|_| fs_err::canonicalize(&self.lockfile).ok())
+            .map(|target| {
+                let app_dir =
+                    fs_err::canonicalize(&self.app_dir).unwrap_or_else(|_| self.app_dir.clone());
+                target
+                    .strip_prefix(&app_dir)
+                    .unwrap_or(&target)
+                    .display()
+                    .to_string()
+            })
+    }
+}
+
+/// The lockfile bundler uses for a Gemfile
+///
+/// `gems.rb` is locked to `gems.locked`, every other Gemfile to the same name with `.lock`
+/// appended.
+fn lockfile_for(gemfile: &Path) -> PathBuf {
+    if gemfile.file_name().is_some_and(|name| name == "gems.rb") {
+        gemfile.with_file_name("gems.locked")
+    } else {
+        let mut name = gemfile.as_os_str().to_owned();
+        name.push(".lock");
+        PathBuf::from(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_env() {
+        let app_dir = Path::new("/workspace");
+        let mut env = Env::new();
+        let paths = GemfilePaths::from_env(&env, app_dir);
+        assert_eq!(paths.gemfile(), Path::new("/workspace/Gemfile"));
+        assert_eq!(paths.lockfile(), Path::new("/workspace/Gemfile.lock"));
+        assert!(!paths.configured());
+
+        env.insert(BUNDLE_GEMFILE_ENV_KEY, "Gemfile_next");
+        let paths = GemfilePaths::from_env(&env, app_dir);
+        assert_eq!(paths.gemfile(), Path::new("/workspace/Gemfile_next"));
+        assert_eq!(paths.lockfile(), Path::new("/workspace/Gemfile_next.lock"));
+        assert!(paths.configured());
+
+        env.insert(BUNDLE_GEMFILE_ENV_KEY, "gems.rb");
+        assert_eq!(
+            GemfilePaths::from_env(&env, app_dir).lockfile(),
+            Path::new("/workspace/gems.locked")
+        );
+
+        env.insert(BUNDLE_GEMFILE_ENV_KEY, "gemfiles/rails_next.gemfile");
+        let paths = GemfilePaths::from_env(&env, app_dir);
+        assert_eq!(
+            paths.display(paths.lockfile()),
+            "gemfiles/rails_next.gemfile.lock"
+        );
+        assert_eq!(
+            paths.digest_files(),
+            [
+                Path::new("/workspace/gemfiles/rails_next.gemfile"),
+                Path::new("/workspace/gemfiles/rails_next.gemfile.lock")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lockfile_symlinks() {
+        let dir = tempfile::tempdir().unwrap();
+        let app_dir = dir.path();
+        let paths = GemfilePaths::from_env(&Env::new(), app_dir);
+        assert!(!paths.lockfile_exists().unwrap());
+
+        fs_err::create_dir_all(app_dir.join("gemfiles")).unwrap();
+        std::os::unix::fs::symlink("gemfiles/rails.gemfile.lock", app_dir.join("Gemfile.lock"))
+            .unwrap();
+        let error = paths.lockfile_exists().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Gemfile.lock is a symlink to gemfiles/rails.gemfile.lock, which does not exist"
+        );
+
+        fs_err::write(app_dir.join("gemfiles/rails.gemfile.lock"), "").unwrap();
+        assert!(paths.lockfile_exists().unwrap());
+        assert_eq!(
+            paths.lockfile_symlink_target(),
+            Some(String::from("gemfiles/rails.gemfile.lock"))
+        );
+    }
+}
@@ -1,5 +1,14 @@
+pub(crate) mod assets_digest_layer;
+pub(crate) mod build_logs_layer;
+pub(crate) mod build_report_layer;
+pub(crate) mod bundle_check_install;
 pub(crate) mod bundle_download_layer;
 pub(crate) mod bundle_install_layer;
+pub(crate) mod doctor_install;
+pub(crate) mod inventory_layer;
+pub(crate) mod jruby_warmup_layer;
 pub(crate) mod metrics_agent_install;
+pub(crate) mod path_check_install;
 pub(crate) mod ruby_install_layer;
 mod shared;
+pub(crate) mod writable_tmp_install;
@@ -0,0 +1,339 @@
+//! # Skip asset compilation when asset sources are unchanged
+//!
+//! ## Layer dir: Empty, metadata only
+//!
+//! Stores a digest of the files that affect the output of `rake assets:precompile` (asset
+//! directories, JS lockfiles, and asset related config) along with the platform environment,
+//! and a digest of the compiled output. When the inputs match the prior build and every output
+//! directory was restored from the cache exactly as it was compiled, the rake task is skipped.
+//! An output directory that's missing or partly evicted from the cache compiles again.
+//!
+//! Only the two hashes are stored, not a list of files, so the metadata stays small no matter
+//! how many assets an application has.
+//!
+//! Set `HEROKU_SKIP_ASSETS_DIGEST=1` to always compile assets.
+use crate::gemfile_path::GemfilePaths;
+use crate::telemetry::Telemetry;
+use crate::RubyBuildpack;
+use crate::RubyBuildpackError;
+use cache_diff::CacheDiff;
+use commons::assets;
+use commons::layer::diff_migrate::{DiffMigrateLayer, Meta};
+use commons::metadata_digest::{sha_from_files, CombinedDigest, DigestError, MetadataDigest};
+use libcnb::build::BuildContext;
+use libcnb::data::layer_name;
+use libcnb::layer::{LayerRef, LayerState};
+use libcnb::Platform;
+use magic_migrate::{try_migrate_deserializer_chain, TryMigrate};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+pub(crate) const SKIP_ASSETS_DIGEST_ENV_KEY: &str = "HEROKU_SKIP_ASSETS_DIGEST";
+
+/// Directories, relative to the application, whose contents are compiled into assets
+const ASSET_DIRS: [&str; 4] = [
+    "app/assets",
+    "app/javascript",
+    "lib/assets",
+    "vendor/assets",
+];
+
+/// Files, relative to the application, that change how assets are compiled
+///
+/// The lockfile is added separately, it depends on `BUNDLE_GEMFILE`
+const ASSET_FILES: [&str; 14] = [
+    "package.json",
+    "package-lock.json",
+    "yarn.lock",
+    "pnpm-lock.yaml",
+    "bun.lockb",
+    "config/application.rb",
+    "config/environments/production.rb",
+    "config/initializers/assets.rb",
+    "config/importmap.rb",
+    "config/shakapacker.yml",
+    "config/webpacker.yml",
+    "postcss.config.js",
+    "tailwind.config.js",
+    "babel.config.js",
+];
+
+#[derive(Debug)]
+pub(crate) enum AssetsState {
+    /// Holds the reason assets need to be compiled
+    Compile(String),
+
+    /// Asset sources and the platform env match the prior build
+    Unchanged,
+}
+
+/// The digest layer, kept until the compiled output is recorded with [`AssetsDigest::save`]
+pub(crate) struct AssetsDigest {
+    layer_ref: LayerRef<RubyBuildpack, Meta<Metadata>, Meta<Metadata>>,
+    metadata: Metadata,
+}
+
+impl AssetsDigest {
+    /// Records the compiled output, call it after asset tasks run (or are skipped)
+    pub(crate) fn save(
+        self,
+        context: &BuildContext<RubyBuildpack>,
+    ) -> libcnb::Result<(), RubyBuildpackError> {
+        self.layer_ref.write_metadata(Metadata {
+            outputs: outputs_digest(&context.app_dir)?,
+            ..self.metadata
+        })?;
+        Ok(())
+    }
+}
+
+/// Call after restoring cached output directories, compares inputs and outputs to the prior build
+pub(crate) fn handle(
+    context: &BuildContext<RubyBuildpack>,
+    telemetry: &mut Telemetry,
+) -> libcnb::Result<(AssetsState, AssetsDigest), RubyBuildpackError> {
+    let metadata = Metadata {
+        outputs: outputs_digest(&context.app_dir)?,
+        inputs: CombinedDigest::new_env_files(
+            &context.platform,
+            &asset_inputs(
+                &context.app_dir,
+                GemfilePaths::from_env(context.platform.env(), &context.app_dir).lockfile(),
+            )
+            .map_err(|error| RubyBuildpackError::AssetsDigestError(context.app_dir.clone(), error))?
+            .iter()
+            .map(PathBuf::as_path)
+            .collect::<Vec<_>>(),
+        )
+        .map_err(digest_error)?,
+    };
+
+    let layer_ref = DiffMigrateLayer {
+        build: false,
+        launch: false,
+    }
+    .cached_layer(layer_name!("assets_digest"), context, &metadata)?;
+    telemetry.record_layer(&layer_ref.state);
+
+    let state = match &layer_ref.state {
+        LayerState::Restored {
+            cause: Meta::Data(old),
+        } => assets_state(old, &metadata, context.platform.env()),
+        LayerState::Restored { .. } | LayerState::Empty { .. } => {
+            AssetsState::Compile(String::new())
+        }
+    };
+    Ok((
+        state,
+        AssetsDigest {
+            layer_ref,
+            metadata,
+        },
+    ))
+}
+
+fn assets_state(old: &Metadata, now: &Metadata, platform_env: &libcnb::Env) -> AssetsState {
+    if let Some(value) = platform_env.get(SKIP_ASSETS_DIGEST_ENV_KEY) {
+        let value = value.to_string_lossy();
+        AssetsState::Compile(format!("found {SKIP_ASSETS_DIGEST_ENV_KEY}={value}"))
+    } else if let Some(changed) = now.inputs.changed(&old.inputs) {
+        AssetsState::Compile(format!("{changed}"))
+    } else if now.outputs != old.outputs {
+        AssetsState::Compile(String::from(
+            "compiled assets were not fully restored from the cache",
+        ))
+    } else {
+        AssetsState::Unchanged
+    }
+}
+
+fn digest_error(error: DigestError) -> RubyBuildpackError {
+    match error {
+        DigestError::CannotReadFile(path, error) => {
+            RubyBuildpackError::AssetsDigestError(path, error)
+        }
+    }
+}
+
+/// A hash of every file in the output directories, a missing or evicted file changes it
+fn outputs_digest(app_dir: &Path) -> Result<String, RubyBuildpackError> {
+    let mut files = Vec::new();
+    for (dir, _) in assets::OUTPUT_DIRS {
+        files_in(&app_dir.join(dir), &mut files)
+            .map_err(|error| RubyBuildpackError::AssetsDigestError(app_dir.join(dir), error))?;
+    }
+    files.sort();
+    sha_from_files(&files.iter().map(PathBuf::as_path).collect::<Vec<_>>()).map_err(digest_error)
+}
+
+/// Every file in the asset directories, plus the lockfile and asset related files that exist
+pub(crate) fn asset_inputs(
+    app_dir: &Path,
+    lockfile: &Path,
+) -> Result<Vec<PathBuf>, std::io::Error> {
+    let mut files = ASSET_FILES
+        .iter()
+        .map(|file| app_dir.join(file))
+        .chain([lockfile.to_path_buf()])
+        .filter(|path| path.is_file())
+        .collect::<Vec<_>>();
+    for dir in ASSET_DIRS {
+        files_in(&app_dir.join(dir), &mut files)?;
+    }
+    // Compiled output is restored from the cache before this runs
+    files.retain(|path| {
+        !assets::OUTPUT_DIRS
+            .iter()
+            .any(|(dir, _)| path.starts_with(app_dir.join(dir)))
+    });
+    files.sort();
+    Ok(files)
+}
+
+fn files_in(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), std::io::Error> {
+    if !dir.is_dir() {
+        return Ok(());
+    }
+    for entry in fs_err::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            files_in(&path, files)?;
+        } else if path.is_file() {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+pub(crate) type Metadata = MetadataV2;
+try_migrate_deserializer_chain!(
+    deserializer: toml::Deserializer::new,
+    error: MetadataError,
+    chain: [MetadataV1, MetadataV2],
+);
+
+#[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct MetadataV1 {
+    pub(crate) digest: MetadataDigest,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct MetadataV2 {
+    /// Hash of the output directories after the last build, see [`outputs_digest`]
+    pub(crate) outputs: String,
+    pub(crate) inputs: CombinedDigest, // Must be last for serde to be happy https://github.com/toml-rs/toml-rs/issues/142
+}
+
+/// Never clears the layer, digests are compared in [`assets_state`] so the reason can be
+/// reported
+impl CacheDiff for MetadataV2 {
+    fn diff(&self, _old: &Self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// The per file digest of V1 can't be converted, assets compile once after the upgrade
+impl From<MetadataV1> for MetadataV2 {
+    fn from(_v1: MetadataV1) -> Self {
+        Self {
+            outputs: String::new(),
+            inputs: CombinedDigest::default(),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum MetadataError {
+    // Update if migrating between a metadata version can error
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libcnb::Env;
+
+    #[test]
+    fn test_asset_inputs() {
+        let dir = tempfile::tempdir().unwrap();
+        let app_dir = dir.path();
+        fs_err::create_dir_all(app_dir.join("app/assets/images")).unwrap();
+        fs_err::write(app_dir.join("app/assets/images/logo.svg"), "<svg/>").unwrap();
+        fs_err::create_dir_all(app_dir.join("app/assets/builds")).unwrap();
+        fs_err::write(app_dir.join("app/assets/builds/application.css"), "").unwrap();
+        fs_err::write(app_dir.join("yarn.lock"), "").unwrap();
+        fs_err::write(app_dir.join("README.md"), "").unwrap();
+        fs_err::write(app_dir.join("Gemfile_next.lock"), "").unwrap();
+
+        assert_eq!(
+            asset_inputs(app_dir, &app_dir.join("Gemfile.lock")).unwrap(),
+            vec![
+                app_dir.join("app/assets/images/logo.svg"),
+                app_dir.join("yarn.lock")
+            ]
+        );
+        assert_eq!(
+            asset_inputs(app_dir, &app_dir.join("Gemfile_next.lock")).unwrap(),
+            vec![
+                app_dir.join("Gemfile_next.lock"),
+                app_dir.join("app/assets/images/logo.svg"),
+                app_dir.join("yarn.lock")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_assets_state() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("yarn.lock");
+        fs_err::write(&file, "v1").unwrap();
+        fs_err::create_dir_all(dir.path().join("public/assets")).unwrap();
+        fs_err::write(dir.path().join("public/assets/app-abc123.css"), "").unwrap();
+
+        let platform = libcnb::generic::GenericPlatform::new(Env::new());
+        let old = Metadata {
+            outputs: outputs_digest(dir.path()).unwrap(),
+            inputs: CombinedDigest::new_env_files(&platform, &[&file]).unwrap(),
+        };
+        let mut env = Env::new();
+        assert!(matches!(
+            assets_state(&old, &old.clone(), &env),
+            AssetsState::Unchanged
+        ));
+
+        env.insert(SKIP_ASSETS_DIGEST_ENV_KEY, "1");
+        assert!(matches!(
+            assets_state(&old, &old.clone(), &env),
+            AssetsState::Compile(_)
+        ));
+
+        fs_err::remove_file(dir.path().join("public/assets/app-abc123.css")).unwrap();
+        let evicted = Metadata {
+            outputs: outputs_digest(dir.path()).unwrap(),
+            ..old.clone()
+        };
+        assert!(matches!(
+            assets_state(&old, &evicted, &Env::new()),
+            AssetsState::Compile(reason) if reason.contains("not fully restored")
+        ));
+
+        fs_err::write(&file, "v2").unwrap();
+        let now = Metadata {
+            inputs: CombinedDigest::new_env_files(&platform, &[&file]).unwrap(),
+            ..old.clone()
+        };
+        assert!(matches!(
+            assets_state(&old, &now, &Env::new()),
+            AssetsState::Compile(_)
+        ));
+    }
+
+    #[test]
+    fn test_migrate_v1() {
+        let v2 = Metadata::try_from_str_migrations("digest = {}")
+            .unwrap()
+            .unwrap();
+        assert_eq!(v2.inputs, CombinedDigest::default());
+    }
+}
@@ -0,0 +1,253 @@
+//! # Keep the full output of key build commands
+//!
+//! ## Layer dir: Log files
+//!
+//! The full output of `bundle install`, the rake asset tasks, and `jekyll build` is written to
+//! `<layer-dir>/<step>.log` while it's streamed to the build output. The layer is cached and
+//! available at launch, so the complete log of a build can be read from the image (for
+//! example with `heroku run cat /layers/heroku_ruby/logs/bundle_install.log`).
+//!
+//! Layers are only exported when the build succeeds. The log of a failed build is never
+//! available in an image, the streamed build output is the only record of that build.
+//!
+//! Logs rotate at the start of each build, `bundle_install.log` becomes `bundle_install.1.log`
+//! and so on, keeping the logs of the prior `KEEP_PRIOR` builds. Each log stops growing at
+//! `MAX_LOG_BYTES`. Writing logs is best effort, failing to write one never fails the build.
+use crate::telemetry::Telemetry;
+use crate::{RubyBuildpack, RubyBuildpackError};
+use libcnb::build::BuildContext;
+use libcnb::data::layer_name;
+use libcnb::generic::GenericMetadata;
+use libcnb::layer::{CachedLayerDefinition, InvalidMetadataAction, RestoredLayerAction};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+const LOGS_DIR: &str = "logs";
+const KEEP_PRIOR: usize = 2;
+const MAX_LOG_BYTES: u64 = 5 * 1024 * 1024;
+
+/// Names of the logs written during the build
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum BuildLog {
+    BundleInstall,
+    RakeAssets,
+    JekyllBuild,
+}
+
+impl BuildLog {
+    const ALL: [BuildLog; 3] = [
+        BuildLog::BundleInstall,
+        BuildLog::RakeAssets,
+        BuildLog::JekyllBuild,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            BuildLog::BundleInstall => "bundle_install",
+            BuildLog::RakeAssets => "rake_assets",
+            BuildLog::JekyllBuild => "jekyll_build",
+        }
+    }
+}
+
+/// Creates the logs layer and rotates logs from prior builds
+pub(crate) fn handle(
+    context: &BuildContext<RubyBuildpack>,
+    telemetry: &mut Telemetry,
+) -> libcnb::Result<(), RubyBuildpackError> {
+    let layer_ref = context.cached_layer(
+        layer_name!("logs"),
+        CachedLayerDefinition {
+            build: false,
+            launch: true,
+            invalid_metadata_action: &|_| InvalidMetadataAction::DeleteLayer,
+            restored_layer_action: &|_: &GenericMetadata, _| RestoredLayerAction::KeepLayer,
+        },
+    )?;
+    telemetry.record_layer(&layer_ref.state);
+    for log in BuildLog::ALL {
+        // Best effort, a log that can't be rotated is overwritten
+        let _ = rotate(&layer_ref.path(), log.name());
+    }
+    Ok(())
+}
+
+/// Wraps the writers of a streamed command so its output is also written to a log
+pub(crate) fn tee<O: Write, E: Write>(
+    context: &BuildContext<RubyBuildpack>,
+    log: BuildLog,
+    stdout: O,
+    stderr: E,
+) -> (Tee<O>, Tee<E>) {
+    let log = Arc::new(Mutex::new(LogFile::open(&log_path(
+        &context.layers_dir.join(LOGS_DIR),
+        log.name(),
+        0,
+    ))));
+    (
+        Tee {
+            inner: stdout,
+            log: Arc::clone(&log),
+        },
+        Tee { inner: stderr, log },
+    )
+}
+
+/// A log file shared by the stdout and stderr of a command, and the bytes it has left
+struct LogFile {
+    file: Option<std::fs::File>,
+    remaining: u64,
+}
+
+impl LogFile {
+    /// Appends to the log, the same log can be written by more than one command in a build
+    fn open(path: &Path) -> Self {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .ok();
+        let remaining = file
+            .as_ref()
+            .and_then(|file| file.metadata().ok())
+            .map_or(0, |metadata| MAX_LOG_BYTES.saturating_sub(metadata.len()));
+        Self {
+            file: file.filter(|_| remaining > 0),
+            remaining,
+        }
+    }
+
+    /// Writes what fits in the remaining bytes, then marks the log truncated and closes it
+    fn write(&mut self, buf: &[u8]) {
+        let Some(file) = &mut self.file else {
+            return;
+        };
+        let len = buf
+            .len()
+            .min(usize::try_from(self.remaining).unwrap_or(usize::MAX));
+        if file.write_all(&buf[..len]).is_err() || len < buf.len() {
+            let _ = file.write_all(b"\n[log truncated]\n");
+            self.file = None;
+        }
+        self.remaining -= len as u64;
+    }
+}
+
+/// Writes to the build output, and to a log file until it reaches `MAX_LOG_BYTES`
+pub(crate) struct Tee<W> {
+    inner: W,
+    log: Arc<Mutex<LogFile>>,
+}
+
+impl<W: Write> Write for Tee<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        if let Ok(mut log) = self.log.lock() {
+            log.write(&buf[..written]);
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+fn log_path(dir: &Path, name: &str, generation: usize) -> PathBuf {
+    if generation == 0 {
+        dir.join(format!("{name}.log"))
+    } else {
+        dir.join(format!("{name}.{generation}.log"))
+    }
+}
+
+/// Shifts `<name>.log` to `<name>.1.log` and so on, removing logs older than `KEEP_PRIOR`
+fn rotate(dir: &Path, name: &str) -> Result<(), std::io::Error> {
+    let oldest = log_path(dir, name, KEEP_PRIOR);
+    if oldest.exists() {
+        fs_err::remove_file(oldest)?;
+    }
+    for generation in (0..KEEP_PRIOR).rev() {
+        let from = log_path(dir, name, generation);
+        if from.exists() {
+            fs_err::rename(from, log_path(dir, name, generation + 1))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rotate() {
+        let dir = tempfile::tempdir().unwrap();
+        let read = |generation| fs_err::read_to_string(log_path(dir.path(), "rake", generation));
+
+        for build in ["one", "two", "three"] {
+            rotate(dir.path(), "rake").unwrap();
+            fs_err::write(log_path(dir.path(), "rake", 0), build).unwrap();
+        }
+
+        assert_eq!(read(0).unwrap(), "three");
+        assert_eq!(read(1).unwrap(), "two");
+        assert_eq!(read(2).unwrap(), "one");
+
+        rotate(dir.path(), "rake").unwrap();
+        assert!(read(0).is_err());
+        assert_eq!(read(2).unwrap(), "two");
+    }
+
+    #[test]
+    fn test_tee_writes_both() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.log");
+        let mut output = Vec::new();
+        let mut tee = Tee {
+            inner: &mut output,
+            log: Arc::new(Mutex::new(LogFile::open(&path))),
+        };
+        write!(tee, "Fetching rack 3.1.8").unwrap();
+        drop(tee);
+
+        assert_eq!(output, b"Fetching rack 3.1.8");
+        assert_eq!(fs_err::read_to_string(path).unwrap(), "Fetching rack 3.1.8");
+    }
+
+    #[test]
+    fn test_log_truncated_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.log");
+        fs_err::write(
+            &path,
+            "a".repeat(usize::try_from(MAX_LOG_BYTES).unwrap() - 4),
+        )
+        .unwrap();
+
+        let log = Arc::new(Mutex::new(LogFile::open(&path)));
+        let (mut stdout, mut stderr) = (
+            Tee {
+                inner: Vec::new(),
+                log: Arc::clone(&log),
+            },
+            Tee {
+                inner: Vec::new(),
+                log,
+            },
+        );
+        write!(stdout, "bbbbbb").unwrap();
+        write!(stderr, "cccccc").unwrap();
+        assert_eq!(stdout.inner, b"bbbbbb");
+        assert_eq!(stderr.inner, b"cccccc");
+
+        let contents = fs_err::read_to_string(&path).unwrap();
+        assert!(contents.ends_with("abbbb\n[log truncated]\n"));
+        assert_eq!(contents.matches("[log truncated]").count(), 1);
+
+        // A full log from an earlier command in the same build is left alone
+        LogFile::open(&path).write(b"dddd");
+        assert_eq!(fs_err::read_to_string(&path).unwrap(), contents);
+    }
+}
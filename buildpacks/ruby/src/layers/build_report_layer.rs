@@ -0,0 +1,229 @@
+//! # Report what changed since the last build
+//!
+//! ## Layer dir: Empty, metadata only
+//!
+//! Stores a snapshot of the inputs that decide which steps re-run: the Ruby and Bundler
+//! versions, the gems in `Gemfile.lock`, the names of user configured environment variables,
+//! and a digest of asset sources and the platform environment. On a rebuild the snapshot from
+//! the prior successful build is compared with the current one and the differences are printed
+//! before any step runs, so users can see why steps re-ran.
+use crate::gemfile_path::GemfilePaths;
+use crate::layers::assets_digest_layer::asset_inputs;
+use crate::telemetry::Telemetry;
+use crate::{RubyBuildpack, RubyBuildpackError};
+use bullet_stream::state::Bullet;
+use bullet_stream::{style, Print};
+use commons::display::SentenceList;
+use commons::gemfile_lock::{locked_gems, ResolvedBundlerVersion, ResolvedRubyVersion};
+use commons::metadata_digest::{CombinedChanged, CombinedDigest, DigestError};
+use libcnb::build::BuildContext;
+use libcnb::data::layer_name;
+use libcnb::layer::{
+    CachedLayerDefinition, InvalidMetadataAction, LayerState, RestoredLayerAction,
+};
+use libcnb::Platform;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Stdout;
+use std::path::PathBuf;
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Snapshot {
+    ruby_version: String,
+    bundler_version: String,
+    env_names: Vec<String>,
+    /// Gem name to locked version, platform specific versions are joined with `, `
+    gems: BTreeMap<String, String>,
+    /// Asset sources and the platform environment
+    digest: CombinedDigest, // Must be last for serde to be happy https://github.com/toml-rs/toml-rs/issues/142
+}
+
+/// Prints the changes since the last successful build, nothing is printed on a first build
+pub(crate) fn handle(
+    mut output: Print<Bullet<Stdout>>,
+    context: &BuildContext<RubyBuildpack>,
+    lockfile_contents: &str,
+    ruby_version: &ResolvedRubyVersion,
+    bundler_version: &ResolvedBundlerVersion,
+    telemetry: &mut Telemetry,
+) -> libcnb::Result<Print<Bullet<Stdout>>, RubyBuildpackError> {
+    let snapshot = Snapshot {
+        ruby_version: ruby_version.to_string(),
+        bundler_version: bundler_version.to_string(),
+        env_names: context
+            .platform
+            .env()
+            .iter()
+            .map(|(key, _)| key.to_string_lossy().to_string())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect(),
+        gems: locked_gems(lockfile_contents),
+        digest: CombinedDigest::new_env_files(
+            &context.platform,
+            &asset_inputs(
+                &context.app_dir,
+                GemfilePaths::from_env(context.platform.env(), &context.app_dir).lockfile(),
+            )
+            .map_err(|error| RubyBuildpackError::AssetsDigestError(context.app_dir.clone(), error))?
+            .iter()
+            .map(PathBuf::as_path)
+            .collect::<Vec<_>>(),
+        )
+        .map_err(|error| match error {
+            DigestError::CannotReadFile(path, error) => {
+                RubyBuildpackError::AssetsDigestError(path, error)
+            }
+        })?,
+    };
+
+    let layer_ref = context.cached_layer(
+        layer_name!("build_report"),
+        CachedLayerDefinition {
+            build: false,
+            launch: false,
+            invalid_metadata_action: &|_| (InvalidMetadataAction::DeleteLayer, None::<Snapshot>),
+            restored_layer_action: &|old: &Snapshot, _| {
+                (RestoredLayerAction::KeepLayer, Some(old.clone()))
+            },
+        },
+    )?;
+    telemetry.record_layer(&layer_ref.state);
+
+    if let LayerState::Restored { cause: Some(old) } = &layer_ref.state {
+        let mut bullet = output.bullet("Changes since last build");
+        let changes = changes(old, &snapshot);
+        if changes.is_empty() {
+            bullet = bullet.sub_bullet("No changes detected");
+        }
+        for change in changes {
+            bullet = bullet.sub_bullet(change);
+        }
+        output = bullet.done();
+    }
+    layer_ref.write_metadata(snapshot)?;
+
+    Ok(output)
+}
+
+fn changes(old: &Snapshot, now: &Snapshot) -> Vec<String> {
+    let mut changes = Vec::new();
+    for (name, old, now) in [
+        ("Ruby", &old.ruby_version, &now.ruby_version),
+        ("Bundler", &old.bundler_version, &now.bundler_version),
+    ] {
+        if old != now {
+            changes.push(format!(
+                "{name} version changed from {} to {}",
+                style::value(old),
+                style::value(now)
+            ));
+        }
+    }
+
+    let added = now.gems.keys().filter(|gem| !old.gems.contains_key(*gem));
+    let removed = old.gems.keys().filter(|gem| !now.gems.contains_key(*gem));
+    let updated = now
+        .gems
+        .iter()
+        .filter(|(gem, version)| old.gems.get(*gem).is_some_and(|old| old != *version));
+    let gems = [
+        (added.count(), "added"),
+        (removed.count(), "removed"),
+        (updated.count(), "updated"),
+    ]
+    .into_iter()
+    .filter(|(count, _)| *count > 0)
+    .map(|(count, action)| match count {
+        1 => format!("1 gem {action}"),
+        _ => format!("{count} gems {action}"),
+    })
+    .collect::<Vec<_>>();
+    if !gems.is_empty() {
+        changes.push(format!(
+            "{} in {}",
+            SentenceList::new(&gems),
+            style::value("Gemfile.lock")
+        ));
+    }
+
+    let digest = now.digest.changed(&old.digest);
+    if digest.as_ref().is_some_and(CombinedChanged::files_changed) {
+        changes.push(String::from("Asset sources changed"));
+    }
+
+    let names = |from: &Snapshot, to: &Snapshot| {
+        from.env_names
+            .iter()
+            .filter(|name| !to.env_names.contains(name))
+            .map(style::value)
+            .collect::<Vec<_>>()
+    };
+    let added = names(now, old);
+    let removed = names(old, now);
+    if !added.is_empty() {
+        changes.push(format!(
+            "Environment variables added: {}",
+            SentenceList::new(&added)
+        ));
+    }
+    if !removed.is_empty() {
+        changes.push(format!(
+            "Environment variables removed: {}",
+            SentenceList::new(&removed)
+        ));
+    }
+    if added.is_empty()
+        && removed.is_empty()
+        && digest.is_some_and(|changed| changed.platform_env_changed())
+    {
+        changes.push(String::from("Environment variable values changed"));
+    }
+
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bullet_stream::strip_ansi;
+
+    fn snapshot(gems: &[(&str, &str)], env_names: &[&str]) -> Snapshot {
+        Snapshot {
+            ruby_version: String::from("3.3.5"),
+            bundler_version: String::from("2.5.6"),
+            env_names: env_names.iter().map(ToString::to_string).collect(),
+            gems: gems
+                .iter()
+                .map(|(name, version)| ((*name).to_string(), (*version).to_string()))
+                .collect(),
+            digest: CombinedDigest::default(),
+        }
+    }
+
+    #[test]
+    fn test_changes() {
+        let old = snapshot(&[("rack", "3.1.7"), ("puma", "6.4.2")], &["RAILS_ENV"]);
+        assert!(changes(&old, &old.clone()).is_empty());
+
+        let now = Snapshot {
+            ruby_version: String::from("3.3.6"),
+            ..snapshot(
+                &[("rack", "3.1.8"), ("rails", "7.2.1"), ("railties", "7.2.1")],
+                &["RAILS_ENV", "SECRET_KEY_BASE"],
+            )
+        };
+        assert_eq!(
+            changes(&old, &now)
+                .iter()
+                .map(strip_ansi)
+                .collect::<Vec<_>>(),
+            vec![
+                "Ruby version changed from `3.3.5` to `3.3.6`",
+                "2 gems added, 1 gem removed, and 1 gem updated in `Gemfile.lock`",
+                "Environment variables added: `SECRET_KEY_BASE`",
+            ]
+        );
+    }
+}
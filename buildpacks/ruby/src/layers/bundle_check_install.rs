@@ -0,0 +1,62 @@
+//! # Check installed gems when the container starts
+//!
+//! ## Layer dir
+//!
+//! Writes an exec.d script to `<layer-dir>/bundle_check` that runs `bundle check` each time
+//! the container starts. The layer is only available at launch.
+//!
+//! When the gems layer and `Gemfile.lock` have drifted (for example after modifying an image
+//! without rebuilding it), the script prints an explanation before the application boots
+//! instead of the application failing with a `Gem::LoadError`. The script always exits
+//! successfully so it never prevents the container from starting.
+//!
+//! Opt-in with `HEROKU_RUBY_FEATURE_BUNDLE_CHECK_AT_LAUNCH=1`.
+use crate::features::Feature;
+use crate::{RubyBuildpack, RubyBuildpackError};
+use bullet_stream::state::SubBullet;
+use bullet_stream::{style, Print};
+use libcnb::data::layer_name;
+use libcnb::layer::UncachedLayerDefinition;
+use std::fs::Permissions;
+use std::io::Stdout;
+use std::os::unix::fs::PermissionsExt;
+
+const SCRIPT: &str = r#"#!/usr/bin/env bash
+
+if ! output=$(bundle check 2>&1); then
+  echo "! Error: Installed gems do not match the Gemfile.lock"
+  echo "!"
+  echo "! \`bundle check\` failed when the container started. The gems in this image"
+  echo "! were not installed from the current Gemfile.lock, the application may fail"
+  echo "! to boot with a Gem::LoadError. Rebuild the image to install the correct gems."
+  echo "!"
+  echo "${output}" | sed 's/^/!     /'
+fi
+
+exit 0
+"#;
+
+pub(crate) fn handle(
+    context: &libcnb::build::BuildContext<RubyBuildpack>,
+    bullet: Print<SubBullet<Stdout>>,
+) -> libcnb::Result<Print<SubBullet<Stdout>>, RubyBuildpackError> {
+    let layer_ref = context.uncached_layer(
+        layer_name!("bundle_check"),
+        UncachedLayerDefinition {
+            build: false,
+            launch: true,
+        },
+    )?;
+    let script = layer_ref.path().join("bundle_check");
+
+    fs_err::write(&script, SCRIPT)
+        .and_then(|()| fs_err::set_permissions(&script, Permissions::from_mode(0o755)))
+        .map_err(RubyBuildpackError::BundleCheckInstallError)?;
+    layer_ref.write_exec_d_programs([("bundle_check".to_string(), script)])?;
+
+    Ok(bullet.sub_bullet(format!(
+        "Installed a launch check that runs {} when the container starts (enabled with {})",
+        style::command("bundle check"),
+        style::value(format!("{}=1", Feature::BundleCheckAtLaunch.env_key()))
+    )))
+}
@@ -4,36 +4,120 @@
 //!
 //! Installs a copy of `bundler` to the `<layer-dir>` with a bundler executable in
 //! `<layer-dir>/bin`. Must run before [`crate.steps.bundle_install`].
+//!
+//! When the application vendors the exact bundler gem in `vendor/cache` or `vendor/bundler`
+//! it's installed from disk instead of rubygems.org, so builds work without network access.
+//!
+//! When bundler is installed from rubygems.org and a newer patch release of the same
+//! `<major>.<minor>` exists, a notice recommends upgrading. Releases come from the
+//! [`crate::layers::inventory_layer`], so no request is made each build.
+//!
+//! On a cache miss the bundler gem is downloaded with [`prefetch`] while Ruby is installed, the
+//! two downloads are independent. `gem install` needs Ruby, so it runs afterwards and installs
+//! the prefetched gem from disk.
+//!
+//! The layer is keyed on the bundler version and the Ruby ABI version (for example `3.3.0`).
+//! Upgrading to a new Ruby `<major>.<minor>` clears the layer so `gem install` regenerates the
+//! `bundle` executable and gem stubs for the new Ruby.
+use crate::diagnostics::Diagnostics;
+use crate::features::Features;
+use crate::http::{self, ProxyConfig};
+use crate::layers::inventory_layer::Inventory;
+use crate::telemetry::Telemetry;
 use crate::RubyBuildpack;
 use crate::RubyBuildpackError;
 use bullet_stream::state::SubBullet;
 use bullet_stream::{style, Print};
 use cache_diff::CacheDiff;
 use commons::gemfile_lock::ResolvedBundlerVersion;
-use commons::layer::diff_migrate::DiffMigrateLayer;
+use commons::layer::diff_migrate::{DiffMigrateLayer, Meta};
 use fun_run::{self, CommandWithName};
 use libcnb::data::layer_name;
-use libcnb::layer::{EmptyLayerCause, LayerState};
+use libcnb::layer::{EmptyLayerCause, LayerRef, LayerState};
 use libcnb::layer_env::{LayerEnv, ModificationBehavior, Scope};
 use libcnb::Env;
 use magic_migrate::{try_migrate_deserializer_chain, TryMigrate};
 use serde::{Deserialize, Serialize};
 use std::io::Stdout;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use tempfile::TempDir;
 
-pub(crate) fn handle(
+/// Where rubygems.org serves `.gem` files
+const BUNDLER_DOWNLOAD_URL: &str = "https://rubygems.org/downloads";
+
+/// The bundler layer, created before Ruby is installed so a download can start early
+pub(crate) struct BundlerLayer {
+    layer_ref: LayerRef<RubyBuildpack, Meta<Metadata>, Meta<Metadata>>,
+    metadata: Metadata,
+}
+
+impl BundlerLayer {
+    /// True when the layer was not restored and there's no vendored gem to install from
+    pub(crate) fn needs_download(&self, app_dir: &Path, version: &ResolvedBundlerVersion) -> bool {
+        matches!(self.layer_ref.state, LayerState::Empty { .. })
+            && vendored_bundler_gem(app_dir, version).is_none()
+    }
+}
+
+pub(crate) fn layer(
     context: &libcnb::build::BuildContext<RubyBuildpack>,
-    env: &Env,
-    mut bullet: Print<SubBullet<Stdout>>,
     metadata: &Metadata,
-) -> libcnb::Result<(Print<SubBullet<Stdout>>, LayerEnv), RubyBuildpackError> {
+    telemetry: &mut Telemetry,
+) -> libcnb::Result<BundlerLayer, RubyBuildpackError> {
     let layer_ref = DiffMigrateLayer {
         build: true,
         launch: true,
     }
     .cached_layer(layer_name!("bundler"), context, metadata)?;
+    telemetry.record_layer(&layer_ref.state);
+
+    Ok(BundlerLayer {
+        layer_ref,
+        metadata: metadata.clone(),
+    })
+}
+
+/// A bundler gem downloaded before Ruby is installed
+pub(crate) struct PrefetchedGem {
+    path: PathBuf,
+    // Removes the gem when dropped
+    _dir: TempDir,
+}
 
+/// Downloads the bundler gem from rubygems.org, this does not need Ruby
+///
+/// Failures are not reported, without a prefetched gem `gem install` downloads it instead and
+/// reports any error.
+pub(crate) fn prefetch(
+    proxy: &ProxyConfig,
+    version: &ResolvedBundlerVersion,
+) -> Option<PrefetchedGem> {
+    let dir = tempfile::tempdir().ok()?;
+    let path = dir.path().join(format!("bundler-{version}.gem"));
+    http::download(
+        proxy,
+        format!("{BUNDLER_DOWNLOAD_URL}/bundler-{version}.gem"),
+        &path,
+        |_| {},
+    )
+    .ok()?;
+
+    Some(PrefetchedGem { path, _dir: dir })
+}
+
+pub(crate) fn handle(
+    context: &libcnb::build::BuildContext<RubyBuildpack>,
+    env: &Env,
+    features: &Features,
+    inventory: &Inventory,
+    mut bullet: Print<SubBullet<Stdout>>,
+    BundlerLayer {
+        layer_ref,
+        metadata,
+    }: BundlerLayer,
+    prefetched: Option<&PrefetchedGem>,
+) -> libcnb::Result<(Print<SubBullet<Stdout>>, LayerEnv), RubyBuildpackError> {
     let layer_env = LayerEnv::new()
         .chainable_insert(Scope::All, ModificationBehavior::Delimiter, "PATH", ":")
         .chainable_insert(
@@ -63,24 +147,61 @@ pub(crate) fn handle(
                     bullet = bullet.sub_bullet(cause);
                 }
             }
-            bullet = download_bundler(bullet, env, metadata, &layer_ref.path())?;
+            bullet = download_bundler(
+                bullet,
+                env,
+                features,
+                &metadata,
+                &context.app_dir,
+                prefetched,
+                &layer_ref.path(),
+            )?;
+        }
+    }
+    if vendored_bundler_gem(&context.app_dir, &metadata.version).is_none() {
+        if let Some(newer) = newer_patch_release(inventory, &metadata.version) {
+            bullet = bullet.sub_bullet(format!(
+                "Bundler {newer} is available, you are on {current} (see {url})",
+                newer = style::value(newer.to_string()),
+                current = style::value(metadata.version.to_string()),
+                url = style::url("https://devcenter.heroku.com/articles/bundler-version"),
+            ));
         }
     }
     Ok((bullet, layer_ref.read_env()?))
 }
 
-pub(crate) type Metadata = MetadataV1;
+pub(crate) type Metadata = MetadataV2;
 try_migrate_deserializer_chain!(
     deserializer: toml::Deserializer::new,
     error: MetadataError,
-    chain: [MetadataV1],
+    chain: [MetadataV1, MetadataV2],
 );
 
-#[derive(Deserialize, Serialize, Debug, Clone, CacheDiff)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(deny_unknown_fields)]
 pub(crate) struct MetadataV1 {
+    pub(crate) version: ResolvedBundlerVersion,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, CacheDiff)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct MetadataV2 {
     #[cache_diff(rename = "Bundler version")]
     pub(crate) version: ResolvedBundlerVersion,
+    /// From [`ResolvedRubyVersion::abi`](commons::gemfile_lock::ResolvedRubyVersion::abi)
+    #[cache_diff(rename = "Ruby ABI version")]
+    pub(crate) ruby_abi: String,
+}
+
+/// Layers from before the Ruby ABI was recorded are cleared on the next build
+impl From<MetadataV1> for MetadataV2 {
+    fn from(v1: MetadataV1) -> Self {
+        Self {
+            version: v1.version,
+            ruby_abi: String::from("unknown"),
+        }
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -88,19 +209,47 @@ pub(crate) enum MetadataError {
     // Update if migrating between a metadata version can error
 }
 
+/// Directories, relative to the application, that are searched for a vendored bundler gem
+const VENDORED_BUNDLER_DIRS: &[&str] = &["vendor/cache", "vendor/bundler"];
+
+/// Returns the path to a vendored `bundler-<version>.gem` relative to the app dir, if one exists
+fn vendored_bundler_gem(app_dir: &Path, version: &ResolvedBundlerVersion) -> Option<PathBuf> {
+    VENDORED_BUNDLER_DIRS
+        .iter()
+        .map(|dir| Path::new(dir).join(format!("bundler-{version}.gem")))
+        .find(|path| app_dir.join(path).is_file())
+}
+
 fn download_bundler(
-    bullet: Print<SubBullet<Stdout>>,
+    mut bullet: Print<SubBullet<Stdout>>,
     env: &Env,
+    features: &Features,
     metadata: &Metadata,
+    app_dir: &Path,
+    prefetched: Option<&PrefetchedGem>,
     gem_path: &Path,
 ) -> Result<Print<SubBullet<Stdout>>, RubyBuildpackError> {
     let bin_dir = gem_path.join("bin");
 
     let mut cmd = Command::new("gem");
-    cmd.args(["install", "bundler"]);
-    cmd.args(["--version", &metadata.version.to_string()]) // Specify exact version to install
-        .env_clear()
-        .envs(env);
+    if let Some(vendored) = vendored_bundler_gem(app_dir, &metadata.version) {
+        bullet = bullet.sub_bullet(format!(
+            "Using vendored {}",
+            style::value(vendored.to_string_lossy())
+        ));
+        cmd.args(["install", "--local"])
+            .arg(&vendored) // Install from disk without contacting rubygems.org
+            .current_dir(app_dir);
+    } else if let Some(PrefetchedGem { path, .. }) = prefetched {
+        bullet = bullet.sub_bullet("Downloaded while installing Ruby");
+        cmd.args(["install", "--local"])
+            .arg(path.file_name().unwrap_or_default())
+            .current_dir(path.parent().unwrap_or(app_dir));
+    } else {
+        cmd.args(["install", "bundler"]);
+        cmd.args(["--version", &metadata.version.to_string()]); // Specify exact version to install
+    }
+    cmd.env_clear().envs(env);
 
     let short_name = fun_run::display(&mut cmd); // Format `gem install --version <version>` without other content for display
 
@@ -116,46 +265,154 @@ fn download_bundler(
 
     cmd.named_output()
         .map_err(|error| fun_run::map_which_problem(error, cmd.mut_cmd(), env.get("PATH").cloned()))
-        .map_err(RubyBuildpackError::GemInstallBundlerCommandError)?;
+        .map_err(|error| {
+            RubyBuildpackError::GemInstallBundlerCommandError(
+                error,
+                Diagnostics::command(
+                    features,
+                    Command::new("gem").arg("env").env_clear().envs(env),
+                ),
+            )
+        })?;
 
     Ok(timer.done())
 }
 
+/// Returns a newer patch release of bundler published on rubygems.org
+///
+/// This is advisory, releases that could not be listed are treated as no newer release.
+fn newer_patch_release(
+    inventory: &Inventory,
+    version: &ResolvedBundlerVersion,
+) -> Option<ResolvedBundlerVersion> {
+    newest_patch(
+        &version.to_string(),
+        inventory.bundler_versions().iter().map(String::as_str),
+    )
+    .map(ResolvedBundlerVersion)
+}
+
+/// Returns the highest `<major>.<minor>.<patch>` release that only differs from the current
+/// version by a higher patch number
+fn newest_patch<'a>(current: &str, releases: impl Iterator<Item = &'a str>) -> Option<String> {
+    let segments = |version: &str| -> Option<(u32, u32, u32)> {
+        let mut parts = version.split('.').map(|part| part.parse::<u32>().ok());
+        match (parts.next()?, parts.next()?, parts.next()?, parts.next()) {
+            (Some(major), Some(minor), Some(patch), None) => Some((major, minor, patch)),
+            _ => None,
+        }
+    };
+    let (major, minor, patch) = segments(current)?;
+
+    releases
+        .filter_map(|release| segments(release).map(|parsed| (parsed, release)))
+        .filter(|(parsed, _)| parsed.0 == major && parsed.1 == minor && parsed.2 > patch)
+        .max_by_key(|(parsed, _)| parsed.2)
+        .map(|(_, release)| release.to_string())
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
     use bullet_stream::strip_ansi;
 
+    #[test]
+    fn test_vendored_bundler_gem() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let app_dir = tmpdir.path();
+        let version = ResolvedBundlerVersion("2.5.6".to_string());
+        assert_eq!(vendored_bundler_gem(app_dir, &version), None);
+
+        fs_err::create_dir_all(app_dir.join("vendor").join("bundler")).unwrap();
+        fs_err::write(
+            app_dir
+                .join("vendor")
+                .join("bundler")
+                .join("bundler-2.5.6.gem"),
+            "",
+        )
+        .unwrap();
+        assert_eq!(
+            vendored_bundler_gem(app_dir, &version),
+            Some(PathBuf::from("vendor/bundler/bundler-2.5.6.gem"))
+        );
+
+        fs_err::create_dir_all(app_dir.join("vendor").join("cache")).unwrap();
+        fs_err::write(
+            app_dir
+                .join("vendor")
+                .join("cache")
+                .join("bundler-2.5.6.gem"),
+            "",
+        )
+        .unwrap();
+        assert_eq!(
+            vendored_bundler_gem(app_dir, &version),
+            Some(PathBuf::from("vendor/cache/bundler-2.5.6.gem"))
+        );
+        assert_eq!(
+            vendored_bundler_gem(app_dir, &ResolvedBundlerVersion("2.5.7".to_string())),
+            None
+        );
+    }
+
     #[test]
     fn test_metadata_diff() {
         let old = Metadata {
             version: ResolvedBundlerVersion("2.3.5".to_string()),
+            ruby_abi: String::from("3.2.0"),
         };
         assert!(old.diff(&old).is_empty());
 
         let diff = Metadata {
             version: ResolvedBundlerVersion("2.3.6".to_string()),
+            ruby_abi: String::from("3.3.0"),
         }
         .diff(&old);
         assert_eq!(
             diff.iter().map(strip_ansi).collect::<Vec<String>>(),
-            vec!["Bundler version (`2.3.5` to `2.3.6`)"]
+            vec![
+                "Bundler version (`2.3.5` to `2.3.6`)",
+                "Ruby ABI version (`3.2.0` to `3.3.0`)"
+            ]
         );
     }
 
+    #[test]
+    fn test_migrate_v1() {
+        let metadata = Metadata::try_from_str_migrations(r#"version = "2.3.6""#)
+            .unwrap()
+            .unwrap();
+        assert_eq!(metadata.ruby_abi, "unknown");
+    }
+
     /// If this test fails due to a change you'll need to implement
     /// `migrate_incompatible_metadata` for the Layer trait
     #[test]
     fn metadata_guard() {
         let metadata = Metadata {
             version: ResolvedBundlerVersion(String::from("2.3.6")),
+            ruby_abi: String::from("3.3.0"),
         };
 
         let actual = toml::to_string(&metadata).unwrap();
         let expected = r#"
 version = "2.3.6"
+ruby_abi = "3.3.0"
 "#
         .trim();
         assert_eq!(expected, actual.trim());
     }
+
+    #[test]
+    fn test_newest_patch() {
+        let releases = ["2.5.6", "2.5.10", "2.5.9", "2.6.0", "2.5", "3.0.0.pre1"];
+        assert_eq!(
+            newest_patch("2.5.6", releases.into_iter()),
+            Some(String::from("2.5.10"))
+        );
+        assert_eq!(newest_patch("2.5.10", releases.into_iter()), None);
+        assert_eq!(newest_patch("2.6.0", releases.into_iter()), None);
+        assert_eq!(newest_patch("2.5", releases.into_iter()), None);
+    }
 }
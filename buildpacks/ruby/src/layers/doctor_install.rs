@@ -0,0 +1,45 @@
+//! # Install the `heroku-ruby-doctor` diagnostic executable
+//!
+//! ## Layer dir
+//!
+//! Copies the `heroku_ruby_doctor` buildpack binary to `<layer-dir>/bin/heroku-ruby-doctor`.
+//! The layer is only available at launch, the CNB lifecycle adds `<layer-dir>/bin` to the PATH.
+//!
+//! The executable is not run automatically. Users invoke it inside of a running container
+//! to print information about the Ruby environment. It's only installed when
+//! `HEROKU_RUBY_FEATURE_DOCTOR=1` is set, so images don't ship an extra executable by default.
+use crate::{RubyBuildpack, RubyBuildpackError};
+use bullet_stream::state::SubBullet;
+use bullet_stream::{style, Print};
+use libcnb::additional_buildpack_binary_path;
+use libcnb::data::layer_name;
+use libcnb::layer::UncachedLayerDefinition;
+use std::io::Stdout;
+
+pub(crate) const DOCTOR_EXECUTABLE: &str = "heroku-ruby-doctor";
+
+pub(crate) fn handle(
+    context: &libcnb::build::BuildContext<RubyBuildpack>,
+    bullet: Print<SubBullet<Stdout>>,
+) -> libcnb::Result<Print<SubBullet<Stdout>>, RubyBuildpackError> {
+    let layer_ref = context.uncached_layer(
+        layer_name!("doctor"),
+        UncachedLayerDefinition {
+            build: false,
+            launch: true,
+        },
+    )?;
+    let bin_dir = layer_ref.path().join("bin");
+
+    fs_err::create_dir_all(&bin_dir).map_err(RubyBuildpackError::DoctorInstallError)?;
+    fs_err::copy(
+        additional_buildpack_binary_path!("heroku_ruby_doctor"),
+        bin_dir.join(DOCTOR_EXECUTABLE),
+    )
+    .map_err(RubyBuildpackError::DoctorInstallError)?;
+
+    Ok(bullet.sub_bullet(format!(
+        "Installed {doctor}, run it in your container to print Ruby environment details",
+        doctor = style::command(DOCTOR_EXECUTABLE)
+    )))
+}
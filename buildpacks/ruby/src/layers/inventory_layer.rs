@@ -0,0 +1,306 @@
+//! # Versions available for download
+//!
+//! ## Layer dir: Empty, metadata only
+//!
+//! Stores the Ruby versions that have a prebuilt binary for the target and the published
+//! Bundler releases. Ruby versions come from a single listing of the binary bucket, Bundler
+//! releases from rubygems.org. The stored versions are reused for a day so most builds make no
+//! request. They're listed again when the target changes, or when the requested Ruby version
+//! isn't in them, so a version released since the last listing is found.
+//!
+//! A failed listing keeps the prior versions. The versions resolve partial Ruby versions, list
+//! nearby versions when a binary is missing, and recommend newer patch releases.
+use crate::http::ProxyConfig;
+use crate::layers::ruby_install_layer::{self, RubyInstallError};
+use crate::target_id::TargetId;
+use crate::telemetry::Telemetry;
+use crate::{RubyBuildpack, RubyBuildpackError};
+use commons::gemfile_lock::ResolvedRubyVersion;
+use libcnb::build::BuildContext;
+use libcnb::data::layer_name;
+use libcnb::layer::{
+    CachedLayerDefinition, InvalidMetadataAction, LayerState, RestoredLayerAction,
+};
+use libcnb::Env;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use url::Url;
+
+/// Lists every published version of the bundler gem
+const BUNDLER_VERSIONS_URL: &str = "https://rubygems.org/api/v1/versions/bundler.json";
+
+/// How long listed versions are reused before listing again
+const MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How long to wait on each listing request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Bucket listings return up to 1,000 keys per page
+const MAX_PAGES: usize = 10;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Metadata {
+    /// Where Ruby binaries for the target are stored, for example `heroku-24/amd64/ruby-`
+    ruby_prefix: String,
+    /// Seconds since the unix epoch
+    listed_at: u64,
+    ruby: Vec<String>,
+    bundler: Vec<String>,
+}
+
+/// Versions available for download
+#[derive(Debug)]
+pub(crate) struct Inventory {
+    ruby: Vec<String>,
+    bundler: Vec<String>,
+    /// Why Ruby versions could not be listed, when there are no prior versions to use
+    ruby_error: Option<String>,
+}
+
+impl Inventory {
+    /// Ruby versions with a binary for the target
+    ///
+    /// # Errors
+    ///
+    /// Errors when the versions could not be listed.
+    pub(crate) fn ruby_versions(&self) -> Result<&[String], RubyInstallError> {
+        match &self.ruby_error {
+            Some(error) if self.ruby.is_empty() => {
+                Err(RubyInstallError::ListVersionsError(error.clone()))
+            }
+            _ => Ok(&self.ruby),
+        }
+    }
+
+    /// Released (not prerelease) versions of bundler, empty when they could not be listed
+    pub(crate) fn bundler_versions(&self) -> &[String] {
+        &self.bundler
+    }
+}
+
+pub(crate) fn handle(
+    context: &BuildContext<RubyBuildpack>,
+    env: &Env,
+    target: &TargetId,
+    requested: &ResolvedRubyVersion,
+    telemetry: &mut Telemetry,
+) -> libcnb::Result<Inventory, RubyBuildpackError> {
+    let layer_ref = context.cached_layer(
+        layer_name!("inventory"),
+        CachedLayerDefinition {
+            build: false,
+            launch: false,
+            invalid_metadata_action: &|_| (InvalidMetadataAction::DeleteLayer, None::<Metadata>),
+            restored_layer_action: &|old: &Metadata, _| {
+                (RestoredLayerAction::KeepLayer, Some(old.clone()))
+            },
+        },
+    )?;
+    telemetry.record_layer(&layer_ref.state);
+    let old = match &layer_ref.state {
+        LayerState::Restored { cause: Some(old) } => old.clone(),
+        _ => Metadata::default(),
+    };
+
+    let proxy = ProxyConfig::from_env(env);
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    let ruby_prefix = ruby_install_layer::binary_prefix(target);
+    let (metadata, ruby_error) = match &ruby_prefix {
+        Ok(prefix) if needs_listing(&old, prefix, now, requested) => {
+            match list_ruby(&proxy, prefix) {
+                Ok(ruby) => (
+                    Metadata {
+                        ruby_prefix: prefix.clone(),
+                        listed_at: now,
+                        ruby,
+                        bundler: list_bundler(&proxy).unwrap_or(old.bundler),
+                    },
+                    None,
+                ),
+                // Listed again on the next build
+                Err(error) if old.ruby_prefix == *prefix => (old, Some(error.to_string())),
+                Err(error) => (
+                    Metadata {
+                        bundler: old.bundler,
+                        ..Metadata::default()
+                    },
+                    Some(error.to_string()),
+                ),
+            }
+        }
+        Ok(_) => (old, None),
+        Err(error) => (Metadata::default(), Some(error.to_string())),
+    };
+    layer_ref.write_metadata(metadata.clone())?;
+
+    Ok(Inventory {
+        ruby: metadata.ruby,
+        bundler: metadata.bundler,
+        ruby_error,
+    })
+}
+
+/// Listing is skipped when the stored versions are recent, for the same target, and include
+/// the requested Ruby version
+fn needs_listing(old: &Metadata, prefix: &str, now: u64, requested: &ResolvedRubyVersion) -> bool {
+    let requested = requested.to_string();
+    let found = old.ruby.iter().any(|version| {
+        *version == requested
+            || version
+                .strip_prefix(&requested)
+                .and_then(|rest| rest.strip_prefix('.'))
+                .is_some_and(|patch| patch.chars().all(|c| c.is_ascii_digit()))
+    });
+
+    old.ruby_prefix != prefix || now.saturating_sub(old.listed_at) >= MAX_AGE.as_secs() || !found
+}
+
+/// Lists the bucket keys under the prefix, following continuation tokens
+fn list_ruby(proxy: &ProxyConfig, prefix: &str) -> Result<Vec<String>, RubyInstallError> {
+    let mut versions = Vec::new();
+    let mut token: Option<String> = None;
+    for _ in 0..MAX_PAGES {
+        let mut url = Url::parse(ruby_install_layer::BINARY_BASE_URL)
+            .map_err(RubyInstallError::UrlParseError)?;
+        url.query_pairs_mut()
+            .append_pair("list-type", "2")
+            .append_pair("prefix", prefix);
+        if let Some(token) = &token {
+            url.query_pairs_mut()
+                .append_pair("continuation-token", token);
+        }
+        let body = proxy
+            .agent_for(url.as_str())
+            .and_then(|agent| {
+                agent
+                    .get(url.as_str())
+                    .timeout(REQUEST_TIMEOUT)
+                    .call()
+                    .map_err(Box::new)
+            })
+            .map_err(RubyInstallError::RequestError)?
+            .into_string()
+            .map_err(|error| RubyInstallError::ListVersionsError(error.to_string()))?;
+
+        let (page, next) = parse_listing(&body);
+        versions.extend(page);
+        token = next;
+        if token.is_none() {
+            break;
+        }
+    }
+    Ok(versions)
+}
+
+/// Returns the Ruby versions in a bucket listing and the token for the next page
+fn parse_listing(body: &str) -> (Vec<String>, Option<String>) {
+    let key = Regex::new(r"<Key>[^<]*ruby-([^<]+)\.tgz</Key>").expect("Clippy checked");
+    let token = Regex::new(r"<NextContinuationToken>([^<]+)</NextContinuationToken>")
+        .expect("Clippy checked");
+
+    (
+        key.captures_iter(body)
+            .map(|captures| captures[1].to_string())
+            .collect(),
+        body.contains("<IsTruncated>true</IsTruncated>")
+            .then(|| token.captures(body).map(|captures| captures[1].to_string()))
+            .flatten(),
+    )
+}
+
+/// Lists released versions of bundler from rubygems.org
+fn list_bundler(proxy: &ProxyConfig) -> Option<Vec<String>> {
+    #[derive(Deserialize)]
+    struct Release {
+        number: String,
+        prerelease: bool,
+    }
+
+    let body = proxy
+        .agent_for(BUNDLER_VERSIONS_URL)
+        .ok()?
+        .get(BUNDLER_VERSIONS_URL)
+        .timeout(REQUEST_TIMEOUT)
+        .call()
+        .ok()?
+        .into_string()
+        .ok()?;
+
+    Some(
+        serde_json::from_str::<Vec<Release>>(&body)
+            .ok()?
+            .into_iter()
+            .filter(|release| !release.prerelease)
+            .map(|release| release.number)
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_listing() {
+        let body = r"<?xml version='1.0' encoding='UTF-8'?>
+<ListBucketResult>
+  <Prefix>heroku-24/amd64/ruby-</Prefix>
+  <IsTruncated>true</IsTruncated>
+  <Contents><Key>heroku-24/amd64/ruby-3.3.5.tgz</Key></Contents>
+  <Contents><Key>heroku-24/amd64/ruby-3.4.0-preview1.tgz</Key></Contents>
+  <Contents><Key>heroku-24/amd64/ruby-3.3.5.tgz.sha256</Key></Contents>
+  <NextContinuationToken>abc123</NextContinuationToken>
+</ListBucketResult>";
+
+        assert_eq!(
+            parse_listing(body),
+            (
+                vec![String::from("3.3.5"), String::from("3.4.0-preview1")],
+                Some(String::from("abc123"))
+            )
+        );
+        assert_eq!(
+            parse_listing(&body.replace("<IsTruncated>true", "<IsTruncated>false")).1,
+            None
+        );
+    }
+
+    #[test]
+    fn test_needs_listing() {
+        let old = Metadata {
+            ruby_prefix: String::from("heroku-24/amd64/ruby-"),
+            listed_at: 1_000,
+            ruby: vec![String::from("3.3.4"), String::from("3.3.5")],
+            bundler: Vec::new(),
+        };
+        let version = |version: &str| ResolvedRubyVersion(version.to_string());
+        let prefix = "heroku-24/amd64/ruby-";
+
+        assert!(!needs_listing(&old, prefix, 1_000, &version("3.3.5")));
+        assert!(!needs_listing(&old, prefix, 1_000, &version("3.3")));
+        assert!(needs_listing(&old, prefix, 1_000, &version("3.4")));
+        assert!(needs_listing(&old, prefix, 1_000, &version("3.3.6")));
+        assert!(needs_listing(
+            &old,
+            "heroku-24/arm64/ruby-",
+            1_000,
+            &version("3.3.5")
+        ));
+        assert!(needs_listing(
+            &old,
+            prefix,
+            1_000 + MAX_AGE.as_secs(),
+            &version("3.3.5")
+        ));
+        assert!(needs_listing(
+            &Metadata::default(),
+            prefix,
+            0,
+            &version("3.3.5")
+        ));
+    }
+}
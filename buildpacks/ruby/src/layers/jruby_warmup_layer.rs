@@ -0,0 +1,244 @@
+//! # Warm up JRuby at build time
+//!
+//! ## Layer dir: A JVM class data sharing (CDS) archive
+//!
+//! JRuby boots slowly because the JVM loads and verifies thousands of classes. When
+//! `HEROKU_RUBY_FEATURE_JRUBY_WARMUP=1` is set for a JRuby app, the application is loaded once during the
+//! build with `-XX:ArchiveClassesAtExit` to record the classes it uses into an archive. At
+//! launch `JAVA_TOOL_OPTIONS` points the JVM at the archive so those classes are mapped
+//! instead of loaded.
+//!
+//! By default the warmup runs `Bundler.require` and, for Rails apps, loads
+//! `config/environment.rb`. Apps can provide an executable `bin/heroku-jruby-warmup` Ruby
+//! script to exercise more code.
+//!
+//! ## Cache invalidation
+//!
+//! The archive is regenerated when the Ruby version, Java version, `Gemfile.lock`, warmup
+//! script, or platform environment changes. The JVM refuses an archive written by a different
+//! Java version, so the first line of `java -version` is stored with it.
+use crate::features::{Feature, Features};
+use crate::gemfile_path::GemfilePaths;
+use crate::telemetry::Telemetry;
+use crate::{RubyBuildpack, RubyBuildpackError};
+use bullet_stream::state::SubBullet;
+use bullet_stream::{style, Print};
+use cache_diff::CacheDiff;
+use commons::display::SentenceList;
+use commons::gemfile_lock::{Engine, ResolvedRubyVersion};
+use commons::layer::diff_migrate::{DiffMigrateLayer, Meta};
+use commons::metadata_digest::{DigestError, MetadataDigest};
+use fun_run::{self, CommandWithName};
+use libcnb::build::BuildContext;
+use libcnb::data::layer_name;
+use libcnb::layer::LayerState;
+use libcnb::layer_env::{LayerEnv, ModificationBehavior, Scope};
+use libcnb::{Env, Platform};
+use magic_migrate::{try_migrate_deserializer_chain, TryMigrate};
+use serde::{Deserialize, Serialize};
+use std::io::Stdout;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const WARMUP_SCRIPT: &str = "bin/heroku-jruby-warmup";
+const ARCHIVE_FILE: &str = "jruby.jsa";
+
+/// True when the app runs on JRuby and opted into the warmup
+pub(crate) fn enabled(features: &Features, engine: &Engine) -> bool {
+    matches!(engine, Engine::Jruby { .. }) && features.enabled(Feature::JrubyWarmup)
+}
+
+pub(crate) fn handle(
+    mut bullet: Print<SubBullet<Stdout>>,
+    context: &BuildContext<RubyBuildpack>,
+    env: &Env,
+    ruby_version: &ResolvedRubyVersion,
+    telemetry: &mut Telemetry,
+) -> libcnb::Result<Print<SubBullet<Stdout>>, RubyBuildpackError> {
+    let script = context.app_dir.join(WARMUP_SCRIPT);
+    let gemfile_paths = GemfilePaths::from_env(context.platform.env(), &context.app_dir);
+    let mut files = vec![gemfile_paths.lockfile().to_path_buf()];
+    if script.is_file() {
+        files.push(script.clone());
+    }
+    let metadata = Metadata {
+        ruby_version: ruby_version.clone(),
+        java_version: java_version(env),
+        digest: MetadataDigest::new_env_files(
+            &context.platform,
+            &files.iter().map(PathBuf::as_path).collect::<Vec<_>>(),
+        )
+        .map_err(|error| match error {
+            DigestError::CannotReadFile(path, error) => {
+                RubyBuildpackError::JrubyWarmupDigestError(path, error)
+            }
+        })?,
+    };
+
+    let layer_ref = DiffMigrateLayer {
+        build: false,
+        launch: true,
+    }
+    .cached_layer(layer_name!("jruby_warmup"), context, &metadata)?;
+    telemetry.record_layer(&layer_ref.state);
+    let archive = layer_ref.path().join(ARCHIVE_FILE);
+
+    let changed = match &layer_ref.state {
+        LayerState::Restored {
+            cause: Meta::Data(old),
+        } => metadata
+            .digest
+            .changed(&old.digest)
+            .map(|changed| changed.to_string()),
+        LayerState::Restored { cause } => {
+            bullet = bullet.sub_bullet(cause);
+            Some(String::new())
+        }
+        LayerState::Empty { .. } => Some(String::new()),
+    };
+
+    match changed {
+        None if archive.is_file() => {
+            bullet = bullet.sub_bullet(format!(
+                "Using cached archive (no changes found in {})",
+                SentenceList::new(&metadata.digest.checked_list()).join_str("or")
+            ));
+        }
+        changed => {
+            if let Some(reason) = changed.filter(|reason| !reason.is_empty()) {
+                bullet = bullet.sub_bullet(reason);
+            }
+            bullet = warmup(bullet, context, env, &archive)?;
+        }
+    }
+
+    layer_ref.write_env(
+        LayerEnv::new()
+            .chainable_insert(
+                Scope::Launch,
+                ModificationBehavior::Append,
+                "JAVA_TOOL_OPTIONS",
+                format!("-XX:SharedArchiveFile={}", archive.display()),
+            )
+            .chainable_insert(
+                Scope::Launch,
+                ModificationBehavior::Delimiter,
+                "JAVA_TOOL_OPTIONS",
+                " ",
+            ),
+    )?;
+
+    Ok(bullet)
+}
+
+fn warmup(
+    mut bullet: Print<SubBullet<Stdout>>,
+    context: &BuildContext<RubyBuildpack>,
+    env: &Env,
+    archive: &Path,
+) -> Result<Print<SubBullet<Stdout>>, RubyBuildpackError> {
+    let mut cmd = Command::new("ruby");
+    cmd.arg(format!("-J-XX:ArchiveClassesAtExit={}", archive.display()));
+    if context.app_dir.join(WARMUP_SCRIPT).is_file() {
+        cmd.arg(WARMUP_SCRIPT);
+    } else {
+        cmd.args(["-e", default_warmup(&context.app_dir)]);
+    }
+    cmd.current_dir(&context.app_dir).env_clear().envs(env);
+
+    bullet
+        .stream_with(
+            format!("Running {}", style::command(cmd.name())),
+            |stdout, stderr| cmd.stream_output(stdout, stderr),
+        )
+        .map_err(|error| fun_run::map_which_problem(error, &mut cmd, env.get("PATH").cloned()))
+        .map_err(RubyBuildpackError::JrubyWarmupFailed)?;
+
+    Ok(bullet)
+}
+
+/// The first line of `java -version`, such as `openjdk version "21.0.4" 2024-07-16 LTS`
+fn java_version(env: &Env) -> String {
+    let mut cmd = Command::new("java");
+    cmd.arg("-version").env_clear().envs(env);
+    cmd.named_output()
+        .ok()
+        .and_then(|output| {
+            output
+                .stderr_lossy()
+                .lines()
+                .next()
+                .map(|line| line.trim().to_string())
+        })
+        .unwrap_or_else(|| String::from("unknown"))
+}
+
+fn default_warmup(app_dir: &Path) -> &'static str {
+    if app_dir.join("config").join("environment.rb").is_file() {
+        "require 'bundler/setup'; Bundler.require(:default); require './config/environment'"
+    } else {
+        "require 'bundler/setup'; Bundler.require(:default)"
+    }
+}
+
+pub(crate) type Metadata = MetadataV2;
+try_migrate_deserializer_chain!(
+    deserializer: toml::Deserializer::new,
+    error: MetadataError,
+    chain: [MetadataV1, MetadataV2],
+);
+
+#[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct MetadataV1 {
+    pub(crate) ruby_version: ResolvedRubyVersion,
+    pub(crate) digest: MetadataDigest, // Must be last for serde to be happy https://github.com/toml-rs/toml-rs/issues/142
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq, CacheDiff)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct MetadataV2 {
+    #[cache_diff(rename = "Ruby version")]
+    pub(crate) ruby_version: ResolvedRubyVersion,
+
+    #[cache_diff(rename = "Java version")]
+    pub(crate) java_version: String,
+
+    /// Compared manually so the changed files can be reported
+    #[cache_diff(ignore)]
+    pub(crate) digest: MetadataDigest, // Must be last for serde to be happy https://github.com/toml-rs/toml-rs/issues/142
+}
+
+/// Archives from before the Java version was recorded are regenerated on the next build
+impl From<MetadataV1> for MetadataV2 {
+    fn from(v1: MetadataV1) -> Self {
+        Self {
+            ruby_version: v1.ruby_version,
+            java_version: String::from("unknown"),
+            digest: v1.digest,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum MetadataError {
+    // Update if migrating between a metadata version can error
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_enabled() {
+        let jruby = Engine::Jruby {
+            version: String::from("9.4.8.0"),
+        };
+        let mut env = Env::new();
+        assert!(!enabled(&Features::from_env(&env), &jruby));
+
+        env.insert(Feature::JrubyWarmup.env_key(), "1");
+        assert!(enabled(&Features::from_env(&env), &jruby));
+        assert!(!enabled(&Features::from_env(&env), &Engine::Mri));
+    }
+}
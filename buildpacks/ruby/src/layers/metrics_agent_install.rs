@@ -1,3 +1,5 @@
+use crate::http::{self, redact_url, DownloadError, ProxyConfig};
+use crate::telemetry::Telemetry;
 use crate::{RubyBuildpack, RubyBuildpackError};
 use bullet_stream::state::SubBullet;
 use bullet_stream::{style, Print};
@@ -7,6 +9,7 @@ use libcnb::data::layer_name;
 use libcnb::layer::{
     CachedLayerDefinition, EmptyLayerCause, InvalidMetadataAction, LayerState, RestoredLayerAction,
 };
+use libcnb::Env;
 use libherokubuildpack::digest::sha256;
 use serde::{Deserialize, Serialize};
 use std::io::Stdout;
@@ -46,9 +49,8 @@ pub(crate) enum MetricsAgentInstallError {
     #[error("Could not untar: {0}")]
     CouldNotUnpack(std::io::Error),
 
-    // Boxed to prevent `large_enum_variant` errors since `ureq::Error` is massive.
-    #[error("Download error: {0}")]
-    RequestError(Box<ureq::Error>),
+    #[error(transparent)]
+    DownloadError(DownloadError),
 
     #[error("Could not create file: {0}")]
     CouldNotCreateDestinationFile(std::io::Error),
@@ -62,7 +64,9 @@ pub(crate) enum MetricsAgentInstallError {
 
 pub(crate) fn handle_metrics_agent_layer(
     context: &libcnb::build::BuildContext<RubyBuildpack>,
+    env: &Env,
     mut bullet: Print<SubBullet<Stdout>>,
+    telemetry: &mut Telemetry,
 ) -> libcnb::Result<Print<SubBullet<Stdout>>, RubyBuildpackError> {
     let metadata = Metadata {
         download_url: DOWNLOAD_URL.to_string(),
@@ -89,6 +93,7 @@ pub(crate) fn handle_metrics_agent_layer(
             },
         },
     )?;
+    telemetry.record_layer(&layer_ref.state);
 
     match layer_ref.state.clone() {
         LayerState::Restored { .. } => {
@@ -105,12 +110,19 @@ pub(crate) fn handle_metrics_agent_layer(
                 }
             }
             let bin_dir = layer_ref.path().join("bin");
+            let proxy = ProxyConfig::from_env(env);
+            if let Some(url) = proxy.proxy_for(&metadata.download_url) {
+                bullet = bullet.sub_bullet(format!(
+                    "Downloading through proxy {}",
+                    style::url(redact_url(url))
+                ));
+            }
 
             let timer = bullet.start_timer(format!(
                 "Installing metrics agent from {url}",
                 url = style::url(&metadata.download_url)
             ));
-            let agentmon = install_agentmon(&bin_dir, &metadata)
+            let agentmon = install_agentmon(&proxy, &bin_dir, &metadata)
                 .map_err(RubyBuildpackError::MetricsAgentError)?;
             bullet = timer.done();
 
@@ -168,21 +180,28 @@ fn write_execd_script(
     Ok(execd)
 }
 
-fn install_agentmon(dir: &Path, metadata: &Metadata) -> Result<PathBuf, MetricsAgentInstallError> {
-    let agentmon = download_untar(&metadata.download_url, dir).map(|()| dir.join("agentmon"))?;
+fn install_agentmon(
+    proxy: &ProxyConfig,
+    dir: &Path,
+    metadata: &Metadata,
+) -> Result<PathBuf, MetricsAgentInstallError> {
+    let agentmon =
+        download_untar(proxy, &metadata.download_url, dir).map(|()| dir.join("agentmon"))?;
 
     chmod_plus_x(&agentmon).map_err(MetricsAgentInstallError::PermissionError)?;
     Ok(agentmon)
 }
 
 fn download_untar(
+    proxy: &ProxyConfig,
     url: impl AsRef<str>,
     destination: &Path,
 ) -> Result<(), MetricsAgentInstallError> {
     let agentmon_tgz =
         NamedTempFile::new().map_err(MetricsAgentInstallError::CouldNotCreateDestinationFile)?;
 
-    download(url, agentmon_tgz.path())?;
+    http::download(proxy, url, agentmon_tgz.path(), |_| {})
+        .map_err(MetricsAgentInstallError::DownloadError)?;
 
     sha256(agentmon_tgz.path())
         .map_err(MetricsAgentInstallError::CouldNotOpenFile)
@@ -225,24 +244,6 @@ fn chmod_plus_x(path: &Path) -> Result<(), std::io::Error> {
     fs_err::set_permissions(path, perms)
 }
 
-fn download(
-    uri: impl AsRef<str>,
-    destination: impl AsRef<Path>,
-) -> Result<(), MetricsAgentInstallError> {
-    let mut response_reader = ureq::get(uri.as_ref())
-        .call()
-        .map_err(|err| MetricsAgentInstallError::RequestError(Box::new(err)))?
-        .into_reader();
-
-    let mut destination_file = fs_err::File::create(destination.as_ref())
-        .map_err(MetricsAgentInstallError::CouldNotCreateDestinationFile)?;
-
-    std::io::copy(&mut response_reader, &mut destination_file)
-        .map_err(MetricsAgentInstallError::CouldNotWriteDestinationFile)?;
-
-    Ok(())
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,158 @@
+//! # Check the order of executables on the PATH when the container starts
+//!
+//! ## Layer dir
+//!
+//! Writes an exec.d script to `<layer-dir>/path_check` that runs each time the container
+//! starts. The layer is only available at launch.
+//!
+//! The application `bin/` directory, the gems bin directory, and the Ruby bin directory must be
+//! on the PATH in that order, otherwise a different `rake` or `rails` runs than the one that was
+//! installed (for example when a `PATH` config var replaces the value set by the buildpack).
+//! When they're missing or reordered the script prints a warning. It always exits successfully
+//! so it never prevents the container from starting.
+//!
+//! The launcher runs exec.d programs before it sources `profile.d` scripts and the application
+//! `.profile`, so the script sees the PATH from config vars and layer environment only. Changes
+//! made in `.profile` or in the process command itself are not checked.
+use crate::layers::bundle_install_layer::gems_layer_name;
+use crate::{RubyBuildpack, RubyBuildpackError};
+use bullet_stream::state::SubBullet;
+use bullet_stream::Print;
+use indoc::formatdoc;
+use libcnb::data::layer_name;
+use libcnb::layer::UncachedLayerDefinition;
+use std::fs::Permissions;
+use std::io::Stdout;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+
+pub(crate) fn handle(
+    context: &libcnb::build::BuildContext<RubyBuildpack>,
+    bullet: Print<SubBullet<Stdout>>,
+) -> libcnb::Result<Print<SubBullet<Stdout>>, RubyBuildpackError> {
+    let dirs = [
+        context.app_dir.join("bin"),
+        context
+            .layers_dir
+            .join(gems_layer_name().as_str())
+            .join("bin"),
+        context.layers_dir.join("binruby").join("bin"),
+    ]
+    .into_iter()
+    .filter(|dir| dir.is_dir())
+    .collect::<Vec<_>>();
+    if dirs.len() < 2 {
+        return Ok(bullet);
+    }
+
+    let layer_ref = context.uncached_layer(
+        layer_name!("path_check"),
+        UncachedLayerDefinition {
+            build: false,
+            launch: true,
+        },
+    )?;
+    let script = layer_ref.path().join("path_check");
+
+    fs_err::write(&script, script_contents(&dirs))
+        .and_then(|()| fs_err::set_permissions(&script, Permissions::from_mode(0o755)))
+        .map_err(RubyBuildpackError::PathCheckInstallError)?;
+    layer_ref.write_exec_d_programs([("path_check".to_string(), script)])?;
+
+    Ok(bullet.sub_bullet("Installed a launch check for the order of executables on the PATH"))
+}
+
+/// A bash script that warns unless every directory is on the PATH, in order
+fn script_contents(dirs: &[PathBuf]) -> String {
+    let expected = dirs
+        .iter()
+        .map(|dir| format!("\"{}\"", dir.display()))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let listed = dirs
+        .iter()
+        .map(|dir| format!("    echo \"!   {}\"", dir.display()))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    formatdoc! {r#"
+        #!/usr/bin/env bash
+
+        expected=({expected})
+        IFS=: read -r -a path <<< "${{PATH}}"
+
+        last=-1
+        for dir in "${{expected[@]}}"; do
+          index=-1
+          for i in "${{!path[@]}}"; do
+            if [ "${{path[$i]}}" = "${{dir}}" ]; then
+              index=$i
+              break
+            fi
+          done
+
+          if [ "${{index}}" -le "${{last}}" ]; then
+            echo "! Warning: Ruby executables are missing from the PATH or out of order"
+            echo "!"
+            echo "! These directories should be on the PATH in this order:"
+            echo "!"
+        {listed}
+            echo "!"
+            echo "! Otherwise a different \`rake\`, \`rails\`, or \`bundle\` may run than the one"
+            echo "! installed by the buildpack. Check for a \`PATH\` config var or other changes"
+            echo "! to the PATH before the application starts. Changes made in \`.profile\` are"
+            echo "! applied after this check and are not included."
+            echo "!"
+            echo "! Current PATH: ${{PATH}}"
+            break
+          fi
+          last=$index
+        done
+
+        exit 0
+    "#}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    fn run(dirs: &[PathBuf], path: &str) -> String {
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("path_check");
+        fs_err::write(&script, script_contents(dirs)).unwrap();
+
+        let output = Command::new("/bin/bash")
+            .arg(&script)
+            .env("PATH", path)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8_lossy(&output.stdout).to_string()
+    }
+
+    #[test]
+    fn test_script_contents() {
+        let dirs = [
+            "/workspace/bin",
+            "/layers/heroku_ruby/gems/bin",
+            "/layers/heroku_ruby/binruby/bin",
+        ]
+        .map(PathBuf::from);
+
+        assert_eq!(
+            run(
+                &dirs,
+                "/workspace/bin:/layers/heroku_ruby/gems/bin:/layers/heroku_ruby/binruby/bin:/usr/bin"
+            ),
+            ""
+        );
+        assert!(run(
+            &dirs,
+            "/layers/heroku_ruby/binruby/bin:/workspace/bin:/layers/heroku_ruby/gems/bin"
+        )
+        .contains("out of order"));
+        assert!(run(&dirs, "/workspace/bin:/usr/bin").contains("!   /layers/heroku_ruby/gems/bin"));
+    }
+}
@@ -7,37 +7,95 @@
 //!
 //! This layer relies on the CNB lifecycle to add `<layer-dir>/bin` to the PATH.
 //!
+//! The download honors `HTTPS_PROXY`, `HTTP_PROXY`, and `NO_PROXY`.
+//!
+//! ## Partial versions
+//!
+//! A version without a patch segment, such as `3.3` from `.ruby-version`, resolves to the
+//! newest `3.3.x` binary available for the target. Available versions come from the
+//! [`crate::layers::inventory_layer`], no request is made per patch release. The concrete
+//! version is recorded in the metadata, so the cache is cleared when a newer patch release
+//! becomes available.
+//!
+//! When a full version is used and a newer patch release has a binary for the target, a
+//! notice recommends upgrading. Versions that could not be listed never fail the build.
+//!
+//! ## Unavailable versions
+//!
+//! Targets that never have binaries are rejected before any request. Otherwise a `HEAD` request
+//! checks the binary exists before downloading. When it doesn't, the newest patch release of
+//! the requested and prior minor versions that have a binary for the target are looked up in
+//! the available versions and listed in the error, so a brand new patch release that isn't built yet points at the
+//! closest version that is.
+//!
+//! ## Build from source
+//!
+//! When `HEROKU_RUBY_BUILD_FROM_SOURCE=1` is set and no prebuilt binary exists for the
+//! requested version and target, the MRI source tarball is downloaded and compiled with
+//! `<layer-dir>` as the prefix. The source url is recorded in the metadata, so the compiled
+//! Ruby is cached and only rebuilt when the version or target changes.
+//!
+//! Checking for a prebuilt binary needs a `HEAD` request. When the cached layer already holds
+//! the requested version and target, from either url, it's reused without a request, so a
+//! rebuild never depends on the network. Compiling doesn't use `ccache`, a cold install
+//! compiles every file.
+//!
+//! ## Shared library check
+//!
+//! After Ruby is installed, `ldd` is run against the `ruby` executable and the `openssl`,
+//! `psych`, and `fiddle` extensions. If a shared library they link against (such as `libssl`,
+//! `libyaml`, or `libffi`) is missing from the image the build fails instead of crashing at
+//! runtime. The check is skipped when `ldd` is not available.
+//!
 //! ## Cache invalidation
 //!
-//! When the Ruby version changes, invalidate and re-run.
+//! When the Ruby version, OS distribution, CPU architecture, or download URL changes, invalidate and re-run.
 //!
+use crate::features::{Feature, Features};
+use crate::http::{self, redact_url, DownloadError, ProxyConfig};
+use crate::layers::inventory_layer::Inventory;
 use crate::target_id::OsDistribution;
+use crate::telemetry::Telemetry;
+use crate::warning::Warning;
 use crate::{
     target_id::{TargetId, TargetIdError},
     RubyBuildpack, RubyBuildpackError,
 };
 use bullet_stream::state::SubBullet;
-use bullet_stream::Print;
+use bullet_stream::{style, Print};
 use cache_diff::CacheDiff;
 use commons::gemfile_lock::ResolvedRubyVersion;
 use commons::layer::diff_migrate::{DiffMigrateLayer, LayerRename};
 use flate2::read::GzDecoder;
+use fun_run::{self, CommandWithName};
+use indoc::formatdoc;
 use libcnb::data::layer_name;
 use libcnb::layer::{EmptyLayerCause, LayerState};
 use libcnb::layer_env::LayerEnv;
+use libcnb::Env;
 use magic_migrate::{try_migrate_deserializer_chain, TryMigrate};
 use serde::{Deserialize, Serialize};
-use std::io::{self, Stdout};
+use std::io::{Stdout, Write};
 use std::path::Path;
+use std::process::Command;
 use tar::Archive;
 use tempfile::NamedTempFile;
 use url::Url;
 
 pub(crate) fn handle(
     context: &libcnb::build::BuildContext<RubyBuildpack>,
+    env: &Env,
+    features: &Features,
+    inventory: &Inventory,
     mut bullet: Print<SubBullet<Stdout>>,
     metadata: &Metadata,
-) -> libcnb::Result<(Print<SubBullet<Stdout>>, LayerEnv), RubyBuildpackError> {
+    telemetry: &mut Telemetry,
+) -> libcnb::Result<(Print<SubBullet<Stdout>>, LayerEnv, u64), RubyBuildpackError> {
+    let mut downloaded = 0;
+    let proxy = ProxyConfig::from_env(env);
+    let cached = cached_metadata(&context.layers_dir);
+    let metadata = &source_fallback(metadata, features, &proxy, cached.as_ref())
+        .map_err(RubyBuildpackError::RubyInstallError)?;
     let layer_ref = DiffMigrateLayer {
         build: true,
         launch: true,
@@ -50,6 +108,7 @@ pub(crate) fn handle(
         context,
         metadata,
     )?;
+    telemetry.record_layer(&layer_ref.state);
     match &layer_ref.state {
         LayerState::Restored { cause } => {
             bullet = bullet.sub_bullet(cause);
@@ -62,27 +121,308 @@ pub(crate) fn handle(
                     bullet = bullet.sub_bullet(cause);
                 }
             }
-            let timer = bullet.start_timer("Installing");
-            install_ruby(metadata, &layer_ref.path())?;
-            bullet = timer.done();
+            if let Some(url) = proxy.proxy_for(&metadata.download_url) {
+                bullet = bullet.sub_bullet(format!(
+                    "Downloading through proxy {}",
+                    style::url(redact_url(url))
+                ));
+            }
+            if is_source_url(&metadata.download_url) {
+                bullet = bullet.sub_bullet(format!(
+                    "No prebuilt Ruby binary found, compiling from source (enabled with {})",
+                    style::value(format!("{BUILD_FROM_SOURCE_ENV_KEY}=1"))
+                ));
+                (bullet, downloaded) =
+                    compile_ruby(bullet, env, &proxy, metadata, &layer_ref.path())?;
+            } else {
+                (bullet, downloaded) =
+                    install_ruby(bullet, metadata, &proxy, inventory, &layer_ref.path())?;
+            }
+            check_shared_libraries(&layer_ref.path(), &metadata.os_distribution)
+                .map_err(RubyBuildpackError::RubyInstallError)?;
         }
     }
-    Ok((bullet, layer_ref.read_env()?))
+    if !is_source_url(&metadata.download_url) {
+        if let Some(newer) = newer_patch_release(inventory, &metadata.ruby_version) {
+            bullet = bullet.sub_bullet(format!(
+                "Ruby {newer} is available, you are on {current} (see {url})",
+                newer = style::value(newer.to_string()),
+                current = style::value(metadata.ruby_version.to_string()),
+                url =
+                    style::url("https://devcenter.heroku.com/articles/ruby-support#ruby-versions"),
+            ));
+        }
+    }
+    Ok((bullet, layer_ref.read_env()?, downloaded))
 }
 
-fn install_ruby(metadata: &Metadata, layer_path: &Path) -> Result<(), RubyBuildpackError> {
+/// Warns when the distribution version is newer than any with Ruby binaries, and Ruby built
+/// for an older release is installed
+pub(crate) fn distribution_warning(metadata: &Metadata) -> Option<Warning> {
+    let found = metadata.target_id().stack_match().ok()?;
+    (!found.exact).then(|| Warning {
+        code: "unknown_distribution",
+        title: "Unknown distribution version",
+        body: formatdoc! {"
+            {distro} is not a known distribution version. Ruby built for {stack} was installed
+            instead. It may link against system libraries that differ from the ones in this
+            image.
+
+            Use a supported base image, or check for a buildpack release that supports {distro}.
+            ",
+            distro = style::value(metadata.os_distribution.to_string()),
+            stack = style::value(found.stack),
+        },
+        url: None,
+    })
+}
+
+/// Returns the number of bytes downloaded
+fn install_ruby(
+    mut bullet: Print<SubBullet<Stdout>>,
+    metadata: &Metadata,
+    proxy: &ProxyConfig,
+    inventory: &Inventory,
+    layer_path: &Path,
+) -> Result<(Print<SubBullet<Stdout>>, u64), RubyBuildpackError> {
     let tmp_ruby_tgz = NamedTempFile::new()
         .map_err(RubyInstallError::CouldNotCreateDestinationFile)
         .map_err(RubyBuildpackError::RubyInstallError)?;
 
-    let url = download_url(&metadata.target_id(), &metadata.ruby_version)
+    let target = metadata.target_id();
+    validate_target(&target, &metadata.ruby_version)
         .map_err(RubyBuildpackError::RubyInstallError)?;
 
-    download(url.as_ref(), tmp_ruby_tgz.path()).map_err(RubyBuildpackError::RubyInstallError)?;
+    check_available(
+        proxy,
+        &metadata.download_url,
+        &target,
+        &metadata.ruby_version,
+    )
+    .map_err(|error| match error {
+        RubyInstallError::NoBinaryForTarget { .. } => no_binary(
+            &target,
+            &metadata.ruby_version,
+            nearby_versions(inventory, &metadata.ruby_version),
+        ),
+        error => error,
+    })
+    .map_err(RubyBuildpackError::RubyInstallError)?;
 
+    let downloaded = download_with_progress(
+        &mut bullet,
+        proxy,
+        &metadata.download_url,
+        tmp_ruby_tgz.path(),
+    )
+    .map_err(RubyBuildpackError::RubyInstallError)?;
+
+    let timer = bullet.start_timer("Installing");
     untar(tmp_ruby_tgz.path(), layer_path).map_err(RubyBuildpackError::RubyInstallError)?;
 
-    Ok(())
+    Ok((timer.done(), downloaded))
+}
+
+/// When set to `1`, Ruby is compiled from source if no prebuilt binary exists
+pub(crate) const BUILD_FROM_SOURCE_ENV_KEY: &str = "HEROKU_RUBY_BUILD_FROM_SOURCE";
+const SOURCE_BASE_URL: &str = "https://cache.ruby-lang.org/pub/ruby";
+
+/// Swaps the binary download url for the source tarball url when the user opted into building
+/// from source and no prebuilt binary exists. A cached install of either url is kept without
+/// checking.
+fn source_fallback(
+    metadata: &Metadata,
+    features: &Features,
+    proxy: &ProxyConfig,
+    cached: Option<&Metadata>,
+) -> Result<Metadata, RubyInstallError> {
+    if !features.enabled(Feature::BuildFromSource) {
+        return Ok(metadata.clone());
+    }
+    let source = Metadata {
+        download_url: source_url(&metadata.ruby_version)?.to_string(),
+        ..metadata.clone()
+    };
+    if let Some(cached) = cached.filter(|cached| **cached == source || *cached == metadata) {
+        return Ok(cached.clone());
+    }
+
+    let target = metadata.target_id();
+    let missing = matches!(
+        validate_target(&target, &metadata.ruby_version).and_then(|()| check_available(
+            proxy,
+            &metadata.download_url,
+            &target,
+            &metadata.ruby_version
+        )),
+        Err(RubyInstallError::NoBinaryForTarget { .. })
+    );
+    if missing {
+        Ok(source)
+    } else {
+        Ok(metadata.clone())
+    }
+}
+
+/// Metadata of the Ruby installed by a prior build, if it's in the current format
+fn cached_metadata(layers_dir: &Path) -> Option<Metadata> {
+    let contents = fs_err::read_to_string(layers_dir.join("binruby.toml")).ok()?;
+    toml::from_str::<toml::Table>(&contents)
+        .ok()?
+        .remove("metadata")?
+        .try_into()
+        .ok()
+}
+
+/// MRI source tarballs are grouped by `<major>.<minor>` for example
+/// `https://cache.ruby-lang.org/pub/ruby/3.4/ruby-3.4.1.tar.gz`
+fn source_url(version: &ResolvedRubyVersion) -> Result<Url, RubyInstallError> {
+    let version = version.to_string();
+    let major_minor = version.split('.').take(2).collect::<Vec<_>>().join(".");
+    let mut url = Url::parse(SOURCE_BASE_URL).map_err(RubyInstallError::UrlParseError)?;
+    url.path_segments_mut()
+        .map_err(|()| RubyInstallError::InvalidBaseUrl(String::from(SOURCE_BASE_URL)))?
+        .push(&major_minor)
+        .push(&format!("ruby-{version}.tar.gz"));
+    Ok(url)
+}
+
+fn is_source_url(url: &str) -> bool {
+    url.starts_with(SOURCE_BASE_URL)
+}
+
+/// Downloads the MRI source and runs `./configure`, `make`, and `make install`
+///
+/// Returns the number of bytes downloaded.
+fn compile_ruby(
+    mut bullet: Print<SubBullet<Stdout>>,
+    env: &Env,
+    proxy: &ProxyConfig,
+    metadata: &Metadata,
+    layer_path: &Path,
+) -> Result<(Print<SubBullet<Stdout>>, u64), RubyBuildpackError> {
+    let tmpdir = tempfile::tempdir()
+        .map_err(RubyInstallError::CouldNotCreateDestinationFile)
+        .map_err(RubyBuildpackError::RubyInstallError)?;
+    let tarball = tmpdir.path().join("ruby.tar.gz");
+
+    let downloaded = download_with_progress(&mut bullet, proxy, &metadata.download_url, &tarball)
+        .and_then(|downloaded| untar(&tarball, tmpdir.path()).map(|()| downloaded))
+        .map_err(RubyBuildpackError::RubyInstallError)?;
+
+    let source_dir = tmpdir
+        .path()
+        .join(format!("ruby-{}", metadata.ruby_version));
+    let jobs = std::thread::available_parallelism().map_or(1, std::num::NonZeroUsize::get);
+    for args in [
+        vec![
+            String::from("./configure"),
+            format!("--prefix={}", layer_path.display()),
+            String::from("--disable-install-doc"),
+        ],
+        vec![String::from("make"), format!("-j{jobs}")],
+        vec![String::from("make"), String::from("install")],
+    ] {
+        let mut cmd = Command::new(&args[0]);
+        cmd.args(&args[1..])
+            .current_dir(&source_dir)
+            .env_clear()
+            .envs(env);
+
+        bullet
+            .stream_with(
+                format!("Running {}", style::command(cmd.name())),
+                |stdout, stderr| cmd.stream_output(stdout, stderr),
+            )
+            .map_err(|error| fun_run::map_which_problem(error, &mut cmd, env.get("PATH").cloned()))
+            .map_err(RubyInstallError::CompileError)
+            .map_err(RubyBuildpackError::RubyInstallError)?;
+    }
+
+    Ok((bullet, downloaded))
+}
+
+/// Ruby extensions that link against system libraries
+const CHECKED_EXTENSIONS: [&str; 3] = ["openssl.so", "psych.so", "fiddle.so"];
+
+/// Fails when the installed Ruby links against shared libraries that are not on the image
+fn check_shared_libraries(
+    layer_path: &Path,
+    distro: &OsDistribution,
+) -> Result<(), RubyInstallError> {
+    let mut files = vec![layer_path.join("bin").join("ruby")];
+    extensions_in(&layer_path.join("lib").join("ruby"), &mut files);
+
+    // `libruby.so` is installed into the layer and found via `LD_LIBRARY_PATH` at runtime
+    let Ok(output) = Command::new("ldd")
+        .args(&files)
+        .env("LD_LIBRARY_PATH", layer_path.join("lib"))
+        .output()
+    else {
+        return Ok(());
+    };
+
+    let missing = missing_libraries(&String::from_utf8_lossy(&output.stdout));
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(RubyInstallError::MissingSharedLibraries {
+            distro: distro.to_string(),
+            packages: missing
+                .iter()
+                .filter_map(|library| package_for(distro, library))
+                .map(String::from)
+                .collect(),
+            missing,
+        })
+    }
+}
+
+fn extensions_in(dir: &Path, files: &mut Vec<std::path::PathBuf>) {
+    let Ok(entries) = fs_err::read_dir(dir) else {
+        return;
+    };
+    for path in entries.flatten().map(|entry| entry.path()) {
+        if path.is_dir() {
+            extensions_in(&path, files);
+        } else if path
+            .file_name()
+            .is_some_and(|name| CHECKED_EXTENSIONS.iter().any(|ext| name == *ext))
+        {
+            files.push(path);
+        }
+    }
+}
+
+/// Library names from `ldd` output lines such as `libyaml-0.so.2 => not found`
+fn missing_libraries(ldd_output: &str) -> Vec<String> {
+    let mut missing = ldd_output
+        .lines()
+        .filter_map(|line| line.trim().strip_suffix("=> not found"))
+        .map(|library| library.trim().to_string())
+        .collect::<Vec<_>>();
+    missing.sort();
+    missing.dedup();
+    missing
+}
+
+/// The Ubuntu package that provides a library, used to suggest a fix
+fn package_for(distro: &OsDistribution, library: &str) -> Option<&'static str> {
+    if !distro.distro().is_ubuntu() {
+        return None;
+    }
+    [
+        ("libssl.so.3", "libssl3"),
+        ("libcrypto.so.3", "libssl3"),
+        ("libssl.so.1.1", "libssl1.1"),
+        ("libcrypto.so.1.1", "libssl1.1"),
+        ("libyaml-0.so.2", "libyaml-0-2"),
+        ("libffi.so.8", "libffi8"),
+        ("libffi.so.7", "libffi7"),
+    ]
+    .into_iter()
+    .find(|(name, _)| *name == library)
+    .map(|(_, package)| package)
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -101,18 +441,49 @@ pub(crate) struct MetadataV2 {
     pub(crate) ruby_version: ResolvedRubyVersion,
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq, CacheDiff)]
+#[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq)]
 #[serde(deny_unknown_fields)]
 pub(crate) struct MetadataV3 {
+    pub(crate) os_distribution: OsDistribution,
+    pub(crate) cpu_architecture: String,
+    pub(crate) ruby_version: ResolvedRubyVersion,
+}
+
+/// The download URL is recorded so that a change to where Ruby binaries are hosted, or to how
+/// they're named, invalidates the cache instead of being masked by a previously installed Ruby.
+#[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq, CacheDiff)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct MetadataV4 {
     #[cache_diff(rename = "OS Distribution")]
     pub(crate) os_distribution: OsDistribution,
     #[cache_diff(rename = "CPU architecture")]
     pub(crate) cpu_architecture: String,
     #[cache_diff(rename = "Ruby version")]
     pub(crate) ruby_version: ResolvedRubyVersion,
+    #[cache_diff(rename = "Download URL")]
+    pub(crate) download_url: String,
 }
 
-impl MetadataV3 {
+impl MetadataV4 {
+    pub(crate) fn new(
+        os_distribution: OsDistribution,
+        cpu_architecture: String,
+        ruby_version: ResolvedRubyVersion,
+    ) -> Result<Self, RubyInstallError> {
+        let target = TargetId {
+            cpu_architecture: cpu_architecture.clone(),
+            distro_name: os_distribution.name.clone(),
+            distro_version: os_distribution.version.clone(),
+        };
+        let download_url = download_url(&target, &ruby_version)?.to_string();
+        Ok(Self {
+            os_distribution,
+            cpu_architecture,
+            ruby_version,
+            download_url,
+        })
+    }
+
     pub(crate) fn target_id(&self) -> TargetId {
         TargetId {
             cpu_architecture: self.cpu_architecture.clone(),
@@ -122,9 +493,9 @@ impl MetadataV3 {
     }
 }
 
-pub(crate) type Metadata = MetadataV3;
+pub(crate) type Metadata = MetadataV4;
 try_migrate_deserializer_chain!(
-    chain: [MetadataV1, MetadataV2, MetadataV3],
+    chain: [MetadataV1, MetadataV2, MetadataV3, MetadataV4],
     error: MetadataMigrateError,
     deserializer: toml::Deserializer::new,
 );
@@ -133,6 +504,9 @@ try_migrate_deserializer_chain!(
 pub(crate) enum MetadataMigrateError {
     #[error("Cannot migrate metadata due to target id error: {0}")]
     TargetIdError(TargetIdError),
+
+    #[error("Cannot migrate metadata due to download url error: {0}")]
+    DownloadUrlError(RubyInstallError),
 }
 
 impl TryFrom<MetadataV1> for MetadataV2 {
@@ -166,44 +540,197 @@ impl TryFrom<MetadataV2> for MetadataV3 {
     }
 }
 
+impl TryFrom<MetadataV3> for MetadataV4 {
+    type Error = MetadataMigrateError;
+
+    fn try_from(v3: MetadataV3) -> Result<Self, Self::Error> {
+        MetadataV4::new(v3.os_distribution, v3.cpu_architecture, v3.ruby_version)
+            .map_err(MetadataMigrateError::DownloadUrlError)
+    }
+}
+
+/// Where prebuilt Ruby binaries are stored
+pub(crate) const BINARY_BASE_URL: &str = "https://heroku-buildpack-ruby.s3.us-east-1.amazonaws.com";
+
+/// The start of the binary paths for a target, for example `heroku-24/amd64/ruby-`
+pub(crate) fn binary_prefix(target: &TargetId) -> Result<String, RubyInstallError> {
+    let stack = target
+        .stack_match()
+        .map_err(RubyInstallError::TargetError)?
+        .stack;
+    if target.is_arch_aware() {
+        Ok(format!("{stack}/{}/ruby-", target.cpu_architecture))
+    } else {
+        Ok(format!("{stack}/ruby-"))
+    }
+}
+
 fn download_url(
     target: &TargetId,
     version: impl std::fmt::Display,
 ) -> Result<Url, RubyInstallError> {
-    let filename = format!("ruby-{version}.tgz");
-    let base = "https://heroku-buildpack-ruby.s3.us-east-1.amazonaws.com";
-    let mut url = Url::parse(base).map_err(RubyInstallError::UrlParseError)?;
-    {
-        let mut segments = url
-            .path_segments_mut()
-            .map_err(|()| RubyInstallError::InvalidBaseUrl(String::from(base)))?;
-
-        segments.push(&target.stack_name().map_err(RubyInstallError::TargetError)?);
-        if target.is_arch_aware() {
-            segments.push(&target.cpu_architecture);
-        }
-        segments.push(&filename);
-    }
+    let path = format!("{}{version}.tgz", binary_prefix(target)?);
+    let mut url = Url::parse(BINARY_BASE_URL).map_err(RubyInstallError::UrlParseError)?;
+    url.path_segments_mut()
+        .map_err(|()| RubyInstallError::InvalidBaseUrl(String::from(BINARY_BASE_URL)))?
+        .extend(path.split('/'));
 
     Ok(url)
 }
 
-pub(crate) fn download(
-    uri: impl AsRef<str>,
-    destination: impl AsRef<Path>,
+/// Resolves a `<major>.<minor>` version to the newest patch release with a binary for the
+/// target, other versions are returned unchanged
+///
+/// # Errors
+///
+/// Errors when no patch release has a binary for the target, or the versions could not be
+/// listed.
+pub(crate) fn resolve_partial_version(
+    inventory: &Inventory,
+    target: &TargetId,
+    version: &ResolvedRubyVersion,
+) -> Result<ResolvedRubyVersion, RubyInstallError> {
+    let Some(prefix) = partial_version(&version.to_string()) else {
+        return Ok(version.clone());
+    };
+    validate_target(target, version)?;
+
+    newest_patch(&prefix, inventory.ruby_versions()?)
+        .map(|patch| ResolvedRubyVersion(format!("{prefix}.{patch}")))
+        .ok_or_else(|| no_binary(target, version, nearby_versions(inventory, version)))
+}
+
+/// Returns a newer patch release of an `<major>.<minor>.<patch>` version when one has a binary
+/// for the target
+///
+/// This is advisory, versions that could not be listed are treated as no newer release.
+fn newer_patch_release(
+    inventory: &Inventory,
+    version: &ResolvedRubyVersion,
+) -> Option<ResolvedRubyVersion> {
+    let (prefix, patch) = version
+        .to_string()
+        .rsplit_once('.')
+        .and_then(|(prefix, patch)| partial_version(prefix).zip(patch.parse::<u32>().ok()))?;
+
+    newest_patch(&prefix, inventory.ruby_versions().ok()?)
+        .filter(|newest| *newest > patch)
+        .map(|newest| ResolvedRubyVersion(format!("{prefix}.{newest}")))
+}
+
+/// Returns the `<major>.<minor>` of a version without a patch segment
+fn partial_version(version: &str) -> Option<String> {
+    let segments = version.split('.').collect::<Vec<_>>();
+    (segments.len() == 2
+        && segments
+            .iter()
+            .all(|segment| !segment.is_empty() && segment.chars().all(|c| c.is_ascii_digit())))
+    .then(|| version.to_string())
+}
+
+/// The highest `<prefix>.<patch>` release, prereleases such as `3.4.0-preview1` are ignored
+fn newest_patch(prefix: &str, versions: &[String]) -> Option<u32> {
+    versions
+        .iter()
+        .filter_map(|version| version.strip_prefix(prefix)?.strip_prefix('.'))
+        .filter_map(|patch| patch.parse::<u32>().ok())
+        .max()
+}
+
+/// Rejects targets that are known to have no Ruby binaries without a network request
+///
+/// Binaries for architectures other than `amd64` are only built for arch aware stacks.
+fn validate_target(
+    target: &TargetId,
+    version: &ResolvedRubyVersion,
+) -> Result<(), RubyInstallError> {
+    if target.cpu_architecture != "amd64" && !target.is_arch_aware() {
+        Err(no_binary(target, version, Vec::new()))
+    } else {
+        Ok(())
+    }
+}
+
+/// Checks that the artifact exists before downloading it
+///
+/// Other failures are ignored here so they're reported with full details by the download.
+fn check_available(
+    proxy: &ProxyConfig,
+    url: &str,
+    target: &TargetId,
+    version: &ResolvedRubyVersion,
 ) -> Result<(), RubyInstallError> {
-    let mut response_reader = ureq::get(uri.as_ref())
-        .call()
-        .map_err(|err| RubyInstallError::RequestError(Box::new(err)))?
-        .into_reader();
+    let agent = proxy
+        .agent_for(url)
+        .map_err(RubyInstallError::RequestError)?;
+    match agent.head(url).call() {
+        Err(ureq::Error::Status(403 | 404, _)) => Err(no_binary(target, version, Vec::new())),
+        _ => Ok(()),
+    }
+}
+
+fn no_binary(
+    target: &TargetId,
+    version: &ResolvedRubyVersion,
+    nearby: Vec<String>,
+) -> RubyInstallError {
+    RubyInstallError::NoBinaryForTarget {
+        version: version.to_string(),
+        cpu_architecture: target.cpu_architecture.clone(),
+        distro: target.distro().to_string(),
+        nearby,
+    }
+}
 
-    let mut destination_file = fs_err::File::create(destination.as_ref())
-        .map_err(RubyInstallError::CouldNotCreateDestinationFile)?;
+/// The newest patch release of the requested and prior minor versions with a binary for the
+/// target
+///
+/// Only used to suggest a version after the requested one is missing, versions that could not
+/// be listed are treated as no binary.
+fn nearby_versions(inventory: &Inventory, version: &ResolvedRubyVersion) -> Vec<String> {
+    let Ok(versions) = inventory.ruby_versions() else {
+        return Vec::new();
+    };
+    nearby_prefixes(version)
+        .into_iter()
+        .filter_map(|prefix| {
+            newest_patch(&prefix, versions).map(|patch| format!("{prefix}.{patch}"))
+        })
+        .filter(|nearby| *nearby != version.to_string())
+        .collect()
+}
 
-    io::copy(&mut response_reader, &mut destination_file)
-        .map_err(RubyInstallError::CouldNotWriteDestinationFile)?;
+/// The `<major>.<minor>` of a version, and the minor version before it
+fn nearby_prefixes(version: &ResolvedRubyVersion) -> Vec<String> {
+    let version = version.to_string();
+    let mut segments = version.split('.').map(str::parse::<u32>);
+    let (Some(Ok(major)), Some(Ok(minor))) = (segments.next(), segments.next()) else {
+        return Vec::new();
+    };
+    [Some(minor), minor.checked_sub(1)]
+        .into_iter()
+        .flatten()
+        .map(|minor| format!("{major}.{minor}"))
+        .collect()
+}
 
-    Ok(())
+/// Downloads a file, streaming progress lines into the build output
+fn download_with_progress(
+    bullet: &mut Print<SubBullet<Stdout>>,
+    proxy: &ProxyConfig,
+    uri: &str,
+    destination: &Path,
+) -> Result<u64, RubyInstallError> {
+    bullet.stream_with(
+        format!("Downloading {}", style::url(uri)),
+        |mut stdout, _| {
+            http::download(proxy, uri, destination, |line| {
+                // Progress is informational, a failed write should not fail the download
+                let _ = writeln!(stdout, "{line}");
+            })
+            .map_err(RubyInstallError::DownloadError)
+        },
+    )
 }
 
 pub(crate) fn untar(
@@ -222,6 +749,15 @@ pub(crate) enum RubyInstallError {
     #[error("Unknown install target: {0}")]
     TargetError(TargetIdError),
 
+    #[error("No Ruby {version} binary exists for {cpu_architecture} on {distro}")]
+    NoBinaryForTarget {
+        version: String,
+        cpu_architecture: String,
+        distro: String,
+        /// Versions close to the requested one that have a binary for the target
+        nearby: Vec<String>,
+    },
+
     #[error("Could not parse url {0}")]
     UrlParseError(url::ParseError),
 
@@ -238,11 +774,24 @@ pub(crate) enum RubyInstallError {
     #[error("Download error: {0}")]
     RequestError(Box<ureq::Error>),
 
+    #[error("Could not list the Ruby versions available for download: {0}")]
+    ListVersionsError(String),
+
     #[error("Could not create file: {0}")]
     CouldNotCreateDestinationFile(std::io::Error),
 
-    #[error("Could not write file: {0}")]
-    CouldNotWriteDestinationFile(std::io::Error),
+    #[error(transparent)]
+    DownloadError(DownloadError),
+
+    #[error("Could not compile Ruby from source: {0}")]
+    CompileError(fun_run::CmdError),
+
+    #[error("Ruby requires shared libraries missing from {distro}: {}", missing.join(", "))]
+    MissingSharedLibraries {
+        distro: String,
+        missing: Vec<String>,
+        packages: Vec<String>,
+    },
 }
 
 #[cfg(test)]
@@ -264,12 +813,16 @@ mod tests {
             },
             cpu_architecture: String::from("amd64"),
             ruby_version: ResolvedRubyVersion(String::from("3.1.3")),
+            download_url: String::from(
+                "https://heroku-buildpack-ruby.s3.us-east-1.amazonaws.com/heroku-22/ruby-3.1.3.tgz",
+            ),
         };
 
         let actual = toml::to_string(&metadata).unwrap();
         let expected = r#"
 cpu_architecture = "amd64"
 ruby_version = "3.1.3"
+download_url = "https://heroku-buildpack-ruby.s3.us-east-1.amazonaws.com/heroku-22/ruby-3.1.3.tgz"
 
 [os_distribution]
 name = "ubuntu"
@@ -325,6 +878,200 @@ version = "3.1.3"
         );
     }
 
+    #[test]
+    fn test_source_url() {
+        let url = source_url(&ResolvedRubyVersion(String::from("3.4.1"))).unwrap();
+        assert_eq!(
+            url.as_ref(),
+            "https://cache.ruby-lang.org/pub/ruby/3.4/ruby-3.4.1.tar.gz"
+        );
+        assert!(is_source_url(url.as_ref()));
+
+        let url = source_url(&ResolvedRubyVersion(String::from("3.5.0-preview1"))).unwrap();
+        assert_eq!(
+            url.as_ref(),
+            "https://cache.ruby-lang.org/pub/ruby/3.5/ruby-3.5.0-preview1.tar.gz"
+        );
+    }
+
+    #[test]
+    fn test_source_fallback_requires_opt_in() {
+        let metadata = Metadata::new(
+            OsDistribution {
+                name: String::from("ubuntu"),
+                version: String::from("22.04"),
+            },
+            String::from("arm64"),
+            ResolvedRubyVersion(String::from("3.3.5")),
+        )
+        .unwrap();
+        let proxy = ProxyConfig::default();
+
+        let mut env = Env::new();
+        assert_eq!(
+            source_fallback(&metadata, &Features::from_env(&env), &proxy, None).unwrap(),
+            metadata
+        );
+
+        env.insert(BUILD_FROM_SOURCE_ENV_KEY, "1");
+        assert_eq!(
+            source_fallback(&metadata, &Features::from_env(&env), &proxy, None)
+                .unwrap()
+                .download_url,
+            "https://cache.ruby-lang.org/pub/ruby/3.3/ruby-3.3.5.tar.gz"
+        );
+    }
+
+    #[test]
+    fn test_source_fallback_reuses_cached_install() {
+        let temp = tempfile::tempdir().unwrap();
+        let context = temp_build_context::<RubyBuildpack>(temp.path());
+        let metadata = Metadata::new(
+            OsDistribution {
+                name: String::from("ubuntu"),
+                version: String::from("24.04"),
+            },
+            String::from("amd64"),
+            ResolvedRubyVersion(String::from("3.3.5")),
+        )
+        .unwrap();
+        let source = Metadata {
+            download_url: source_url(&metadata.ruby_version).unwrap().to_string(),
+            ..metadata.clone()
+        };
+        DiffMigrateLayer {
+            build: true,
+            launch: true,
+        }
+        .cached_layer(layer_name!("binruby"), &context, &source)
+        .unwrap();
+        let cached = cached_metadata(&context.layers_dir);
+        assert_eq!(cached.as_ref(), Some(&source));
+
+        // A binary exists for this target, an unreachable proxy shows no request is made
+        let mut env = Env::new();
+        env.insert(BUILD_FROM_SOURCE_ENV_KEY, "1");
+        env.insert("HTTPS_PROXY", "http://127.0.0.1:9");
+        let proxy = ProxyConfig::from_env(&env);
+        let features = Features::from_env(&env);
+        assert_eq!(
+            source_fallback(&metadata, &features, &proxy, cached.as_ref()).unwrap(),
+            source
+        );
+
+        let other = Metadata {
+            ruby_version: ResolvedRubyVersion(String::from("3.3.4")),
+            ..source.clone()
+        };
+        assert_eq!(
+            source_fallback(&metadata, &features, &proxy, Some(&other)).unwrap(),
+            metadata
+        );
+    }
+
+    #[test]
+    fn test_partial_version() {
+        assert_eq!(partial_version("3.3"), Some(String::from("3.3")));
+        assert_eq!(partial_version("3.3.5"), None);
+        assert_eq!(partial_version("3"), None);
+        assert_eq!(partial_version("3.4.0-preview1"), None);
+        assert_eq!(partial_version("3.x"), None);
+    }
+
+    #[test]
+    fn test_newest_patch() {
+        let available = [
+            "3.3.1",
+            "3.3.3",
+            "3.3.5",
+            "3.3.10",
+            "3.4.0-preview1",
+            "3.31.0",
+        ]
+        .map(String::from);
+        assert_eq!(newest_patch("3.3", &available), Some(10));
+        assert_eq!(newest_patch("3.4", &available), None);
+        assert_eq!(newest_patch("3.9", &available), None);
+    }
+
+    #[test]
+    fn test_validate_target() {
+        let version = ResolvedRubyVersion(String::from("3.3.5"));
+        let target = |arch: &str, distro_version: &str| TargetId {
+            cpu_architecture: String::from(arch),
+            distro_name: String::from("ubuntu"),
+            distro_version: String::from(distro_version),
+        };
+
+        assert!(validate_target(&target("amd64", "22.04"), &version).is_ok());
+        assert!(validate_target(&target("arm64", "24.04"), &version).is_ok());
+        assert!(matches!(
+            validate_target(&target("arm64", "22.04"), &version),
+            Err(RubyInstallError::NoBinaryForTarget { .. })
+        ));
+    }
+
+    #[test]
+    fn test_nearby_prefixes() {
+        let prefixes = |version: &str| nearby_prefixes(&ResolvedRubyVersion(version.to_string()));
+        assert_eq!(prefixes("3.4.2"), vec!["3.4", "3.3"]);
+        assert_eq!(prefixes("3.0.7"), vec!["3.0"]);
+        assert!(prefixes("ruby").is_empty());
+    }
+
+    #[test]
+    fn test_missing_libraries() {
+        let output = "/layers/ruby/bin/ruby:\n\tlinux-vdso.so.1 (0x00007ffd)\n\tlibz.so.1 => /lib/x86_64-linux-gnu/libz.so.1 (0x00007f)\n/layers/ruby/lib/ruby/3.3.0/x86_64-linux/psych.so:\n\tlibyaml-0.so.2 => not found\n/layers/ruby/lib/ruby/3.3.0/x86_64-linux/openssl.so:\n\tlibssl.so.3 => not found\n\tlibyaml-0.so.2 => not found\n";
+        assert_eq!(
+            missing_libraries(output),
+            vec!["libssl.so.3".to_string(), "libyaml-0.so.2".to_string()]
+        );
+
+        let ubuntu = OsDistribution {
+            name: String::from("ubuntu"),
+            version: String::from("24.04"),
+        };
+        assert_eq!(package_for(&ubuntu, "libyaml-0.so.2"), Some("libyaml-0-2"));
+        assert_eq!(package_for(&ubuntu, "libunknown.so.1"), None);
+    }
+
+    #[test]
+    fn test_ruby_url_newer_distro_version() {
+        let out = download_url(
+            &TargetId {
+                cpu_architecture: String::from("arm64"),
+                distro_name: String::from("ubuntu"),
+                distro_version: String::from("26.04"),
+            },
+            "3.3.5",
+        )
+        .unwrap();
+        assert_eq!(
+            out.as_ref(),
+            "https://heroku-buildpack-ruby.s3.us-east-1.amazonaws.com/heroku-24/arm64/ruby-3.3.5.tgz",
+        );
+    }
+
+    #[test]
+    fn test_distribution_warning() {
+        let metadata = |version: &str| {
+            Metadata::new(
+                OsDistribution {
+                    name: String::from("ubuntu"),
+                    version: version.to_string(),
+                },
+                String::from("amd64"),
+                ResolvedRubyVersion(String::from("3.3.5")),
+            )
+            .unwrap()
+        };
+
+        assert_eq!(distribution_warning(&metadata("24.04")), None);
+        let warning = distribution_warning(&metadata("26.04")).unwrap();
+        assert_eq!(warning.code, "unknown_distribution");
+        assert!(strip_ansi(&warning.body).contains("Ruby built for `heroku-24`"));
+    }
+
     #[test]
     fn metadata_diff_messages() {
         let old = Metadata {
@@ -334,6 +1081,7 @@ version = "3.1.3"
                 version: "20.04".to_string(),
             },
             cpu_architecture: "amd64".to_string(),
+            download_url: "https://example.com/heroku-20/ruby-3.5.3.tgz".to_string(),
         };
         assert_eq!(old.diff(&old), Vec::<String>::new());
 
@@ -344,6 +1092,7 @@ version = "3.1.3"
                 version: "20.04".to_string(),
             },
             cpu_architecture: old.cpu_architecture.clone(),
+            download_url: old.download_url.clone(),
         }
         .diff(&old);
 
@@ -359,6 +1108,7 @@ version = "3.1.3"
                 version: "3.20.0".to_string(),
             },
             cpu_architecture: old.cpu_architecture.clone(),
+            download_url: old.download_url.clone(),
         }
         .diff(&old);
 
@@ -374,6 +1124,7 @@ version = "3.1.3"
                 version: old.os_distribution.version.clone(),
             },
             cpu_architecture: "arm64".to_string(),
+            download_url: old.download_url.clone(),
         }
         .diff(&old);
 
@@ -381,6 +1132,39 @@ version = "3.1.3"
             diff.iter().map(strip_ansi).collect::<Vec<String>>(),
             vec!["CPU architecture (`amd64` to `arm64`)".to_string()]
         );
+
+        let diff = Metadata {
+            download_url: "https://example.org/heroku-20/ruby-3.5.3.tgz".to_string(),
+            ..old.clone()
+        }
+        .diff(&old);
+
+        assert_eq!(
+            diff.iter().map(strip_ansi).collect::<Vec<String>>(),
+            vec!["Download URL (`https://example.com/heroku-20/ruby-3.5.3.tgz` to `https://example.org/heroku-20/ruby-3.5.3.tgz`)".to_string()]
+        );
+    }
+
+    #[test]
+    fn metadata_migrate_v3_to_v4() {
+        let v3 = MetadataV3 {
+            os_distribution: OsDistribution {
+                name: String::from("ubuntu"),
+                version: String::from("24.04"),
+            },
+            cpu_architecture: String::from("arm64"),
+            ruby_version: ResolvedRubyVersion(String::from("3.3.5")),
+        };
+
+        let actual = toml::to_string(&v3).unwrap();
+        let deserialized: MetadataV4 = MetadataV4::try_from_str_migrations(&actual)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            deserialized.download_url,
+            "https://heroku-buildpack-ruby.s3.us-east-1.amazonaws.com/heroku-24/arm64/ruby-3.3.5.tgz"
+        );
     }
 
     #[test]
@@ -394,6 +1178,7 @@ version = "3.1.3"
                 version: "20.04".to_string(),
             },
             cpu_architecture: "x86_64".to_string(),
+            download_url: "https://example.com/heroku-20/ruby-2.7.2.tgz".to_string(),
         };
         let differences = old.diff(&old);
         assert_eq!(differences, Vec::<String>::new());
@@ -0,0 +1,109 @@
+//! # Writable temporary directories for read-only workspaces
+//!
+//! ## Layer dir
+//!
+//! Some platforms mount the application directory read-only at runtime. When
+//! `HEROKU_RUBY_FEATURE_READ_ONLY_WORKSPACE=1` is set:
+//!
+//! - The application's `tmp/` directory is replaced with a symlink to `/tmp/app-tmp` at the
+//!   end of the build.
+//! - An exec.d script creates `/tmp/app-tmp` and the `cache`, `pids`, and `sockets`
+//!   directories Rails expects each time the container starts.
+//! - `TMPDIR` defaults to `/tmp` and `BOOTSNAP_CACHE_DIR` defaults to `/tmp/cache` at launch.
+//!
+//! The layer is only available at launch.
+use crate::features::Feature;
+use crate::{RubyBuildpack, RubyBuildpackError};
+use bullet_stream::state::SubBullet;
+use bullet_stream::{style, Print};
+use libcnb::data::layer_name;
+use libcnb::layer::UncachedLayerDefinition;
+use libcnb::layer_env::{LayerEnv, ModificationBehavior, Scope};
+use std::fs::Permissions;
+use std::io::Stdout;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+const APP_TMP: &str = "/tmp/app-tmp";
+
+const SCRIPT: &str = r"#!/usr/bin/env bash
+
+mkdir -p /tmp/app-tmp/cache /tmp/app-tmp/pids /tmp/app-tmp/sockets
+exit 0
+";
+
+pub(crate) fn handle(
+    context: &libcnb::build::BuildContext<RubyBuildpack>,
+    mut bullet: Print<SubBullet<Stdout>>,
+) -> libcnb::Result<Print<SubBullet<Stdout>>, RubyBuildpackError> {
+    let layer_ref = context.uncached_layer(
+        layer_name!("writable_tmp"),
+        UncachedLayerDefinition {
+            build: false,
+            launch: true,
+        },
+    )?;
+    let script = layer_ref.path().join("writable_tmp");
+    fs_err::write(&script, SCRIPT)
+        .and_then(|()| fs_err::set_permissions(&script, Permissions::from_mode(0o755)))
+        .map_err(RubyBuildpackError::WritableTmpError)?;
+    layer_ref.write_exec_d_programs([("writable_tmp".to_string(), script)])?;
+    layer_ref.write_env(
+        LayerEnv::new()
+            .chainable_insert(
+                Scope::Launch,
+                ModificationBehavior::Default,
+                "TMPDIR",
+                "/tmp",
+            )
+            .chainable_insert(
+                Scope::Launch,
+                ModificationBehavior::Default,
+                "BOOTSNAP_CACHE_DIR",
+                "/tmp/cache",
+            ),
+    )?;
+
+    let tmp = context.app_dir.join("tmp");
+    link_app_tmp(&tmp).map_err(RubyBuildpackError::WritableTmpError)?;
+    bullet = bullet.sub_bullet(format!(
+        "Linked {} to {} (enabled with {})",
+        style::value("tmp/"),
+        style::value(APP_TMP),
+        style::value(format!("{}=1", Feature::ReadOnlyWorkspace.env_key()))
+    ));
+
+    Ok(bullet.sub_bullet(format!(
+        "Defaulting {} and {} to writable directories at launch",
+        style::value("TMPDIR"),
+        style::value("BOOTSNAP_CACHE_DIR")
+    )))
+}
+
+/// Replaces the app's `tmp` directory with a symlink to a writable location
+fn link_app_tmp(tmp: &Path) -> Result<(), std::io::Error> {
+    if tmp.is_symlink() {
+        fs_err::remove_file(tmp)?;
+    } else if tmp.is_dir() {
+        fs_err::remove_dir_all(tmp)?;
+    }
+    fs_err::os::unix::fs::symlink(APP_TMP, tmp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_link_app_tmp() {
+        let dir = tempfile::tempdir().unwrap();
+        let tmp = dir.path().join("tmp");
+        fs_err::create_dir_all(tmp.join("cache")).unwrap();
+
+        link_app_tmp(&tmp).unwrap();
+        assert_eq!(fs_err::read_link(&tmp).unwrap(), Path::new(APP_TMP));
+
+        link_app_tmp(&tmp).unwrap();
+        assert_eq!(fs_err::read_link(&tmp).unwrap(), Path::new(APP_TMP));
+    }
+}
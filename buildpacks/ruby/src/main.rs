@@ -1,10 +1,17 @@
+use build_plan::BuildPlanError;
+use build_steps::{BuildStep, BuildSteps, BuildStepsError};
 use bullet_stream::{style, Print};
+use commons::assets::AssetManifestError;
 use commons::cache::CacheError;
+use commons::gemfile::Gemfile;
 use commons::gemfile_lock::GemfileLock;
 use commons::metadata_digest::MetadataDigest;
 use core::str::FromStr;
+use diagnostics::Diagnostics;
+use features::{Feature, Features};
 use fs_err::PathExt;
 use fun_run::CmdError;
+use gemfile_path::GemfilePaths;
 use layers::{
     metrics_agent_install::MetricsAgentInstallError, ruby_install_layer::RubyInstallError,
 };
@@ -19,14 +26,35 @@ use libcnb::layer_env::{LayerEnv, ModificationBehavior, Scope};
 use libcnb::Platform;
 use libcnb::{buildpack_main, Buildpack};
 use std::io::stdout;
-
+use step_timings::StepTimings;
+use steps::{AssetTasksError, BuildHook, CacheDirsError};
+use telemetry::Telemetry;
+
+mod build_plan;
+mod build_steps;
+mod bundle_phases;
+mod detect_explain;
+mod diagnostics;
+mod disk_usage;
+mod env_plan;
+mod error_kind;
+mod features;
 mod gem_list;
+mod gemfile_path;
+mod http;
 mod layers;
+mod native_extension;
+mod pipeline;
+mod project_toml;
 mod rake_status;
 mod rake_task_detect;
+mod ruby_version_file;
+mod step_timings;
 mod steps;
 mod target_id;
+mod telemetry;
 mod user_errors;
+mod warning;
 
 #[cfg(test)]
 use libcnb_test as _;
@@ -35,7 +63,10 @@ use pretty_assertions as _;
 
 use clap as _;
 
-use crate::target_id::OsDistribution;
+use crate::target_id::{OsDistribution, TargetId};
+
+/// Installed when the `Gemfile.lock` has no `BUNDLED WITH`
+pub(crate) const DEFAULT_BUNDLER_VERSION: &str = "2.5.6";
 
 struct RubyBuildpack;
 
@@ -60,52 +91,91 @@ impl Buildpack for RubyBuildpack {
     type Error = RubyBuildpackError;
 
     fn detect(&self, context: DetectContext<Self>) -> libcnb::Result<DetectResult, Self::Error> {
-        let mut plan_builder = BuildPlanBuilder::new().provides("ruby");
+        let mut requires = Vec::new();
+        let mut explain = detect_explain::DetectExplain::default();
 
-        let lockfile = context.app_dir.join("Gemfile.lock");
-
-        if lockfile
-            .fs_err_try_exists()
+        let gemfile_paths = GemfilePaths::from_env(context.platform.env(), &context.app_dir);
+        let lockfile = gemfile_paths.lockfile();
+        let has_lockfile = gemfile_paths
+            .lockfile_exists()
             .map_err(DetectError::GemfileLock)
-            .map_err(RubyBuildpackError::BuildpackDetectionError)?
-        {
-            plan_builder = plan_builder.requires("ruby");
+            .map_err(RubyBuildpackError::BuildpackDetectionError)?;
+        explain.file(
+            gemfile_paths.display(lockfile),
+            has_lockfile,
+            "requires ruby",
+        );
+
+        if has_lockfile {
+            requires.push("ruby");
 
-            if context
+            let has_package_json = context
                 .app_dir
                 .join("package.json")
                 .fs_err_try_exists()
                 .map_err(DetectError::PackageJson)
-                .map_err(RubyBuildpackError::BuildpackDetectionError)?
-            {
-                plan_builder = plan_builder.requires("node");
+                .map_err(RubyBuildpackError::BuildpackDetectionError)?;
+            explain.file("package.json", has_package_json, "requires node");
+            if has_package_json {
+                // A `build` script (for example from jsbundling-rails) needs node even when no
+                // Node.js lockfile is committed, a warning is printed during build in that case
+                requires.push("node");
             }
 
-            if context
+            let has_yarn_lock = context
                 .app_dir
                 .join("yarn.lock")
                 .fs_err_try_exists()
                 .map_err(DetectError::YarnLock)
-                .map_err(RubyBuildpackError::BuildpackDetectionError)?
-            {
-                plan_builder = plan_builder.requires("yarn");
+                .map_err(RubyBuildpackError::BuildpackDetectionError)?;
+            explain.file("yarn.lock", has_yarn_lock, "requires yarn");
+            if has_yarn_lock {
+                requires.push("yarn");
             }
 
             if fs_err::read_to_string(lockfile)
                 .map_err(DetectError::GemfileLock)
                 .map_err(RubyBuildpackError::BuildpackDetectionError)
-                .map(needs_java)?
+                .map(|contents| {
+                    GemfileLock::from_str(&contents)
+                        .expect("Infallible")
+                        .engine()
+                        .needs_java()
+                })?
             {
-                plan_builder = plan_builder.requires("jdk");
+                requires.push("jdk");
             }
-        } else if context
-            .app_dir
-            .join("Gemfile")
-            .fs_err_try_exists()
-            .map_err(DetectError::Gemfile)
-            .map_err(RubyBuildpackError::BuildpackDetectionError)?
-        {
-            plan_builder = plan_builder.requires("ruby");
+        } else {
+            let has_gemfile = gemfile_paths
+                .gemfile()
+                .fs_err_try_exists()
+                .map_err(DetectError::Gemfile)
+                .map_err(RubyBuildpackError::BuildpackDetectionError)?;
+            explain.file(
+                gemfile_paths.display(gemfile_paths.gemfile()),
+                has_gemfile,
+                "requires ruby",
+            );
+            if has_gemfile {
+                requires.push("ruby");
+            }
+        }
+
+        if detect_explain::detect_explain_enabled(context.platform.env()) {
+            explain.print(&requires);
+        }
+
+        // When a later buildpack (such as `heroku/ruby-assets`) requires `ruby_assets` the first
+        // plan is used and asset compilation is delegated to it, otherwise the second plan is used.
+        let mut plan_builder = BuildPlanBuilder::new()
+            .provides("ruby")
+            .provides(build_plan::RUBY_ASSETS_PLAN_NAME);
+        for name in &requires {
+            plan_builder = plan_builder.requires(*name);
+        }
+        plan_builder = plan_builder.or().provides("ruby");
+        for name in &requires {
+            plan_builder = plan_builder.requires(*name);
         }
 
         DetectResultBuilder::pass()
@@ -116,24 +186,108 @@ impl Buildpack for RubyBuildpack {
     #[allow(clippy::too_many_lines)]
     fn build(&self, context: BuildContext<Self>) -> libcnb::Result<BuildResult, Self::Error> {
         let mut build_output = Print::new(stdout()).h2("Heroku Ruby Buildpack");
+        let mut timings = StepTimings::start();
+        let features = Features::from_env(context.platform.env());
+        let mut telemetry = Telemetry::default();
 
         // ## Set default environment
-        let (mut env, store) =
+        let (mut env, mut store) =
             crate::steps::default_env(&context, &context.platform.env().clone())?;
 
         // Gather static information about project
-        let lockfile = context.app_dir.join("Gemfile.lock");
-        let lockfile_contents = fs_err::read_to_string(&lockfile)
-            .map_err(|error| RubyBuildpackError::MissingGemfileLock(lockfile, error))?;
+        let gemfile_paths = GemfilePaths::from_env(context.platform.env(), &context.app_dir);
+        let lockfile = gemfile_paths.lockfile().to_path_buf();
+        // A broken symlink is reported instead of a missing file
+        let lockfile_contents = gemfile_paths
+            .lockfile_exists()
+            .and_then(|_| fs_err::read_to_string(&lockfile))
+            .map_err(|error| {
+                let diagnostics = Diagnostics::list_parent(&features, &lockfile);
+                RubyBuildpackError::MissingGemfileLock(lockfile, error, diagnostics)
+            })?;
         let gemfile_lock = GemfileLock::from_str(&lockfile_contents).expect("Infallible");
-        let bundler_version = gemfile_lock.resolve_bundler("2.5.6");
-        let ruby_version = gemfile_lock.resolve_ruby("3.2.6");
+        // Only used for warnings, an unreadable Gemfile fails `bundle install` with a clearer error
+        let gemfile = fs_err::read_to_string(gemfile_paths.gemfile())
+            .map(|contents| Gemfile::parse(&contents))
+            .unwrap_or_default();
+        let bundler_version = gemfile_lock.resolve_bundler(DEFAULT_BUNDLER_VERSION);
+        let ruby_version_pin = ruby_version_file::ruby_version_pin(&context.app_dir)
+            .map_err(RubyBuildpackError::RubyVersionFileError)?;
+        let (ruby_version, ruby_source) = build_plan::resolve_ruby(
+            &gemfile_lock,
+            ruby_version_pin.as_ref(),
+            &build_plan::ruby_version_requirements(&context.buildpack_plan),
+            "3.2.6",
+        )
+        .map_err(RubyBuildpackError::BuildPlanError)?;
+        let build_steps =
+            BuildSteps::from_env_or_project_toml(context.platform.env(), &context.app_dir)
+                .map_err(RubyBuildpackError::BuildStepsError)?;
+        let target = TargetId {
+            cpu_architecture: context.target.arch.clone(),
+            distro_name: context.target.distro_name.clone(),
+            distro_version: context.target.distro_version.clone(),
+        };
+        // Fail before any work is done when the builder has no Ruby binaries, an architecture
+        // without binaries is allowed when Ruby is compiled from source
+        if build_steps.enabled(BuildStep::RubyInstall) {
+            target
+                .stack_match()
+                .map_err(RubyBuildpackError::UnsupportedTarget)?;
+            if !features.enabled(Feature::BuildFromSource) {
+                target
+                    .check_architecture()
+                    .map_err(RubyBuildpackError::UnsupportedTarget)?;
+            }
+        }
+        let requested_ruby_version = ruby_version;
+        let inventory = layers::inventory_layer::handle(
+            &context,
+            &env,
+            &target,
+            &requested_ruby_version,
+            &mut telemetry,
+        )?;
+        let ruby_version = if build_steps.enabled(BuildStep::RubyInstall) {
+            layers::ruby_install_layer::resolve_partial_version(
+                &inventory,
+                &target,
+                &requested_ruby_version,
+            )
+            .map_err(RubyBuildpackError::RubyInstallError)?
+        } else {
+            requested_ruby_version.clone()
+        };
+        let cache_dirs = steps::cache_dirs_from_project_toml(&context.app_dir)
+            .map_err(RubyBuildpackError::CacheDirsError)?;
+        let asset_tasks =
+            steps::AssetTasks::from_env_or_project_toml(context.platform.env(), &context.app_dir)
+                .map_err(RubyBuildpackError::AssetTasksError)?;
+        let warnings = warning::Warnings::from_env(context.platform.env());
+        layers::build_logs_layer::handle(&context, &mut telemetry)?;
+        timings.lap("Setup");
+
+        // ## Changes since last build
+        build_output = layers::build_report_layer::handle(
+            build_output,
+            &context,
+            &lockfile_contents,
+            &ruby_version,
+            &bundler_version,
+            &mut telemetry,
+        )?;
 
         // ## Install metrics agent
         build_output = {
             let bullet = build_output.bullet("Metrics agent");
             if lockfile_contents.contains("barnes") {
-                layers::metrics_agent_install::handle_metrics_agent_layer(&context, bullet)?.done()
+                layers::metrics_agent_install::handle_metrics_agent_layer(
+                    &context,
+                    &env,
+                    bullet,
+                    &mut telemetry,
+                )?
+                .done()
             } else {
                 bullet
                     .sub_bullet(format!(
@@ -143,29 +297,93 @@ impl Buildpack for RubyBuildpack {
                     .done()
             }
         };
+        timings.lap("Metrics agent");
+
+        let bundler_metadata = layers::bundle_download_layer::Metadata {
+            version: bundler_version.clone(),
+            ruby_abi: ruby_version.abi(),
+        };
+        let bundler_layer = if build_steps.enabled(BuildStep::Bundler) {
+            Some(layers::bundle_download_layer::layer(
+                &context,
+                &bundler_metadata,
+                &mut telemetry,
+            )?)
+        } else {
+            None
+        };
 
         // ## Install executable ruby version
-        (build_output, env) = {
-            let bullet = build_output.bullet(format!(
+        //
+        // Bundler is installed with Ruby's `gem` command, but its download does not need Ruby.
+        // It's prefetched on another thread while Ruby downloads, all output and layer writes
+        // stay on this thread.
+        let prefetched_bundler;
+        (build_output, env, prefetched_bundler) = std::thread::scope(|scope| {
+            let prefetch = bundler_layer
+                .as_ref()
+                .filter(|layer| layer.needs_download(&context.app_dir, &bundler_version))
+                .map(|_| {
+                    let proxy = http::ProxyConfig::from_env(&env);
+                    let bundler_version = &bundler_version;
+                    scope.spawn(move || {
+                        layers::bundle_download_layer::prefetch(&proxy, bundler_version)
+                    })
+                });
+
+            let mut bullet = build_output.bullet(format!(
                 "Ruby version {} from {}",
                 style::value(ruby_version.to_string()),
-                style::value(gemfile_lock.ruby_source())
+                style::value(ruby_source)
             ));
-            let (bullet, layer_env) = layers::ruby_install_layer::handle(
-                &context,
-                bullet,
-                &layers::ruby_install_layer::Metadata {
-                    os_distribution: OsDistribution {
+            if requested_ruby_version != ruby_version {
+                bullet = bullet.sub_bullet(format!(
+                    "Resolved {} to the newest available version {}",
+                    style::value(requested_ruby_version.to_string()),
+                    style::value(ruby_version.to_string())
+                ));
+            }
+            let (build_output, env) = if build_steps.enabled(BuildStep::RubyInstall) {
+                let metadata = layers::ruby_install_layer::Metadata::new(
+                    OsDistribution {
                         name: context.target.distro_name.clone(),
                         version: context.target.distro_version.clone(),
                     },
-                    cpu_architecture: context.target.arch.clone(),
-                    ruby_version: ruby_version.clone(),
-                },
-            )?;
-
-            (bullet.done(), layer_env.apply(Scope::Build, &env))
-        };
+                    context.target.arch.clone(),
+                    ruby_version.clone(),
+                )
+                .map_err(RubyBuildpackError::RubyInstallError)?;
+                let (bullet, layer_env, downloaded) = layers::ruby_install_layer::handle(
+                    &context,
+                    &env,
+                    &features,
+                    &inventory,
+                    bullet,
+                    &metadata,
+                    &mut telemetry,
+                )?;
+                telemetry.set("bytes_downloaded", downloaded);
+
+                (
+                    warnings.print(
+                        bullet.done(),
+                        layers::ruby_install_layer::distribution_warning(&metadata),
+                    ),
+                    layer_env.apply(Scope::Build, &env),
+                )
+            } else {
+                (
+                    bullet
+                        .sub_bullet(build_steps.skip_reason(BuildStep::RubyInstall))
+                        .done(),
+                    env,
+                )
+            };
+            // A panicked prefetch is treated like a failed one, `gem install` downloads instead
+            let prefetched = prefetch.and_then(|handle| handle.join().ok().flatten());
+            Ok::<_, libcnb::Error<RubyBuildpackError>>((build_output, env, prefetched))
+        })?;
+        timings.lap("Ruby install");
 
         // ## Setup bundler
         (build_output, env) = {
@@ -174,52 +392,84 @@ impl Buildpack for RubyBuildpack {
                 style::value(bundler_version.to_string()),
                 style::value(gemfile_lock.bundler_source())
             ));
-            let (bullet, layer_env) = layers::bundle_download_layer::handle(
-                &context,
-                &env,
-                bullet,
-                &layers::bundle_download_layer::Metadata {
-                    version: bundler_version,
-                },
-            )?;
-
-            (bullet.done(), layer_env.apply(Scope::Build, &env))
+            if let Some(bundler_layer) = bundler_layer {
+                let (bullet, layer_env) = layers::bundle_download_layer::handle(
+                    &context,
+                    &env,
+                    &features,
+                    &inventory,
+                    bullet,
+                    bundler_layer,
+                    prefetched_bundler.as_ref(),
+                )?;
+                let env = layer_env.apply(Scope::Build, &env);
+                let (bullet, warning) =
+                    steps::bundle_platform_ruby(bullet, &env, &context.app_dir, &ruby_version);
+
+                (warnings.print(bullet.done(), warning), env)
+            } else {
+                (
+                    bullet
+                        .sub_bullet(build_steps.skip_reason(BuildStep::Bundler))
+                        .done(),
+                    env,
+                )
+            }
         };
+        timings.lap("Bundler download");
+
+        build_output = steps::run_build_hook(build_output, BuildHook::Prebuild, &context, &env)?;
 
         // ## Bundle install
         (build_output, env) = {
             let bullet = build_output.bullet("Bundle install gems");
-            let (bullet, layer_env) = layers::bundle_install_layer::handle(
-                &context,
-                &env,
-                bullet,
-                &layers::bundle_install_layer::Metadata {
-                    os_distribution: OsDistribution {
-                        name: context.target.distro_name.clone(),
-                        version: context.target.distro_version.clone(),
+            if build_steps.enabled(BuildStep::BundleInstall) {
+                let (bullet, layer_env) = layers::bundle_install_layer::handle(
+                    &context,
+                    &env,
+                    &features,
+                    bullet,
+                    &layers::bundle_install_layer::Metadata {
+                        os_distribution: OsDistribution {
+                            name: context.target.distro_name.clone(),
+                            version: context.target.distro_version.clone(),
+                        },
+                        cpu_architecture: context.target.arch.clone(),
+                        ruby_version: ruby_version.clone(),
+                        force_bundle_install_key: String::from(
+                            crate::layers::bundle_install_layer::FORCE_BUNDLE_INSTALL_CACHE_KEY,
+                        ),
+                        bundle_with: layers::bundle_install_layer::bundle_with(
+                            context.platform.env(),
+                        ),
+                        digest: MetadataDigest::new_env_files(
+                            &context.platform,
+                            &gemfile_paths.digest_files(),
+                        )
+                        .map_err(|error| match error {
+                            commons::metadata_digest::DigestError::CannotReadFile(path, error) => {
+                                let diagnostics = Diagnostics::list_parent(&features, &path);
+                                RubyBuildpackError::BundleInstallDigestError(
+                                    path,
+                                    error,
+                                    diagnostics,
+                                )
+                            }
+                        })?,
                     },
-                    cpu_architecture: context.target.arch.clone(),
-                    ruby_version: ruby_version.clone(),
-                    force_bundle_install_key: String::from(
-                        crate::layers::bundle_install_layer::FORCE_BUNDLE_INSTALL_CACHE_KEY,
-                    ),
-                    digest: MetadataDigest::new_env_files(
-                        &context.platform,
-                        &[
-                            &context.app_dir.join("Gemfile"),
-                            &context.app_dir.join("Gemfile.lock"),
-                        ],
-                    )
-                    .map_err(|error| match error {
-                        commons::metadata_digest::DigestError::CannotReadFile(path, error) => {
-                            RubyBuildpackError::BundleInstallDigestError(path, error)
-                        }
-                    })?,
-                },
-                &BundleWithout::new("development:test"),
-            )?;
+                    &gemfile_lock,
+                    &mut telemetry,
+                )?;
 
-            (bullet.done(), layer_env.apply(Scope::Build, &env))
+                (bullet.done(), layer_env.apply(Scope::Build, &env))
+            } else {
+                (
+                    bullet
+                        .sub_bullet(build_steps.skip_reason(BuildStep::BundleInstall))
+                        .done(),
+                    env,
+                )
+            }
         };
 
         env = {
@@ -243,44 +493,276 @@ impl Buildpack for RubyBuildpack {
 
             user_binstubs.read_env()?.apply(Scope::Build, &env)
         };
+        timings.lap("Bundle install");
 
         // ## Detect gems
-        let (mut build_output, gem_list, default_process) = {
+        let (mut build_output, gem_list, processes) = {
             let bullet = build_output.bullet("Default process detection");
 
-            let (bullet, gem_list) =
-                gem_list::bundle_list(bullet, &env).map_err(RubyBuildpackError::GemListGetError)?;
-            let (bullet, default_process) = steps::get_default_process(bullet, &context, &gem_list);
+            if build_steps.enabled(BuildStep::GemDetect) {
+                let (bullet, gem_list) = gem_list::bundle_list(bullet, &env, &lockfile_contents)
+                    .map_err(|error| {
+                        RubyBuildpackError::GemListGetError(
+                            error,
+                            Diagnostics::capture(&features, &env, &context.app_dir),
+                        )
+                    })?;
+                telemetry.set("gems_installed", gem_list.gems.len());
+                telemetry.set("gems_platform_specific", gem_list.platform_specific());
+                let (bullet, default_process) =
+                    steps::get_default_process(bullet, &context, &gem_list);
+                let (bullet, rake_process) = steps::rake_process(bullet, &gem_list);
+
+                (
+                    bullet.done(),
+                    gem_list,
+                    default_process.into_iter().chain(rake_process).collect(),
+                )
+            } else {
+                (
+                    bullet
+                        .sub_bullet(build_steps.skip_reason(BuildStep::GemDetect))
+                        .done(),
+                    gem_list::GemList::default(),
+                    Vec::new(),
+                )
+            }
+        };
+        timings.lap("Gem detection");
+        build_output = warnings.print(
+            build_output,
+            [
+                steps::procfile_warning(&context),
+                steps::slugignore_warning(&context.app_dir),
+                steps::dummy_app_warning(&context.app_dir),
+                steps::active_storage_warning(&gem_list, &context.app_dir),
+                steps::database_url_warning(&gem_list, context.platform.env()),
+                steps::js_lockfile_warning(&context.app_dir),
+                steps::legacy_gems_warning(&gem_list),
+                steps::spring_warning(&gem_list, context.platform.env()),
+                steps::dotenv_warning(&gem_list, &lockfile_contents, &gemfile, &context.app_dir),
+                steps::duplicate_gems_warning(&gem_list),
+                steps::vendor_bundle_warning(&features),
+                features.unknown_warning(),
+            ]
+            .into_iter()
+            .flatten(),
+        );
+
+        // ## Launch environment
+        build_output = steps::launch_env_defaults(
+            build_output.bullet("Launch environment"),
+            &context,
+            &gem_list,
+        )?
+        .done();
+
+        // ## Bootsnap cache
+        let (build_output, bootsnap_cache) = {
+            let (bullet, store) = steps::bootsnap_cache_load(
+                build_output.bullet("Bootsnap cache"),
+                &context,
+                &gem_list,
+                &ruby_version,
+            )?;
+            (bullet.done(), store)
+        };
+        timings.lap("Bootsnap cache");
 
-            (bullet.done(), gem_list, default_process)
+        // ## Cache directories from project.toml
+        let (mut build_output, cache_dirs) = if cache_dirs.is_empty() {
+            (build_output, None)
+        } else {
+            let (bullet, collection) = steps::cache_dirs_load(
+                build_output.bullet("Cache directories"),
+                &context,
+                &cache_dirs,
+            )?;
+            (bullet.done(), Some(collection))
         };
 
         // ## Assets install
-        build_output = {
+        if !build_steps.enabled(BuildStep::Assets) {
+            build_output = build_output
+                .bullet("Rake assets install")
+                .sub_bullet(build_steps.skip_reason(BuildStep::Assets))
+                .done();
+        } else if let Some(dummy) = steps::dummy_app(&context.app_dir) {
+            build_output = build_output
+                .bullet("Rake assets install")
+                .sub_bullet(format!(
+                    "Skipping (found {} and a dummy Rails app in {}, not a Rails application)",
+                    style::value(dummy.gemspec),
+                    style::value(dummy.dir)
+                ))
+                .done();
+        } else if steps::jekyll_site(&gem_list, &context.app_dir) {
+            build_output =
+                steps::jekyll_build(build_output.bullet("Jekyll build"), &context, &env)?.done();
+            timings.lap("Assets");
+        } else if build_plan::assets_delegated(&context.buildpack_plan) {
+            build_output = build_output
+                .bullet("Rake assets install")
+                .sub_bullet(format!(
+                    "Skipping (asset compilation is handled by {})",
+                    style::value("heroku/ruby-assets")
+                ))
+                .done();
+        } else {
             let (bullet, rake_detect) = crate::steps::detect_rake_tasks(
                 build_output.bullet("Rake assets install"),
                 &gem_list,
                 &context,
                 &env,
+                &features,
+                &asset_tasks,
             )?;
-
-            if let Some(rake_detect) = rake_detect {
-                crate::steps::rake_assets_install(bullet, &context, &env, &rake_detect)?
+            timings.lap("Rake detection");
+
+            build_output = if let Some(rake_detect) = &rake_detect {
+                crate::steps::rake_assets_install(
+                    bullet,
+                    &context,
+                    &env,
+                    &features,
+                    &gem_list,
+                    rake_detect,
+                    &asset_tasks,
+                    &mut telemetry,
+                )?
             } else {
                 bullet
             }
-            .done()
+            .done();
+            timings.lap("Assets");
+
+            build_output = warnings.print(
+                build_output,
+                steps::css_build_warning(&gem_list, rake_detect.as_ref())
+                    .into_iter()
+                    .chain(steps::asset_pipeline_warning(
+                        &gem_list,
+                        rake_detect.as_ref(),
+                        &context.app_dir,
+                    )),
+            );
+        }
+
+        // ## JRuby warmup
+        if layers::jruby_warmup_layer::enabled(&features, gemfile_lock.engine()) {
+            build_output = layers::jruby_warmup_layer::handle(
+                build_output.bullet("JRuby warmup"),
+                &context,
+                &env,
+                &ruby_version,
+                &mut telemetry,
+            )?
+            .done();
+            timings.lap("JRuby warmup");
+        }
+
+        build_output = steps::run_build_hook(build_output, BuildHook::Postbuild, &context, &env)?;
+
+        // ## Zeitwerk check
+        if let Some(mode) = steps::zeitwerk_check_mode(context.platform.env()) {
+            let (bullet, warning) = steps::zeitwerk_check(
+                build_output.bullet("Zeitwerk check"),
+                mode,
+                &context,
+                &env,
+                &gem_list,
+            )?;
+            build_output = warnings.print(bullet.done(), warning);
+            timings.lap("Zeitwerk check");
+        }
+
+        // ## Boot check
+        if features.enabled(Feature::BootCheck) {
+            build_output =
+                steps::boot_check(build_output.bullet("Boot check"), &context, &env, &gem_list)?
+                    .done();
+            timings.lap("Boot check");
+        }
+
+        if let Some(store) = &bootsnap_cache {
+            build_output =
+                steps::bootsnap_cache_save(build_output.bullet("Bootsnap cache"), store)?.done();
+        }
+        if let Some(collection) = &cache_dirs {
+            build_output =
+                steps::cache_dirs_save(build_output.bullet("Cache directories"), collection)?
+                    .done();
+        }
+
+        // ## Read-only workspace
+        if features.enabled(Feature::ReadOnlyWorkspace) {
+            build_output = layers::writable_tmp_install::handle(
+                &context,
+                build_output.bullet("Writable tmp directories"),
+            )?
+            .done();
+        }
+
+        // ## Runtime diagnostics
+        build_output = {
+            let bullet = build_output.bullet("Runtime diagnostics");
+            let bullet = if features.enabled(Feature::Doctor) {
+                layers::doctor_install::handle(&context, bullet)?
+            } else {
+                bullet.sub_bullet(format!(
+                    "Skipping {doctor} install (enable with {env})",
+                    doctor = style::command(layers::doctor_install::DOCTOR_EXECUTABLE),
+                    env = style::value("HEROKU_RUBY_FEATURE_DOCTOR=1")
+                ))
+            };
+            layers::path_check_install::handle(&context, bullet)?.done()
         };
+        if features.enabled(Feature::BundleCheckAtLaunch) {
+            build_output = layers::bundle_check_install::handle(
+                &context,
+                build_output.bullet("Launch bundle check"),
+            )?
+            .done();
+        }
+        timings.lap("Runtime diagnostics");
+
+        let (bullet, large_files) = steps::large_files(
+            build_output.bullet("Image size"),
+            &context.app_dir,
+            &context.layers_dir,
+        );
+        build_output = warnings.print(bullet.done(), large_files);
+
+        // ## Image labels
+        let (bullet, labels) = steps::image_labels(
+            build_output.bullet("Image labels"),
+            &ruby_version,
+            &bundler_version,
+            &gem_list,
+        );
+        build_output = bullet.done();
+
+        if env_plan::enabled(&features, context.platform.env()) {
+            build_output = env_plan::print_env_plan(
+                build_output.bullet("Environment plan"),
+                &context.layers_dir,
+            )
+            .done();
+        }
+
+        build_output = timings.print(build_output.bullet("Step timings")).done();
         build_output.done();
+        telemetry.write(&timings, &mut store);
 
-        if let Some(default_process) = default_process {
-            BuildResultBuilder::new()
-                .launch(LaunchBuilder::new().process(default_process).build())
-                .store(store)
-                .build()
-        } else {
-            BuildResultBuilder::new().store(store).build()
+        let mut launch = LaunchBuilder::new();
+        launch.labels(labels);
+        for process in processes {
+            launch.process(process);
         }
+        BuildResultBuilder::new()
+            .launch(launch.build())
+            .store(store)
+            .build()
     }
 
     fn on_error(&self, err: libcnb::Error<Self::Error>) {
@@ -288,24 +770,44 @@ impl Buildpack for RubyBuildpack {
     }
 }
 
-fn needs_java(gemfile_lock: impl AsRef<str>) -> bool {
-    let java_regex = regex::Regex::new(r"\(jruby ").expect("clippy");
-    java_regex.is_match(gemfile_lock.as_ref())
-}
-
 #[derive(Debug)]
 pub(crate) enum RubyBuildpackError {
     BuildpackDetectionError(DetectError),
-    RakeDetectError(CmdError),
-    GemListGetError(CmdError),
+    BuildPlanError(BuildPlanError),
+    BuildStepsError(BuildStepsError),
+    CacheDirsError(CacheDirsError),
+    AssetTasksError(AssetTasksError),
+    RubyVersionFileError(std::io::Error),
+    RakeDetectError(CmdError, Diagnostics),
+    GemListGetError(CmdError, Diagnostics),
     RubyInstallError(RubyInstallError),
+    UnsupportedTarget(target_id::TargetIdError),
     MetricsAgentError(MetricsAgentInstallError),
-    MissingGemfileLock(std::path::PathBuf, std::io::Error),
+    MissingGemfileLock(std::path::PathBuf, std::io::Error, Diagnostics),
     InAppDirCacheError(CacheError),
-    BundleInstallDigestError(std::path::PathBuf, std::io::Error),
-    BundleInstallCommandError(CmdError),
+    BootsnapCacheError(std::io::Error),
+    BundleInstallDigestError(std::path::PathBuf, std::io::Error, Diagnostics),
+    AssetsDigestError(std::path::PathBuf, std::io::Error),
+    BundleInstallCommandError(CmdError, Diagnostics),
+    NativeExtensionError(
+        CmdError,
+        Box<native_extension::NativeExtensionFailure>,
+        Diagnostics,
+    ),
+    BundleAddPlatformError(CmdError),
     RakeAssetsPrecompileFailed(CmdError),
-    GemInstallBundlerCommandError(CmdError),
+    AssetManifestError(AssetManifestError),
+    JekyllBuildFailed(CmdError),
+    BootCheckFailed(CmdError),
+    BuildHookFailed(CmdError),
+    JrubyWarmupFailed(CmdError),
+    JrubyWarmupDigestError(std::path::PathBuf, std::io::Error),
+    ZeitwerkCheckFailed(CmdError),
+    GemInstallBundlerCommandError(CmdError, Diagnostics),
+    DoctorInstallError(std::io::Error),
+    BundleCheckInstallError(std::io::Error),
+    PathCheckInstallError(std::io::Error),
+    WritableTmpError(std::io::Error),
 }
 
 impl From<RubyBuildpackError> for libcnb::Error<RubyBuildpackError> {
@@ -327,21 +829,15 @@ impl BundleWithout {
     fn as_str(&self) -> &str {
         &self.0
     }
-}
 
-#[cfg(test)]
-mod test {
-    use super::*;
-
-    #[test]
-    fn test_needs_java() {
-        let gemfile_lock = r"";
-        assert!(!needs_java(gemfile_lock));
-
-        let gemfile_lock = r"
-RUBY VERSION
-   ruby 2.5.7p001 (jruby 9.2.13.0)
-";
-        assert!(needs_java(gemfile_lock));
+    /// Removes groups listed in `with`, separated by `:`, so they are installed
+    fn excluding(&self, with: &str) -> Self {
+        Self(
+            self.0
+                .split(':')
+                .filter(|group| !with.split(':').any(|with| with == *group))
+                .collect::<Vec<_>>()
+                .join(":"),
+        )
     }
 }
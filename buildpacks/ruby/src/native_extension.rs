@@ -0,0 +1,175 @@
+//! Isolate the log of a native extension that failed to compile
+//!
+//! Bundler installs gems in parallel and `make` may compile in parallel too, so when a native
+//! extension fails the compiler errors are interleaved with output from other gems. When
+//! `bundle install` fails on a native extension it runs again with `--jobs 1` and
+//! `MAKEFLAGS=-j1`. Gems that installed before the failure are skipped, so only the remaining
+//! gems are built, one at a time. The output from the failed gem is cut from that run and
+//! reported instead of the interleaved output.
+//!
+//! The second run is skipped when `HEROKU_RUBY_FEATURE_DISABLE_DIAGNOSTICS=1` is set. It's stopped
+//! when it takes longer than ten minutes, and the log is cut from the original output instead.
+use crate::diagnostics;
+use fun_run::CommandWithName;
+use libcnb::Env;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+/// Lines kept from the end of a log that has no clear start
+const MAX_LOG_LINES: usize = 100;
+
+/// How long the serial run may take before it's stopped
+const SERIAL_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// A gem whose native extension failed to compile, and the focused log of the failure
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct NativeExtensionFailure {
+    pub(crate) gem: String,
+    pub(crate) version: String,
+    /// The command that produced the log
+    pub(crate) command: String,
+    pub(crate) log: String,
+    pub(crate) serial: SerialRun,
+}
+
+/// How the serial `bundle install` went
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SerialRun {
+    /// Every gem installed, the original failure is likely caused by compiling in parallel
+    Succeeded,
+    /// The log is from the serial run
+    Failed,
+    /// It could not start or timed out, the log is from the original run
+    Incomplete,
+}
+
+/// Returns the name and version of the gem when `bundle install` failed on a native extension
+pub(crate) fn failed_gem(output: &str) -> Option<(String, String)> {
+    if !output.contains("Failed to build gem native extension") {
+        return None;
+    }
+    output.lines().rev().find_map(|line| {
+        let (gem, rest) = line
+            .trim()
+            .strip_prefix("An error occurred while installing ")?
+            .split_once(" (")?;
+        let (version, _) = rest.split_once(')')?;
+        Some((gem.to_string(), version.to_string()))
+    })
+}
+
+/// Runs `bundle install` again without parallelism and returns the log of the failed gem
+///
+/// When the second run can't start, times out, or succeeds, the log is cut from the original
+/// output.
+pub(crate) fn isolate(env: &Env, output: &str, gem: &str, version: &str) -> NativeExtensionFailure {
+    let mut cmd = Command::new("bundle");
+    cmd.args(["install", "--jobs", "1"])
+        .env_clear()
+        .envs(env)
+        .env("MAKEFLAGS", "-j1")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    let command = format!("MAKEFLAGS=-j1 {}", cmd.name());
+
+    let serial = cmd
+        .spawn()
+        .and_then(|child| diagnostics::wait_with_timeout(vec![child], SERIAL_TIMEOUT));
+    let (log, serial) = match serial {
+        Ok(serial) if serial.status.success() => {
+            (focused_log(output, gem, version), SerialRun::Succeeded)
+        }
+        Ok(serial) => (
+            focused_log(
+                &format!(
+                    "{}\n{}",
+                    String::from_utf8_lossy(&serial.stdout),
+                    String::from_utf8_lossy(&serial.stderr)
+                ),
+                gem,
+                version,
+            ),
+            SerialRun::Failed,
+        ),
+        Err(_) => (focused_log(output, gem, version), SerialRun::Incomplete),
+    };
+
+    NativeExtensionFailure {
+        gem: gem.to_string(),
+        version: version.to_string(),
+        command,
+        log,
+        serial,
+    }
+}
+
+/// Cuts the output from where bundler starts installing the gem until it reports the failure
+///
+/// Falls back to the end of the output when the start or end can't be found.
+fn focused_log(output: &str, gem: &str, version: &str) -> String {
+    let lines = output.lines().collect::<Vec<_>>();
+    let start = lines.iter().rposition(|line| {
+        line.trim() == format!("Installing {gem} {version} with native extensions")
+    });
+    let end = lines.iter().rposition(|line| {
+        line.trim().starts_with(&format!(
+            "An error occurred while installing {gem} ({version})"
+        ))
+    });
+
+    let section = match (start, end) {
+        (Some(start), Some(end)) if start < end => &lines[start..=end],
+        (_, Some(end)) => &lines[end.saturating_sub(MAX_LOG_LINES)..=end],
+        _ => &lines[lines.len().saturating_sub(MAX_LOG_LINES)..],
+    };
+    section.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    const OUTPUT: &str = indoc! {"
+        Fetching nokogiri 1.16.0
+        Installing nokogiri 1.16.0 with native extensions
+        Installing rack 3.0.8
+        Gem::Ext::BuildError: ERROR: Failed to build gem native extension.
+
+            current directory: /layers/heroku_ruby/gems/ruby/3.3.0/gems/nokogiri-1.16.0/ext/nokogiri
+        Installing puma 6.4.0 with native extensions
+        extconf failed, exit code 1
+
+        An error occurred while installing nokogiri (1.16.0), and Bundler cannot continue.
+
+        In Gemfile:
+          rails was resolved to 7.1.3, which depends on
+            nokogiri
+    "};
+
+    #[test]
+    fn test_failed_gem() {
+        assert_eq!(
+            failed_gem(OUTPUT),
+            Some((String::from("nokogiri"), String::from("1.16.0")))
+        );
+        assert_eq!(
+            failed_gem(
+                "An error occurred while installing pg (1.5.4), and Bundler cannot continue."
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_focused_log() {
+        let log = focused_log(OUTPUT, "nokogiri", "1.16.0");
+        assert!(log.starts_with("Installing nokogiri 1.16.0 with native extensions"));
+        assert!(log.ends_with("and Bundler cannot continue."));
+        assert!(!log.contains("In Gemfile"));
+
+        let log = focused_log(OUTPUT, "pg", "1.5.4");
+        assert!(log.starts_with("Fetching nokogiri"));
+    }
+}
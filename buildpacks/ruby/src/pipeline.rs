@@ -0,0 +1,103 @@
+//! Run two commands connected by a pipe
+//!
+//! `fun_run` names and runs a single command. Some steps need `cmd1 | cmd2` semantics, for
+//! example filtering the output of `bundle env`. A `NamedPipeline` connects the stdout of the
+//! first command to the stdin of the second and names the whole pipeline for output and errors.
+//! Callers wait on both processes, so either one can be killed or reported as failed.
+use std::process::{Child, Command, Stdio};
+
+#[derive(Debug)]
+pub(crate) struct NamedPipeline {
+    name: String,
+    first: Command,
+    second: Command,
+}
+
+impl NamedPipeline {
+    pub(crate) fn new(mut first: Command, mut second: Command) -> Self {
+        Self {
+            name: format!(
+                "{} | {}",
+                fun_run::display(&mut first),
+                fun_run::display(&mut second)
+            ),
+            first,
+            second,
+        }
+    }
+
+    pub(crate) fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    /// Starts both commands, the stdout of the second and stderr of both are piped
+    ///
+    /// For callers that need to wait on or kill the processes themselves. The first command is
+    /// killed when the second cannot be started.
+    ///
+    /// # Errors
+    ///
+    /// Errors when either command cannot be started.
+    pub(crate) fn spawn(&mut self) -> std::io::Result<(Child, Child)> {
+        let mut first = self
+            .first
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let pipe = first.stdout.take().map_or_else(Stdio::null, Stdio::from);
+        match self
+            .second
+            .stdin(pipe)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+        {
+            Ok(second) => Ok((first, second)),
+            Err(error) => {
+                let _ = first.kill();
+                let _ = first.wait();
+                Err(error)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    fn command(program: &str, args: &[&str]) -> Command {
+        let mut cmd = Command::new(program);
+        cmd.args(args);
+        cmd
+    }
+
+    #[test]
+    fn test_spawn() {
+        let mut pipeline =
+            NamedPipeline::new(command("echo", &["hello"]), command("tr", &["a-z", "A-Z"]));
+        assert_eq!(pipeline.name(), "echo hello | tr a-z A-Z");
+
+        let (mut first, mut second) = pipeline.spawn().unwrap();
+        let mut stdout = String::new();
+        second
+            .stdout
+            .take()
+            .unwrap()
+            .read_to_string(&mut stdout)
+            .unwrap();
+        assert!(first.wait().unwrap().success());
+        assert!(second.wait().unwrap().success());
+        assert_eq!(stdout, "HELLO\n");
+    }
+
+    #[test]
+    fn test_spawn_missing_command() {
+        assert!(
+            NamedPipeline::new(command("echo", &["hello"]), command("does-not-exist", &[]))
+                .spawn()
+                .is_err()
+        );
+    }
+}
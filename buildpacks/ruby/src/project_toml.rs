@@ -0,0 +1,88 @@
+//! Read buildpack settings from `project.toml`
+//!
+//! Settings live in the `[com.heroku.buildpacks.ruby]` table:
+//!
+//! ```toml
+//! [com.heroku.buildpacks.ruby]
+//! skip_steps = ["assets"]
+//! ```
+//!
+//! A missing file, table, or key is not an error. A value of the wrong type is, for example a
+//! list with a number in it where a list of strings is expected.
+use serde::de::DeserializeOwned;
+use std::path::Path;
+
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum ProjectTomlError {
+    #[error("{0}")]
+    Read(std::io::Error),
+
+    #[error("{0}")]
+    Parse(toml::de::Error),
+
+    #[error("Invalid `{0}`: {1}")]
+    Invalid(String, toml::de::Error),
+}
+
+/// Reads `key` from the `[com.heroku.buildpacks.ruby]` table of `project.toml` in `app_dir`
+///
+/// # Errors
+///
+/// Errors when `project.toml` cannot be read or parsed, or the value is not a `T`.
+pub(crate) fn ruby_setting<T: DeserializeOwned>(
+    app_dir: &Path,
+    key: &str,
+) -> Result<Option<T>, ProjectTomlError> {
+    let contents = match fs_err::read_to_string(app_dir.join("project.toml")) {
+        Ok(contents) => contents,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(error) => return Err(ProjectTomlError::Read(error)),
+    };
+    let table = contents
+        .parse::<toml::Table>()
+        .map_err(ProjectTomlError::Parse)?;
+
+    table
+        .get("com")
+        .and_then(|com| com.get("heroku"))
+        .and_then(|heroku| heroku.get("buildpacks"))
+        .and_then(|buildpacks| buildpacks.get("ruby"))
+        .and_then(|ruby| ruby.get(key))
+        .cloned()
+        .map(|value| {
+            value
+                .try_into::<T>()
+                .map_err(|error| ProjectTomlError::Invalid(key.to_string(), error))
+        })
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ruby_setting() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(ruby_setting::<Vec<String>>(dir.path(), "skip_steps")
+            .unwrap()
+            .is_none());
+
+        fs_err::write(
+            dir.path().join("project.toml"),
+            "[com.heroku.buildpacks.ruby]\nskip_steps = [\"assets\"]\nassets_tasks = [1]\n",
+        )
+        .unwrap();
+        assert_eq!(
+            ruby_setting::<Vec<String>>(dir.path(), "skip_steps").unwrap(),
+            Some(vec![String::from("assets")])
+        );
+        assert!(ruby_setting::<Vec<String>>(dir.path(), "cache_dirs")
+            .unwrap()
+            .is_none());
+        assert!(matches!(
+            ruby_setting::<Vec<String>>(dir.path(), "assets_tasks"),
+            Err(ProjectTomlError::Invalid(..))
+        ));
+    }
+}
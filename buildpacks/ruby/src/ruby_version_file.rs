@@ -0,0 +1,155 @@
+//! Read Ruby versions pinned by version manager files
+//!
+//! Applications often pin Ruby for local development with a version manager. When the
+//! `Gemfile.lock` does not specify a Ruby version, these files are used (in order) so the
+//! version installed by the buildpack matches the version used locally:
+//!
+//! - `.ruby-version` (rbenv, chruby, rvm)
+//! - `.tool-versions` (asdf, mise)
+//! - `mise.toml` (mise)
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct RubyVersionPin {
+    pub(crate) version: String,
+    pub(crate) source: &'static str,
+}
+
+/// Reads a version from the contents of a version manager file
+type VersionParser = fn(&str) -> Option<String>;
+
+/// Returns the first Ruby version pinned by a version manager file in the app dir
+///
+/// # Errors
+///
+/// Errors if a file exists but cannot be read.
+pub(crate) fn ruby_version_pin(app_dir: &Path) -> Result<Option<RubyVersionPin>, std::io::Error> {
+    let parsers: [(&'static str, VersionParser); 3] = [
+        (".ruby-version", parse_ruby_version),
+        (".tool-versions", parse_tool_versions),
+        ("mise.toml", parse_mise_toml),
+    ];
+
+    for (source, parse) in parsers {
+        let contents = match fs_err::read_to_string(app_dir.join(source)) {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(error) => return Err(error),
+        };
+        if let Some(version) = parse(&contents) {
+            return Ok(Some(RubyVersionPin { version, source }));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Versions are written as `3.3.5` or `ruby-3.3.5`
+fn normalize(version: &str) -> Option<String> {
+    let version = version.trim();
+    let version = version.strip_prefix("ruby-").unwrap_or(version);
+    if version.starts_with(|c: char| c.is_ascii_digit()) {
+        Some(version.to_string())
+    } else {
+        None
+    }
+}
+
+fn parse_ruby_version(contents: &str) -> Option<String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))
+        .and_then(normalize)
+}
+
+/// Lines look like `ruby 3.3.5`, extra versions after the first are fallbacks
+fn parse_tool_versions(contents: &str) -> Option<String> {
+    contents.lines().find_map(|line| {
+        let line = line.split('#').next().unwrap_or_default();
+        let mut parts = line.split_whitespace();
+        if parts.next() == Some("ruby") {
+            parts.next().and_then(normalize)
+        } else {
+            None
+        }
+    })
+}
+
+/// Supports `ruby = "3.3.5"`, `ruby = ["3.3.5"]`, and `ruby = { version = "3.3.5" }`
+fn parse_mise_toml(contents: &str) -> Option<String> {
+    let table = contents.parse::<toml::Table>().ok()?;
+    let ruby = table.get("tools")?.get("ruby")?;
+    match ruby {
+        toml::Value::String(version) => Some(version.as_str()),
+        toml::Value::Array(versions) => versions.first().and_then(toml::Value::as_str),
+        toml::Value::Table(options) => options.get("version").and_then(toml::Value::as_str),
+        _ => None,
+    }
+    .and_then(normalize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ruby_version() {
+        assert_eq!(parse_ruby_version("3.3.5\n"), Some("3.3.5".to_string()));
+        assert_eq!(parse_ruby_version("ruby-3.2.6"), Some("3.2.6".to_string()));
+        assert_eq!(parse_ruby_version("system"), None);
+        assert_eq!(parse_ruby_version(""), None);
+    }
+
+    #[test]
+    fn test_parse_tool_versions() {
+        assert_eq!(
+            parse_tool_versions("nodejs 20.1.0\nruby 3.3.5 3.2.6 # comment\n"),
+            Some("3.3.5".to_string())
+        );
+        assert_eq!(parse_tool_versions("# ruby 3.3.5\nnodejs 20.1.0"), None);
+    }
+
+    #[test]
+    fn test_parse_mise_toml() {
+        assert_eq!(
+            parse_mise_toml("[tools]\nruby = \"3.3.5\"\n"),
+            Some("3.3.5".to_string())
+        );
+        assert_eq!(
+            parse_mise_toml("[tools]\nruby = [\"3.3.5\", \"3.2.6\"]\n"),
+            Some("3.3.5".to_string())
+        );
+        assert_eq!(
+            parse_mise_toml("[tools.ruby]\nversion = \"3.3.5\"\n"),
+            Some("3.3.5".to_string())
+        );
+        assert_eq!(parse_mise_toml("[tools]\nnode = \"20\"\n"), None);
+        assert_eq!(parse_mise_toml("not toml ["), None);
+    }
+
+    #[test]
+    fn test_ruby_version_pin_priority() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(ruby_version_pin(dir.path()).unwrap(), None);
+
+        fs_err::write(dir.path().join("mise.toml"), "[tools]\nruby = \"3.1.6\"\n").unwrap();
+        fs_err::write(dir.path().join(".tool-versions"), "ruby 3.2.6\n").unwrap();
+        assert_eq!(
+            ruby_version_pin(dir.path()).unwrap(),
+            Some(RubyVersionPin {
+                version: "3.2.6".to_string(),
+                source: ".tool-versions"
+            })
+        );
+
+        fs_err::write(dir.path().join(".ruby-version"), "3.3.5\n").unwrap();
+        assert_eq!(
+            ruby_version_pin(dir.path()).unwrap(),
+            Some(RubyVersionPin {
+                version: "3.3.5".to_string(),
+                source: ".ruby-version"
+            })
+        );
+    }
+}
@@ -0,0 +1,106 @@
+//! Record how long each major build step takes
+//!
+//! Durations are printed as an aligned table at the end of the build so changes in build
+//! time can be attributed to a specific step.
+use bullet_stream::state::SubBullet;
+use bullet_stream::Print;
+use std::io::Stdout;
+use std::time::{Duration, Instant};
+
+#[derive(Debug)]
+pub(crate) struct StepTimings {
+    last: Instant,
+    steps: Vec<(String, Duration)>,
+}
+
+impl StepTimings {
+    pub(crate) fn start() -> Self {
+        Self {
+            last: Instant::now(),
+            steps: Vec::new(),
+        }
+    }
+
+    /// Records the time elapsed since the previous step finished (or since the timer started)
+    pub(crate) fn lap(&mut self, name: impl AsRef<str>) {
+        let now = Instant::now();
+        self.steps
+            .push((name.as_ref().to_string(), now.duration_since(self.last)));
+        self.last = now;
+    }
+
+    /// Recorded steps in the order they finished
+    pub(crate) fn steps(&self) -> &[(String, Duration)] {
+        &self.steps
+    }
+
+    /// Prints one sub bullet per recorded step
+    pub(crate) fn print(&self, mut bullet: Print<SubBullet<Stdout>>) -> Print<SubBullet<Stdout>> {
+        for row in self.rows() {
+            bullet = bullet.sub_bullet(row);
+        }
+        bullet
+    }
+
+    fn rows(&self) -> Vec<String> {
+        let durations = self
+            .steps
+            .iter()
+            .map(|(_, duration)| format_duration(*duration))
+            .collect::<Vec<_>>();
+        let name_width = self
+            .steps
+            .iter()
+            .map(|(name, _)| name.chars().count())
+            .max()
+            .unwrap_or_default();
+        let duration_width = durations.iter().map(String::len).max().unwrap_or_default();
+
+        self.steps
+            .iter()
+            .zip(durations)
+            .map(|((name, _), duration)| {
+                format!("{name:<name_width$}  {duration:>duration_width$}")
+            })
+            .collect()
+    }
+}
+
+pub(crate) fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    if seconds >= 60 {
+        format!("{}m {:02}s", seconds / 60, seconds % 60)
+    } else {
+        format!("{:.2}s", duration.as_secs_f64())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::from_millis(1_234)), "1.23s");
+        assert_eq!(format_duration(Duration::from_secs(65)), "1m 05s");
+    }
+
+    #[test]
+    fn test_rows_are_aligned() {
+        let timings = StepTimings {
+            last: Instant::now(),
+            steps: vec![
+                (String::from("Ruby install"), Duration::from_millis(1_500)),
+                (String::from("Bundle install"), Duration::from_secs(125)),
+            ],
+        };
+
+        assert_eq!(
+            timings.rows(),
+            vec![
+                String::from("Ruby install     1.50s"),
+                String::from("Bundle install  2m 05s"),
+            ]
+        );
+    }
+}
@@ -1,9 +1,41 @@
+mod app_warnings;
+mod asset_tasks;
+mod boot_check;
+mod bootsnap_cache;
+mod build_hooks;
+mod bundle_platform;
+mod cache_dirs;
 mod default_env;
 mod detect_rake_tasks;
+mod dummy_app;
 mod get_default_process;
+mod image_labels;
+mod jekyll_build;
+mod large_files;
+mod launch_env;
 mod rake_assets_install;
+mod zeitwerk_check;
 
+pub(crate) use self::app_warnings::{
+    active_storage_warning, asset_pipeline_warning, css_build_warning, database_url_warning,
+    dotenv_warning, duplicate_gems_warning, js_lockfile_warning, legacy_gems_warning,
+    slugignore_warning, spring_warning, vendor_bundle_warning,
+};
+pub(crate) use self::asset_tasks::{AssetTasks, AssetTasksError};
+pub(crate) use self::boot_check::boot_check;
+pub(crate) use self::bootsnap_cache::{bootsnap_cache_load, bootsnap_cache_save};
+pub(crate) use self::build_hooks::{run_build_hook, BuildHook};
+pub(crate) use self::bundle_platform::bundle_platform_ruby;
+pub(crate) use self::cache_dirs::{
+    cache_dirs_from_project_toml, cache_dirs_load, cache_dirs_save, CacheDirsError,
+};
 pub(crate) use self::default_env::default_env;
 pub(crate) use self::detect_rake_tasks::detect_rake_tasks;
-pub(crate) use self::get_default_process::get_default_process;
+pub(crate) use self::dummy_app::{dummy_app, dummy_app_warning};
+pub(crate) use self::get_default_process::{get_default_process, procfile_warning, rake_process};
+pub(crate) use self::image_labels::image_labels;
+pub(crate) use self::jekyll_build::{jekyll_build, jekyll_site};
+pub(crate) use self::large_files::large_files;
+pub(crate) use self::launch_env::launch_env_defaults;
 pub(crate) use self::rake_assets_install::rake_assets_install;
+pub(crate) use self::zeitwerk_check::{zeitwerk_check, zeitwerk_check_mode};
@@ -0,0 +1,850 @@
+//! Advisory warnings about application configuration
+//!
+//! These checks never fail the build. They inspect the gem list and files in the application
+//! to point out configuration that is known to cause problems once the application is running.
+use crate::disk_usage::{disk_size, human_size};
+use crate::features::{Feature, Features};
+use crate::gem_list::GemList;
+use crate::steps::rake_assets_install::CSS_BUILD_GEMS;
+use crate::warning::Warning;
+use bullet_stream::style;
+use commons::gemfile::Gemfile;
+use commons::gemfile_lock::direct_dependencies;
+use commons::rake_task_detect::RakeDetect;
+use indoc::formatdoc;
+use libcnb::Env;
+use std::path::{Path, PathBuf};
+
+/// Database adapter gems that usually read their connection from `DATABASE_URL`
+const DATABASE_GEMS: &[&str] = &["pg", "mysql2", "trilogy"];
+
+/// Gems that were needed on the classic Heroku stack and are obsolete or harmful now,
+/// paired with what to use instead
+const LEGACY_GEMS: &[(&str, &str)] = &[
+    (
+        "rails_12factor",
+        "Rails 5+ logs to stdout and serves static files based on `RAILS_LOG_TO_STDOUT` and `RAILS_SERVE_STATIC_FILES`, which this buildpack sets",
+    ),
+    (
+        "rails_serve_static_assets",
+        "Rails serves static files when `RAILS_SERVE_STATIC_FILES` is set, which this buildpack sets",
+    ),
+    (
+        "rails_stdout_logging",
+        "Rails logs to stdout when `RAILS_LOG_TO_STDOUT` is set, which this buildpack sets",
+    ),
+    (
+        "heroku-deflater",
+        "Use `Rack::Deflater` in `config/application.rb`, or compress responses at a CDN",
+    ),
+];
+
+/// Warns about installed gems that were needed on the classic Heroku stack
+pub(crate) fn legacy_gems_warning(gem_list: &GemList) -> Option<Warning> {
+    let found = LEGACY_GEMS
+        .iter()
+        .filter(|(gem, _)| gem_list.has(gem))
+        .map(|(gem, replacement)| format!("- {}: {replacement}", style::value(gem)))
+        .collect::<Vec<_>>();
+    if found.is_empty() {
+        return None;
+    }
+
+    Some(Warning {
+        code: "legacy_gems",
+        title: "Legacy Heroku gems detected",
+        body: formatdoc! {"
+            These gems were needed for older Heroku stacks and are obsolete or can cause
+            problems with this buildpack. Remove them from your {gemfile}:
+
+            {found}
+            ",
+            gemfile = style::value("Gemfile"),
+            found = found.join("\n"),
+        },
+        url: None,
+    })
+}
+
+/// Warns when `spring` is installed in the production bundle
+///
+/// Spring preloads the application in a background process to speed up development. It's
+/// disabled with `DISABLE_SPRING=1` by default, but it should not be installed in production at
+/// all. When the user set `DISABLE_SPRING` themselves the warning says so, as Spring may run.
+pub(crate) fn spring_warning(gem_list: &GemList, platform_env: &Env) -> Option<Warning> {
+    if !gem_list.has("spring") {
+        return None;
+    }
+    let disable_spring = platform_env
+        .get("DISABLE_SPRING")
+        .map(|value| value.to_string_lossy().to_string());
+
+    Some(Warning {
+        code: "spring",
+        title: "Spring is installed in production",
+        body: formatdoc! {"
+            The {spring} gem preloads your application in a background process to speed up
+            development. It's not needed in production and can cause stale code to run. Move
+            it to the {development} group of your {gemfile}:
+
+            group :development do
+              gem \"spring\"
+            end
+            {env}",
+            spring = style::value("spring"),
+            development = style::value("development"),
+            gemfile = style::value("Gemfile"),
+            env = disable_spring.map_or_else(
+                || format!(
+                    "\nUntil then it's disabled with {}.\n",
+                    style::value("DISABLE_SPRING=1")
+                ),
+                |value| format!(
+                    "\nYou set {}, so this buildpack does not set a default for it.\n",
+                    style::value(format!("DISABLE_SPRING={value}"))
+                )
+            ),
+        },
+        url: None,
+    })
+}
+
+/// Warns when `dotenv` is in the `Gemfile` outside the `development` and `test` groups
+///
+/// The gem list only includes gems that were installed, so a match is in a group that's
+/// loaded in production. Gems that only depend on `dotenv` are not the application's choice,
+/// so the gem must also be in the lockfile `DEPENDENCIES`. A gem the `Gemfile` only declares
+/// in the `development` and `test` groups is installed when a production gem depends on it,
+/// that's not a mistake either. Applications that expect `dotenv` to load a `.env` file at
+/// runtime are missing the variables when the file is not committed, which it should not be.
+pub(crate) fn dotenv_warning(
+    gem_list: &GemList,
+    lockfile_contents: &str,
+    gemfile: &Gemfile,
+    app_path: &Path,
+) -> Option<Warning> {
+    let dependencies = direct_dependencies(lockfile_contents);
+    let development_only = |name: &str| {
+        let mut declarations = gemfile
+            .gems
+            .iter()
+            .filter(|gem| gem.name == name)
+            .peekable();
+        declarations.peek().is_some()
+            && declarations.all(|gem| {
+                !gem.groups.is_empty()
+                    && gem
+                        .groups
+                        .iter()
+                        .all(|group| group == "development" || group == "test")
+            })
+    };
+    let gem = ["dotenv-rails", "dotenv"]
+        .into_iter()
+        .find(|gem| dependencies.contains(*gem) && gem_list.has(gem) && !development_only(gem))?;
+    let dotenv_file = if app_path.join(".env").exists() {
+        format!(
+            "\nA {env} file was found in your application. If it contains secrets, remove it\nfrom git and set them as config vars instead.\n",
+            env = style::value(".env")
+        )
+    } else {
+        String::new()
+    };
+
+    Some(Warning {
+        code: "dotenv",
+        title: "dotenv is installed in production",
+        body: formatdoc! {"
+            The {gem} gem is installed outside of the {development} and {test} groups. It
+            loads environment variables from a {env} file, which usually is not committed, so
+            those variables are missing when your application runs.
+
+            Set environment variables as config vars in production, and move {gem} into the
+            {development} and {test} groups of your {gemfile}:
+
+            group :development, :test do
+              gem \"{name}\"
+            end
+            {dotenv_file}",
+            gem = style::value(gem),
+            name = gem,
+            development = style::value("development"),
+            test = style::value("test"),
+            env = style::value(".env"),
+            gemfile = style::value("Gemfile"),
+        },
+        url: None,
+    })
+}
+
+/// Warns when Active Storage is only configured to store files on the local disk
+///
+/// Files written to disk at runtime are lost when the container restarts.
+pub(crate) fn active_storage_warning(gem_list: &GemList, app_path: &Path) -> Option<Warning> {
+    if !gem_list.has("activestorage") {
+        return None;
+    }
+
+    let storage_yml = app_path.join("config").join("storage.yml");
+    let contents = fs_err::read_to_string(&storage_yml).ok()?;
+    let services = storage_services(&contents);
+
+    if !services.is_empty() && services.iter().all(|service| service == "Disk") {
+        Some(Warning {
+            code: "active_storage_disk",
+            title: "Active Storage is only configured to use local disk",
+            body: formatdoc! {"
+                The {activestorage} gem is installed, but {storage_yml} only configures the {disk}
+                service. Files uploaded to the local disk are lost when your application restarts
+                or is deployed.
+
+                Configure an object storage service such as Amazon S3 for production.
+                ",
+                activestorage = style::value("activestorage"),
+                storage_yml = style::value("config/storage.yml"),
+                disk = style::value("Disk"),
+            },
+            url: Some("https://devcenter.heroku.com/articles/active-storage-on-heroku"),
+        })
+    } else {
+        None
+    }
+}
+
+/// Warns when a database adapter gem is installed but `DATABASE_URL` is not configured
+///
+/// Applications that connect to a database while booting (for example during asset
+/// compilation or at runtime) fail in confusing ways when this value is missing.
+pub(crate) fn database_url_warning(gem_list: &GemList, platform_env: &Env) -> Option<Warning> {
+    let gems = DATABASE_GEMS
+        .iter()
+        .filter(|gem| gem_list.has(gem))
+        .map(style::value)
+        .collect::<Vec<_>>();
+
+    if gems.is_empty() || platform_env.get("DATABASE_URL").is_some() {
+        None
+    } else {
+        Some(Warning {
+            code: "database_url",
+            title: "No database configured",
+            body: formatdoc! {"
+                Detected the database {gems} but the {database_url} environment
+                variable is not set. Your application may fail to boot if it connects to a database.
+
+                If your application needs a database, provision one (for example a Heroku Postgres
+                add-on) or set {database_url} before deploying.
+                ",
+                gems = format!(
+                    "{} {}",
+                    if gems.len() > 1 { "gems" } else { "gem" },
+                    commons::display::list_to_sentence(&gems)
+                ),
+                database_url = style::value("DATABASE_URL"),
+            },
+            url: None,
+        })
+    }
+}
+
+/// Warns when a stylesheet gem is installed but no rake task will compile its stylesheets
+///
+/// `cssbundling-rails` and `tailwindcss-rails` hook into `assets:precompile`. When neither that
+/// task nor the gem's own build task is found, styles are silently missing at runtime.
+pub(crate) fn css_build_warning(gem_list: &GemList, rake: Option<&RakeDetect>) -> Option<Warning> {
+    let rake = rake?;
+    if rake.has_task("assets:precompile") {
+        return None;
+    }
+
+    let (gem, task) = CSS_BUILD_GEMS
+        .into_iter()
+        .find(|(gem, task)| gem_list.has(gem) && !rake.has_task(task))?;
+
+    Some(Warning {
+        code: "css_build",
+        title: "Stylesheets will not be compiled",
+        body: formatdoc! {"
+            The {gem} gem is installed, but neither {precompile} nor {task} was
+            found via {rake_p}. Styles built by {gem} will be missing when your
+            application runs.
+
+            Ensure {task} is listed when running {rake_p} locally with
+            {rails_env}.
+            ",
+            gem = style::value(gem),
+            precompile = style::value("rake assets:precompile"),
+            task = style::value(format!("rake {task}")),
+            rake_p = style::command("rake -P"),
+            rails_env = style::value("RAILS_ENV=production"),
+        },
+        url: None,
+    })
+}
+
+/// Warns when a Rails application has asset sources but no task that compiles them
+///
+/// Without an `assets:precompile` task nothing compiles the assets, so files that reference
+/// them get 404 responses in production. Nothing is reported when rake tasks weren't detected.
+pub(crate) fn asset_pipeline_warning(
+    gem_list: &GemList,
+    rake: Option<&RakeDetect>,
+    app_path: &Path,
+) -> Option<Warning> {
+    let rake = rake?;
+    let dirs = ["app/assets", "app/javascript"]
+        .into_iter()
+        .filter(|dir| app_path.join(dir).is_dir())
+        .map(style::value)
+        .collect::<Vec<_>>();
+    if dirs.is_empty() || !gem_list.has("railties") || rake.has_task("assets:precompile") {
+        return None;
+    }
+
+    Some(Warning {
+        code: "asset_pipeline",
+        title: "No asset pipeline found",
+        body: formatdoc! {"
+            Found {dirs}, but the {precompile} task was not found via
+            {rake_p}. Assets will not be compiled, and static files that reference
+            compiled assets may return 404 errors in production.
+
+            Add an asset pipeline gem such as {propshaft} to your {gemfile}.
+            ",
+            dirs = commons::display::list_to_sentence(&dirs),
+            precompile = style::value("rake assets:precompile"),
+            rake_p = style::command("rake -P"),
+            propshaft = style::value("propshaft"),
+            gemfile = style::value("Gemfile"),
+        },
+        url: None,
+    })
+}
+
+/// Warns when a gem is locked at more than one version
+///
+/// Bundler picks a version per platform, so the version that's loaded at runtime can differ
+/// from the one used while developing locally.
+pub(crate) fn duplicate_gems_warning(gem_list: &GemList) -> Option<Warning> {
+    let duplicates = gem_list
+        .duplicates()
+        .iter()
+        .map(|(gem, versions)| {
+            format!(
+                "- {}: {}",
+                style::value(gem),
+                commons::display::list_to_sentence(
+                    &versions.iter().map(style::value).collect::<Vec<_>>()
+                )
+            )
+        })
+        .collect::<Vec<_>>();
+    if duplicates.is_empty() {
+        return None;
+    }
+
+    Some(Warning {
+        code: "duplicate_gems",
+        title: "Gems locked at multiple versions",
+        body: formatdoc! {"
+            These gems are locked at more than one version in your {lockfile}, usually
+            because each platform resolved to a different version:
+
+            {duplicates}
+
+            The version loaded at runtime may differ from the one you use locally. Run
+            {update} to resolve every platform to the same version.
+            ",
+            lockfile = style::value("Gemfile.lock"),
+            duplicates = duplicates.join("\n"),
+            update = style::value("bundle lock --update <gem>"),
+        },
+        url: None,
+    })
+}
+
+/// Node.js lockfiles, any of them means the package manager and versions are pinned
+const JS_LOCKFILES: &[&str] = &[
+    "yarn.lock",
+    "package-lock.json",
+    "pnpm-lock.yaml",
+    "bun.lockb",
+];
+
+/// Warns when `package.json` has a `build` script but no Node.js lockfile is committed
+///
+/// Tools such as `jsbundling-rails` run the `build` script while compiling assets. Without a
+/// lockfile the package manager that runs it may not be installed, and dependency versions can
+/// change between builds.
+pub(crate) fn js_lockfile_warning(app_path: &Path) -> Option<Warning> {
+    if !has_build_script(app_path) || JS_LOCKFILES.iter().any(|name| app_path.join(name).exists()) {
+        return None;
+    }
+
+    Some(Warning {
+        code: "js_lockfile",
+        title: "No Node.js lockfile found",
+        body: formatdoc! {"
+            The {package_json} file has a {build} script, but none of {lockfiles} are
+            committed. Asset compilation may fail if it runs the {build} script with a
+            package manager that is not installed, and dependency versions may change
+            between deploys.
+
+            Run your package manager's install command locally (for example {npm_install})
+            and commit the lockfile it creates.
+            ",
+            package_json = style::value("package.json"),
+            build = style::value("build"),
+            lockfiles = commons::display::list_to_sentence(
+                &JS_LOCKFILES.iter().map(style::value).collect::<Vec<_>>()
+            ),
+            npm_install = style::command("npm install"),
+        },
+        url: None,
+    })
+}
+
+/// Returns true when `package.json` defines a `scripts.build` entry
+fn has_build_script(app_path: &Path) -> bool {
+    fs_err::read_to_string(app_path.join("package.json"))
+        .ok()
+        .and_then(|contents| serde_json::from_str::<serde_json::Value>(&contents).ok())
+        .is_some_and(|package_json| package_json["scripts"]["build"].is_string())
+}
+
+/// Returns the `service:` values from an Active Storage `storage.yml` file
+///
+/// This is not a YAML parser, it only looks at uncommented `service:` keys.
+fn storage_services(contents: &str) -> Vec<String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| line.strip_prefix("service:"))
+        .map(|service| {
+            service
+                .trim()
+                .trim_matches(|c| c == '"' || c == '\'')
+                .to_string()
+        })
+        .collect()
+}
+
+/// Warns about the image size cost of installing gems into `vendor/bundle`
+///
+/// Vendored gems are part of the application directory. Every deploy ships them in the
+/// application layer, even when no gem changed.
+pub(crate) fn vendor_bundle_warning(features: &Features) -> Option<Warning> {
+    features.enabled(Feature::VendorBundle).then(|| Warning {
+        code: "vendor_bundle",
+        title: "Gems installed into the application directory",
+        body: formatdoc! {"
+            Gems were installed into {vendor_bundle} because {enable} is set. They are part of
+            the application directory, so the image layer that holds your application grows by
+            the size of every installed gem and is rebuilt and pushed on each deploy, even when
+            no gem changed. Gems installed into the buildpack's own layer are only pushed when
+            they change.
+
+            Remove {enable} unless you need to inspect installed gems in {vendor_bundle}.
+            ",
+            vendor_bundle = style::value("vendor/bundle"),
+            enable = style::value("HEROKU_RUBY_FEATURE_VENDOR_BUNDLE"),
+        },
+        url: None,
+    })
+}
+
+/// Warns that `.slugignore` has no effect, and shows what it would have excluded
+///
+/// Heroku's classic build system removed paths listed in `.slugignore` from the slug. The
+/// closest replacement is the `exclude` list of the project descriptor, which is applied
+/// before the build starts.
+pub(crate) fn slugignore_warning(app_path: &Path) -> Option<Warning> {
+    let contents = fs_err::read_to_string(app_path.join(".slugignore")).ok()?;
+    let patterns = contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .collect::<Vec<_>>();
+
+    let mut matched = slugignore_matches(app_path, &patterns)
+        .into_iter()
+        .map(|path| {
+            let size = disk_size(&path);
+            (path, size)
+        })
+        .collect::<Vec<_>>();
+    matched.sort_by_key(|(_, size)| std::cmp::Reverse(*size));
+    let matched = if matched.is_empty() {
+        String::from("No files in the application match it.")
+    } else {
+        format!(
+            "The largest paths it would have excluded:\n\n{}",
+            matched
+                .iter()
+                .take(5)
+                .map(|(path, size)| format!(
+                    "- {} ({})",
+                    style::value(
+                        path.strip_prefix(app_path)
+                            .unwrap_or(path)
+                            .to_string_lossy()
+                    ),
+                    human_size(*size)
+                ))
+                .collect::<Vec<_>>()
+                .join("\n")
+        )
+    };
+
+    Some(Warning {
+        code: "slugignore",
+        title: "The .slugignore file is ignored",
+        body: formatdoc! {"
+            Found {slugignore}, it is not used by Cloud Native Buildpacks and every file it
+            lists will be included in the image.
+
+            {matched}
+
+            To exclude these paths, move the patterns to {project_toml} and delete
+            {slugignore}:
+
+            [_]
+            schema-version = \"0.2\"
+
+            [io.buildpacks]
+            exclude = [{exclude}]
+
+            Projects using schema version 0.1 list them in {build_exclude} instead.
+            ",
+            slugignore = style::value(".slugignore"),
+            project_toml = style::value("project.toml"),
+            build_exclude = style::value("[build] exclude"),
+            exclude = patterns
+                .iter()
+                .map(|pattern| toml::Value::from(*pattern).to_string())
+                .collect::<Vec<_>>()
+                .join(", "),
+        },
+        url: Some("https://buildpacks.io/docs/reference/config/project-descriptor/"),
+    })
+}
+
+/// Paths matching `.slugignore` patterns
+///
+/// Patterns with a `/` are relative to the application, other patterns match at any depth.
+/// Paths inside a matched directory are not listed separately.
+fn slugignore_matches(app_path: &Path, patterns: &[&str]) -> Vec<PathBuf> {
+    let root = glob::Pattern::escape(&app_path.to_string_lossy());
+    let mut paths = patterns
+        .iter()
+        .map(|pattern| pattern.trim_end_matches('/'))
+        .map(|pattern| match pattern.strip_prefix('/') {
+            Some(anchored) => format!("{root}/{anchored}"),
+            None if pattern.contains('/') => format!("{root}/{pattern}"),
+            None => format!("{root}/**/{pattern}"),
+        })
+        .filter_map(|pattern| glob::glob(&pattern).ok())
+        .flat_map(|paths| paths.filter_map(Result::ok))
+        .collect::<Vec<_>>();
+    paths.sort();
+    paths.dedup();
+
+    let mut matches = Vec::<PathBuf>::new();
+    for path in paths {
+        if !matches.last().is_some_and(|dir| path.starts_with(dir)) {
+            matches.push(path);
+        }
+    }
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::str::FromStr;
+
+    fn gem_list(gems: &[&str]) -> GemList {
+        GemList::from_str(
+            &gems
+                .iter()
+                .map(|gem| format!("  * {gem} (1.0.0)"))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_legacy_gems_warning() {
+        assert!(legacy_gems_warning(&gem_list(&["rails"])).is_none());
+
+        let warning = legacy_gems_warning(&gem_list(&["rails", "rails_12factor"])).unwrap();
+        let body = bullet_stream::strip_ansi(warning.body);
+        assert!(body.contains("- `rails_12factor`: Rails 5+"));
+        assert!(!body.contains("heroku-deflater"));
+    }
+
+    #[test]
+    fn test_spring_warning() {
+        let mut env = Env::new();
+        assert!(spring_warning(&gem_list(&["rails"]), &env).is_none());
+
+        let warning = spring_warning(&gem_list(&["rails", "spring"]), &env).unwrap();
+        let body = bullet_stream::strip_ansi(warning.body);
+        assert!(body.contains("disabled with `DISABLE_SPRING=1`"), "{body}");
+
+        env.insert("DISABLE_SPRING", "0");
+        let warning = spring_warning(&gem_list(&["rails", "spring"]), &env).unwrap();
+        let body = bullet_stream::strip_ansi(warning.body);
+        assert!(body.contains("You set `DISABLE_SPRING=0`"), "{body}");
+    }
+
+    #[test]
+    fn test_dotenv_warning() {
+        let dir = tempfile::tempdir().unwrap();
+        let app_path = dir.path();
+        let lockfile = |gems: &[&str]| {
+            format!(
+                "DEPENDENCIES\n{}\n\nBUNDLED WITH\n   2.5.6\n",
+                gems.iter()
+                    .map(|gem| format!("  {gem}"))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            )
+        };
+        let gemfile = Gemfile::default();
+        assert!(dotenv_warning(
+            &gem_list(&["rails"]),
+            &lockfile(&["rails"]),
+            &gemfile,
+            app_path
+        )
+        .is_none());
+        // Installed as a dependency of another gem
+        assert!(dotenv_warning(
+            &gem_list(&["dotenv", "rails"]),
+            &lockfile(&["rails"]),
+            &gemfile,
+            app_path
+        )
+        .is_none());
+        // In the Gemfile, but not installed
+        assert!(dotenv_warning(
+            &gem_list(&["rails"]),
+            &lockfile(&["dotenv"]),
+            &gemfile,
+            app_path
+        )
+        .is_none());
+        // In the Gemfile, but only in the development and test groups
+        assert!(dotenv_warning(
+            &gem_list(&["dotenv", "rails"]),
+            &lockfile(&["dotenv"]),
+            &Gemfile::parse("group :development, :test do\n  gem \"dotenv\"\nend\n"),
+            app_path
+        )
+        .is_none());
+
+        let warning = dotenv_warning(
+            &gem_list(&["dotenv", "dotenv-rails"]),
+            &lockfile(&["dotenv-rails"]),
+            &Gemfile::parse("gem \"dotenv-rails\"\n"),
+            app_path,
+        )
+        .unwrap();
+        let body = bullet_stream::strip_ansi(warning.body);
+        assert!(body.contains("gem \"dotenv-rails\""), "{body}");
+        assert!(!body.contains("file was found"), "{body}");
+
+        fs_err::write(app_path.join(".env"), "SECRET=1").unwrap();
+        let warning = dotenv_warning(
+            &gem_list(&["dotenv"]),
+            &lockfile(&["dotenv"]),
+            &gemfile,
+            app_path,
+        )
+        .unwrap();
+        let body = bullet_stream::strip_ansi(warning.body);
+        assert!(body.contains("A `.env` file was found"), "{body}");
+    }
+
+    #[test]
+    fn test_vendor_bundle_warning() {
+        let mut env = Env::new();
+        assert!(vendor_bundle_warning(&Features::from_env(&env)).is_none());
+
+        env.insert("HEROKU_RUBY_FEATURE_VENDOR_BUNDLE", "1");
+        assert!(vendor_bundle_warning(&Features::from_env(&env)).is_some());
+    }
+
+    #[test]
+    fn test_duplicate_gems_warning() {
+        let mut gems = gem_list(&["nokogiri", "rack"]);
+        gems.add_lockfile(
+            "GEM
+  specs:
+    nokogiri (1.16.7-aarch64-linux)
+    nokogiri (1.16.7-x86_64-linux)
+    rack (3.1.8)
+",
+        );
+        assert!(duplicate_gems_warning(&gems).is_none());
+
+        gems.add_lockfile(
+            "GEM
+  specs:
+    nokogiri (1.16.7-aarch64-linux)
+    nokogiri (1.15.6-x86_64-linux)
+    rack (3.1.8)
+",
+        );
+        let warning = duplicate_gems_warning(&gems).unwrap();
+        let body = bullet_stream::strip_ansi(warning.body);
+        assert!(body.contains("- `nokogiri`: `1.15.6` and `1.16.7`"));
+        assert!(!body.contains("rack"));
+    }
+
+    #[test]
+    fn test_database_url_warning() {
+        let mut env = Env::new();
+        assert!(database_url_warning(&gem_list(&["rails"]), &env).is_none());
+
+        let warning = database_url_warning(&gem_list(&["pg", "trilogy"]), &env).unwrap();
+        assert!(bullet_stream::strip_ansi(warning.body).contains("gems `pg` and `trilogy`"));
+
+        env.insert("DATABASE_URL", "postgres://localhost/db");
+        assert!(database_url_warning(&gem_list(&["pg"]), &env).is_none());
+    }
+
+    #[test]
+    fn test_storage_services() {
+        let services = storage_services(
+            r#"
+test:
+  service: Disk
+  root: <%= Rails.root.join("tmp/storage") %>
+
+local:
+  service: "Disk"
+  root: <%= Rails.root.join("storage") %>
+
+# amazon:
+#   service: S3
+"#,
+        );
+        assert_eq!(services, vec!["Disk".to_string(), "Disk".to_string()]);
+    }
+
+    #[test]
+    fn test_active_storage_warning() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let app_path = tmpdir.path();
+        let with_gem = gem_list(&["activestorage"]);
+
+        assert!(active_storage_warning(&with_gem, app_path).is_none());
+
+        fs_err::create_dir_all(app_path.join("config")).unwrap();
+        fs_err::write(
+            app_path.join("config").join("storage.yml"),
+            "local:\n  service: Disk\n",
+        )
+        .unwrap();
+        assert!(active_storage_warning(&with_gem, app_path).is_some());
+        assert!(active_storage_warning(&gem_list(&["rails"]), app_path).is_none());
+
+        fs_err::write(
+            app_path.join("config").join("storage.yml"),
+            "local:\n  service: Disk\namazon:\n  service: S3\n",
+        )
+        .unwrap();
+        assert!(active_storage_warning(&with_gem, app_path).is_none());
+    }
+
+    #[test]
+    fn test_js_lockfile_warning() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let app_path = tmpdir.path();
+        assert!(js_lockfile_warning(app_path).is_none());
+
+        fs_err::write(
+            app_path.join("package.json"),
+            r#"{"scripts": {"start": "node server.js"}}"#,
+        )
+        .unwrap();
+        assert!(js_lockfile_warning(app_path).is_none());
+
+        fs_err::write(
+            app_path.join("package.json"),
+            r#"{"scripts": {"build": "esbuild app/javascript/*.* --bundle"}}"#,
+        )
+        .unwrap();
+        assert!(js_lockfile_warning(app_path).is_some());
+
+        fs_err::write(app_path.join("package-lock.json"), "{}").unwrap();
+        assert!(js_lockfile_warning(app_path).is_none());
+    }
+
+    #[test]
+    fn test_css_build_warning() {
+        let tailwind = gem_list(&["tailwindcss-rails"]);
+        let rake = |output: &str| RakeDetect::from_str(output).unwrap();
+
+        assert!(css_build_warning(&tailwind, None).is_none());
+        assert!(css_build_warning(&tailwind, Some(&rake("rake assets:precompile"))).is_none());
+        assert!(css_build_warning(&tailwind, Some(&rake("rake tailwindcss:build"))).is_none());
+        assert!(css_build_warning(&gem_list(&["rails"]), Some(&rake("rake about"))).is_none());
+
+        let warning = css_build_warning(&tailwind, Some(&rake("rake about"))).unwrap();
+        assert!(bullet_stream::strip_ansi(warning.body).contains("`rake tailwindcss:build`"));
+    }
+
+    #[test]
+    fn test_slugignore_warning() {
+        let dir = tempfile::tempdir().unwrap();
+        let app_path = dir.path();
+        assert!(slugignore_warning(app_path).is_none());
+
+        fs_err::create_dir_all(app_path.join("spec/models")).unwrap();
+        fs_err::create_dir_all(app_path.join("app/assets")).unwrap();
+        fs_err::write(app_path.join("spec/models/user_spec.rb"), "12345").unwrap();
+        fs_err::write(app_path.join("app/assets/logo.psd"), "123").unwrap();
+        fs_err::write(app_path.join(".slugignore"), "# Tests\n/spec\n*.psd\n").unwrap();
+
+        assert_eq!(
+            slugignore_matches(app_path, &["/spec", "*.psd", "spec/models"]),
+            vec![app_path.join("app/assets/logo.psd"), app_path.join("spec")]
+        );
+        let warning = slugignore_warning(app_path).unwrap();
+        let body = bullet_stream::strip_ansi(warning.body);
+        assert!(
+            body.contains("- `spec` (5 B)\n- `app/assets/logo.psd` (3 B)"),
+            "{body}"
+        );
+        assert!(body.contains(r#"exclude = ["/spec", "*.psd"]"#), "{body}");
+    }
+
+    #[test]
+    fn test_asset_pipeline_warning() {
+        let dir = tempfile::tempdir().unwrap();
+        let app_path = dir.path();
+        let rails = gem_list(&["railties"]);
+        let rake = |output: &str| RakeDetect::from_str(output).unwrap();
+        let no_precompile = rake("rake db:migrate");
+        assert!(asset_pipeline_warning(&rails, Some(&no_precompile), app_path).is_none());
+
+        fs_err::create_dir_all(app_path.join("app/javascript")).unwrap();
+        assert!(
+            asset_pipeline_warning(&gem_list(&["sinatra"]), Some(&no_precompile), app_path)
+                .is_none()
+        );
+        assert!(asset_pipeline_warning(&rails, None, app_path).is_none());
+        let warning = asset_pipeline_warning(&rails, Some(&no_precompile), app_path).unwrap();
+        assert!(bullet_stream::strip_ansi(warning.body).starts_with("Found `app/javascript`"));
+
+        assert!(
+            asset_pipeline_warning(&rails, Some(&rake("rake assets:precompile")), app_path)
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,184 @@
+//! Select the rake tasks that compile assets
+//!
+//! The assets step runs `rake assets:precompile` by default. Frameworks other than Rails name
+//! their tasks differently, so tasks can be set with a comma separated list in
+//! `HEROKU_RUBY_ASSETS_TASKS`, or with `assets_tasks` in `project.toml`:
+//!
+//! ```toml
+//! [com.heroku.buildpacks.ruby]
+//! assets_tasks = ["hanami:assets:compile"]
+//! ```
+//!
+//! The environment variable takes precedence over `project.toml`. The tasks run in order in a
+//! single `rake` command once every one of them is found, with the same caching as
+//! `assets:precompile`. `assets:clean` still runs after them when it exists.
+use crate::project_toml;
+use bullet_stream::style;
+use commons::rake_task_detect::RakeDetect;
+use libcnb::Env;
+use std::path::Path;
+
+pub(crate) const ASSETS_TASKS_ENV_KEY: &str = "HEROKU_RUBY_ASSETS_TASKS";
+const DEFAULT_TASK: &str = "assets:precompile";
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub(crate) enum AssetTasksError {
+    #[error("Invalid asset task `{name}` in {origin}, task names cannot contain whitespace or start with `-`")]
+    InvalidTask { name: String, origin: String },
+
+    #[error("No asset tasks listed in {0}")]
+    Empty(String),
+
+    #[error("Cannot parse project.toml: {0}")]
+    ProjectToml(String),
+}
+
+/// The tasks that compile assets, and where they were configured
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct AssetTasks {
+    tasks: Vec<String>,
+    origin: Option<String>,
+}
+
+impl Default for AssetTasks {
+    fn default() -> Self {
+        Self {
+            tasks: vec![String::from(DEFAULT_TASK)],
+            origin: None,
+        }
+    }
+}
+
+impl AssetTasks {
+    /// Reads `HEROKU_RUBY_ASSETS_TASKS` from the platform env, or `assets_tasks` from
+    /// `project.toml`
+    ///
+    /// # Errors
+    ///
+    /// Errors when a task name is invalid, no tasks are listed, or `project.toml` cannot be read.
+    pub(crate) fn from_env_or_project_toml(
+        platform_env: &Env,
+        app_dir: &Path,
+    ) -> Result<Self, AssetTasksError> {
+        if let Some(value) = platform_env.get(ASSETS_TASKS_ENV_KEY) {
+            let value = value.to_string_lossy();
+            return Self::from_names(value.split(','), ASSETS_TASKS_ENV_KEY);
+        }
+
+        let Some(names) = project_toml::ruby_setting::<Vec<String>>(app_dir, "assets_tasks")
+            .map_err(|error| AssetTasksError::ProjectToml(error.to_string()))?
+        else {
+            return Ok(Self::default());
+        };
+
+        Self::from_names(names.iter().map(String::as_str), "project.toml")
+    }
+
+    fn from_names<'a>(
+        names: impl IntoIterator<Item = &'a str>,
+        origin: &str,
+    ) -> Result<Self, AssetTasksError> {
+        let mut tasks = Vec::<String>::new();
+        for name in names.into_iter().map(str::trim).filter(|n| !n.is_empty()) {
+            if name.starts_with('-') || name.contains(char::is_whitespace) {
+                return Err(AssetTasksError::InvalidTask {
+                    name: name.to_string(),
+                    origin: origin.to_string(),
+                });
+            }
+            if !tasks.iter().any(|task| task == name) {
+                tasks.push(name.to_string());
+            }
+        }
+        if tasks.is_empty() {
+            return Err(AssetTasksError::Empty(origin.to_string()));
+        }
+
+        Ok(Self {
+            tasks,
+            origin: Some(origin.to_string()),
+        })
+    }
+
+    pub(crate) fn tasks(&self) -> &[String] {
+        &self.tasks
+    }
+
+    /// True when the tasks include `assets:precompile`
+    pub(crate) fn precompiles(&self) -> bool {
+        self.tasks.iter().any(|task| task == DEFAULT_TASK)
+    }
+
+    /// True when every task was found via `rake -P`
+    pub(crate) fn found(&self, rake: &RakeDetect) -> bool {
+        self.tasks.iter().all(|task| rake.has_task(task))
+    }
+
+    /// The command that runs the tasks, for example `rake assets:precompile`
+    pub(crate) fn command(&self) -> String {
+        format!("rake {}", self.tasks.join(" "))
+    }
+
+    /// Where the tasks were configured, `None` for the default
+    pub(crate) fn origin(&self) -> Option<String> {
+        self.origin.as_ref().map(style::value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_env_or_project_toml() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut env = Env::new();
+        assert_eq!(
+            AssetTasks::from_env_or_project_toml(&env, dir.path()).unwrap(),
+            AssetTasks::default()
+        );
+
+        fs_err::write(
+            dir.path().join("project.toml"),
+            r#"
+[com.heroku.buildpacks.ruby]
+assets_tasks = ["hanami:assets:compile"]
+"#,
+        )
+        .unwrap();
+        let tasks = AssetTasks::from_env_or_project_toml(&env, dir.path()).unwrap();
+        assert_eq!(tasks.tasks(), ["hanami:assets:compile"]);
+        assert_eq!(tasks.command(), "rake hanami:assets:compile");
+
+        env.insert(ASSETS_TASKS_ENV_KEY, "css:build, assets:precompile");
+        let tasks = AssetTasks::from_env_or_project_toml(&env, dir.path()).unwrap();
+        assert_eq!(tasks.tasks(), ["css:build", "assets:precompile"]);
+        assert!(tasks.origin().is_some_and(
+            |origin| bullet_stream::strip_ansi(origin) == "`HEROKU_RUBY_ASSETS_TASKS`"
+        ));
+    }
+
+    #[test]
+    fn test_invalid_tasks() {
+        assert_eq!(
+            AssetTasks::from_names(["--trace"], "project.toml"),
+            Err(AssetTasksError::InvalidTask {
+                name: String::from("--trace"),
+                origin: String::from("project.toml")
+            })
+        );
+        assert_eq!(
+            AssetTasks::from_names([" "], ASSETS_TASKS_ENV_KEY),
+            Err(AssetTasksError::Empty(String::from(ASSETS_TASKS_ENV_KEY)))
+        );
+    }
+
+    #[test]
+    fn test_found() {
+        let rake = RakeDetect::from_rake_output("rake assets:precompile\nrake css:build\n");
+        assert!(AssetTasks::default().found(&rake));
+        assert!(!AssetTasks::from_names(["jekyll:build"], "project.toml")
+            .unwrap()
+            .found(&rake));
+    }
+}
@@ -0,0 +1,109 @@
+use crate::features::Feature;
+use crate::gem_list::GemList;
+use crate::RubyBuildpack;
+use crate::RubyBuildpackError;
+use bullet_stream::state::SubBullet;
+use bullet_stream::{style, Print};
+use fun_run::{self, CommandWithName};
+use libcnb::build::BuildContext;
+use libcnb::Env;
+use std::io::Stdout;
+use std::path::Path;
+use std::process::Command;
+
+/// Maximum time the boot check is allowed to run before it's considered a failure
+pub(crate) const BOOT_CHECK_TIMEOUT_SECONDS: u64 = 120;
+
+/// Boot the application to turn load errors into build failures
+///
+/// Applications that fail to load (for example due to a missing constant or a gem that
+/// is only available in development) otherwise build successfully and then crash loop at
+/// runtime. The command is wrapped in `timeout` so an application that never finishes
+/// booting does not hang the build.
+pub(crate) fn boot_check(
+    bullet: Print<SubBullet<Stdout>>,
+    context: &BuildContext<RubyBuildpack>,
+    env: &Env,
+    gem_list: &GemList,
+) -> Result<Print<SubBullet<Stdout>>, RubyBuildpackError> {
+    let enabled_with = style::value(format!("{}=1", Feature::BootCheck.env_key()));
+    let Some(boot_args) = boot_command(gem_list, &context.app_dir) else {
+        return Ok(bullet.sub_bullet(format!(
+            "Skipping boot check ({railties} gem and {environment} not found, enabled with {enabled_with})",
+            railties = style::value("railties"),
+            environment = style::value("config/environment.rb"),
+        )));
+    };
+
+    let mut cmd = Command::new("timeout");
+    cmd.args(["--kill-after=5", &BOOT_CHECK_TIMEOUT_SECONDS.to_string()])
+        .args(&boot_args)
+        .current_dir(&context.app_dir)
+        .env_clear()
+        .envs(env);
+
+    let mut bullet = bullet.sub_bullet(format!(
+        "Booting application (enabled with {enabled_with}, timeout {BOOT_CHECK_TIMEOUT_SECONDS}s)"
+    ));
+    bullet
+        .stream_with(
+            format!("Running {}", style::command(cmd.name())),
+            |stdout, stderr| cmd.stream_output(stdout, stderr),
+        )
+        .map_err(|error| fun_run::map_which_problem(error, &mut cmd, env.get("PATH").cloned()))
+        .map_err(RubyBuildpackError::BootCheckFailed)?;
+
+    Ok(bullet)
+}
+
+/// The command that loads the application
+///
+/// Rails apps are booted with `bin/rails runner`, other apps are booted by requiring
+/// `config/environment.rb` when it exists.
+fn boot_command(gem_list: &GemList, app_path: &Path) -> Option<Vec<String>> {
+    if gem_list.has("railties") {
+        Some(
+            ["bin/rails", "runner", "puts Rails.env"]
+                .map(String::from)
+                .to_vec(),
+        )
+    } else if app_path.join("config").join("environment.rb").exists() {
+        Some(
+            ["ruby", "-e", "require './config/environment'"]
+                .map(String::from)
+                .to_vec(),
+        )
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::str::FromStr;
+
+    #[test]
+    fn test_boot_command() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let rails = GemList::from_str("  * railties (7.1.0)").unwrap();
+        let rack = GemList::from_str("  * rack (3.0.0)").unwrap();
+
+        assert_eq!(
+            boot_command(&rails, tmpdir.path()),
+            Some(vec![
+                "bin/rails".to_string(),
+                "runner".to_string(),
+                "puts Rails.env".to_string()
+            ])
+        );
+        assert_eq!(boot_command(&rack, tmpdir.path()), None);
+
+        fs_err::create_dir_all(tmpdir.path().join("config")).unwrap();
+        fs_err::write(tmpdir.path().join("config").join("environment.rb"), "").unwrap();
+        assert_eq!(
+            boot_command(&rack, tmpdir.path()).map(|args| args[0].clone()),
+            Some("ruby".to_string())
+        );
+    }
+}
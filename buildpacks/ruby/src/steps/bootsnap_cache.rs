@@ -0,0 +1,97 @@
+use crate::gem_list::GemList;
+use crate::RubyBuildpack;
+use crate::RubyBuildpackError;
+use bullet_stream::state::SubBullet;
+use bullet_stream::{style, Print};
+use commons::assets;
+use commons::cache::{mib, AppCache, CacheConfig, Eviction, KeepPath};
+use commons::gemfile_lock::ResolvedRubyVersion;
+use libcnb::build::BuildContext;
+use std::io::Stdout;
+use std::path::Path;
+
+/// Written inside the bootsnap cache to detect Ruby version changes between builds
+const RUBY_VERSION_FILE: &str = ".heroku-ruby-version";
+
+/// Loads the bootsnap cache when the `bootsnap` gem is in use
+///
+/// Bootsnap caches compiled Ruby and YAML in `tmp/cache/bootsnap`. Restoring it speeds up
+/// booting the application during rake task detection and asset compilation. Compiled
+/// instruction sequences are specific to a Ruby version, so the cache is cleared when the
+/// version changes. It's only needed during the build so it never ships in the image.
+pub(crate) fn bootsnap_cache_load(
+    mut bullet: Print<SubBullet<Stdout>>,
+    context: &BuildContext<RubyBuildpack>,
+    gem_list: &GemList,
+    ruby_version: &ResolvedRubyVersion,
+) -> Result<(Print<SubBullet<Stdout>>, Option<AppCache>), RubyBuildpackError> {
+    if !gem_list.has("bootsnap") {
+        return Ok((
+            bullet.sub_bullet(format!(
+                "Skipping ({} gem not found)",
+                style::value("bootsnap")
+            )),
+            None,
+        ));
+    }
+
+    let store = AppCache::new_and_load(
+        context,
+        CacheConfig {
+            path: context.app_dir.join("tmp").join("cache").join("bootsnap"),
+            limit: mib(100),
+            keep_path: KeepPath::BuildOnly,
+            eviction: Eviction::Lru,
+        },
+    )
+    .map_err(RubyBuildpackError::InAppDirCacheError)?;
+    bullet = assets::load_message(bullet, &store);
+
+    if let Some(old) = cached_ruby_version(store.path()) {
+        if old != ruby_version.to_string() {
+            bullet = bullet.sub_bullet(format!(
+                "Clearing cache (Ruby version changed from {} to {})",
+                style::value(old),
+                style::value(ruby_version.to_string())
+            ));
+            fs_err::remove_dir_all(store.path())
+                .and_then(|()| fs_err::create_dir_all(store.path()))
+                .map_err(RubyBuildpackError::BootsnapCacheError)?;
+        }
+    }
+    fs_err::write(
+        store.path().join(RUBY_VERSION_FILE),
+        ruby_version.to_string(),
+    )
+    .map_err(RubyBuildpackError::BootsnapCacheError)?;
+
+    Ok((bullet, Some(store)))
+}
+
+/// Stores the bootsnap cache for the next build
+pub(crate) fn bootsnap_cache_save(
+    bullet: Print<SubBullet<Stdout>>,
+    store: &AppCache,
+) -> Result<Print<SubBullet<Stdout>>, RubyBuildpackError> {
+    assets::save_and_clean(bullet, store).map_err(RubyBuildpackError::InAppDirCacheError)
+}
+
+fn cached_ruby_version(cache_path: &Path) -> Option<String> {
+    fs_err::read_to_string(cache_path.join(RUBY_VERSION_FILE))
+        .ok()
+        .map(|version| version.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cached_ruby_version() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(cached_ruby_version(dir.path()), None);
+
+        fs_err::write(dir.path().join(RUBY_VERSION_FILE), "3.3.5\n").unwrap();
+        assert_eq!(cached_ruby_version(dir.path()), Some("3.3.5".to_string()));
+    }
+}
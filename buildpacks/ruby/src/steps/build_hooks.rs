@@ -0,0 +1,60 @@
+use crate::RubyBuildpack;
+use crate::RubyBuildpackError;
+use bullet_stream::state::Bullet;
+use bullet_stream::{style, Print};
+use fun_run::{self, CommandWithName};
+use libcnb::build::BuildContext;
+use libcnb::Env;
+use std::io::Stdout;
+use std::process::Command;
+
+/// Executable scripts in the app that run at fixed points of the build
+///
+/// They give applications an escape hatch for custom steps (such as generating files or
+/// uploading source maps) without forking the buildpack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum BuildHook {
+    /// Runs after Ruby and Bundler are installed, before `bundle install`
+    Prebuild,
+    /// Runs after assets are compiled, before the boot and Zeitwerk checks
+    Postbuild,
+}
+
+impl BuildHook {
+    pub(crate) fn path(self) -> &'static str {
+        match self {
+            BuildHook::Prebuild => "bin/heroku-ruby-prebuild",
+            BuildHook::Postbuild => "bin/heroku-ruby-postbuild",
+        }
+    }
+}
+
+/// Runs the hook with the build environment when it exists in the app
+///
+/// Nothing is printed when the app does not define the hook.
+pub(crate) fn run_build_hook(
+    output: Print<Bullet<Stdout>>,
+    hook: BuildHook,
+    context: &BuildContext<RubyBuildpack>,
+    env: &Env,
+) -> Result<Print<Bullet<Stdout>>, RubyBuildpackError> {
+    let path = context.app_dir.join(hook.path());
+    if !path.is_file() {
+        return Ok(output);
+    }
+
+    let mut cmd = Command::new(&path);
+    cmd.current_dir(&context.app_dir).env_clear().envs(env);
+    let mut cmd = cmd.named(hook.path());
+
+    let mut bullet = output.bullet(format!("Build hook {}", style::value(hook.path())));
+    bullet
+        .stream_with(
+            format!("Running {}", style::command(cmd.name())),
+            |stdout, stderr| cmd.stream_output(stdout, stderr),
+        )
+        .map_err(|error| fun_run::map_which_problem(error, cmd.mut_cmd(), env.get("PATH").cloned()))
+        .map_err(RubyBuildpackError::BuildHookFailed)?;
+
+    Ok(bullet.done())
+}
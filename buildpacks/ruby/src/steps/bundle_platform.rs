@@ -0,0 +1,183 @@
+//! Compare the Ruby version Bundler requires with the version the buildpack installs
+//!
+//! The buildpack resolves Ruby from the `RUBY VERSION` section of the `Gemfile.lock`, while
+//! Bundler checks the `ruby` directive of the `Gemfile`. When the two drift apart, for example
+//! after the `Gemfile` is changed without running `bundle install`, the build fails later with
+//! a less helpful message. `bundle platform --ruby` prints Bundler's view, it's shown in the
+//! build output and a warning is printed when it does not match the installed version.
+use crate::warning::Warning;
+use bullet_stream::state::SubBullet;
+use bullet_stream::{style, Print};
+use commons::gemfile_lock::ResolvedRubyVersion;
+use commons::requirements::{matches_all, parse_requirements};
+use fun_run::CommandWithName;
+use indoc::formatdoc;
+use libcnb::Env;
+use std::io::Stdout;
+use std::path::Path;
+use std::process::Command;
+
+/// The Ruby requirement from `bundle platform --ruby`, for example
+/// `ruby 3.1.4p001 (jruby 9.4.8.0)`
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct BundlerRuby {
+    /// Comma separated requirements such as `~> 3.3.0` or `3.3.6`
+    requirement: String,
+    /// Engine and version, such as `jruby 9.4.8.0`, `None` for MRI
+    engine: Option<String>,
+}
+
+/// Returns `None` when the `Gemfile` does not specify a Ruby version
+fn parse(output: &str) -> Option<BundlerRuby> {
+    let line = output
+        .lines()
+        .map(str::trim)
+        .find(|line| line.starts_with("ruby "))?;
+    let line = line.strip_prefix("ruby ")?;
+    let (versions, engine) = match line.split_once(" (") {
+        Some((versions, engine)) => (versions, Some(engine.trim_end_matches(')').to_string())),
+        None => (line, None),
+    };
+    let requirement = versions
+        .split(',')
+        .map(|version| {
+            let version = version.trim();
+            // Drop a patchlevel, such as the `p001` of `3.1.4p001`
+            match version.rsplit_once('p') {
+                Some((version, patchlevel))
+                    if !patchlevel.is_empty() && patchlevel.chars().all(|c| c.is_ascii_digit()) =>
+                {
+                    version
+                }
+                _ => version,
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    Some(BundlerRuby {
+        requirement,
+        engine: engine.filter(|engine| !engine.starts_with("ruby ")),
+    })
+}
+
+/// True when the resolved version, such as `3.3.6` or `3.1.4-jruby-9.4.8.0`, satisfies Bundler
+fn satisfies(bundler: &BundlerRuby, resolved: &ResolvedRubyVersion) -> bool {
+    let resolved = resolved.to_string();
+    let (version, engine) = match resolved.split_once("-jruby-") {
+        Some((version, jruby)) => (version.to_string(), Some(format!("jruby {jruby}"))),
+        None => (resolved, None),
+    };
+
+    bundler.engine == engine
+        && parse_requirements(&bundler.requirement)
+            .is_ok_and(|requirements| matches_all(&requirements, &version))
+}
+
+/// Prints Bundler's required Ruby version, returns a warning when it disagrees with the
+/// resolved version
+///
+/// This is advisory, a failure to run the command is printed and otherwise ignored.
+pub(crate) fn bundle_platform_ruby(
+    bullet: Print<SubBullet<Stdout>>,
+    env: &Env,
+    app_dir: &Path,
+    resolved: &ResolvedRubyVersion,
+) -> (Print<SubBullet<Stdout>>, Option<Warning>) {
+    let mut cmd = Command::new("bundle");
+    cmd.args(["platform", "--ruby"])
+        .current_dir(app_dir)
+        .env_clear()
+        .envs(env);
+    let name = style::command(cmd.name());
+
+    let output = match cmd.named_output() {
+        Ok(output) => output.stdout_lossy(),
+        Err(error) => {
+            return (
+                bullet.sub_bullet(format!(
+                    "Skipping Ruby version check, {name} failed: {error}"
+                )),
+                None,
+            );
+        }
+    };
+    let Some(bundler) = parse(&output) else {
+        return (
+            bullet.sub_bullet(format!("{name}: no Ruby version in the Gemfile")),
+            None,
+        );
+    };
+    let bullet = bullet.sub_bullet(format!("{name}: {}", output.trim()));
+    if satisfies(&bundler, resolved) {
+        return (bullet, None);
+    }
+
+    let warning = Warning {
+        code: "bundler_ruby_version",
+        title: "Bundler requires a different Ruby version",
+        body: formatdoc! {"
+            This buildpack installs Ruby {resolved}, but {name} reports that your
+            {gemfile} requires {required}. The {ruby} directive in your {gemfile} is likely
+            out of sync with the {ruby_version} section of your {lockfile}, and
+            {bundle_install} may fail.
+
+            Run {bundle_update} locally and commit the updated {lockfile}.
+            ",
+            resolved = style::value(resolved.to_string()),
+            required = style::value(output.trim()),
+            lockfile = style::value("Gemfile.lock"),
+            gemfile = style::value("Gemfile"),
+            ruby = style::value("ruby"),
+            ruby_version = style::value("RUBY VERSION"),
+            bundle_install = style::command("bundle install"),
+            bundle_update = style::command("bundle update --ruby"),
+        },
+        url: None,
+    };
+    (bullet, Some(warning))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse("No ruby version specified\n"), None);
+        assert_eq!(
+            parse("ruby 3.3.6p108\n"),
+            Some(BundlerRuby {
+                requirement: String::from("3.3.6"),
+                engine: None
+            })
+        );
+        assert_eq!(
+            parse("ruby ~> 3.3.0, >= 3.3.1\n"),
+            Some(BundlerRuby {
+                requirement: String::from("~> 3.3.0, >= 3.3.1"),
+                engine: None
+            })
+        );
+        assert_eq!(
+            parse("ruby 3.1.4p001 (jruby 9.4.8.0)\n"),
+            Some(BundlerRuby {
+                requirement: String::from("3.1.4"),
+                engine: Some(String::from("jruby 9.4.8.0"))
+            })
+        );
+    }
+
+    #[test]
+    fn test_satisfies() {
+        let version = |version: &str| ResolvedRubyVersion(version.to_string());
+        let bundler = parse("ruby ~> 3.3.0").unwrap();
+        assert!(satisfies(&bundler, &version("3.3.6")));
+        assert!(!satisfies(&bundler, &version("3.4.1")));
+
+        let bundler = parse("ruby 3.1.4p001 (jruby 9.4.8.0)").unwrap();
+        assert!(satisfies(&bundler, &version("3.1.4-jruby-9.4.8.0")));
+        assert!(!satisfies(&bundler, &version("3.1.4")));
+        assert!(!satisfies(&bundler, &version("3.1.4-jruby-9.4.9.0")));
+    }
+}
@@ -0,0 +1,260 @@
+//! Cache application directories listed in `project.toml`
+//!
+//! Apps can persist extra directories between builds, for example the cache of a JS bundler:
+//!
+//! ```toml
+//! [[com.heroku.buildpacks.ruby.cache_dirs]]
+//! path = "tmp/cache/webpacker"
+//! limit_mib = 200
+//! ```
+//!
+//! Paths are relative to the application directory and cannot leave it. A path cannot be
+//! inside of, or contain, a directory the buildpack already caches or another listed path,
+//! or the same files would be stored twice. The limit defaults
+//! to 100 MiB, files over the limit are removed least recently modified first. Directories
+//! are kept in the image.
+use crate::project_toml;
+use crate::RubyBuildpack;
+use crate::RubyBuildpackError;
+use bullet_stream::state::SubBullet;
+use bullet_stream::Print;
+use commons::assets;
+use commons::cache::{mib, AppCacheCollection, CacheConfig, Eviction, KeepPath};
+use libcnb::build::BuildContext;
+use serde::Deserialize;
+use std::io::Stdout;
+use std::path::{Component, Path, PathBuf};
+
+const DEFAULT_LIMIT_MIB: usize = 100;
+
+/// Directories the buildpack already caches, listing them again would store them twice
+const RESERVED: &[&str] = &[
+    "_site",
+    ".jekyll-cache",
+    "public/assets",
+    "public/packs",
+    "public/vite",
+    "app/assets/builds",
+    "tmp/cache/assets",
+    "tmp/cache/bootsnap",
+    "vendor/bundle",
+];
+
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct CacheDir {
+    path: PathBuf,
+    limit_mib: Option<usize>,
+}
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub(crate) enum CacheDirsError {
+    #[error("Cannot parse project.toml: {0}")]
+    ProjectToml(String),
+
+    #[error("Cache directory `{}` must be a relative path inside the application directory", .0.display())]
+    OutsideAppDir(PathBuf),
+
+    #[error("Cache directory `{}` is already cached by the buildpack", .0.display())]
+    Reserved(PathBuf),
+
+    #[error("Cache directory `{}` is listed more than once, or overlaps `{}`", .0.display(), .1.display())]
+    Duplicate(PathBuf, PathBuf),
+
+    #[error("Cache directory `{}` must have a `limit_mib` greater than zero", .0.display())]
+    ZeroLimit(PathBuf),
+}
+
+/// Reads `cache_dirs` from the `[com.heroku.buildpacks.ruby]` table of `project.toml`
+///
+/// # Errors
+///
+/// Errors when `project.toml` cannot be read, or an entry is invalid.
+pub(crate) fn cache_dirs_from_project_toml(
+    app_dir: &Path,
+) -> Result<Vec<CacheDir>, CacheDirsError> {
+    let dirs = project_toml::ruby_setting::<Vec<CacheDir>>(app_dir, "cache_dirs")
+        .map_err(|error| CacheDirsError::ProjectToml(error.to_string()))?
+        .unwrap_or_default();
+
+    validate(dirs)
+}
+
+/// Normalizes paths (`./tmp/cache/` is `tmp/cache`) and rejects overlapping directories
+fn validate(dirs: Vec<CacheDir>) -> Result<Vec<CacheDir>, CacheDirsError> {
+    let mut valid: Vec<CacheDir> = Vec::with_capacity(dirs.len());
+    for dir in dirs {
+        let path =
+            normalize(&dir.path).ok_or_else(|| CacheDirsError::OutsideAppDir(dir.path.clone()))?;
+        if RESERVED
+            .iter()
+            .any(|reserved| overlaps(&path, Path::new(reserved)))
+        {
+            return Err(CacheDirsError::Reserved(dir.path));
+        }
+        if let Some(other) = valid.iter().find(|other| overlaps(&path, &other.path)) {
+            return Err(CacheDirsError::Duplicate(dir.path, other.path.clone()));
+        }
+        if dir.limit_mib == Some(0) {
+            return Err(CacheDirsError::ZeroLimit(dir.path));
+        }
+        valid.push(CacheDir { path, ..dir });
+    }
+    Ok(valid)
+}
+
+/// Drops `.` components, `None` when the path is empty or can leave the application directory
+fn normalize(path: &Path) -> Option<PathBuf> {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => normalized.push(name),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    (!normalized.as_os_str().is_empty()).then_some(normalized)
+}
+
+/// True when the directories are the same, or one is inside of the other
+fn overlaps(a: &Path, b: &Path) -> bool {
+    a.starts_with(b) || b.starts_with(a)
+}
+
+/// Loads the configured directories from the cache
+pub(crate) fn cache_dirs_load(
+    mut bullet: Print<SubBullet<Stdout>>,
+    context: &BuildContext<RubyBuildpack>,
+    dirs: &[CacheDir],
+) -> Result<(Print<SubBullet<Stdout>>, AppCacheCollection), RubyBuildpackError> {
+    let collection = AppCacheCollection::new_and_load(
+        context,
+        dirs.iter().map(|dir| CacheConfig {
+            path: context.app_dir.join(&dir.path),
+            limit: mib(dir.limit_mib.unwrap_or(DEFAULT_LIMIT_MIB)),
+            keep_path: KeepPath::Runtime,
+            eviction: Eviction::Lru,
+        }),
+    )
+    .map_err(RubyBuildpackError::InAppDirCacheError)?;
+
+    for store in collection.caches() {
+        bullet = assets::load_message(bullet, store);
+    }
+
+    Ok((bullet, collection))
+}
+
+/// Stores the configured directories for the next build
+pub(crate) fn cache_dirs_save(
+    mut bullet: Print<SubBullet<Stdout>>,
+    collection: &AppCacheCollection,
+) -> Result<Print<SubBullet<Stdout>>, RubyBuildpackError> {
+    for store in collection.caches() {
+        bullet = assets::save_and_clean(bullet, store)
+            .map_err(RubyBuildpackError::InAppDirCacheError)?;
+    }
+
+    Ok(bullet)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dir(path: &str) -> CacheDir {
+        CacheDir {
+            path: PathBuf::from(path),
+            limit_mib: None,
+        }
+    }
+
+    #[test]
+    fn test_from_project_toml() {
+        let app_dir = tempfile::tempdir().unwrap();
+        assert_eq!(
+            cache_dirs_from_project_toml(app_dir.path()).unwrap(),
+            Vec::new()
+        );
+
+        fs_err::write(
+            app_dir.path().join("project.toml"),
+            r#"
+[com.heroku.buildpacks.ruby]
+skip_steps = []
+
+[[com.heroku.buildpacks.ruby.cache_dirs]]
+path = "tmp/cache/webpacker"
+limit_mib = 200
+
+[[com.heroku.buildpacks.ruby.cache_dirs]]
+path = ".yarn/cache"
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            cache_dirs_from_project_toml(app_dir.path()).unwrap(),
+            vec![
+                CacheDir {
+                    path: PathBuf::from("tmp/cache/webpacker"),
+                    limit_mib: Some(200),
+                },
+                dir(".yarn/cache"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(validate(vec![dir("tmp/cache/webpacker")]).is_ok());
+        assert_eq!(
+            validate(vec![dir("../other")]),
+            Err(CacheDirsError::OutsideAppDir(PathBuf::from("../other")))
+        );
+        assert_eq!(
+            validate(vec![dir("/tmp")]),
+            Err(CacheDirsError::OutsideAppDir(PathBuf::from("/tmp")))
+        );
+        assert_eq!(
+            validate(vec![dir("./tmp/cache/webpacker/")]),
+            Ok(vec![dir("tmp/cache/webpacker")])
+        );
+        assert_eq!(
+            validate(vec![dir(".")]),
+            Err(CacheDirsError::OutsideAppDir(PathBuf::from(".")))
+        );
+        for reserved in [
+            "public/assets",
+            "./public/assets",
+            "public/assets/foo",
+            "tmp/cache",
+            "public",
+        ] {
+            assert_eq!(
+                validate(vec![dir(reserved)]),
+                Err(CacheDirsError::Reserved(PathBuf::from(reserved)))
+            );
+        }
+        assert_eq!(
+            validate(vec![dir(".yarn/cache"), dir(".yarn/cache")]),
+            Err(CacheDirsError::Duplicate(
+                PathBuf::from(".yarn/cache"),
+                PathBuf::from(".yarn/cache")
+            ))
+        );
+        assert_eq!(
+            validate(vec![dir(".yarn"), dir("./.yarn/cache")]),
+            Err(CacheDirsError::Duplicate(
+                PathBuf::from("./.yarn/cache"),
+                PathBuf::from(".yarn")
+            ))
+        );
+        assert_eq!(
+            validate(vec![CacheDir {
+                limit_mib: Some(0),
+                ..dir(".yarn/cache")
+            }]),
+            Err(CacheDirsError::ZeroLimit(PathBuf::from(".yarn/cache")))
+        );
+    }
+}
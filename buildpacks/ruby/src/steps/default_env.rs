@@ -1,6 +1,5 @@
 use crate::{RubyBuildpack, RubyBuildpackError};
-use libcnb::layer::UncachedLayerDefinition;
-use libcnb::layer_env::{LayerEnv, ModificationBehavior};
+use commons::layer::env_layer::default_env_layer;
 use libcnb::{
     build::BuildContext,
     data::{layer_name, store::Store},
@@ -37,32 +36,24 @@ pub(crate) fn default_env(
         })
         .to_string();
 
-    let layer_ref = context.uncached_layer(
+    // Defaults are only used when the user has not set the variable, so `DISABLE_SPRING` can
+    // be set to another value by users who need Spring at build or launch.
+    let env = default_env_layer(
+        context,
         layer_name!("env_defaults"),
-        UncachedLayerDefinition {
-            build: true,
-            launch: true,
-        },
-    )?;
-    let env = layer_ref
-        .write_env({
-            [
-                ("SECRET_KEY_BASE", default_secret_key_base.as_str()),
-                ("JRUBY_OPTS", "-Xcompile.invokedynamic=false"),
-                ("RACK_ENV", "production"),
-                ("RAILS_ENV", "production"),
-                ("RAILS_SERVE_STATIC_FILES", "enabled"),
-                ("RAILS_LOG_TO_STDOUT", "enabled"),
-                ("MALLOC_ARENA_MAX", "2"),
-                ("DISABLE_SPRING", "1"),
-            ]
-            .iter()
-            .fold(LayerEnv::new(), |layer_env, (name, value)| {
-                layer_env.chainable_insert(Scope::All, ModificationBehavior::Default, name, value)
-            })
-        })
-        .and_then(|()| layer_ref.read_env())?
-        .apply(Scope::Build, &env);
+        [
+            ("SECRET_KEY_BASE", default_secret_key_base.as_str()),
+            ("JRUBY_OPTS", "-Xcompile.invokedynamic=false"),
+            ("JEKYLL_ENV", "production"),
+            ("RACK_ENV", "production"),
+            ("RAILS_ENV", "production"),
+            ("RAILS_SERVE_STATIC_FILES", "enabled"),
+            ("RAILS_LOG_TO_STDOUT", "enabled"),
+            ("MALLOC_ARENA_MAX", "2"),
+            ("DISABLE_SPRING", "1"),
+        ],
+    )?
+    .apply(Scope::Build, &env);
 
     Ok((env, store))
 }
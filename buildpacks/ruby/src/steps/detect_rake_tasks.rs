@@ -1,10 +1,16 @@
+use crate::diagnostics::Diagnostics;
+use crate::features::Features;
 use crate::gem_list::GemList;
 use crate::rake_status::{check_rake_ready, RakeStatus};
-use crate::rake_task_detect::{self, RakeDetect};
+use crate::rake_task_detect;
+use crate::steps::asset_tasks::AssetTasks;
+use crate::steps::rake_assets_install::CSS_BUILD_GEMS;
 use crate::RubyBuildpack;
 use crate::RubyBuildpackError;
 use bullet_stream::state::SubBullet;
 use bullet_stream::{style, Print};
+use commons::display::SentenceList;
+use commons::rake_task_detect::RakeDetect;
 use libcnb::build::BuildContext;
 use libcnb::Env;
 use std::io::Stdout;
@@ -14,6 +20,8 @@ pub(crate) fn detect_rake_tasks(
     gem_list: &GemList,
     context: &BuildContext<RubyBuildpack>,
     env: &Env,
+    features: &Features,
+    asset_tasks: &AssetTasks,
 ) -> Result<(Print<SubBullet<Stdout>>, Option<RakeDetect>), RubyBuildpackError> {
     let help = style::important("HELP");
     let rake = style::value("rake");
@@ -38,8 +46,8 @@ pub(crate) fn detect_rake_tasks(
         )),
         RakeStatus::MissingRakefile => Ok((
             bullet
-                .sub_bullet(format!("Skipping rake tasks ({rakefile} not found)",))
-                .sub_bullet(format!("{help} Add {rakefile} to your project to enable",)),
+                .sub_bullet(format!("Skipping rake tasks ({rakefile} not found)"))
+                .sub_bullet(format!("{help} Add {rakefile} to your project to enable")),
             None,
         )),
         RakeStatus::SkipManifestFound(paths) => {
@@ -72,9 +80,84 @@ pub(crate) fn detect_rake_tasks(
                 env,
                 true,
             )
-            .map_err(RubyBuildpackError::RakeDetectError)?;
+            .map_err(|error| {
+                RubyBuildpackError::RakeDetectError(
+                    error,
+                    Diagnostics::capture(features, env, &context.app_dir),
+                )
+            })?;
 
-            Ok((bullet, Some(rake_detect)))
+            Ok((
+                bullet.sub_bullet(task_summary(&rake_detect, gem_list, asset_tasks)),
+                Some(rake_detect),
+            ))
         }
     }
 }
+
+/// Which of the tasks that decide the asset steps were found
+///
+/// The stylesheet build task of a gem is only listed when that gem is installed.
+fn task_summary(rake: &RakeDetect, gem_list: &GemList, asset_tasks: &AssetTasks) -> String {
+    let tasks = asset_tasks
+        .tasks()
+        .iter()
+        .map(String::as_str)
+        .chain(["assets:clean"])
+        .chain(
+            CSS_BUILD_GEMS
+                .into_iter()
+                .filter(|(gem, _)| gem_list.has(gem))
+                .map(|(_, task)| task),
+        )
+        .collect::<Vec<_>>();
+    let (found, missing): (Vec<_>, Vec<_>) =
+        tasks.into_iter().partition(|task| rake.has_task(task));
+    let list = |tasks: Vec<&str>| {
+        SentenceList::new(&tasks.into_iter().map(style::value).collect::<Vec<_>>()).to_string()
+    };
+
+    match (found.is_empty(), missing.is_empty()) {
+        (false, false) => format!("Found {}, missing {}", list(found), list(missing)),
+        (false, true) => format!("Found {}", list(found)),
+        (true, _) => format!("Missing {}", list(missing)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::str::FromStr;
+
+    #[test]
+    fn test_task_summary() {
+        let rake = |tasks: &str| RakeDetect::from_rake_output(tasks);
+        let gems = GemList::from_str("  * railties (7.2.1)").unwrap();
+        assert_eq!(
+            bullet_stream::strip_ansi(task_summary(
+                &rake("rake assets:precompile\nrake assets:clean\n"),
+                &gems,
+                &AssetTasks::default()
+            )),
+            "Found `assets:precompile` and `assets:clean`"
+        );
+        assert_eq!(
+            bullet_stream::strip_ansi(task_summary(
+                &rake("rake assets:precompile\n"),
+                &gems,
+                &AssetTasks::default()
+            )),
+            "Found `assets:precompile`, missing `assets:clean`"
+        );
+
+        let gems = GemList::from_str("  * cssbundling-rails (1.4.1)").unwrap();
+        assert_eq!(
+            bullet_stream::strip_ansi(task_summary(
+                &rake("rake db:migrate\n"),
+                &gems,
+                &AssetTasks::default()
+            )),
+            "Missing `assets:precompile`, `assets:clean`, and `css:build`"
+        );
+    }
+}
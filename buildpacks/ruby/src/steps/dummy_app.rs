@@ -0,0 +1,107 @@
+//! Recognize repositories that are a gem with a Rails dummy application
+//!
+//! Rails engines and other gems that integrate with Rails are tested against a small
+//! application in `spec/dummy` or `test/dummy`. The root of the repository has a `.gemspec` and
+//! `railties` is installed, but the root is not a Rails application, so `rake
+//! assets:precompile` and `bin/rails server` fail in confusing ways. For these repositories
+//! asset compilation and the default web process are skipped and a warning explains how to run
+//! the dummy application.
+use crate::warning::Warning;
+use bullet_stream::style;
+use indoc::formatdoc;
+use std::path::Path;
+
+/// Where gem generators (`bundle gem`, `rails plugin new`) place the dummy application
+const DUMMY_APP_DIRS: [&str; 2] = ["spec/dummy", "test/dummy"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct DummyApp {
+    /// File name of the gemspec at the root of the repository
+    pub(crate) gemspec: String,
+    /// Directory of the dummy application, relative to the root
+    pub(crate) dir: &'static str,
+}
+
+/// Returns the dummy application when the root of the repository is a gem
+///
+/// A root with its own `config/application.rb` is a Rails application, even when it also
+/// has a gemspec.
+pub(crate) fn dummy_app(app_path: &Path) -> Option<DummyApp> {
+    if app_path.join("config").join("application.rb").exists() {
+        return None;
+    }
+    let mut gemspecs = fs_err::read_dir(app_path)
+        .ok()?
+        .filter_map(Result::ok)
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .filter(|name| name.ends_with(".gemspec"))
+        .collect::<Vec<_>>();
+    gemspecs.sort();
+    let gemspec = gemspecs.into_iter().next()?;
+
+    DUMMY_APP_DIRS
+        .into_iter()
+        .find(|dir| {
+            app_path
+                .join(dir)
+                .join("config")
+                .join("application.rb")
+                .exists()
+        })
+        .map(|dir| DummyApp { gemspec, dir })
+}
+
+/// Explains that a gem repository is not deployed as a Rails application
+pub(crate) fn dummy_app_warning(app_path: &Path) -> Option<Warning> {
+    dummy_app(app_path).map(|DummyApp { gemspec, dir }| Warning {
+        code: "dummy_app",
+        title: "Repository is a gem with a dummy Rails application",
+        body: formatdoc! {"
+            Found {gemspec} and a Rails application in {dir}. The root of this repository is a
+            gem, not a Rails application, so asset compilation and the default web process
+            are skipped.
+
+            To run the dummy application, compile its assets with a {postbuild} hook and add a
+            {procfile} that starts it from its own directory, for example:
+
+            web: cd {dir} && bin/rails server --binding \"[::]\" --port \"$PORT\"
+
+            To deploy an application that uses this gem, build that application's repository
+            instead.
+            ",
+            gemspec = style::value(gemspec),
+            dir = dir,
+            postbuild = style::value("bin/heroku-ruby-postbuild"),
+            procfile = style::value("Procfile"),
+        },
+        url: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dummy_app() {
+        let dir = tempfile::tempdir().unwrap();
+        let app_path = dir.path();
+        fs_err::create_dir_all(app_path.join("spec/dummy/config")).unwrap();
+        fs_err::write(app_path.join("spec/dummy/config/application.rb"), "").unwrap();
+        assert_eq!(dummy_app(app_path), None);
+
+        fs_err::write(app_path.join("blorgh.gemspec"), "").unwrap();
+        assert_eq!(
+            dummy_app(app_path),
+            Some(DummyApp {
+                gemspec: String::from("blorgh.gemspec"),
+                dir: "spec/dummy"
+            })
+        );
+        assert!(dummy_app_warning(app_path).is_some());
+
+        fs_err::create_dir_all(app_path.join("config")).unwrap();
+        fs_err::write(app_path.join("config/application.rb"), "").unwrap();
+        assert_eq!(dummy_app(app_path), None);
+    }
+}
@@ -1,11 +1,16 @@
 use crate::gem_list::GemList;
+use crate::steps::dummy_app::{dummy_app, DummyApp};
+use crate::steps::jekyll_build::jekyll_site;
+use crate::warning::Warning;
 use crate::RubyBuildpack;
 use bullet_stream::style;
 use bullet_stream::{state::SubBullet, Print};
+use indoc::formatdoc;
 use libcnb::build::BuildContext;
 use libcnb::data::launch::Process;
 use libcnb::data::launch::ProcessBuilder;
 use libcnb::data::process_type;
+use libcnb::{Env, Platform};
 use std::io::Stdout;
 use std::path::Path;
 
@@ -18,16 +23,74 @@ pub(crate) fn get_default_process(
     let rails = style::value("rails");
     let rack = style::value("rack");
     let railties = style::value("railties");
+    let (bullet, host) = match BindHost::from_env(context.platform.env()) {
+        Ok(None) => (bullet, BindHost::default()),
+        Ok(Some(host)) => (
+            bullet.sub_bullet(format!(
+                "Binding to host {} (from {})",
+                style::value(&host.0),
+                style::value(BIND_HOST_ENV_KEY)
+            )),
+            host,
+        ),
+        Err(value) => (
+            bullet.sub_bullet(format!(
+                "Ignoring {} (not a host name or IP address), binding to {}",
+                style::value(format!("{BIND_HOST_ENV_KEY}={value}")),
+                style::value(DEFAULT_BIND_HOST)
+            )),
+            BindHost::default(),
+        ),
+    };
     match detect_web(gem_list, &context.app_dir) {
+        WebProcess::DummyApp(DummyApp { gemspec, dir }) => (
+            bullet.sub_bullet(format!(
+                "Skipping default web process (found {} and a dummy Rails app in {})",
+                style::value(gemspec),
+                style::value(dir)
+            )),
+            None,
+        ),
+        WebProcess::Unicorn => (
+            bullet.sub_bullet(format!(
+                "Detected unicorn app ({} gem found and {} not found)",
+                style::value("unicorn"),
+                style::value("puma")
+            )),
+            Some(default_unicorn(&context.app_dir).process(&host)),
+        ),
+        WebProcess::Passenger => (
+            bullet.sub_bullet(format!(
+                "Detected passenger app ({} gem found and {} not found)",
+                style::value("passenger"),
+                style::value("puma")
+            )),
+            Some(default_passenger().process(&host)),
+        ),
         WebProcess::Rails => (
             bullet.sub_bullet(format!("Detected rails app ({rails} gem found)")),
-            Some(default_rails()),
+            Some(default_rails().process(&host)),
+        ),
+        WebProcess::Jekyll => (
+            bullet.sub_bullet(format!(
+                "Detected Jekyll site ({} gem found)",
+                style::value("jekyll")
+            )),
+            Some(default_jekyll().process(&host)),
+        ),
+        WebProcess::JekyllMissingWebrick => (
+            bullet.sub_bullet(format!(
+                "Skipping default web process (Jekyll site found but {} gem not found, {} needs it)",
+                style::value("webrick"),
+                style::value("jekyll serve")
+            )),
+            None,
         ),
         WebProcess::RackWithConfigRU => (
             bullet.sub_bullet(format!(
                 "Detected rack app ({rack} gem found and {config_ru} at root of application)"
             )),
-            Some(default_rack()),
+            Some(default_rack().process(&host)),
         ),
         WebProcess::RackMissingConfigRu => (
             bullet.sub_bullet(format!(
@@ -44,54 +107,520 @@ pub(crate) fn get_default_process(
     }
 }
 
+/// Returns a warning when the application has a `Procfile` that won't be used
+///
+/// This buildpack does not read the `Procfile`, that's the job of the `heroku/procfile`
+/// buildpack. When it's not in the buildpack group after `heroku/ruby` the `Procfile` is
+/// silently ignored and the default web process is used instead.
+///
+/// The group is read from the `group.toml` the lifecycle writes to the layers directory after
+/// detection. Nothing is reported when it can't be read, to avoid warning apps that are fine.
+pub(crate) fn procfile_warning(context: &BuildContext<RubyBuildpack>) -> Option<Warning> {
+    let group_toml = context
+        .layers_dir
+        .parent()
+        .and_then(|dir| fs_err::read_to_string(dir.join("group.toml")).ok())?;
+    let ignored = context.app_dir.join("Procfile").exists()
+        && !procfile_runs_after(&group_toml, &context.buildpack_descriptor.buildpack.id)?;
+
+    ignored.then(|| {
+        let procfile = style::value("Procfile");
+        let procfile_buildpack = style::value("heroku/procfile");
+        let ruby_buildpack = style::value("heroku/ruby");
+        Warning {
+            code: "procfile",
+            title: "Detected a Procfile",
+            body: formatdoc! {"
+                Process types from a {procfile} are only used when the {procfile_buildpack} buildpack
+                runs after the {ruby_buildpack} buildpack. Without it, your {procfile} is ignored and
+                the default process types detected by this buildpack are used instead.
+
+                If you're building with `pack build` ensure you're using a builder that includes
+                {procfile_buildpack} or pass both buildpacks in order:
+
+                --buildpack heroku/ruby --buildpack heroku/procfile
+            "},
+            url: None,
+        }
+    })
+}
+
+/// True when a buildpack with `procfile` in its id runs after this buildpack
+///
+/// Processes from later buildpacks replace earlier ones with the same type, so a
+/// `heroku/procfile` that runs first is overridden by the default web process.
+fn procfile_runs_after(group_toml: &str, buildpack_id: &str) -> Option<bool> {
+    #[derive(serde::Deserialize)]
+    struct Group {
+        group: Vec<GroupEntry>,
+    }
+    #[derive(serde::Deserialize)]
+    struct GroupEntry {
+        id: String,
+    }
+
+    let group = toml::from_str::<Group>(group_toml).ok()?.group;
+    let position = group.iter().position(|entry| entry.id == buildpack_id)?;
+    Some(
+        group
+            .iter()
+            .skip(position + 1)
+            .any(|entry| entry.id.contains("procfile")),
+    )
+}
+
+#[derive(Debug, PartialEq, Eq)]
 enum WebProcess {
+    DummyApp(DummyApp),
+    Unicorn,
+    Passenger,
     Rails,
     RackWithConfigRU,
+    Jekyll,
+    JekyllMissingWebrick,
     RackMissingConfigRu,
     Missing,
 }
 
+/// Unicorn and Passenger are used as the server when installed without Puma, because
+/// `bin/rails server` and `rackup` can't boot them
+///
+/// `jekyll serve` needs the `webrick` gem, which is not part of Ruby's standard library since
+/// Ruby 3.0.
 fn detect_web(gem_list: &GemList, app_path: &Path) -> WebProcess {
-    if gem_list.has("railties") {
+    if let Some(dummy) = dummy_app(app_path) {
+        WebProcess::DummyApp(dummy)
+    } else if !gem_list.has("puma") && gem_list.has("unicorn") {
+        WebProcess::Unicorn
+    } else if !gem_list.has("puma") && gem_list.has("passenger") {
+        WebProcess::Passenger
+    } else if gem_list.has("railties") {
         WebProcess::Rails
-    } else if gem_list.has("rack") {
-        if app_path.join("config.ru").exists() {
-            WebProcess::RackWithConfigRU
+    } else if gem_list.has("rack") && app_path.join("config.ru").exists() {
+        WebProcess::RackWithConfigRU
+    } else if jekyll_site(gem_list, app_path) {
+        if gem_list.has("webrick") {
+            WebProcess::Jekyll
         } else {
-            WebProcess::RackMissingConfigRu
+            WebProcess::JekyllMissingWebrick
         }
+    } else if gem_list.has("rack") {
+        WebProcess::RackMissingConfigRu
     } else {
         WebProcess::Missing
     }
 }
 
-fn default_rack() -> Process {
-    ProcessBuilder::new(process_type!("web"), ["bash"])
-        .args([
-            "-c",
-            &[
-                "bundle exec rackup",
-                "--host \"[::]\"",
-                "--port \"${PORT:?Error: PORT env var is not set!}\"",
-            ]
-            .join(" "),
-        ])
-        .default(true)
-        .build()
-}
-
-fn default_rails() -> Process {
-    ProcessBuilder::new(process_type!("web"), ["bash"])
-        .args([
-            "-c",
-            &[
-                "bin/rails server",
-                "--binding \"[::]\"",
-                "--port \"${PORT:?Error: PORT env var is not set!}\"",
-                "--environment \"$RAILS_ENV\"",
-            ]
-            .join(" "),
-        ])
-        .default(true)
-        .build()
+/// A `rake` process to run tasks with the application's environment, such as
+/// `docker run --entrypoint rake <image> db:migrate`
+///
+/// It's only added when the `rake` gem is in the bundle, and is never the default process.
+pub(crate) fn rake_process(
+    bullet: Print<SubBullet<Stdout>>,
+    gem_list: &GemList,
+) -> (Print<SubBullet<Stdout>>, Option<Process>) {
+    if gem_list.has("rake") {
+        (
+            bullet.sub_bullet(format!(
+                "Adding {} process ({})",
+                style::value("rake"),
+                style::command("bundle exec rake")
+            )),
+            Some(ProcessBuilder::new(process_type!("rake"), ["bundle", "exec", "rake"]).build()),
+        )
+    } else {
+        (bullet, None)
+    }
+}
+
+/// When set, the default web process binds to this host instead of `::`
+pub(crate) const BIND_HOST_ENV_KEY: &str = "HEROKU_RUBY_BIND_HOST";
+
+/// Binds to every IPv6 and IPv4 address on dual stack hosts
+const DEFAULT_BIND_HOST: &str = "::";
+
+/// The host the default web process binds to
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct BindHost(String);
+
+impl BindHost {
+    /// Reads `HEROKU_RUBY_BIND_HOST`, brackets around an IPv6 address are optional
+    ///
+    /// # Errors
+    ///
+    /// Errors with the value when it's not a host name or IP address.
+    fn from_env(platform_env: &Env) -> Result<Option<Self>, String> {
+        let Some(value) = platform_env
+            .get(BIND_HOST_ENV_KEY)
+            .map(|value| value.to_string_lossy().trim().to_string())
+            .filter(|value| !value.is_empty())
+        else {
+            return Ok(None);
+        };
+        let host = value.trim_start_matches('[').trim_end_matches(']');
+        if !host.is_empty()
+            && host
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | ':' | '-'))
+        {
+            Ok(Some(Self(host.to_string())))
+        } else {
+            Err(value)
+        }
+    }
+
+    /// IPv6 addresses in brackets, for servers that parse the host together with a port
+    fn bracketed(&self) -> String {
+        if self.0.contains(':') {
+            format!("[{}]", self.0)
+        } else {
+            self.0.clone()
+        }
+    }
+}
+
+impl Default for BindHost {
+    fn default() -> Self {
+        Self(String::from(DEFAULT_BIND_HOST))
+    }
+}
+
+/// A web server started with an explicit host and port
+///
+/// Every default web process is defined with this so the host and port are passed the same
+/// way to each server. Processes are exec'd by the launcher without a shell, so environment
+/// variables are referenced as `$(PORT)` placeholders that the launcher expands. Shell syntax
+/// such as `$PORT` would be passed to the server literally.
+struct WebServer {
+    command: &'static [&'static str],
+    /// The flag that sets the host, such as `--binding`
+    host_flag: &'static str,
+    /// Set for servers that need IPv6 addresses in brackets
+    brackets: bool,
+    /// Arguments after the host and port
+    args: Vec<String>,
+}
+
+impl WebServer {
+    /// The command line, one argument per entry
+    fn argv(&self, host: &BindHost) -> Vec<String> {
+        let host = if self.brackets {
+            host.bracketed()
+        } else {
+            host.0.clone()
+        };
+        self.command
+            .iter()
+            .map(ToString::to_string)
+            .chain([
+                self.host_flag.to_string(),
+                host,
+                String::from("--port"),
+                placeholder("PORT"),
+            ])
+            .chain(self.args.iter().cloned())
+            .collect()
+    }
+
+    /// The command is fixed, the host, port, and other arguments are default arguments that
+    /// arguments given when the container starts replace
+    fn process(&self, host: &BindHost) -> Process {
+        ProcessBuilder::new(process_type!("web"), self.command.iter().copied())
+            .args(self.argv(host).into_iter().skip(self.command.len()))
+            .default(true)
+            .build()
+    }
+}
+
+/// A reference to an environment variable that the CNB launcher expands in exec processes
+fn placeholder(name: &str) -> String {
+    format!("$({name})")
+}
+
+fn default_rack() -> WebServer {
+    WebServer {
+        command: &["bundle", "exec", "rackup"],
+        host_flag: "--host",
+        brackets: true,
+        args: Vec::new(),
+    }
+}
+
+/// Serves the site built into `_site` without rebuilding or watching for changes
+///
+/// `jekyll serve` is a development server, sites with more traffic should serve `_site` with
+/// a production web server from the `Procfile`.
+fn default_jekyll() -> WebServer {
+    WebServer {
+        command: &["bundle", "exec", "jekyll", "serve"],
+        host_flag: "--host",
+        brackets: false,
+        args: vec![
+            String::from("--skip-initial-build"),
+            String::from("--no-watch"),
+        ],
+    }
+}
+
+fn default_unicorn(app_path: &Path) -> WebServer {
+    let mut args = Vec::new();
+    if app_path.join("config").join("unicorn.rb").exists() {
+        args.extend([
+            String::from("--config-file"),
+            String::from("./config/unicorn.rb"),
+        ]);
+    }
+    WebServer {
+        command: &["bundle", "exec", "unicorn"],
+        host_flag: "--host",
+        brackets: true,
+        args,
+    }
+}
+
+fn default_passenger() -> WebServer {
+    WebServer {
+        command: &["bundle", "exec", "passenger", "start"],
+        host_flag: "--address",
+        brackets: false,
+        args: Vec::new(),
+    }
+}
+
+fn default_rails() -> WebServer {
+    WebServer {
+        command: &["bin/rails", "server"],
+        host_flag: "--binding",
+        brackets: true,
+        args: vec![String::from("--environment"), placeholder("RAILS_ENV")],
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::str::FromStr;
+
+    #[test]
+    fn test_detect_web() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let gem_list = |gems: &str| GemList::from_str(gems).unwrap();
+
+        assert_eq!(
+            detect_web(
+                &gem_list("  * railties (7.2.1)\n  * unicorn (6.1.0)\n"),
+                tmpdir.path()
+            ),
+            WebProcess::Unicorn
+        );
+        assert_eq!(
+            detect_web(&gem_list("  * passenger (6.0.23)\n"), tmpdir.path()),
+            WebProcess::Passenger
+        );
+        assert_eq!(
+            detect_web(
+                &gem_list("  * railties (7.2.1)\n  * puma (6.4.3)\n  * unicorn (6.1.0)\n"),
+                tmpdir.path()
+            ),
+            WebProcess::Rails
+        );
+
+        fs_err::write(tmpdir.path().join("_config.yml"), "title: Blog").unwrap();
+        assert_eq!(
+            detect_web(&gem_list("  * jekyll (4.3.4)\n"), tmpdir.path()),
+            WebProcess::JekyllMissingWebrick
+        );
+        assert_eq!(
+            detect_web(
+                &gem_list("  * jekyll (4.3.4)\n  * webrick (1.8.2)\n"),
+                tmpdir.path()
+            ),
+            WebProcess::Jekyll
+        );
+        fs_err::write(tmpdir.path().join("config.ru"), "run App").unwrap();
+        assert_eq!(
+            detect_web(
+                &gem_list("  * jekyll (4.3.4)\n  * rack (3.1.8)\n"),
+                tmpdir.path()
+            ),
+            WebProcess::RackWithConfigRU
+        );
+
+        fs_err::write(tmpdir.path().join("blorgh.gemspec"), "").unwrap();
+        fs_err::create_dir_all(tmpdir.path().join("test/dummy/config")).unwrap();
+        fs_err::write(tmpdir.path().join("test/dummy/config/application.rb"), "").unwrap();
+        assert!(matches!(
+            detect_web(
+                &gem_list("  * railties (7.2.1)\n  * puma (6.4.3)\n"),
+                tmpdir.path()
+            ),
+            WebProcess::DummyApp(DummyApp {
+                dir: "test/dummy",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_unicorn_config_file() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let command = |server: WebServer| server.argv(&BindHost::default()).join(" ");
+        assert!(!command(default_unicorn(tmpdir.path())).contains("--config-file"));
+
+        fs_err::create_dir_all(tmpdir.path().join("config")).unwrap();
+        fs_err::write(tmpdir.path().join("config").join("unicorn.rb"), "").unwrap();
+        assert!(
+            command(default_unicorn(tmpdir.path())).contains("--config-file ./config/unicorn.rb")
+        );
+    }
+
+    /// Expands `$(VAR)` references like the CNB launcher, `$$` escapes a `$`
+    ///
+    /// Like the launcher, a reference to an unset variable is left as is. A server started
+    /// without `PORT` receives the literal `$(PORT)` and fails to parse it as a port.
+    fn expand(arg: &str, env: &Env) -> String {
+        let mut expanded = String::new();
+        let mut rest = arg;
+        while let Some(index) = rest.find('$') {
+            expanded.push_str(&rest[..index]);
+            rest = &rest[index..];
+            if let Some(escaped) = rest.strip_prefix("$$") {
+                expanded.push('$');
+                rest = escaped;
+            } else if let Some((value, after)) = rest
+                .strip_prefix("$(")
+                .and_then(|inner| inner.split_once(')'))
+                .and_then(|(name, after)| Some((env.get(name)?, after)))
+            {
+                expanded.push_str(&value.to_string_lossy());
+                rest = after;
+            } else {
+                expanded.push('$');
+                rest = &rest[1..];
+            }
+        }
+        expanded.push_str(rest);
+        expanded
+    }
+
+    #[test]
+    fn test_default_processes() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let host = BindHost::default();
+        let mut env = Env::new();
+        env.insert("PORT", "5000");
+        env.insert("RAILS_ENV", "production");
+        let command_line = |server: WebServer| {
+            let process = server.process(&host);
+            assert!(process.default);
+            process
+                .command
+                .iter()
+                .chain(&process.args)
+                .map(|arg| expand(arg, &env))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+
+        assert_eq!(
+            command_line(default_rails()),
+            "bin/rails server --binding [::] --port 5000 --environment production"
+        );
+        assert_eq!(
+            command_line(default_rack()),
+            "bundle exec rackup --host [::] --port 5000"
+        );
+        assert_eq!(
+            command_line(default_passenger()),
+            "bundle exec passenger start --address :: --port 5000"
+        );
+        assert_eq!(
+            command_line(default_unicorn(tmpdir.path())),
+            "bundle exec unicorn --host [::] --port 5000"
+        );
+        assert_eq!(
+            command_line(default_jekyll()),
+            "bundle exec jekyll serve --host :: --port 5000 --skip-initial-build --no-watch"
+        );
+
+        let process = default_rack().process(&host);
+        assert_eq!(
+            process
+                .args
+                .iter()
+                .map(|arg| expand(arg, &Env::new()))
+                .collect::<Vec<_>>(),
+            ["--host", "[::]", "--port", "$(PORT)"]
+        );
+    }
+
+    #[test]
+    fn test_bind_host() {
+        let mut env = Env::new();
+        assert_eq!(BindHost::from_env(&env), Ok(None));
+
+        env.insert(BIND_HOST_ENV_KEY, "0.0.0.0");
+        let host = BindHost::from_env(&env).unwrap().unwrap();
+        assert_eq!(
+            default_rails().argv(&host)[2..6],
+            ["--binding", "0.0.0.0", "--port", "$(PORT)"]
+        );
+
+        env.insert(BIND_HOST_ENV_KEY, "[fd00::1]");
+        let host = BindHost::from_env(&env).unwrap().unwrap();
+        assert_eq!(default_rack().argv(&host)[4], "[fd00::1]");
+        assert_eq!(default_passenger().argv(&host)[5], "fd00::1");
+
+        env.insert(BIND_HOST_ENV_KEY, "0.0.0.0; rm -rf /");
+        assert_eq!(
+            BindHost::from_env(&env),
+            Err(String::from("0.0.0.0; rm -rf /"))
+        );
+    }
+
+    #[test]
+    fn test_rake_process() {
+        let bullet = || {
+            bullet_stream::Print::new(std::io::stdout())
+                .without_header()
+                .bullet("Test")
+        };
+        let (_, process) =
+            rake_process(bullet(), &GemList::from_str("  * rack (3.1.8)\n").unwrap());
+        assert_eq!(process, None);
+
+        let (_, process) =
+            rake_process(bullet(), &GemList::from_str("  * rake (13.2.1)\n").unwrap());
+        let process = process.unwrap();
+        assert_eq!(process.command, ["bundle", "exec", "rake"]);
+        assert!(process.args.is_empty());
+        assert!(!process.default);
+    }
+
+    #[test]
+    fn test_procfile_runs_after() {
+        let group = |ids: &[&str]| {
+            ids.iter()
+                .map(|id| format!("[[group]]\n  id = \"{id}\"\n  version = \"1.0.0\"\n"))
+                .collect::<Vec<_>>()
+                .concat()
+        };
+        assert_eq!(
+            procfile_runs_after(&group(&["heroku/ruby", "heroku/procfile"]), "heroku/ruby"),
+            Some(true)
+        );
+        assert_eq!(
+            procfile_runs_after(&group(&["heroku/procfile", "heroku/ruby"]), "heroku/ruby"),
+            Some(false)
+        );
+        assert_eq!(
+            procfile_runs_after(&group(&["heroku/ruby"]), "heroku/ruby"),
+            Some(false)
+        );
+        assert_eq!(
+            procfile_runs_after(&group(&["heroku/nodejs"]), "heroku/ruby"),
+            None
+        );
+        assert_eq!(procfile_runs_after("not toml", "heroku/ruby"), None);
+    }
 }
@@ -0,0 +1,78 @@
+use crate::gem_list::GemList;
+use bullet_stream::state::SubBullet;
+use bullet_stream::{style, Print};
+use commons::gemfile_lock::{ResolvedBundlerVersion, ResolvedRubyVersion};
+use libcnb::data::launch::Label;
+use std::io::Stdout;
+
+/// Image labels with the resolved runtime versions
+///
+/// Operators can audit the versions running across images with `docker inspect` instead of
+/// starting a container. The Rails label is only set when `railties` is installed.
+pub(crate) fn image_labels(
+    mut bullet: Print<SubBullet<Stdout>>,
+    ruby_version: &ResolvedRubyVersion,
+    bundler_version: &ResolvedBundlerVersion,
+    gem_list: &GemList,
+) -> (Print<SubBullet<Stdout>>, Vec<Label>) {
+    let labels = labels(ruby_version, bundler_version, gem_list);
+    for label in &labels {
+        bullet = bullet.sub_bullet(style::value(format!("{}={}", label.key, label.value)));
+    }
+    (bullet, labels)
+}
+
+fn labels(
+    ruby_version: &ResolvedRubyVersion,
+    bundler_version: &ResolvedBundlerVersion,
+    gem_list: &GemList,
+) -> Vec<Label> {
+    [
+        ("com.heroku.ruby.version", Some(ruby_version.to_string())),
+        (
+            "com.heroku.bundler.version",
+            Some(bundler_version.to_string()),
+        ),
+        (
+            "com.heroku.rails.version",
+            gem_list.version_for("railties").map(ToString::to_string),
+        ),
+    ]
+    .into_iter()
+    .filter_map(|(key, value)| {
+        value.map(|value| Label {
+            key: key.to_string(),
+            value,
+        })
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::str::FromStr;
+
+    #[test]
+    fn test_labels() {
+        let ruby = ResolvedRubyVersion(String::from("3.3.5"));
+        let bundler = ResolvedBundlerVersion(String::from("2.5.6"));
+
+        let keys = |gem_list: &str| {
+            labels(&ruby, &bundler, &GemList::from_str(gem_list).unwrap())
+                .into_iter()
+                .map(|label| format!("{}={}", label.key, label.value))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            keys("  * railties (7.2.1)\n"),
+            vec![
+                "com.heroku.ruby.version=3.3.5",
+                "com.heroku.bundler.version=2.5.6",
+                "com.heroku.rails.version=7.2.1"
+            ]
+        );
+        assert_eq!(keys("").len(), 2);
+    }
+}
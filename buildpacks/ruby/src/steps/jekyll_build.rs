@@ -0,0 +1,110 @@
+use crate::gem_list::GemList;
+use crate::layers::build_logs_layer::{self, BuildLog};
+use crate::RubyBuildpack;
+use crate::RubyBuildpackError;
+use bullet_stream::state::SubBullet;
+use bullet_stream::{style, Print};
+use commons::assets;
+use commons::cache::{mib, CacheConfig, Eviction, KeepPath};
+use fun_run::{self, CommandWithName};
+use libcnb::build::BuildContext;
+use libcnb::Env;
+use std::io::Stdout;
+use std::path::Path;
+use std::process::Command;
+
+/// Jekyll reads its configuration from the first of these files it finds
+const JEKYLL_CONFIG_FILES: [&str; 3] = ["_config.yml", "_config.yaml", "_config.toml"];
+
+/// True when the application is a Jekyll site
+///
+/// The `jekyll` gem alone isn't enough, some applications only use it to build their
+/// documentation. A site also has a Jekyll config file at the root of the application.
+pub(crate) fn jekyll_site(gem_list: &GemList, app_dir: &Path) -> bool {
+    gem_list.has("jekyll")
+        && JEKYLL_CONFIG_FILES
+            .iter()
+            .any(|file| app_dir.join(file).exists())
+}
+
+/// Builds a Jekyll site into `_site`
+///
+/// The generated site in `_site` and Jekyll's own cache in `.jekyll-cache` are stored between
+/// builds. `_site` is served at runtime so it ships in the image, `.jekyll-cache` is only needed
+/// during the build.
+pub(crate) fn jekyll_build(
+    bullet: Print<SubBullet<Stdout>>,
+    context: &BuildContext<RubyBuildpack>,
+    env: &Env,
+) -> Result<Print<SubBullet<Stdout>>, RubyBuildpackError> {
+    let bullet = bullet.sub_bullet(format!(
+        "Detected Jekyll site ({} gem found)",
+        style::value("jekyll")
+    ));
+
+    let caches = [
+        CacheConfig {
+            path: context.app_dir.join("_site"),
+            limit: mib(100),
+            keep_path: KeepPath::Runtime,
+            eviction: Eviction::Lru,
+        },
+        CacheConfig {
+            path: context.app_dir.join(".jekyll-cache"),
+            limit: mib(100),
+            keep_path: KeepPath::BuildOnly,
+            eviction: Eviction::Lru,
+        },
+    ];
+
+    assets::with_caches(
+        bullet,
+        context,
+        caches,
+        RubyBuildpackError::InAppDirCacheError,
+        |mut bullet| {
+            let mut cmd = Command::new("bundle");
+            cmd.args(["exec", "jekyll", "build"])
+                .current_dir(&context.app_dir)
+                .env_clear()
+                .envs(env);
+            let mut cmd =
+                cmd.named_fn(|cmd| fun_run::display_with_env_keys(cmd, env, ["JEKYLL_ENV"]));
+
+            bullet
+                .stream_with(
+                    format!("Running {}", style::command(cmd.name())),
+                    |stdout, stderr| {
+                        let (stdout, stderr) =
+                            build_logs_layer::tee(context, BuildLog::JekyllBuild, stdout, stderr);
+                        cmd.stream_output(stdout, stderr)
+                    },
+                )
+                .map_err(|error| {
+                    fun_run::map_which_problem(error, cmd.mut_cmd(), env.get("PATH").cloned())
+                })
+                .map_err(RubyBuildpackError::JekyllBuildFailed)?;
+            Ok(bullet)
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::str::FromStr;
+
+    #[test]
+    fn test_jekyll_site() {
+        let dir = tempfile::tempdir().unwrap();
+        let gems = GemList::from_str("  * jekyll (4.3.4)").unwrap();
+        assert!(!jekyll_site(&gems, dir.path()));
+
+        fs_err::write(dir.path().join("_config.yml"), "title: Blog").unwrap();
+        assert!(jekyll_site(&gems, dir.path()));
+        assert!(!jekyll_site(
+            &GemList::from_str("  * rack (3.1.8)").unwrap(),
+            dir.path()
+        ));
+    }
+}
@@ -0,0 +1,240 @@
+//! Report the largest files that ship in the image
+//!
+//! Database dumps, logs, or build caches committed by accident make every deploy slower to
+//! push and start. After the build, the application directory and the layers available at
+//! launch are scanned and the largest files and top level directories are listed. When any
+//! file is over [`LARGE_FILE_BYTES`] a warning is printed too.
+//!
+//! The scan stops after [`MAX_ENTRIES`] files and directories so very large applications
+//! don't slow down the build, files past that point are not reported.
+use crate::disk_usage::human_size;
+use crate::warning::Warning;
+use bullet_stream::state::SubBullet;
+use bullet_stream::{style, Print};
+use indoc::formatdoc;
+use std::cmp::Reverse;
+use std::io::Stdout;
+use std::path::{Path, PathBuf};
+
+const LARGE_FILE_BYTES: u64 = 100 * 1024 * 1024;
+const MAX_ENTRIES: usize = 200_000;
+/// How many files and directories are listed
+const TOP: usize = 5;
+
+#[derive(Debug, Default)]
+struct Scan {
+    /// The [`TOP`] largest files, largest first
+    files: Vec<(PathBuf, u64)>,
+    /// Top level directories of each scanned root with their total size
+    dirs: Vec<(PathBuf, u64)>,
+    entries: usize,
+}
+
+impl Scan {
+    fn add_file(&mut self, path: &Path, size: u64) {
+        if self.files.len() < TOP || self.files.last().is_some_and(|last| size > last.1) {
+            self.files.push((path.to_path_buf(), size));
+            self.files.sort_by_key(|(_, size)| Reverse(*size));
+            self.files.truncate(TOP);
+        }
+    }
+}
+
+/// Lists the largest files and directories in the image, and warns when files over 100 MiB
+/// will ship in it
+pub(crate) fn large_files(
+    mut bullet: Print<SubBullet<Stdout>>,
+    app_dir: &Path,
+    layers_dir: &Path,
+) -> (Print<SubBullet<Stdout>>, Option<Warning>) {
+    let mut roots = vec![app_dir.to_path_buf()];
+    roots.extend(launch_layers(layers_dir));
+    let mut scan = scan(&roots);
+    scan.dirs.sort_by_key(|(_, size)| Reverse(*size));
+
+    let display = |path: &Path| {
+        style::value(
+            path.strip_prefix(app_dir)
+                .unwrap_or(path)
+                .display()
+                .to_string(),
+        )
+    };
+    let list = |entries: &[(PathBuf, u64)]| {
+        entries
+            .iter()
+            .take(TOP)
+            .map(|(path, size)| format!("- {} ({})", display(path), human_size(*size)))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    let files = list(&scan.files);
+    let dirs = list(&scan.dirs);
+
+    if !files.is_empty() {
+        bullet = bullet.sub_bullet(format!("Largest files:\n{files}"));
+    }
+    if !dirs.is_empty() {
+        bullet = bullet.sub_bullet(format!("Largest directories:\n{dirs}"));
+    }
+    if scan.entries >= MAX_ENTRIES {
+        bullet = bullet.sub_bullet(format!(
+            "Stopped after {MAX_ENTRIES} files and directories, larger files may exist"
+        ));
+    }
+
+    let large = scan
+        .files
+        .iter()
+        .filter(|(_, size)| *size >= LARGE_FILE_BYTES)
+        .cloned()
+        .collect::<Vec<_>>();
+    if large.is_empty() {
+        return (bullet, None);
+    }
+
+    let large = list(&large);
+    let warning = Warning {
+        code: "large_files",
+        title: "Large files in the image",
+        body: formatdoc! {"
+            Files over {limit} will be included in the image. Large images are slower to
+            push and to start. Check that these files are needed at runtime:
+
+            {large}
+
+            Remove files from the repository, or exclude them from the image with {exclude}
+            in {project_toml}.
+            ",
+            limit = human_size(LARGE_FILE_BYTES),
+            exclude = style::value("[io.buildpacks] exclude"),
+            project_toml = style::value("project.toml"),
+        },
+        url: Some("https://buildpacks.io/docs/reference/config/project-descriptor/"),
+    };
+    (bullet, Some(warning))
+}
+
+/// Layers written by this buildpack that are available at launch
+fn launch_layers(layers_dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs_err::read_dir(layers_dir) else {
+        return Vec::new();
+    };
+    let mut layers = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .filter(|path| {
+            fs_err::read_to_string(path.with_extension("toml"))
+                .ok()
+                .and_then(|contents| contents.parse::<toml::Table>().ok())
+                .and_then(|table| table.get("types")?.get("launch")?.as_bool())
+                .unwrap_or(false)
+        })
+        .collect::<Vec<_>>();
+    layers.sort();
+    layers
+}
+
+fn scan(roots: &[PathBuf]) -> Scan {
+    let mut scan = Scan::default();
+    for root in roots {
+        let Ok(entries) = fs_err::read_dir(root) else {
+            continue;
+        };
+        for path in entries.filter_map(Result::ok).map(|entry| entry.path()) {
+            let size = walk(&path, &mut scan);
+            if path.is_dir() && !path.is_symlink() {
+                scan.dirs.push((path, size));
+            }
+        }
+    }
+    scan
+}
+
+/// Records the largest files under `path` and returns its total size, symlinks are not
+/// followed
+fn walk(path: &Path, scan: &mut Scan) -> u64 {
+    if scan.entries >= MAX_ENTRIES {
+        return 0;
+    }
+    scan.entries += 1;
+    let Ok(metadata) = fs_err::symlink_metadata(path) else {
+        return 0;
+    };
+    if metadata.is_dir() {
+        fs_err::read_dir(path).map_or(0, |entries| {
+            entries
+                .filter_map(Result::ok)
+                .map(|entry| walk(&entry.path(), scan))
+                .sum()
+        })
+    } else {
+        scan.add_file(path, metadata.len());
+        metadata.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_large_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let app_dir = dir.path().join("app");
+        let layers_dir = dir.path().join("layers");
+        fs_err::create_dir_all(app_dir.join("db")).unwrap();
+        fs_err::create_dir_all(layers_dir.join("gems")).unwrap();
+        fs_err::create_dir_all(layers_dir.join("build_only")).unwrap();
+        fs_err::write(app_dir.join("db/schema.rb"), "").unwrap();
+
+        let bullet = Print::new(std::io::stdout())
+            .without_header()
+            .bullet("Test");
+        let (bullet, warning) = large_files(bullet, &app_dir, &layers_dir);
+        assert_eq!(warning, None);
+
+        fs_err::File::create(app_dir.join("db/dump.sql"))
+            .unwrap()
+            .set_len(LARGE_FILE_BYTES)
+            .unwrap();
+        let (_, warning) = large_files(bullet, &app_dir, &layers_dir);
+        let body = bullet_stream::strip_ansi(&warning.unwrap().body);
+        assert!(body.contains("- `db/dump.sql` (100.0 MiB)"), "{body}");
+        assert!(!body.contains("schema.rb"), "{body}");
+    }
+
+    #[test]
+    fn test_scan() {
+        let dir = tempfile::tempdir().unwrap();
+        fs_err::create_dir_all(dir.path().join("db")).unwrap();
+        for (name, size) in [("a", 1), ("b", 7), ("c", 3), ("d", 5), ("e", 2), ("f", 6)] {
+            fs_err::write(dir.path().join("db").join(name), vec![0u8; size]).unwrap();
+        }
+
+        let scan = scan(&[dir.path().to_path_buf()]);
+        assert_eq!(
+            scan.files
+                .iter()
+                .map(|(path, size)| (path.file_name().unwrap().to_str().unwrap(), *size))
+                .collect::<Vec<_>>(),
+            [("b", 7), ("f", 6), ("d", 5), ("c", 3), ("e", 2)]
+        );
+        assert_eq!(scan.dirs, vec![(dir.path().join("db"), 24)]);
+    }
+
+    #[test]
+    fn test_launch_layers() {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, launch) in [("gems", true), ("assets_digest", false)] {
+            fs_err::create_dir_all(dir.path().join(name)).unwrap();
+            fs_err::write(
+                dir.path().join(format!("{name}.toml")),
+                format!("[types]\nlaunch = {launch}\n"),
+            )
+            .unwrap();
+        }
+        assert_eq!(launch_layers(dir.path()), vec![dir.path().join("gems")]);
+    }
+}
@@ -0,0 +1,123 @@
+use crate::gem_list::GemList;
+use crate::RubyBuildpack;
+use crate::RubyBuildpackError;
+use bullet_stream::state::SubBullet;
+use bullet_stream::{style, Print};
+use commons::requirements::{matches_all, parse_requirements};
+use libcnb::build::BuildContext;
+use libcnb::data::layer_name;
+use libcnb::layer::UncachedLayerDefinition;
+use libcnb::layer_env::{LayerEnv, ModificationBehavior, Scope};
+use std::io::Stdout;
+
+/// A launch environment default that only applies when a gem version is installed
+#[derive(Debug)]
+struct LaunchEnvRule {
+    key: &'static str,
+    value: &'static str,
+    gem: &'static str,
+    /// Version requirement for the gem, in `Gemfile` syntax
+    requirement: &'static str,
+}
+
+/// Keep the Heroku router idle timeout (90 seconds) below the server's keep-alive timeout
+/// so the server does not close connections the router still considers open.
+const LAUNCH_ENV_RULES: &[LaunchEnvRule] = &[LaunchEnvRule {
+    key: "PUMA_PERSISTENT_TIMEOUT",
+    value: "95",
+    gem: "puma",
+    requirement: ">= 6.4.0",
+}];
+
+#[derive(Debug, PartialEq, Eq)]
+enum Decision {
+    Set,
+    /// Holds the reason the default was not set
+    Skip(String),
+}
+
+fn decide(rule: &LaunchEnvRule, gem_list: &GemList) -> Decision {
+    let gem = style::value(rule.gem);
+    match gem_list.version_for(rule.gem) {
+        None => Decision::Skip(format!("{gem} gem not found")),
+        Some(version) => {
+            let version = version.to_string();
+            if parse_requirements(rule.requirement)
+                .is_ok_and(|requirements| matches_all(&requirements, &version))
+            {
+                Decision::Set
+            } else {
+                Decision::Skip(format!(
+                    "{gem} {version} does not match {}",
+                    style::value(rule.requirement)
+                ))
+            }
+        }
+    }
+}
+
+/// Sets launch environment defaults that depend on installed gem versions
+///
+/// Every rule's decision is printed. Values are defaults, so a value configured by the
+/// application takes precedence.
+pub(crate) fn launch_env_defaults(
+    mut bullet: Print<SubBullet<Stdout>>,
+    context: &BuildContext<RubyBuildpack>,
+    gem_list: &GemList,
+) -> libcnb::Result<Print<SubBullet<Stdout>>, RubyBuildpackError> {
+    let mut layer_env = LayerEnv::new();
+    for rule in LAUNCH_ENV_RULES {
+        let env_var = style::value(format!("{}={}", rule.key, rule.value));
+        match decide(rule, gem_list) {
+            Decision::Set => {
+                bullet = bullet.sub_bullet(format!(
+                    "Setting default {env_var} ({} {})",
+                    style::value(rule.gem),
+                    style::value(rule.requirement)
+                ));
+                layer_env = layer_env.chainable_insert(
+                    Scope::Launch,
+                    ModificationBehavior::Default,
+                    rule.key,
+                    rule.value,
+                );
+            }
+            Decision::Skip(reason) => {
+                bullet = bullet.sub_bullet(format!("Skipping {env_var} ({reason})"));
+            }
+        }
+    }
+
+    let layer_ref = context.uncached_layer(
+        layer_name!("launch_env_defaults"),
+        UncachedLayerDefinition {
+            build: false,
+            launch: true,
+        },
+    )?;
+    layer_ref.write_env(layer_env)?;
+
+    Ok(bullet)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::str::FromStr;
+
+    #[test]
+    fn test_decide() {
+        let rule = &LAUNCH_ENV_RULES[0];
+        let gem_list = |list: &str| GemList::from_str(list).unwrap();
+
+        assert_eq!(decide(rule, &gem_list("  * puma (6.4.3)\n")), Decision::Set);
+        assert!(matches!(
+            decide(rule, &gem_list("  * puma (5.6.9)\n")),
+            Decision::Skip(_)
+        ));
+        assert!(matches!(
+            decide(rule, &gem_list("  * rack (3.1.8)\n")),
+            Decision::Skip(_)
+        ));
+    }
+}
@@ -1,129 +1,183 @@
-use crate::rake_task_detect::RakeDetect;
+use crate::features::{Feature, Features};
+use crate::gem_list::GemList;
+use crate::layers::assets_digest_layer::{self, AssetsState};
+use crate::layers::build_logs_layer::{self, BuildLog};
+use crate::steps::asset_tasks::AssetTasks;
+use crate::telemetry::Telemetry;
 use crate::RubyBuildpack;
 use crate::RubyBuildpackError;
 use bullet_stream::state::SubBullet;
 use bullet_stream::{style, Print};
-use commons::cache::{mib, AppCache, CacheConfig, CacheError, CacheState, KeepPath, PathState};
+use commons::assets::{self, AssetPipeline};
+use commons::cache::CacheError;
+use commons::rake_task_detect::RakeDetect;
 use fun_run::{self, CommandWithName};
 use libcnb::build::BuildContext;
 use libcnb::Env;
 use std::io::Stdout;
 use std::process::Command;
 
+#[allow(clippy::too_many_arguments)]
 pub(crate) fn rake_assets_install(
     mut bullet: Print<SubBullet<Stdout>>,
     context: &BuildContext<RubyBuildpack>,
     env: &Env,
+    features: &Features,
+    gem_list: &GemList,
     rake_detect: &RakeDetect,
-) -> Result<Print<SubBullet<Stdout>>, RubyBuildpackError> {
+    asset_tasks: &AssetTasks,
+    telemetry: &mut Telemetry,
+) -> libcnb::Result<Print<SubBullet<Stdout>>, RubyBuildpackError> {
     let help = style::important("HELP");
-    let cases = asset_cases(rake_detect);
-    let rake_assets_precompile = style::value("rake assets:precompile");
+    let cases = asset_cases(rake_detect, asset_tasks);
+    let rake_assets_precompile = style::value(asset_tasks.command());
     let rake_assets_clean = style::value("rake assets:clean");
     let rake_detect_cmd = style::value("rake -P");
+    let pipeline = AssetPipeline::detect(|gem| gem_list.has(gem));
+    bullet = AssetPipeline::print(pipeline, bullet);
+    let build_caches = pipeline
+        .unwrap_or(AssetPipeline::Sprockets)
+        .build_caches(&context.app_dir);
 
     match cases {
         AssetCases::None => {
+            if let Some(origin) = asset_tasks.origin() {
+                bullet = bullet.sub_bullet(format!(
+                    "Skipping {rake_assets_precompile} (listed in {origin}, not found via {rake_detect_cmd})"
+                ));
+            }
             bullet = bullet.sub_bullet(format!(
                 "Skipping {rake_assets_clean} (task not found via {rake_detect_cmd})",
             )).sub_bullet(format!("{help} Enable cleaning assets by ensuring {rake_assets_clean} is present when running the detect command locally"));
+
+            if let Some((gem, task)) = css_build_task(gem_list, rake_detect) {
+                bullet = bullet.sub_bullet(format!(
+                    "Compiling stylesheets ({gem} gem found, {rake_assets_precompile} not found)",
+                    gem = style::value(gem),
+                ));
+
+                bullet = rake(bullet, context, env, &[task.to_string()], &["--trace"])?;
+            }
         }
         AssetCases::PrecompileOnly => {
             bullet = bullet.sub_bullet(
-                format!("Compiling assets without cache (Clean task not found via {rake_detect_cmd})"),
+                format!("Compiling assets without {public_assets} cache (Clean task not found via {rake_detect_cmd})", public_assets = style::value("public/assets")),
             ).sub_bullet(format!("{help} Enable caching by ensuring {rake_assets_clean} is present when running the detect command locally"));
 
-            let mut cmd = Command::new("rake");
-            cmd.args(["assets:precompile", "--trace"])
-                .env_clear()
-                .envs(env);
-
-            bullet
-                .stream_with(
-                    format!("Running {}", style::command(cmd.name())),
-                    |stdout, stderr| cmd.stream_output(stdout, stderr),
-                )
-                .map_err(|error| {
-                    fun_run::map_which_problem(error, &mut cmd, env.get("PATH").cloned())
-                })
-                .map_err(RubyBuildpackError::RakeAssetsPrecompileFailed)?;
+            // Only build state is cached, without `assets:clean` cached `public/assets` would grow forever
+            bullet = assets::with_caches(bullet, context, build_caches, cache_error, |bullet| {
+                let bullet = rake(bullet, context, env, asset_tasks.tasks(), &["--trace"])?;
+                check_manifest(bullet, context, features, pipeline, asset_tasks)
+            })?;
         }
         AssetCases::PrecompileAndClean => {
             bullet = bullet.sub_bullet(format!("Compiling assets with cache (detected {rake_assets_precompile} and {rake_assets_clean} via {rake_detect_cmd})"));
 
-            let cache_config = [
-                CacheConfig {
-                    path: context.app_dir.join("public").join("assets"),
-                    limit: mib(100),
-                    keep_path: KeepPath::Runtime,
+            let cache_config =
+                assets::output_cache_configs(&context.app_dir, |gem| gem_list.has(gem))
+                    .into_iter()
+                    .chain(build_caches);
+            bullet = assets::with_caches(
+                bullet,
+                context,
+                cache_config,
+                cache_error,
+                |mut bullet| {
+                    let (assets_state, assets_digest) =
+                        assets_digest_layer::handle(context, telemetry)?;
+                    match assets_state {
+                        AssetsState::Unchanged => {
+                            bullet = bullet.sub_bullet(format!(
+                            "Skipping {rake_assets_precompile} (no changes found in asset sources or platform env, compiled assets restored from cache)",
+                        )).sub_bullet(format!(
+                            "{help} To force asset compilation set {}",
+                            style::value(format!("{}=1", assets_digest_layer::SKIP_ASSETS_DIGEST_ENV_KEY))
+                        ));
+                        }
+                        AssetsState::Compile(reason) => {
+                            if !reason.is_empty() {
+                                bullet = bullet.sub_bullet(format!("Compiling assets: {reason}"));
+                            }
+                            bullet = rake(
+                                bullet,
+                                context,
+                                env,
+                                asset_tasks.tasks(),
+                                &["assets:clean", "--trace"],
+                            )?;
+                        }
+                    }
+                    assets_digest.save(context)?;
+
+                    check_manifest(bullet, context, features, pipeline, asset_tasks)
                 },
-                CacheConfig {
-                    path: context.app_dir.join("tmp").join("cache").join("assets"),
-                    limit: mib(100),
-                    keep_path: KeepPath::BuildOnly,
-                },
-            ];
-
-            let caches = cache_config
-                .into_iter()
-                .map(|config| AppCache::new_and_load(context, config))
-                .collect::<Result<Vec<AppCache>, CacheError>>()
-                .map_err(RubyBuildpackError::InAppDirCacheError)?;
-
-            for store in &caches {
-                let path = store.path().display();
-                bullet = bullet.sub_bullet(match store.cache_state() {
-                    CacheState::NewEmpty => format!("Creating cache for {path}"),
-                    CacheState::ExistsEmpty => format!("Loading (empty) cache for {path}"),
-                    CacheState::ExistsWithContents => format!("Loading cache for {path}"),
-                });
-            }
-
-            let mut cmd = Command::new("rake");
-            cmd.args(["assets:precompile", "assets:clean", "--trace"])
-                .env_clear()
-                .envs(env);
-
-            bullet
-                .stream_with(
-                    format!("Running {}", style::command(cmd.name())),
-                    |stdout, stderr| cmd.stream_output(stdout, stderr),
-                )
-                .map_err(|error| {
-                    fun_run::map_which_problem(error, &mut cmd, env.get("PATH").cloned())
-                })
-                .map_err(RubyBuildpackError::RakeAssetsPrecompileFailed)?;
-
-            for store in caches {
-                let path = store.path().display();
-
-                bullet = bullet.sub_bullet(match store.path_state() {
-                    PathState::Empty => format!("Storing cache for (empty) {path}"),
-                    PathState::HasFiles => format!("Storing cache for {path}"),
-                });
-
-                if let Some(removed) = store
-                    .save_and_clean()
-                    .map_err(RubyBuildpackError::InAppDirCacheError)?
-                {
-                    let path = store.path().display();
-                    let limit = store.limit();
-                    let removed_len = removed.files.len();
-                    let removed_size = removed.adjusted_bytes();
-
-                    bullet = bullet.sub_bullet(format!("Detected cache size exceeded (over {limit} limit by {removed_size}) for {path}"))
-                    .sub_bullet(
-                        format!("Removed {removed_len} files from the cache for {path}"),
-
-                    );
-                }
-            }
+            )?;
         }
     }
 
     Ok(bullet)
 }
 
+/// Prints the manifest written by `assets:precompile`, errors when it's missing or empty
+///
+/// Views look up fingerprinted file names in the manifest. Without it every asset helper
+/// falls back to an undigested path that 404s in production, long after the build succeeded.
+/// Only checked when an asset pipeline gem is installed and `assets:precompile` runs.
+/// Applications that move the manifest with `config.assets.prefix` or `config.assets.manifest`
+/// opt out with [`Feature::SkipAssetManifestCheck`].
+fn check_manifest(
+    bullet: Print<SubBullet<Stdout>>,
+    context: &BuildContext<RubyBuildpack>,
+    features: &Features,
+    pipeline: Option<AssetPipeline>,
+    asset_tasks: &AssetTasks,
+) -> libcnb::Result<Print<SubBullet<Stdout>>, RubyBuildpackError> {
+    let Some(pipeline) = pipeline.filter(|_| asset_tasks.precompiles()) else {
+        return Ok(bullet);
+    };
+    if features.enabled(Feature::SkipAssetManifestCheck) {
+        return Ok(bullet.sub_bullet(format!(
+            "Skipping asset manifest check ({} set)",
+            style::value("HEROKU_RUBY_FEATURE_SKIP_ASSET_MANIFEST_CHECK=1")
+        )));
+    }
+    let manifest = pipeline
+        .manifest(&context.app_dir.join("public").join("assets"))
+        .map_err(RubyBuildpackError::AssetManifestError)?;
+
+    Ok(manifest.print(bullet, &context.app_dir))
+}
+
+fn cache_error(error: CacheError) -> libcnb::Error<RubyBuildpackError> {
+    RubyBuildpackError::InAppDirCacheError(error).into()
+}
+
+/// Runs rake with the asset tasks and `extra` arguments, its output is kept in the build logs
+fn rake(
+    mut bullet: Print<SubBullet<Stdout>>,
+    context: &BuildContext<RubyBuildpack>,
+    env: &Env,
+    tasks: &[String],
+    extra: &[&str],
+) -> libcnb::Result<Print<SubBullet<Stdout>>, RubyBuildpackError> {
+    let mut cmd = Command::new("rake");
+    cmd.args(tasks).args(extra).env_clear().envs(env);
+    let mut cmd = cmd.named_fn(|cmd| display_name(cmd, env));
+
+    bullet
+        .stream_with(
+            format!("Running {}", style::command(cmd.name())),
+            |stdout, stderr| {
+                let (stdout, stderr) =
+                    build_logs_layer::tee(context, BuildLog::RakeAssets, stdout, stderr);
+                cmd.stream_output(stdout, stderr)
+            },
+        )
+        .map_err(|error| fun_run::map_which_problem(error, cmd.mut_cmd(), env.get("PATH").cloned()))
+        .map_err(RubyBuildpackError::RakeAssetsPrecompileFailed)?;
+    Ok(bullet)
+}
+
 #[derive(Clone, Debug)]
 enum AssetCases {
     None,
@@ -131,8 +185,8 @@ enum AssetCases {
     PrecompileAndClean,
 }
 
-fn asset_cases(rake: &RakeDetect) -> AssetCases {
-    if !rake.has_task("assets:precompile") {
+fn asset_cases(rake: &RakeDetect, asset_tasks: &AssetTasks) -> AssetCases {
+    if !asset_tasks.found(rake) {
         AssetCases::None
     } else if rake.has_task("assets:clean") {
         AssetCases::PrecompileAndClean
@@ -140,3 +194,42 @@ fn asset_cases(rake: &RakeDetect) -> AssetCases {
         AssetCases::PrecompileOnly
     }
 }
+
+/// Gems that compile stylesheets by enhancing `assets:precompile`, and the task that does the work
+pub(crate) const CSS_BUILD_GEMS: [(&str, &str); 2] = [
+    ("cssbundling-rails", "css:build"),
+    ("tailwindcss-rails", "tailwindcss:build"),
+];
+
+/// When `assets:precompile` is missing, stylesheets from these gems need their own task run
+fn css_build_task(gem_list: &GemList, rake: &RakeDetect) -> Option<(&'static str, &'static str)> {
+    CSS_BUILD_GEMS
+        .into_iter()
+        .find(|(gem, task)| gem_list.has(gem) && rake.has_task(task))
+}
+
+/// Displays the rake command with the environment that selects which
+/// environment assets are compiled for, such as `RAILS_ENV=staging`.
+fn display_name(cmd: &mut Command, env: &Env) -> String {
+    fun_run::display_with_env_keys(cmd, env, ["RAILS_ENV"])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_name_includes_rails_env() {
+        let mut env = Env::new();
+        env.insert("RAILS_ENV", "staging");
+        env.insert("SECRET_KEY_BASE", "abc123");
+
+        let mut cmd = Command::new("rake");
+        cmd.args(["assets:precompile", "--trace"]);
+
+        assert_eq!(
+            display_name(&mut cmd, &env),
+            "RAILS_ENV=\"staging\" rake assets:precompile --trace"
+        );
+    }
+}
@@ -0,0 +1,140 @@
+use crate::gem_list::GemList;
+use crate::warning::Warning;
+use crate::RubyBuildpack;
+use crate::RubyBuildpackError;
+use bullet_stream::state::SubBullet;
+use bullet_stream::{style, Print};
+use commons::gem_version::GemVersion;
+use core::str::FromStr;
+use fun_run::{self, CommandWithName};
+use indoc::formatdoc;
+use libcnb::build::BuildContext;
+use libcnb::Env;
+use std::io::Stdout;
+use std::process::Command;
+
+/// Set to `warn` to report autoloading violations as a warning, or `1` to fail the build
+pub(crate) const ZEITWERK_CHECK_ENV_KEY: &str = "HEROKU_RUBY_ZEITWERK_CHECK";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ZeitwerkCheck {
+    Warn,
+    Error,
+}
+
+/// Returns how the user opted into `rails zeitwerk:check`, if at all
+pub(crate) fn zeitwerk_check_mode(platform_env: &Env) -> Option<ZeitwerkCheck> {
+    let value = platform_env.get(ZEITWERK_CHECK_ENV_KEY)?;
+    match value.to_string_lossy().trim() {
+        "warn" => Some(ZeitwerkCheck::Warn),
+        "1" | "true" | "error" => Some(ZeitwerkCheck::Error),
+        _ => None,
+    }
+}
+
+/// Run `rails zeitwerk:check` to find autoloading problems
+///
+/// Rails eager loads application code in production. Files that do not define the
+/// constant Zeitwerk expects only fail once they're eager loaded, this step surfaces those
+/// problems during the build instead.
+///
+/// Returns a warning when the check fails in [`ZeitwerkCheck::Warn`] mode.
+pub(crate) fn zeitwerk_check(
+    bullet: Print<SubBullet<Stdout>>,
+    mode: ZeitwerkCheck,
+    context: &BuildContext<RubyBuildpack>,
+    env: &Env,
+    gem_list: &GemList,
+) -> Result<(Print<SubBullet<Stdout>>, Option<Warning>), RubyBuildpackError> {
+    let check = style::value("rails zeitwerk:check");
+    if !supports_zeitwerk(gem_list) {
+        return Ok((
+            bullet.sub_bullet(format!(
+                "Skipping {check} (requires {railties} 6.0 or higher)",
+                railties = style::value("railties")
+            )),
+            None,
+        ));
+    }
+
+    let mut cmd = Command::new("bin/rails");
+    cmd.arg("zeitwerk:check")
+        .current_dir(&context.app_dir)
+        .env_clear()
+        .envs(env);
+
+    let mut bullet = bullet;
+    let result = bullet
+        .stream_with(
+            format!("Running {}", style::command(cmd.name())),
+            |stdout, stderr| cmd.stream_output(stdout, stderr),
+        )
+        .map_err(|error| fun_run::map_which_problem(error, &mut cmd, env.get("PATH").cloned()));
+
+    match (result, mode) {
+        (Ok(_), _) => Ok((bullet, None)),
+        (Err(error), ZeitwerkCheck::Error) => Err(RubyBuildpackError::ZeitwerkCheckFailed(error)),
+        (Err(error), ZeitwerkCheck::Warn) => Ok((
+            bullet,
+            Some(Warning {
+                code: "zeitwerk_check",
+                title: "Autoloading problems found",
+                body: formatdoc! {"
+                    The command {check} failed. Your application may fail to boot in production
+                    where code is eager loaded.
+
+                    {error}
+
+                    To fail the build instead of warning, set {fail}.
+                    ",
+                    fail = style::value(format!("{ZEITWERK_CHECK_ENV_KEY}=1")),
+                },
+                url: None,
+            }),
+        )),
+    }
+}
+
+/// The `zeitwerk:check` task was introduced in Rails 6.0
+fn supports_zeitwerk(gem_list: &GemList) -> bool {
+    let minimum = GemVersion::from_str("6.0.0").expect("Internal error: invalid version");
+    gem_list
+        .version_for("railties")
+        .is_some_and(|version| version >= &minimum)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zeitwerk_check_mode() {
+        let mut env = Env::new();
+        assert_eq!(zeitwerk_check_mode(&env), None);
+
+        env.insert(ZEITWERK_CHECK_ENV_KEY, "warn");
+        assert_eq!(zeitwerk_check_mode(&env), Some(ZeitwerkCheck::Warn));
+
+        env.insert(ZEITWERK_CHECK_ENV_KEY, "1");
+        assert_eq!(zeitwerk_check_mode(&env), Some(ZeitwerkCheck::Error));
+
+        env.insert(ZEITWERK_CHECK_ENV_KEY, "0");
+        assert_eq!(zeitwerk_check_mode(&env), None);
+    }
+
+    #[test]
+    fn test_supports_zeitwerk() {
+        assert!(supports_zeitwerk(
+            &GemList::from_str("  * railties (6.0.0)").unwrap()
+        ));
+        assert!(supports_zeitwerk(
+            &GemList::from_str("  * railties (7.1.3)").unwrap()
+        ));
+        assert!(!supports_zeitwerk(
+            &GemList::from_str("  * railties (5.2.8)").unwrap()
+        ));
+        assert!(!supports_zeitwerk(
+            &GemList::from_str("  * rack (3.0.0)").unwrap()
+        ));
+    }
+}
@@ -7,51 +7,236 @@ pub(crate) struct TargetId {
     pub(crate) distro_version: String,
     pub(crate) cpu_architecture: String,
 }
-const ARCH_AWARE_VERSIONS: &[&str] = &["24.04"];
-const DISTRO_VERSION_STACK: &[(&str, &str, &str)] = &[
-    ("ubuntu", "20.04", "heroku-20"),
-    ("ubuntu", "22.04", "heroku-22"),
-    ("ubuntu", "24.04", "heroku-24"),
-];
+/// An operating system distribution parsed from a target's distro name and version
+///
+/// Ruby binaries are built for each known distribution. Supporting a new base image means
+/// adding a variant here and to [`Distro::KNOWN`], every other check is derived from it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Distro {
+    Ubuntu20,
+    Ubuntu22,
+    Ubuntu24,
+    /// A distribution without Ruby binaries of its own, such as a newer Ubuntu release
+    Other {
+        name: String,
+        version: String,
+    },
+}
+
+impl Distro {
+    /// Distributions with Ruby binaries, oldest first
+    pub(crate) const KNOWN: [Distro; 3] = [Distro::Ubuntu20, Distro::Ubuntu22, Distro::Ubuntu24];
+
+    pub(crate) fn parse(name: &str, version: &str) -> Self {
+        Self::KNOWN
+            .into_iter()
+            .find(|known| known.name() == name && known.version() == version)
+            .unwrap_or_else(|| Distro::Other {
+                name: name.to_owned(),
+                version: version.to_owned(),
+            })
+    }
+
+    pub(crate) fn name(&self) -> &str {
+        match self {
+            Distro::Ubuntu20 | Distro::Ubuntu22 | Distro::Ubuntu24 => "ubuntu",
+            Distro::Other { name, .. } => name,
+        }
+    }
+
+    pub(crate) fn version(&self) -> &str {
+        match self {
+            Distro::Ubuntu20 => "20.04",
+            Distro::Ubuntu22 => "22.04",
+            Distro::Ubuntu24 => "24.04",
+            Distro::Other { version, .. } => version,
+        }
+    }
+
+    /// The stack Ruby binaries are built for, `None` for distributions without binaries
+    pub(crate) fn stack(&self) -> Option<&'static str> {
+        match self {
+            Distro::Ubuntu20 => Some("heroku-20"),
+            Distro::Ubuntu22 => Some("heroku-22"),
+            Distro::Ubuntu24 => Some("heroku-24"),
+            Distro::Other { .. } => None,
+        }
+    }
+
+    fn from_stack(stack: &str) -> Option<Self> {
+        Self::KNOWN
+            .into_iter()
+            .find(|known| known.stack() == Some(stack))
+    }
+
+    /// True when Ruby binaries are built for more than one CPU architecture
+    pub(crate) fn is_arch_aware(&self) -> bool {
+        matches!(self, Distro::Ubuntu24)
+    }
+
+    /// CPU architectures Ruby binaries are built for
+    pub(crate) fn architectures(&self) -> &'static [&'static str] {
+        match self {
+            Distro::Ubuntu24 => &["amd64", "arm64"],
+            Distro::Ubuntu20 | Distro::Ubuntu22 => &["amd64"],
+            Distro::Other { .. } => &[],
+        }
+    }
+
+    /// The Heroku builder whose base image is this distribution, such as `heroku/builder:24`
+    pub(crate) fn builder(&self) -> Option<String> {
+        self.stack()
+            .and_then(|stack| stack.strip_prefix("heroku-"))
+            .map(|version| format!("heroku/builder:{version}"))
+    }
+
+    /// True for every Ubuntu release, including ones without binaries
+    pub(crate) fn is_ubuntu(&self) -> bool {
+        self.name() == "ubuntu"
+    }
+
+    /// The known distribution whose binaries run on this one
+    ///
+    /// Known distributions are compatible with themselves. A newer release of a known
+    /// distribution is compatible with the newest known release older than it, as binaries
+    /// built for an older release usually run on a newer one. Unknown distributions and
+    /// unknown older releases have no compatible distribution.
+    pub(crate) fn compatible(&self) -> Option<Distro> {
+        let Distro::Other { name, version } = self else {
+            return Some(self.clone());
+        };
+        let requested = parse_distro_version(version)?;
+        Self::KNOWN
+            .into_iter()
+            .filter(|known| known.name() == name)
+            .filter_map(|known| parse_distro_version(known.version()).map(|parsed| (parsed, known)))
+            .filter(|(parsed, _)| *parsed < requested)
+            .max_by(|(a, _), (b, _)| a.cmp(b))
+            .map(|(_, known)| known)
+    }
+}
+
+impl Display for Distro {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.name(), self.version())
+    }
+}
 
 #[derive(Debug, thiserror::Error)]
 pub(crate) enum TargetIdError {
-    #[error("Distro name and version '{0}-{1}' is not supported. Must be one of: {options}", options = DISTRO_VERSION_STACK.iter().map(|&(name, version, _)| format!("'{name}-{version}'")).collect::<Vec<_>>().join(", "))]
+    #[error("Distro name and version '{0}-{1}' is not supported. Must be one of: {options}", options = Distro::KNOWN.iter().map(|known| format!("'{}-{}'", known.name(), known.version())).collect::<Vec<_>>().join(", "))]
     UnknownDistroNameVersionCombo(String, String),
 
-    #[error("Cannot convert stack name '{0}' into a target OS. Must be one of: {options}", options = DISTRO_VERSION_STACK.iter().map(|&(_, _, stack)| format!("'{stack}'")).collect::<Vec<_>>().join(", "))]
+    #[error("Cannot convert stack name '{0}' into a target OS. Must be one of: {options}", options = Distro::KNOWN.iter().filter_map(Distro::stack).map(|stack| format!("'{stack}'")).collect::<Vec<_>>().join(", "))]
     UnknownStack(String),
+
+    #[error("CPU architecture '{0}' is not supported. Must be one of: {options}", options = known_architectures().iter().map(|arch| format!("'{arch}'")).collect::<Vec<_>>().join(", "))]
+    UnsupportedArchitecture(String),
+}
+
+/// CPU architectures with Ruby binaries on at least one distribution
+fn known_architectures() -> Vec<&'static str> {
+    let mut architectures = Distro::KNOWN
+        .iter()
+        .flat_map(|distro| distro.architectures().iter().copied())
+        .collect::<Vec<_>>();
+    architectures.sort_unstable();
+    architectures.dedup();
+    architectures
+}
+
+/// Builders with Ruby binaries, newest first, for example
+/// `heroku/builder:24 (ubuntu 24.04, amd64 and arm64)`
+pub(crate) fn supported_builders() -> Vec<String> {
+    Distro::KNOWN
+        .iter()
+        .rev()
+        .filter_map(|distro| {
+            distro.builder().map(|builder| {
+                format!(
+                    "{builder} ({distro}, {architectures})",
+                    architectures = distro.architectures().join(" and ")
+                )
+            })
+        })
+        .collect()
+}
+
+/// The stack that Ruby binaries are built for that matches a target
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct StackMatch {
+    pub(crate) stack: String,
+    pub(crate) distro: Distro,
+    /// False when the target is a newer version of a known distribution that the
+    /// buildpack does not know about yet
+    pub(crate) exact: bool,
 }
 
 impl TargetId {
+    pub(crate) fn distro(&self) -> Distro {
+        Distro::parse(&self.distro_name, &self.distro_version)
+    }
+
     pub(crate) fn is_arch_aware(&self) -> bool {
-        ARCH_AWARE_VERSIONS.contains(&self.distro_version.as_str())
-    }
-
-    pub(crate) fn stack_name(&self) -> Result<String, TargetIdError> {
-        DISTRO_VERSION_STACK
-            .iter()
-            .find(|&&(name, version, _)| name == self.distro_name && version == self.distro_version)
-            .map(|&(_, _, stack)| stack.to_owned())
-            .ok_or_else(|| {
-                TargetIdError::UnknownDistroNameVersionCombo(
-                    self.distro_name.clone(),
-                    self.distro_version.clone(),
-                )
+        self.stack_match()
+            .is_ok_and(|found| found.distro.is_arch_aware())
+    }
+
+    /// Finds the stack to use for this target
+    ///
+    /// See [`Distro::compatible`] for how newer releases of a known distribution are matched.
+    pub(crate) fn stack_match(&self) -> Result<StackMatch, TargetIdError> {
+        let distro = self.distro();
+        distro
+            .compatible()
+            .and_then(|found| {
+                found.stack().map(|stack| StackMatch {
+                    stack: stack.to_owned(),
+                    exact: found == distro,
+                    distro: found,
+                })
             })
+            .ok_or_else(|| self.unknown())
+    }
+
+    /// Errors when no distribution has Ruby binaries for the CPU architecture
+    ///
+    /// Binaries may still be missing for this combination of architecture and distribution,
+    /// that's reported with the requested Ruby version when it's installed.
+    pub(crate) fn check_architecture(&self) -> Result<(), TargetIdError> {
+        if known_architectures().contains(&self.cpu_architecture.as_str()) {
+            Ok(())
+        } else {
+            Err(TargetIdError::UnsupportedArchitecture(
+                self.cpu_architecture.clone(),
+            ))
+        }
     }
 
     pub(crate) fn from_stack(stack_id: &str) -> Result<Self, TargetIdError> {
-        DISTRO_VERSION_STACK
-            .iter()
-            .find(|&&(_, _, stack)| stack == stack_id)
-            .map(|&(name, version, _)| TargetId {
+        Distro::from_stack(stack_id)
+            .map(|distro| TargetId {
                 cpu_architecture: String::from("amd64"),
-                distro_name: name.to_owned(),
-                distro_version: version.to_owned(),
+                distro_name: distro.name().to_owned(),
+                distro_version: distro.version().to_owned(),
             })
             .ok_or_else(|| TargetIdError::UnknownStack(stack_id.to_owned()))
     }
+
+    fn unknown(&self) -> TargetIdError {
+        TargetIdError::UnknownDistroNameVersionCombo(
+            self.distro_name.clone(),
+            self.distro_version.clone(),
+        )
+    }
+}
+
+/// Parses versions such as `24.04` into comparable numeric segments
+fn parse_distro_version(version: &str) -> Option<Vec<u32>> {
+    version
+        .split('.')
+        .map(|segment| segment.parse().ok())
+        .collect()
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
@@ -60,6 +245,12 @@ pub(crate) struct OsDistribution {
     pub(crate) version: String,
 }
 
+impl OsDistribution {
+    pub(crate) fn distro(&self) -> Distro {
+        Distro::parse(&self.name, &self.version)
+    }
+}
+
 impl Display for OsDistribution {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "{} {}", self.name, self.version)
@@ -71,10 +262,65 @@ mod test {
     use super::*;
 
     #[test]
-    fn test_arch_aware_versions_are_also_known_as_a_stack() {
-        for version in ARCH_AWARE_VERSIONS {
-            assert!(DISTRO_VERSION_STACK.iter().any(|&(_, v, _)| &v == version));
+    fn test_distro_parse() {
+        for known in Distro::KNOWN {
+            assert_eq!(Distro::parse(known.name(), known.version()), known);
+            assert!(known.stack().is_some());
+            assert_eq!(Distro::from_stack(known.stack().unwrap()), Some(known));
+        }
+        assert_eq!(
+            Distro::parse("ubuntu", "26.04"),
+            Distro::Other {
+                name: String::from("ubuntu"),
+                version: String::from("26.04")
+            }
+        );
+        assert_eq!(Distro::Ubuntu24.to_string(), "ubuntu 24.04");
+    }
+
+    #[test]
+    fn test_distro_compatible() {
+        assert_eq!(Distro::Ubuntu22.compatible(), Some(Distro::Ubuntu22));
+        assert_eq!(
+            Distro::parse("ubuntu", "26.04").compatible(),
+            Some(Distro::Ubuntu24)
+        );
+        assert_eq!(
+            Distro::parse("ubuntu", "23.10").compatible(),
+            Some(Distro::Ubuntu22)
+        );
+        assert_eq!(Distro::parse("ubuntu", "18.04").compatible(), None);
+        assert_eq!(Distro::parse("ubuntu", "noble").compatible(), None);
+        assert_eq!(Distro::parse("alpine", "3.20").compatible(), None);
+        assert!(Distro::parse("ubuntu", "26.04").is_ubuntu());
+        assert!(!Distro::parse("alpine", "3.20").is_ubuntu());
+    }
+
+    #[test]
+    fn test_supported() {
+        for distro in Distro::KNOWN {
+            assert!(distro.builder().is_some());
+            assert!(distro.architectures().contains(&"amd64"));
+            assert_eq!(distro.is_arch_aware(), distro.architectures().len() > 1);
         }
+        assert_eq!(
+            supported_builders().first().map(String::as_str),
+            Some("heroku/builder:24 (ubuntu 24.04, amd64 and arm64)")
+        );
+
+        let target = |arch: &str| TargetId {
+            cpu_architecture: String::from(arch),
+            distro_name: String::from("ubuntu"),
+            distro_version: String::from("22.04"),
+        };
+        assert!(target("arm64").check_architecture().is_ok());
+        assert_eq!(
+            target("ppc64le")
+                .check_architecture()
+                .unwrap_err()
+                .to_string(),
+            "CPU architecture 'ppc64le' is not supported. Must be one of: 'amd64', 'arm64'"
+        );
     }
 
     #[test]
@@ -86,8 +332,9 @@ mod test {
                 distro_name: String::from("ubuntu"),
                 distro_version: String::from("20.04"),
             }
-            .stack_name()
+            .stack_match()
             .unwrap()
+            .stack
         );
 
         assert_eq!(
@@ -97,9 +344,40 @@ mod test {
                 distro_name: String::from("ubuntu"),
                 distro_version: String::from("22.04"),
             }
-            .stack_name()
+            .stack_match()
             .unwrap()
+            .stack
+        );
+    }
+
+    #[test]
+    fn test_stack_match() {
+        let target = |name: &str, version: &str| TargetId {
+            cpu_architecture: String::from("arm64"),
+            distro_name: String::from(name),
+            distro_version: String::from(version),
+        };
+
+        assert_eq!(
+            target("ubuntu", "22.04").stack_match().unwrap(),
+            StackMatch {
+                stack: String::from("heroku-22"),
+                distro: Distro::Ubuntu22,
+                exact: true,
+            }
+        );
+        assert_eq!(
+            target("ubuntu", "26.04").stack_match().unwrap(),
+            StackMatch {
+                stack: String::from("heroku-24"),
+                distro: Distro::Ubuntu24,
+                exact: false,
+            }
         );
+        assert!(target("ubuntu", "26.04").is_arch_aware());
+        assert!(target("ubuntu", "18.04").stack_match().is_err());
+        assert!(target("alpine", "3.20").stack_match().is_err());
+        assert!(target("ubuntu", "noble").stack_match().is_err());
     }
 
     #[test]
@@ -0,0 +1,151 @@
+//! Record counters about each build in the CNB store
+//!
+//! Counters (gems installed, cache hits and misses, bytes downloaded) and step durations are
+//! written to `store.toml` under `[metadata.telemetry]`. The store is restored on the next build,
+//! so the difference from the prior build is written alongside the current values. Platform
+//! analytics can read these values without parsing the build output.
+use crate::step_timings::StepTimings;
+use libcnb::data::store::Store;
+use libcnb::layer::LayerState;
+use std::collections::BTreeMap;
+
+const STORE_KEY: &str = "telemetry";
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct Telemetry {
+    counters: BTreeMap<&'static str, i64>,
+}
+
+impl Telemetry {
+    /// Sets a counter, replacing any prior value
+    pub(crate) fn set(&mut self, name: &'static str, value: impl TryInto<i64>) {
+        self.counters
+            .insert(name, value.try_into().unwrap_or(i64::MAX));
+    }
+
+    /// Counts a cached layer, a restored layer is a cache hit and an empty one a miss
+    ///
+    /// Call once with the state of each cached layer.
+    pub(crate) fn record_layer<R, E>(&mut self, state: &LayerState<R, E>) {
+        let (hit, miss) = match state {
+            LayerState::Restored { .. } => (1, 0),
+            LayerState::Empty { .. } => (0, 1),
+        };
+        *self.counters.entry("cache_hits").or_insert(0) += hit;
+        *self.counters.entry("cache_misses").or_insert(0) += miss;
+    }
+
+    /// Writes counters, step durations, and the change in each counter since the prior build
+    pub(crate) fn write(&self, timings: &StepTimings, store: &mut Store) {
+        let previous = store
+            .metadata
+            .get(STORE_KEY)
+            .and_then(|telemetry| telemetry.get("counters"))
+            .and_then(toml::Value::as_table)
+            .cloned()
+            .unwrap_or_default();
+
+        let mut telemetry = toml::Table::new();
+        telemetry.insert(
+            String::from("counters"),
+            toml::Value::Table(
+                self.counters
+                    .iter()
+                    .map(|(name, value)| ((*name).to_string(), toml::Value::Integer(*value)))
+                    .collect(),
+            ),
+        );
+        telemetry.insert(
+            String::from("deltas"),
+            toml::Value::Table(deltas(&self.counters, &previous)),
+        );
+        telemetry.insert(
+            String::from("step_millis"),
+            toml::Value::Table(
+                timings
+                    .steps()
+                    .iter()
+                    .map(|(name, duration)| {
+                        (
+                            name.clone(),
+                            toml::Value::Integer(
+                                i64::try_from(duration.as_millis()).unwrap_or(i64::MAX),
+                            ),
+                        )
+                    })
+                    .collect(),
+            ),
+        );
+        store
+            .metadata
+            .insert(String::from(STORE_KEY), toml::Value::Table(telemetry));
+    }
+}
+
+/// The change in each counter that was also recorded by the prior build
+fn deltas(counters: &BTreeMap<&'static str, i64>, previous: &toml::Table) -> toml::Table {
+    counters
+        .iter()
+        .filter_map(|(name, value)| {
+            previous
+                .get(*name)
+                .and_then(toml::Value::as_integer)
+                .map(|prior| {
+                    (
+                        (*name).to_string(),
+                        toml::Value::Integer(value.saturating_sub(prior)),
+                    )
+                })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libcnb::layer::EmptyLayerCause;
+
+    #[test]
+    fn test_record_layer() {
+        let mut telemetry = Telemetry::default();
+        telemetry.record_layer(&LayerState::<(), ()>::Restored { cause: () });
+        assert_eq!(telemetry.counters.get("cache_hits"), Some(&1));
+        assert_eq!(telemetry.counters.get("cache_misses"), Some(&0));
+
+        telemetry.record_layer(&LayerState::<(), ()>::Empty {
+            cause: EmptyLayerCause::NewlyCreated,
+        });
+        telemetry.record_layer(&LayerState::<(), ()>::Empty {
+            cause: EmptyLayerCause::RestoredLayerAction { cause: () },
+        });
+        assert_eq!(telemetry.counters.get("cache_hits"), Some(&1));
+        assert_eq!(telemetry.counters.get("cache_misses"), Some(&2));
+    }
+
+    #[test]
+    fn test_write_records_deltas() {
+        let timings = StepTimings::start();
+        let mut store = Store::default();
+
+        let mut telemetry = Telemetry::default();
+        telemetry.set("gems_installed", 40_usize);
+        telemetry.write(&timings, &mut store);
+        let deltas = |store: &Store| store.metadata[STORE_KEY]["deltas"].clone();
+        assert_eq!(deltas(&store), toml::Value::Table(toml::Table::new()));
+
+        telemetry.set("gems_installed", 42_usize);
+        telemetry.set("bytes_downloaded", 1024_u64);
+        telemetry.write(&timings, &mut store);
+        assert_eq!(
+            store.metadata[STORE_KEY]["counters"]["gems_installed"],
+            toml::Value::Integer(42)
+        );
+        assert_eq!(
+            deltas(&store),
+            toml::Value::Table(toml::Table::from_iter([(
+                String::from("gems_installed"),
+                toml::Value::Integer(2)
+            )]))
+        );
+    }
+}
@@ -1,18 +1,25 @@
+use crate::diagnostics::Diagnostics;
+use crate::error_kind::ErrorKind;
+use crate::features::Feature;
+use crate::layers::ruby_install_layer::RubyInstallError;
+use crate::native_extension::{NativeExtensionFailure, SerialRun};
 use crate::{DetectError, RubyBuildpackError};
 use bullet_stream::{state::Bullet, state::SubBullet, style, Print};
-use fun_run::{CmdError, CommandWithName};
+use fun_run::CmdError;
 use indoc::formatdoc;
 use std::io::Stdout;
-use std::process::Command;
 const DEBUG_INFO_STR: &str = "Debug info";
 
 pub(crate) fn on_error(err: libcnb::Error<RubyBuildpackError>) {
     let output = Print::new(std::io::stdout()).without_header();
     let debug_info = style::important(DEBUG_INFO_STR);
     match cause(err) {
-        Cause::OurError(error) => log_our_error(output, error),
+        Cause::OurError(error) => {
+            let output = error_code(output, error.kind());
+            log_our_error(output, error);
+        }
         Cause::FrameworkError(error) =>
-            output
+            error_code(output, ErrorKind::FrameworkError)
             .bullet(&debug_info)
             .sub_bullet(error.to_string())
             .error(formatdoc! {"
@@ -29,7 +36,7 @@ pub(crate) fn on_error(err: libcnb::Error<RubyBuildpackError>) {
                 CLI. If you can reproduce the behavior locally and believe you've found a bug in the
                 buildpack or the framework please open an issue on the buildpack's GitHub repository.
             "}),
-    };
+    }
 }
 
 #[allow(clippy::too_many_lines)]
@@ -98,7 +105,99 @@ fn log_our_error(mut output: Print<Bullet<Stdout>>, error: RubyBuildpackError) {
                 debug using the above information and try again.
             "});
         }
-        RubyBuildpackError::MissingGemfileLock(path, error) => {
+        RubyBuildpackError::BuildPlanError(error) => {
+            output
+                .bullet(debug_info)
+                .sub_bullet(error.to_string())
+                .done()
+                .error(formatdoc! {"
+                    Error: Conflicting Ruby version requirements
+
+                    Another buildpack that ran before the Ruby buildpack requires a Ruby
+                    version that does not match the version detected for your application.
+
+                    Ensure the Ruby version in your `Gemfile.lock` satisfies the version
+                    required by the other buildpack, or update the other buildpack's
+                    configuration, and try again.
+
+                    Supported ruby versions:
+                    {ruby_versions_url}
+                "});
+        }
+        RubyBuildpackError::BuildStepsError(error) => {
+            output
+                .bullet(debug_info)
+                .sub_bullet(error.to_string())
+                .done()
+                .error(formatdoc! {"
+                    Error: Invalid build step configuration
+
+                    Build steps can be skipped with `HEROKU_RUBY_SKIP_STEPS` or with
+                    `skip_steps` in the `[com.heroku.buildpacks.ruby]` table of `project.toml`.
+                    The configuration names an unknown step, or skips a step that a step which
+                    still runs depends on.
+
+                    Valid steps, in the order they run, are `ruby_install`, `bundler`,
+                    `bundle_install`, `gem_detect`, and `assets`. Skipping a step requires
+                    skipping every step after it that depends on it.
+                "});
+        }
+        RubyBuildpackError::CacheDirsError(error) => {
+            output
+                .bullet(debug_info)
+                .sub_bullet(error.to_string())
+                .done()
+                .error(formatdoc! {"
+                    Error: Invalid cache directory configuration
+
+                    Directories to cache between builds are listed with `cache_dirs` in the
+                    `[com.heroku.buildpacks.ruby]` table of `project.toml`. Each entry needs a
+                    `path` relative to the application directory and can set a `limit_mib`:
+
+                    [[com.heroku.buildpacks.ruby.cache_dirs]]
+                    path = \"tmp/cache/webpacker\"
+                    limit_mib = 200
+
+                    Paths cannot leave the application directory, be listed twice, or be a
+                    directory the buildpack already caches (`public/assets`, `tmp/cache/assets`,
+                    and `tmp/cache/bootsnap`).
+                "});
+        }
+        RubyBuildpackError::AssetTasksError(error) => {
+            output
+                .bullet(debug_info)
+                .sub_bullet(error.to_string())
+                .done()
+                .error(formatdoc! {"
+                    Error: Invalid asset task configuration
+
+                    The rake tasks that compile assets can be set with a comma separated list in
+                    the `HEROKU_RUBY_ASSETS_TASKS` environment variable, or with `assets_tasks`
+                    in the `[com.heroku.buildpacks.ruby]` table of `project.toml`:
+
+                    [com.heroku.buildpacks.ruby]
+                    assets_tasks = [\"assets:precompile\"]
+
+                    List at least one task. Task names cannot contain whitespace or start with
+                    `-`. Remove the setting to run `rake assets:precompile`.
+                "});
+        }
+        RubyBuildpackError::RubyVersionFileError(error) => {
+            output
+                .bullet(debug_info)
+                .sub_bullet(error.to_string())
+                .done()
+                .error(formatdoc! {"
+                    Error: Cannot read Ruby version file
+
+                    The buildpack reads `.ruby-version`, `.tool-versions`, and `mise.toml` to
+                    determine which Ruby version to install when the `Gemfile.lock` does not
+                    specify one. One of these files exists but could not be read.
+
+                    Ensure the file is readable, or remove it, and try again.
+                "});
+        }
+        RubyBuildpackError::MissingGemfileLock(path, error, diagnostics) => {
             output = output
                 .bullet(format!(
                     "Could not find {}, details:",
@@ -113,7 +212,7 @@ fn log_our_error(mut output: Print<Bullet<Stdout>>, error: RubyBuildpackError) {
                         "{debug_info} Contents of the {} directory",
                         style::value(dir.to_string_lossy())
                     )),
-                    Command::new("ls").args(["la", &dir.to_string_lossy()]),
+                    &diagnostics,
                 );
             }
 
@@ -129,6 +228,130 @@ fn log_our_error(mut output: Print<Bullet<Stdout>>, error: RubyBuildpackError) {
                 {git_branch_url}
             "});
         }
+        RubyBuildpackError::RubyInstallError(
+            error @ RubyInstallError::NoBinaryForTarget { .. },
+        ) => {
+            let next_steps = if matches!(
+                &error,
+                RubyInstallError::NoBinaryForTarget { cpu_architecture, .. } if cpu_architecture != "amd64"
+            ) {
+                formatdoc! {"
+                    Binaries for CPU architectures other than `amd64` are only available on
+                    Ubuntu 24.04 based builders, and only for recent Ruby versions. Upgrade
+                    your Ruby version, or use an `amd64` builder and try again.
+                "}
+            } else {
+                formatdoc! {"
+                    Use a Ruby version that is supported on this distribution, or use a
+                    builder based on a different distribution and try again.
+                "}
+            };
+            let build_from_source = format!(
+                "`{}=1`",
+                crate::layers::ruby_install_layer::BUILD_FROM_SOURCE_ENV_KEY
+            );
+            let nearby = match &error {
+                RubyInstallError::NoBinaryForTarget {
+                    nearby,
+                    distro,
+                    cpu_architecture,
+                    ..
+                } if !nearby.is_empty() => {
+                    formatdoc! {"
+                        Nearby versions available for {cpu_architecture} on {distro}:
+
+                        {versions}
+
+                    ",
+                        versions = nearby.iter().map(|version| format!("- {version}")).collect::<Vec<_>>().join("\n"),
+                    }
+                }
+                _ => String::new(),
+            };
+            output
+                .bullet(debug_info)
+                .sub_bullet(error.to_string())
+                .error(formatdoc! {"
+                    Error: No Ruby binary available for this target
+
+                    The Ruby buildpack does not have a Ruby binary for the detected version,
+                    CPU architecture, and distribution. The build stopped before downloading.
+
+                    {nearby}{next_steps}
+                    To compile Ruby from source instead, set {build_from_source}.
+
+                    Supported ruby versions:
+                    {ruby_versions_url}
+                "});
+        }
+        RubyBuildpackError::RubyInstallError(
+            error @ RubyInstallError::MissingSharedLibraries { .. },
+        ) => {
+            let next_steps = match &error {
+                RubyInstallError::MissingSharedLibraries { packages, .. }
+                    if !packages.is_empty() =>
+                {
+                    formatdoc! {"
+                        Ensure the build and run images include the {packages} package(s), or use
+                        a builder whose images include them, and try again.
+                    ",
+                        packages = packages.iter().map(|p| format!("`{p}`")).collect::<Vec<_>>().join(", "),
+                    }
+                }
+                _ => formatdoc! {"
+                    Use a builder whose build and run images include these libraries and try again.
+                "},
+            };
+            output
+                .bullet(debug_info)
+                .sub_bullet(error.to_string())
+                .error(formatdoc! {"
+                    Error: Ruby is missing required system libraries
+
+                    The installed Ruby links against shared libraries that are not present on
+                    this image. Without them Ruby would fail to load extensions such as
+                    `openssl` or `psych` at runtime.
+
+                    {next_steps}
+                "});
+        }
+        RubyBuildpackError::RubyInstallError(RubyInstallError::TargetError(error)) => {
+            output
+                .bullet(debug_info)
+                .sub_bullet(error.to_string())
+                .error(formatdoc! {"
+                    Error: Unsupported operating system distribution
+
+                    The Ruby buildpack does not have Ruby binaries built for the distribution
+                    of the base image used by your builder, or for any older version of it.
+
+                    Use a builder based on a supported distribution and try again.
+                "});
+        }
+        RubyBuildpackError::UnsupportedTarget(error) => {
+            output
+                .bullet(debug_info)
+                .sub_bullet(error.to_string())
+                .error(formatdoc! {"
+                    Error: Unsupported builder
+
+                    The Ruby buildpack does not have Ruby binaries for the operating system
+                    distribution or CPU architecture of the base image used by your builder.
+
+                    Ruby binaries are available for these builders:
+
+                    {builders}
+
+                    Use one of these builders, for example with
+                    `pack build --builder heroku/builder:24`, and try again.
+                ",
+                    builders = crate::target_id::supported_builders()
+                        .iter()
+                        .map(|builder| format!("- {}", style::value(builder)))
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                });
+        }
         RubyBuildpackError::RubyInstallError(error) => {
             // Future:
             // - In the future use a manifest file to list if version is available on a different stack
@@ -145,13 +368,13 @@ fn log_our_error(mut output: Print<Bullet<Stdout>>, error: RubyBuildpackError) {
                     {ruby_versions_url}
                 "});
         }
-        RubyBuildpackError::GemInstallBundlerCommandError(error) => {
+        RubyBuildpackError::GemInstallBundlerCommandError(error, diagnostics) => {
             output = output
                 .bullet(&debug_info)
                 .sub_bullet(error.to_string())
                 .done();
 
-            output = debug_cmd(output.bullet(&debug_info), Command::new("gem").arg("env"));
+            output = debug_cmd(output.bullet(&debug_info), &diagnostics);
 
             output.error(formatdoc! {"
                 Error installing bundler
@@ -165,15 +388,48 @@ fn log_our_error(mut output: Print<Bullet<Stdout>>, error: RubyBuildpackError) {
                 Once all incidents have been resolved, please retry your build.
             "});
         }
-        RubyBuildpackError::BundleInstallCommandError(error) => {
-            // Future:
-            // - Grep error output for common things like using sqlite3, use classic buildpack
-            let local_command = local_command_debug(&error);
+        RubyBuildpackError::BundleInstallCommandError(error, _)
+            if is_frozen_lockfile_error(&cmd_output(&error)) =>
+        {
+            let update_gem = locked_gem(&cmd_output(&error))
+                .map(|gem| {
+                    format!(
+                        "\n- If a locked version can no longer be found, run {} locally.",
+                        style::command(format!("bundle update {gem}"))
+                    )
+                })
+                .unwrap_or_default();
             output
                 .bullet(&debug_info)
                 .sub_bullet(error.to_string())
                 .done()
                 .error(formatdoc! {"
+                    Error: Your `Gemfile.lock` does not match your `Gemfile`
+
+                    Gems are installed in frozen (deployment) mode, so the `Gemfile.lock` cannot be
+                    changed during the build. Bundler reported that the `Gemfile.lock` is out of date
+                    with the `Gemfile`, or that a locked gem version cannot be installed.
+
+                    To fix this:
+
+                    - Run `bundle install` locally and commit the updated `Gemfile.lock`.{update_gem}
+                    - If `bundle install` does not change the `Gemfile.lock`, check whether `BUNDLE_FROZEN`
+                      or `BUNDLE_DEPLOYMENT` are set locally, for example in `.bundle/config`. They can hide
+                      the mismatch locally. Frozen mode cannot be disabled for the build.
+
+                    Ensure all files are tracked in Git and that you’re pushing the correct branch:
+                    {git_branch_url}
+                "});
+        }
+        RubyBuildpackError::BundleInstallCommandError(error, diagnostics) => {
+            // Future:
+            // - Grep error output for common things like using sqlite3, use classic buildpack
+            let local_command = local_command_debug(&error);
+            output = output
+                .bullet(&debug_info)
+                .sub_bullet(error.to_string())
+                .done();
+            print_diagnostics(output, &diagnostics).error(formatdoc! {"
                     Error installing your applications's dependencies
 
                     Could not install gems to the system via bundler. Gems are dependencies
@@ -188,7 +444,91 @@ fn log_our_error(mut output: Print<Bullet<Stdout>>, error: RubyBuildpackError) {
                     Use the information above to debug further.
                 "});
         }
-        RubyBuildpackError::BundleInstallDigestError(path, error) => {
+        RubyBuildpackError::NativeExtensionError(error, failure, diagnostics) => {
+            let NativeExtensionFailure {
+                gem,
+                version,
+                command,
+                log,
+                serial,
+            } = *failure;
+            let serial = match serial {
+                SerialRun::Succeeded => formatdoc! {"
+                    When run again with {command} every gem installed, so the failure is likely
+                    caused by compiling in parallel. The log below is from the original run.
+                    Setting {makeflags} for your application may work around it.
+                ", command = style::command(&command), makeflags = style::value("MAKEFLAGS=-j1")},
+                SerialRun::Failed => format!(
+                    "The log below is from running {} so it's not interleaved with other gems.\n",
+                    style::command(&command)
+                ),
+                SerialRun::Incomplete => format!(
+                    "Running {} did not finish, the log below is from the original run.\n",
+                    style::command(&command)
+                ),
+            };
+            output = output
+                .bullet(&debug_info)
+                .sub_bullet(error.to_string())
+                .done()
+                .bullet(format!(
+                    "{} Native extension log for {}",
+                    style::important(DEBUG_INFO_STR),
+                    style::value(format!("{gem} {version}"))
+                ))
+                .sub_bullet(format!("\n{log}\n"))
+                .done();
+            print_diagnostics(output, &diagnostics).error(formatdoc! {"
+                Error compiling the native extension of {gem} {version}
+
+                Bundler could not compile the native extension of this gem. Native extensions
+                are written in C (or another compiled language) and are built when the gem is
+                installed.
+
+                {serial}
+                Common causes are:
+
+                - A system library the gem needs is not installed in the build image.
+                - The gem version does not support the installed Ruby version. Try updating it
+                  with `bundle update {gem}`.
+                - Build options the gem needs. Set them with `BUNDLE_BUILD__{upcase}`, see
+                  https://bundler.io/man/bundle-config.1.html#BUILD-OPTIONS.
+            ", upcase = gem.to_uppercase().replace('-', "___")});
+        }
+        RubyBuildpackError::BundleAddPlatformError(error) => {
+            let local_command = local_command_debug(&error);
+            output
+                .bullet(&debug_info)
+                .sub_bullet(error.to_string())
+                .done()
+                .error(formatdoc! {"
+                    Error adding the build platform to your `Gemfile.lock`
+
+                    The `Gemfile.lock` does not list the platform of this build and `{add_platform}=1`
+                    is set, but bundler could not add it.
+
+                    {local_command}
+
+                    Once it succeeds, commit the updated `Gemfile.lock` and try again.
+                ", add_platform = crate::layers::bundle_install_layer::ADD_PLATFORM_ENV_KEY});
+        }
+        RubyBuildpackError::AssetsDigestError(path, error) => {
+            output
+                .bullet(&debug_info)
+                .sub_bullet(error.to_string())
+                .done()
+                .error(formatdoc! {"
+                    Error generating asset digest
+
+                    An error occurred while reading {path}. To skip compiling assets when
+                    nothing changed, the Ruby buildpack converts your asset directories and
+                    asset related config files into a digest to use in cache invalidation.
+
+                    Ensure that the permissions on the files in your application directory are
+                    correct and that all symlinks correctly resolve.
+                ", path = style::value(path.to_string_lossy())});
+        }
+        RubyBuildpackError::BundleInstallDigestError(path, error, diagnostics) => {
             output = output
                 .bullet(&debug_info)
                 .sub_bullet(error.to_string())
@@ -200,7 +540,7 @@ fn log_our_error(mut output: Print<Bullet<Stdout>>, error: RubyBuildpackError) {
                         "{debug_info} Contents of the {} directory",
                         style::value(dir.to_string_lossy())
                     )),
-                    Command::new("ls").args(["la", &dir.to_string_lossy()]),
+                    &diagnostics,
                 );
             }
 
@@ -220,15 +560,15 @@ fn log_our_error(mut output: Print<Bullet<Stdout>>, error: RubyBuildpackError) {
                 HEROKU_SKIP_BUNDLE_DIGEST=1
             "});
         }
-        RubyBuildpackError::RakeDetectError(error) => {
+        RubyBuildpackError::RakeDetectError(error, diagnostics) => {
             // Future:
             // - Annotate with information on requiring test or development only gems in the Rakefile
             let local_command = local_command_debug(&error);
-            output
+            output = output
                 .bullet(debug_info)
                 .sub_bullet(error.to_string())
-                .done()
-                .error(formatdoc! {"
+                .done();
+            print_diagnostics(output, &diagnostics).error(formatdoc! {"
                     Error detecting rake tasks
 
                     The Ruby buildpack uses rake task information from your application to guide
@@ -255,6 +595,160 @@ fn log_our_error(mut output: Print<Bullet<Stdout>>, error: RubyBuildpackError) {
                     Use the information above to debug further.
                 "});
         }
+        RubyBuildpackError::AssetManifestError(error) => {
+            output
+                .bullet(debug_info)
+                .sub_bullet(error.to_string())
+                .done()
+                .error(formatdoc! {"
+                    Error: Asset manifest missing or empty
+
+                    `rake assets:precompile` finished, but the manifest that maps asset names to
+                    fingerprinted files is missing or lists no assets. Sprockets writes
+                    `public/assets/.sprockets-manifest-*.json` and propshaft writes
+                    `public/assets/.manifest.json`. Without it, pages that reference assets
+                    render with broken links in production.
+
+                    Run `RAILS_ENV=production rake assets:precompile` locally and check that the
+                    manifest lists your assets. Asset sources excluded by `.gitignore` or
+                    `.slugignore` are a common cause.
+
+                    If your application moves the manifest with `config.assets.prefix` or
+                    `config.assets.manifest`, or compiles no assets, skip this check by setting
+                    `HEROKU_RUBY_FEATURE_SKIP_ASSET_MANIFEST_CHECK=1`.
+                "});
+        }
+        RubyBuildpackError::JekyllBuildFailed(error) => {
+            let local_command = local_command_debug(&error);
+            output
+                .bullet(debug_info)
+                .sub_bullet(error.to_string())
+                .done()
+                .error(formatdoc! {"
+                    Error building Jekyll site
+
+                    An error occured while building the site via `jekyll build`. The build runs
+                    with `JEKYLL_ENV=production` unless `JEKYLL_ENV` is set.
+
+                    {local_command}
+
+                    Use the information above to debug further.
+                "});
+        }
+        RubyBuildpackError::BootCheckFailed(error) => {
+            let local_command = local_command_debug(&error);
+            output
+                .bullet(debug_info)
+                .sub_bullet(error.to_string())
+                .done()
+                .error(formatdoc! {"
+                    Error: Application failed to boot
+
+                    The boot check was enabled with `HEROKU_RUBY_FEATURE_BOOT_CHECK=1` and loading your
+                    application failed. An application that cannot boot during the build will
+                    crash when it is started.
+
+                    If the command exited with status 124, it did not finish booting before the
+                    timeout. Ensure your application does not connect to external services or
+                    wait for input while it is loading.
+
+                    {local_command}
+
+                    Use the information above to debug further. To skip this check, unset
+                    `HEROKU_RUBY_FEATURE_BOOT_CHECK`.
+                "});
+        }
+        RubyBuildpackError::BuildHookFailed(error) => {
+            let local_command = local_command_debug(&error);
+            output
+                .bullet(debug_info)
+                .sub_bullet(error.to_string())
+                .done()
+                .error(formatdoc! {"
+                    Error: Build hook failed
+
+                    Your application defines `bin/heroku-ruby-prebuild` or
+                    `bin/heroku-ruby-postbuild` and the script failed. The buildpack runs these
+                    scripts with the build environment when they exist.
+
+                    Ensure the script is executable (`chmod +x`), starts with a shebang line
+                    such as `#!/usr/bin/env bash`, and exits successfully.
+
+                    {local_command}
+
+                    Use the information above to debug further.
+                "});
+        }
+        RubyBuildpackError::JrubyWarmupFailed(error) => {
+            let local_command = local_command_debug(&error);
+            let warmup = Feature::JrubyWarmup.env_key();
+            output
+                .bullet(debug_info)
+                .sub_bullet(error.to_string())
+                .done()
+                .error(formatdoc! {"
+                    Error: JRuby warmup failed
+
+                    The `{warmup}=1` environment variable is set so the buildpack loaded your
+                    application during the build to record a JVM class data sharing archive,
+                    but the command failed.
+
+                    {local_command}
+
+                    Fix the error above, or unset `{warmup}` to skip the warmup, and try again.
+                "});
+        }
+        RubyBuildpackError::JrubyWarmupDigestError(path, error) => {
+            output
+                .bullet(debug_info)
+                .sub_bullet(error.to_string())
+                .done()
+                .error(formatdoc! {"
+                    Error generating JRuby warmup digest
+
+                    An error occurred while reading {path}. To reuse the JRuby warmup archive
+                    between builds, the Ruby buildpack converts the `Gemfile.lock` and warmup
+                    script into a digest to use in cache invalidation.
+
+                    Ensure that the permissions on the files in your application directory are
+                    correct and that all symlinks correctly resolve.
+                ", path = style::value(path.to_string_lossy())});
+        }
+        RubyBuildpackError::ZeitwerkCheckFailed(error) => {
+            let local_command = local_command_debug(&error);
+            output
+                .bullet(debug_info)
+                .sub_bullet(error.to_string())
+                .done()
+                .error(formatdoc! {"
+                    Error: Autoloading problems found
+
+                    The Zeitwerk check was enabled with `HEROKU_RUBY_ZEITWERK_CHECK=1` and
+                    `bin/rails zeitwerk:check` failed. Files that do not define the constant
+                    Zeitwerk expects will cause errors when your application is eager loaded
+                    in production.
+
+                    {local_command}
+
+                    Use the information above to debug further. To report these problems as
+                    a warning instead, set `HEROKU_RUBY_ZEITWERK_CHECK=warn`.
+                "});
+        }
+        RubyBuildpackError::BootsnapCacheError(error) => {
+            output
+                .bullet(debug_info)
+                .sub_bullet(error.to_string())
+                .done()
+                .error(formatdoc! {"
+                    Error clearing bootsnap cache
+
+                    The Ruby version changed since the last build so the bootsnap cache in
+                    `tmp/cache/bootsnap` must be cleared, and an error occurred while doing so.
+
+                    Ensure that the permissions on the files in your application directory are
+                    correct and that all symlinks correctly resolve.
+                "});
+        }
         RubyBuildpackError::InAppDirCacheError(error) => {
             // Future:
             // - Separate between failures in layer dirs or in app dirs, if we can isolate to an app dir we could debug more
@@ -273,19 +767,13 @@ fn log_our_error(mut output: Print<Bullet<Stdout>>, error: RubyBuildpackError) {
                     all symlinks correctly resolve.
                 "});
         }
-        RubyBuildpackError::GemListGetError(error) => {
+        RubyBuildpackError::GemListGetError(error, diagnostics) => {
             output = output
                 .bullet(&debug_info)
                 .sub_bullet(error.to_string())
                 .done();
 
-            output = debug_cmd(output.bullet(&debug_info), Command::new("gem").arg("env"));
-            output = debug_cmd(
-                output.bullet(&debug_info),
-                Command::new("bundle").arg("env"),
-            );
-
-            output.error(formatdoc! {"
+            print_diagnostics(output, &diagnostics).error(formatdoc! {"
                 Error detecting dependencies
 
                 The Ruby buildpack requires information about your application’s dependencies to
@@ -294,6 +782,59 @@ fn log_our_error(mut output: Print<Bullet<Stdout>>, error: RubyBuildpackError) {
                 Use the information above to debug further.
             "});
         }
+        RubyBuildpackError::DoctorInstallError(error) => {
+            output
+                .bullet(debug_info)
+                .sub_bullet(error.to_string())
+                .done()
+                .error(formatdoc! {"
+                    Error: Could not install the Ruby diagnostic tool
+
+                    An error occurred while installing the `heroku-ruby-doctor` executable
+                    and the buildpack cannot continue.
+                "});
+        }
+        RubyBuildpackError::BundleCheckInstallError(error) => {
+            output
+                .bullet(debug_info)
+                .sub_bullet(error.to_string())
+                .done()
+                .error(formatdoc! {"
+                    Error: Could not install the launch bundle check
+
+                    An error occurred while writing the script that runs `bundle check` when
+                    the container starts and the buildpack cannot continue.
+                "});
+        }
+        RubyBuildpackError::PathCheckInstallError(error) => {
+            output
+                .bullet(debug_info)
+                .sub_bullet(error.to_string())
+                .done()
+                .error(formatdoc! {"
+                    Error: Could not install the launch PATH check
+
+                    An error occurred while writing the script that checks the order of
+                    executables on the PATH when the container starts and the buildpack
+                    cannot continue.
+                "});
+        }
+        RubyBuildpackError::WritableTmpError(error) => {
+            output
+                .bullet(debug_info)
+                .sub_bullet(error.to_string())
+                .done()
+                .error(formatdoc! {"
+                    Error: Could not set up writable tmp directories
+
+                    The `{key}=1` environment variable is set, but an error occurred while
+                    replacing the application's `tmp/` directory with a link to `/tmp/app-tmp`
+                    or writing the launch script that creates it.
+
+                    Ensure the files in your application directory have the correct permissions
+                    and try again.
+                ", key = Feature::ReadOnlyWorkspace.env_key()});
+        }
         RubyBuildpackError::MetricsAgentError(error) => {
             output
                 .bullet(debug_info)
@@ -315,6 +856,13 @@ enum Cause {
     FrameworkError(libcnb::Error<RubyBuildpackError>),
 }
 
+/// Prints the stable code of the error for tooling that reads the build output
+fn error_code(output: Print<Bullet<Stdout>>, kind: ErrorKind) -> Print<Bullet<Stdout>> {
+    output
+        .bullet(format!("Error code {}", style::value(kind.code())))
+        .done()
+}
+
 fn cause(err: libcnb::Error<RubyBuildpackError>) -> Cause {
     match err {
         libcnb::Error::BuildpackError(err) => Cause::OurError(err),
@@ -333,27 +881,114 @@ fn local_command_debug(error: &CmdError) -> String {
     "}
 }
 
+/// Output captured from a command that ran, empty when it could not be started
+fn cmd_output(error: &CmdError) -> String {
+    match error {
+        CmdError::SystemError(_, _) => String::new(),
+        CmdError::NonZeroExitNotStreamed(output) | CmdError::NonZeroExitAlreadyStreamed(output) => {
+            format!("{}\n{}", output.stdout_lossy(), output.stderr_lossy())
+        }
+    }
+}
+
+/// Bundler refuses to change the `Gemfile.lock` in frozen or deployment mode
+fn is_frozen_lockfile_error(output: &str) -> bool {
+    [
+        "Your bundle is locked to",
+        "You are trying to install in deployment mode after changing",
+        "because frozen mode is set",
+        "The list of sources changed",
+    ]
+    .iter()
+    .any(|message| output.contains(message))
+}
+
+/// The gem name from bundler's `Your bundle is locked to <gem> (<version>)` message
+fn locked_gem(output: &str) -> Option<&str> {
+    output
+        .split_once("Your bundle is locked to ")
+        .and_then(|(_, rest)| rest.split_whitespace().next())
+}
+
 fn replace_app_path_with_relative(contents: impl AsRef<str>) -> String {
     let app_path_re = regex::Regex::new("/workspace/").expect("Internal error: regex");
 
     app_path_re.replace_all(contents.as_ref(), "./").to_string()
 }
 
-fn debug_cmd(mut log: Print<SubBullet<Stdout>>, command: &mut Command) -> Print<Bullet<Stdout>> {
-    let result = log.stream_with(
-        format!("Running debug command {}", style::command(command.name())),
-        |stdout, stderr| command.stream_output(stdout, stderr),
-    );
-    match result {
-        Ok(_) => log.done(),
-        Err(e) => log.sub_bullet(e.to_string()).done(),
+/// Prints the output of debug commands that ran when the error was created
+fn debug_cmd(log: Print<SubBullet<Stdout>>, diagnostics: &Diagnostics) -> Print<Bullet<Stdout>> {
+    if diagnostics.entries().is_empty() {
+        return log
+            .sub_bullet(format!(
+                "Skipping debug commands (disabled with {})",
+                style::value(format!("{}=1", Feature::DisableDiagnostics.env_key()))
+            ))
+            .done();
     }
+
+    diagnostics
+        .entries()
+        .iter()
+        .fold(log, |log, (name, output)| {
+            log.sub_bullet(format!(
+                "Debug command {}\n\n{output}\n",
+                style::command(name)
+            ))
+        })
+        .done()
+}
+
+/// Prints the diagnostics captured when the step failed
+fn print_diagnostics(
+    output: Print<Bullet<Stdout>>,
+    diagnostics: &Diagnostics,
+) -> Print<Bullet<Stdout>> {
+    if diagnostics.entries().is_empty() {
+        return output
+            .bullet(format!(
+                "{} System diagnostics",
+                style::important(DEBUG_INFO_STR)
+            ))
+            .sub_bullet(format!(
+                "Skipping (disabled with {})",
+                style::value(format!("{}=1", Feature::DisableDiagnostics.env_key()))
+            ))
+            .done();
+    }
+
+    diagnostics
+        .entries()
+        .iter()
+        .fold(
+            output.bullet(format!(
+                "{} System diagnostics",
+                style::important(DEBUG_INFO_STR)
+            )),
+            |log, (name, output)| log.sub_bullet(format!("{}\n\n{output}\n", style::command(name))),
+        )
+        .done()
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
 
+    #[test]
+    fn test_frozen_lockfile_error() {
+        let locked = "Your bundle is locked to rack (3.0.8) from rubygems repository https://rubygems.org/ or installed locally, but that version can no longer be found in that source.";
+        assert!(is_frozen_lockfile_error(locked));
+        assert_eq!(locked_gem(locked), Some("rack"));
+
+        let frozen = "The dependencies in your gemfile changed, but the lockfile can't be updated because frozen mode is set";
+        assert!(is_frozen_lockfile_error(frozen));
+        assert_eq!(locked_gem(frozen), None);
+
+        assert!(!is_frozen_lockfile_error(
+            "An error occurred while installing sqlite3 (1.7.3)"
+        ));
+    }
+
     #[test]
     fn test_relative_path() {
         let expected = r#"BUNDLE_DEPLOYMENT="1" BUNDLE_GEMFILE="./Gemfile" BUNDLE_WITHOUT="development:test" bundle install"#;
@@ -0,0 +1,140 @@
+//! Warnings printed during the build
+//!
+//! Every warning has a stable code. Users who have reviewed a warning and decided it does not
+//! apply can hide it by listing its code in `HEROKU_RUBY_SUPPRESS_WARNINGS`, for example
+//! `HEROKU_RUBY_SUPPRESS_WARNINGS=procfile,database_url`.
+use bullet_stream::state::Bullet;
+use bullet_stream::{style, Print};
+use libcnb::Env;
+use std::fmt;
+use std::io::Write;
+
+pub(crate) const SUPPRESS_WARNINGS_ENV_KEY: &str = "HEROKU_RUBY_SUPPRESS_WARNINGS";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Warning {
+    /// Stable identifier used to suppress the warning
+    pub(crate) code: &'static str,
+    pub(crate) title: &'static str,
+    pub(crate) body: String,
+    pub(crate) url: Option<&'static str>,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.title)?;
+        writeln!(f)?;
+        writeln!(f, "{}", self.body.trim_end())?;
+        if let Some(url) = self.url {
+            writeln!(f)?;
+            writeln!(f, "For more information:")?;
+            writeln!(f, "{}", style::url(url))?;
+        }
+        writeln!(f)?;
+        write!(
+            f,
+            "To hide this warning set {}",
+            style::value(format!("{SUPPRESS_WARNINGS_ENV_KEY}={}", self.code))
+        )
+    }
+}
+
+/// Prints warnings unless their code is suppressed
+#[derive(Debug, Default)]
+pub(crate) struct Warnings {
+    suppressed: Vec<String>,
+}
+
+impl Warnings {
+    /// Reads suppressed codes from `HEROKU_RUBY_SUPPRESS_WARNINGS` in the platform env
+    pub(crate) fn from_env(platform_env: &Env) -> Self {
+        Self {
+            suppressed: platform_env
+                .get(SUPPRESS_WARNINGS_ENV_KEY)
+                .map(|value| {
+                    value
+                        .to_string_lossy()
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|code| !code.is_empty())
+                        .map(String::from)
+                        .collect()
+                })
+                .unwrap_or_default(),
+        }
+    }
+
+    pub(crate) fn is_suppressed(&self, warning: &Warning) -> bool {
+        self.suppressed.iter().any(|code| code == warning.code)
+    }
+
+    pub(crate) fn print<W: Write>(
+        &self,
+        output: Print<Bullet<W>>,
+        warnings: impl IntoIterator<Item = Warning>,
+    ) -> Print<Bullet<W>> {
+        warnings
+            .into_iter()
+            .filter(|warning| !self.is_suppressed(warning))
+            .fold(output, |output, warning| {
+                output.warning(warning.to_string())
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn warning() -> Warning {
+        Warning {
+            code: "procfile",
+            title: "Detected a Procfile",
+            body: String::from("Body\n"),
+            url: Some("https://example.com"),
+        }
+    }
+
+    #[test]
+    fn test_display() {
+        let rendered = bullet_stream::strip_ansi(warning().to_string());
+        assert_eq!(
+            rendered,
+            "Detected a Procfile\n\nBody\n\nFor more information:\nhttps://example.com\n\nTo hide this warning set `HEROKU_RUBY_SUPPRESS_WARNINGS=procfile`"
+        );
+    }
+
+    #[test]
+    fn test_print() {
+        let mut env = Env::new();
+        env.insert(SUPPRESS_WARNINGS_ENV_KEY, "database_url");
+        let output = Print::new(Vec::new())
+            .h2("Heroku Ruby Buildpack")
+            .bullet("Default process detection")
+            .sub_bullet("Detected rack app")
+            .done();
+        let output = Warnings::from_env(&env).print(
+            output,
+            [
+                warning(),
+                Warning {
+                    code: "database_url",
+                    title: "Missing DATABASE_URL",
+                    body: String::from("Suppressed\n"),
+                    url: None,
+                },
+            ],
+        );
+
+        commons::assert_snapshot!("warnings_print", String::from_utf8_lossy(&output.done()));
+    }
+
+    #[test]
+    fn test_suppressed() {
+        let mut env = Env::new();
+        assert!(!Warnings::from_env(&env).is_suppressed(&warning()));
+
+        env.insert(SUPPRESS_WARNINGS_ENV_KEY, "database_url, procfile");
+        assert!(Warnings::from_env(&env).is_suppressed(&warning()));
+    }
+}
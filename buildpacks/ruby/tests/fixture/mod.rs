@@ -0,0 +1,390 @@
+//! Generate minimal applications for integration tests
+//!
+//! Static fixture directories pin a single Ruby and Bundler version, so testing a feature
+//! against several versions meant copying a directory and editing its `Gemfile.lock` by hand.
+//! A [`FixtureApp`] writes a `Gemfile`, `Gemfile.lock`, and the files a framework needs to a
+//! directory, with the Ruby and Bundler versions chosen by the test:
+//!
+//! ```ignore
+//! let app = FixtureApp::new(Framework::Sinatra)
+//!     .ruby_version("3.3.6")
+//!     .bundler_version("2.5.23")
+//!     .tempdir();
+//! TestRunner::default().build(BuildConfig::new("heroku/builder:24", app.path()), |context| {
+//!     // ...
+//! });
+//! ```
+//!
+//! Locked gems are pinned to versions known to resolve together, the lockfile is never
+//! generated by running `bundle lock`.
+use indoc::{formatdoc, indoc};
+use std::path::Path;
+
+/// The kind of application to generate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Framework {
+    /// A `Gemfile` with no gems, for testing Ruby and Bundler installation on their own
+    Bare,
+    /// A `config.ru` served by `puma` through `rackup`
+    Rack,
+    /// A classic style Sinatra application in `config.ru`
+    Sinatra,
+    /// The files the buildpack looks for in a Rails application: `config/application.rb`,
+    /// `bin/rails`, and a `Rakefile` with asset tasks
+    ///
+    /// Not a Rails application, neither `rails` nor `railties` is installed. Locking them
+    /// takes several dozen gems with native extensions. The gems are the same as
+    /// [`Framework::Rack`], so only steps driven by files and rake tasks are exercised.
+    RailsShaped,
+    /// [`Framework::RailsShaped`] with a `package.json` `build` script that `assets:precompile` runs,
+    /// like jsbundling-rails
+    Jsbundling,
+}
+
+/// A gem in the `GEM` section of the lockfile
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct LockedGem {
+    name: &'static str,
+    version: &'static str,
+    /// Requirements in lockfile format, such as `nio4r (~> 2.0)`
+    dependencies: &'static [&'static str],
+    /// Set for gems that publish a `java` platform gem, it's locked instead on JRuby
+    java: bool,
+}
+
+impl LockedGem {
+    const fn new(name: &'static str, version: &'static str) -> Self {
+        Self {
+            name,
+            version,
+            dependencies: &[],
+            java: false,
+        }
+    }
+
+    const fn depends_on(mut self, dependencies: &'static [&'static str]) -> Self {
+        self.dependencies = dependencies;
+        self
+    }
+
+    const fn java(mut self) -> Self {
+        self.java = true;
+        self
+    }
+}
+
+const NIO4R: LockedGem = LockedGem::new("nio4r", "2.7.4").java();
+const PUMA: LockedGem = LockedGem::new("puma", "6.5.0")
+    .depends_on(&["nio4r (~> 2.0)"])
+    .java();
+const RACK: LockedGem = LockedGem::new("rack", "3.1.8");
+const RACKUP: LockedGem = LockedGem::new("rackup", "2.2.1").depends_on(&["rack (>= 3)"]);
+const RAKE: LockedGem = LockedGem::new("rake", "13.2.1");
+
+const SINATRA: &[LockedGem] = &[
+    LockedGem::new("base64", "0.2.0"),
+    LockedGem::new("logger", "1.6.4"),
+    LockedGem::new("mustermann", "3.0.3").depends_on(&["ruby2_keywords (~> 0.0.1)"]),
+    LockedGem::new("rack-protection", "4.1.1").depends_on(&[
+        "base64 (>= 0.1.0)",
+        "logger (>= 1.6.0)",
+        "rack (>= 3.0.0, < 4)",
+    ]),
+    LockedGem::new("rack-session", "2.1.0").depends_on(&["base64 (>= 0.1.0)", "rack (>= 3.0.0)"]),
+    LockedGem::new("ruby2_keywords", "0.0.5"),
+    LockedGem::new("sinatra", "4.1.1").depends_on(&[
+        "logger (>= 1.6.0)",
+        "mustermann (~> 3.0)",
+        "rack (>= 3.0.0, < 4)",
+        "rack-protection (= 4.1.1)",
+        "rack-session (>= 2.0.0, < 3)",
+        "tilt (~> 2.0)",
+    ]),
+    LockedGem::new("tilt", "2.5.0"),
+];
+
+const CONFIG_RU: &str = r##"require 'json'
+
+ruby_version = `ruby -v`.strip
+which_ruby = `which ruby`.strip
+
+payload = %Q[{"ruby_version": "#{ruby_version}", "which_ruby": "#{which_ruby}"}]
+run proc {|env| [200, {'Content-Type' => 'text/plain'}, [payload]] }
+"##;
+
+const SINATRA_CONFIG_RU: &str = r#"require 'sinatra'
+
+get '/' do
+  "ruby_version: #{RUBY_VERSION}"
+end
+
+run Sinatra::Application
+"#;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct FixtureApp {
+    framework: Framework,
+    ruby_version: Option<String>,
+    jruby_version: Option<String>,
+    bundler_version: String,
+    gems: Vec<LockedGem>,
+    direct: Vec<&'static str>,
+}
+
+impl FixtureApp {
+    pub(crate) fn new(framework: Framework) -> Self {
+        let (direct, gems) = match framework {
+            Framework::Bare => (Vec::new(), Vec::new()),
+            Framework::Rack | Framework::RailsShaped | Framework::Jsbundling => (
+                vec!["puma", "rack", "rackup", "rake"],
+                vec![NIO4R, PUMA, RACK, RACKUP, RAKE],
+            ),
+            Framework::Sinatra => (
+                vec!["puma", "rackup", "rake", "sinatra"],
+                [NIO4R, PUMA, RACK, RACKUP, RAKE]
+                    .into_iter()
+                    .chain(SINATRA.iter().copied())
+                    .collect(),
+            ),
+        };
+        Self {
+            framework,
+            ruby_version: None,
+            jruby_version: None,
+            bundler_version: String::from("2.5.23"),
+            gems,
+            direct,
+        }
+    }
+
+    /// Pins the Ruby version in the `Gemfile` and the lockfile `RUBY VERSION`, otherwise the
+    /// buildpack's default version is installed
+    pub(crate) fn ruby_version(mut self, version: impl Into<String>) -> Self {
+        self.ruby_version = Some(version.into());
+        self
+    }
+
+    /// Runs on JRuby, `ruby_version` is the Ruby version the JRuby release implements
+    ///
+    /// Gems with a `java` platform release are locked to it. JRuby needs a JVM, build with
+    /// the `heroku/jvm` buildpack before this one.
+    pub(crate) fn jruby(
+        mut self,
+        ruby_version: impl Into<String>,
+        jruby_version: impl Into<String>,
+    ) -> Self {
+        self.ruby_version = Some(ruby_version.into());
+        self.jruby_version = Some(jruby_version.into());
+        self
+    }
+
+    /// The `BUNDLED WITH` version, defaults to `2.5.23`
+    pub(crate) fn bundler_version(mut self, version: impl Into<String>) -> Self {
+        self.bundler_version = version.into();
+        self
+    }
+
+    /// Writes the application to a new temporary directory
+    pub(crate) fn tempdir(&self) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        self.write(dir.path()).unwrap();
+        dir
+    }
+
+    /// Writes the application to `app_dir`, existing files are overwritten
+    pub(crate) fn write(&self, app_dir: &Path) -> Result<(), std::io::Error> {
+        fs_err::create_dir_all(app_dir)?;
+        fs_err::write(app_dir.join("Gemfile"), self.gemfile())?;
+        fs_err::write(app_dir.join("Gemfile.lock"), self.lockfile())?;
+
+        match self.framework {
+            Framework::Bare => {}
+            Framework::Rack => fs_err::write(app_dir.join("config.ru"), CONFIG_RU)?,
+            Framework::Sinatra => fs_err::write(app_dir.join("config.ru"), SINATRA_CONFIG_RU)?,
+            Framework::RailsShaped => write_rails(app_dir, "")?,
+            Framework::Jsbundling => {
+                write_rails(app_dir, "javascript:build")?;
+                fs_err::write(
+                    app_dir.join("package.json"),
+                    indoc! {r#"
+                        {
+                          "name": "app",
+                          "private": true,
+                          "scripts": {
+                            "build": "mkdir -p app/assets/builds && echo '// built' > app/assets/builds/application.js"
+                          }
+                        }
+                    "#},
+                )?;
+                fs_err::write(
+                    app_dir.join("package-lock.json"),
+                    indoc! {r#"
+                        {
+                          "name": "app",
+                          "lockfileVersion": 3,
+                          "requires": true,
+                          "packages": {
+                            "": {
+                              "name": "app"
+                            }
+                          }
+                        }
+                    "#},
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    fn gemfile(&self) -> String {
+        let mut gemfile = String::from("source \"https://rubygems.org\"\n\n");
+        match (&self.ruby_version, &self.jruby_version) {
+            (Some(ruby), Some(jruby)) => gemfile.push_str(&format!(
+                "ruby '{ruby}', engine: 'jruby', engine_version: '{jruby}'\n\n"
+            )),
+            (Some(ruby), None) => gemfile.push_str(&format!("ruby '{ruby}'\n\n")),
+            (None, _) => {}
+        }
+        for name in self.sorted_direct() {
+            gemfile.push_str(&format!("gem '{name}'\n"));
+        }
+        gemfile
+    }
+
+    fn lockfile(&self) -> String {
+        let java = self.jruby_version.is_some();
+        let mut gems = self.gems.clone();
+        gems.sort_by_key(|gem| gem.name);
+        gems.dedup();
+        let specs = gems
+            .iter()
+            .map(|gem| {
+                let platform = if java && gem.java { "-java" } else { "" };
+                let mut spec = format!("    {} ({}{platform})\n", gem.name, gem.version);
+                for dependency in gem.dependencies {
+                    spec.push_str(&format!("      {dependency}\n"));
+                }
+                spec
+            })
+            .collect::<String>();
+        let dependencies = self
+            .sorted_direct()
+            .iter()
+            .map(|name| format!("  {name}\n"))
+            .collect::<String>();
+        let ruby_version = match (&self.ruby_version, &self.jruby_version) {
+            (Some(ruby), Some(jruby)) => {
+                format!("RUBY VERSION\n   ruby {ruby}p0 (jruby {jruby})\n\n")
+            }
+            (Some(ruby), None) => format!("RUBY VERSION\n   ruby {ruby}\n\n"),
+            (None, _) => String::new(),
+        };
+
+        format!(
+            "GEM\n  remote: https://rubygems.org/\n  specs:\n{specs}\nPLATFORMS\n  {platform}\n\nDEPENDENCIES\n{dependencies}\n{ruby_version}BUNDLED WITH\n   {bundler}\n",
+            platform = if java { "java" } else { "ruby" },
+            bundler = self.bundler_version,
+        )
+    }
+
+    fn sorted_direct(&self) -> Vec<&'static str> {
+        let mut direct = self.direct.clone();
+        direct.sort_unstable();
+        direct.dedup();
+        direct
+    }
+}
+
+/// Writes a Rails shaped application, `assets:precompile` depends on `prerequisite` when set
+fn write_rails(app_dir: &Path, prerequisite: &str) -> Result<(), std::io::Error> {
+    fs_err::create_dir_all(app_dir.join("config"))?;
+    fs_err::create_dir_all(app_dir.join("bin"))?;
+    fs_err::write(app_dir.join("config.ru"), CONFIG_RU)?;
+    fs_err::write(
+        app_dir.join("config").join("application.rb"),
+        "# Rails application marker for the buildpack\n",
+    )?;
+    fs_err::write(
+        app_dir.join("bin").join("rails"),
+        "#!/usr/bin/env ruby\nabort \"The rails gem is not installed in this fixture\"\n",
+    )?;
+    crate::chmod_plus_x(&app_dir.join("bin").join("rails"))?;
+
+    let dependency = if prerequisite.is_empty() {
+        String::new()
+    } else {
+        format!(" => \"{prerequisite}\"")
+    };
+    fs_err::write(
+        app_dir.join("Rakefile"),
+        formatdoc! {r#"
+            STDOUT.sync = true
+
+            task "javascript:build" do
+              sh "npm run build"
+            end
+
+            task "assets:precompile"{dependency} do
+              puts "Compiled assets"
+            end
+
+            task "assets:clean" do
+              puts "Cleaned assets"
+            end
+        "#},
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lockfile() {
+        let app = FixtureApp::new(Framework::Rack)
+            .ruby_version("3.3.6")
+            .bundler_version("2.6.2");
+        assert_eq!(
+            app.lockfile(),
+            formatdoc! {"
+            GEM
+              remote: https://rubygems.org/
+              specs:
+                nio4r (2.7.4)
+                puma (6.5.0)
+                  nio4r (~> 2.0)
+                rack (3.1.8)
+                rackup (2.2.1)
+                  rack (>= 3)
+                rake (13.2.1)
+
+            PLATFORMS
+              ruby
+
+            DEPENDENCIES
+              puma
+              rack
+              rackup
+              rake
+
+            RUBY VERSION
+               ruby 3.3.6
+
+            BUNDLED WITH
+               2.6.2
+        "}
+        );
+
+        let jruby = FixtureApp::new(Framework::Rack).jruby("3.1.4", "9.4.8.0");
+        let lockfile = jruby.lockfile();
+        assert!(lockfile.contains("    puma (6.5.0-java)\n"), "{lockfile}");
+        assert!(lockfile.contains("PLATFORMS\n  java\n"), "{lockfile}");
+        assert!(
+            lockfile.contains("   ruby 3.1.4p0 (jruby 9.4.8.0)\n"),
+            "{lockfile}"
+        );
+        assert!(jruby
+            .gemfile()
+            .contains("ruby '3.1.4', engine: 'jruby', engine_version: '9.4.8.0'"));
+    }
+}
@@ -3,6 +3,9 @@
 // Required due to: https://github.com/rust-lang/rust-clippy/issues/11119
 #![allow(clippy::unwrap_used)]
 
+mod fixture;
+
+use fixture::{FixtureApp, Framework};
 use indoc::{formatdoc, indoc};
 use libcnb_test::{
     assert_contains, assert_contains_match, assert_empty, BuildConfig, BuildpackReference,
@@ -11,7 +14,7 @@ use libcnb_test::{
 use pretty_assertions::assert_eq;
 use regex::Regex;
 use std::os::unix::fs::PermissionsExt;
-use std::path::{Path, PathBuf};
+use std::path::Path;
 use std::thread;
 use std::time::{Duration, Instant};
 use ureq::Response;
@@ -62,17 +65,8 @@ fn test_migrating_metadata_or_layer_names() {
 #[ignore = "integration test"]
 #[allow(clippy::too_many_lines)]
 fn test_default_app_ubuntu20() {
-    let temp = tempfile::tempdir().unwrap();
+    let temp = FixtureApp::new(Framework::Rack).tempdir();
     let app_dir = temp.path();
-
-    copy_dir_all(
-        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
-            .join("tests")
-            .join("fixtures")
-            .join("default_ruby"),
-        app_dir,
-    )
-    .unwrap();
     let config = BuildConfig::new("heroku/builder:20", app_dir);
     TestRunner::default().build(
         config.clone(),
@@ -84,6 +78,7 @@ fn test_default_app_ubuntu20() {
                 r#"`BUNDLE_BIN="/layers/heroku_ruby/gems/bin" BUNDLE_CLEAN="1" BUNDLE_DEPLOYMENT="1" BUNDLE_GEMFILE="/workspace/Gemfile" BUNDLE_PATH="/layers/heroku_ruby/gems" BUNDLE_WITHOUT="development:test" bundle install`"#);
 
             assert_contains!(context.pack_stdout, "Installing puma");
+            assert_contains!(context.pack_stdout, "Setting default `PUMA_PERSISTENT_TIMEOUT=95`");
 
         // Check that at run-time:
         // - The correct env vars are set.
@@ -113,6 +108,7 @@ fn test_default_app_ubuntu20() {
                 LD_LIBRARY_PATH=/layers/heroku_ruby/binruby/lib
                 MALLOC_ARENA_MAX=2
                 PATH=/workspace/bin:/layers/heroku_ruby/bundler/bin:/layers/heroku_ruby/gems/bin:/layers/heroku_ruby/bundler/bin:/layers/heroku_ruby/binruby/bin:/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin
+                PUMA_PERSISTENT_TIMEOUT=95
                 RACK_ENV=production
                 RAILS_ENV=production
                 RAILS_LOG_TO_STDOUT=enabled
@@ -250,7 +246,8 @@ fn test_default_app_ubuntu22() {
 #[test]
 #[ignore = "integration test"]
 fn test_default_app_latest_distro() {
-    let config = amd_arm_builder_config("heroku/builder:24", "tests/fixtures/default_ruby");
+    let app = FixtureApp::new(Framework::Rack).tempdir();
+    let config = amd_arm_builder_config("heroku/builder:24", &app.path().to_string_lossy());
 
     TestRunner::default().build(
         config,
@@ -282,6 +279,11 @@ fn test_default_app_latest_distro() {
                         let server_logs = container.logs_now();
 
                         assert_contains!(server_logs.stdout, "Puma starting");
+                        // The launcher expanded `$(PORT)` from the container environment
+                        assert_contains!(
+                            server_logs.stdout,
+                            &format!("Listening on http://[::]:{TEST_PORT}")
+                        );
                         assert_empty!(server_logs.stderr);
 
                         assert_contains!(body, "ruby_version");
@@ -301,13 +303,39 @@ fn test_default_app_latest_distro() {
 #[test]
 #[ignore = "integration test"]
 fn test_jruby_app() {
+    let app_dir = FixtureApp::new(Framework::Bare)
+        .jruby("3.1.4", "9.4.8.0")
+        .tempdir();
+
+    let mut config = amd_arm_builder_config("heroku/builder:24", &app_dir.path().to_string_lossy());
+
+    TestRunner::default().build(
+        config
+        .buildpacks([
+            BuildpackReference::Other(String::from("heroku/jvm")),
+            BuildpackReference::CurrentCrate,
+        ]),
+        |context| {
+            println!("{}", context.pack_stdout);
+            assert_contains!(context.pack_stdout, "# Heroku Ruby Buildpack");
+            assert_contains!(
+                context.pack_stdout,
+                r#"`BUNDLE_BIN="/layers/heroku_ruby/gems/bin" BUNDLE_CLEAN="1" BUNDLE_DEPLOYMENT="1" BUNDLE_GEMFILE="/workspace/Gemfile" BUNDLE_PATH="/layers/heroku_ruby/gems" BUNDLE_WITHOUT="development:test" bundle install`"#
+            );
+            assert_contains!(context.pack_stdout, "Ruby version `3.1.4-jruby-9.4.8.0` from `Gemfile.lock`");
+            });
+}
+
+#[test]
+#[ignore = "integration test"]
+fn test_bundler_4_app() {
     let app_dir = tempfile::tempdir().unwrap();
     fs_err::write(
         app_dir.path().join("Gemfile"),
         r#"
         source "https://rubygems.org"
 
-        ruby '3.1.4', engine: 'jruby', engine_version: '9.4.8.0'
+        gem "rack"
     "#,
     )
     .unwrap();
@@ -318,32 +346,36 @@ fn test_jruby_app() {
 GEM
   remote: https://rubygems.org/
   specs:
+    rack (3.1.8)
+
 PLATFORMS
-  java
-RUBY VERSION
-   ruby 3.1.4p001 (jruby 9.4.8.0)
+  ruby
+  x86_64-linux
+
 DEPENDENCIES
+  rack
+
+RUBY VERSION
+   ruby 3.4.1p0
+
+BUNDLED WITH
+   4.0.1
 ",
     )
     .unwrap();
 
-    let mut config = amd_arm_builder_config("heroku/builder:24", &app_dir.path().to_string_lossy());
-
     TestRunner::default().build(
-        config
-        .buildpacks([
-            BuildpackReference::Other(String::from("heroku/jvm")),
-            BuildpackReference::CurrentCrate,
-        ]),
+        amd_arm_builder_config("heroku/builder:24", &app_dir.path().to_string_lossy()),
         |context| {
             println!("{}", context.pack_stdout);
-            assert_contains!(context.pack_stdout, "# Heroku Ruby Buildpack");
+            assert_contains!(context.pack_stdout, "Bundler version `4.0.1` from `Gemfile.lock`");
             assert_contains!(
                 context.pack_stdout,
-                r#"`BUNDLE_BIN="/layers/heroku_ruby/gems/bin" BUNDLE_CLEAN="1" BUNDLE_DEPLOYMENT="1" BUNDLE_GEMFILE="/workspace/Gemfile" BUNDLE_PATH="/layers/heroku_ruby/gems" BUNDLE_WITHOUT="development:test" bundle install`"#
+                r#"`BUNDLE_BIN="/layers/heroku_ruby/gems/bin" BUNDLE_CLEAN="1" BUNDLE_DEPLOYMENT="1" BUNDLE_FROZEN="1" BUNDLE_GEMFILE="/workspace/Gemfile" BUNDLE_PATH="/layers/heroku_ruby/gems" BUNDLE_WITHOUT="development:test" bundle install`"#
             );
-            assert_contains!(context.pack_stdout, "Ruby version `3.1.4-jruby-9.4.8.0` from `Gemfile.lock`");
-            });
+            assert_contains!(context.pack_stdout, "Installing rack 3.1.8");
+        },
+    );
 }
 
 #[test]
@@ -366,6 +398,72 @@ fn test_ruby_app_with_yarn_app() {
         );
 }
 
+#[test]
+#[ignore = "integration test"]
+fn test_sinatra_app() {
+    for ruby_version in ["3.2.6", "3.3.6"] {
+        let app = FixtureApp::new(Framework::Sinatra)
+            .ruby_version(ruby_version)
+            .tempdir();
+        TestRunner::default().build(
+            amd_arm_builder_config("heroku/builder:24", &app.path().to_string_lossy()),
+            |context| {
+                println!("{}", context.pack_stdout);
+                assert_contains!(context.pack_stdout, "Installing sinatra 4.1.1");
+                context.start_container(
+                    ContainerConfig::new()
+                        .env("PORT", TEST_PORT.to_string())
+                        .expose_port(TEST_PORT),
+                    |container| {
+                        let body = call_root_until_boot(&container, TEST_PORT)
+                            .unwrap()
+                            .into_string()
+                            .unwrap();
+                        assert_contains!(body, &format!("ruby_version: {ruby_version}"));
+                    },
+                );
+            },
+        );
+    }
+}
+
+#[test]
+#[ignore = "integration test"]
+fn test_rails_asset_tasks() {
+    let app = FixtureApp::new(Framework::RailsShaped).tempdir();
+    TestRunner::default().build(
+        amd_arm_builder_config("heroku/builder:24", &app.path().to_string_lossy()),
+        |context| {
+            println!("{}", context.pack_stdout);
+            assert_contains!(context.pack_stdout, "rake assets:precompile");
+            assert_contains!(context.pack_stdout, "Compiled assets");
+        },
+    );
+}
+
+#[test]
+#[ignore = "integration test"]
+fn test_jsbundling_app() {
+    let app = FixtureApp::new(Framework::Jsbundling).tempdir();
+    TestRunner::default().build(
+        amd_arm_builder_config("heroku/builder:24", &app.path().to_string_lossy()).buildpacks([
+            BuildpackReference::Other(String::from("heroku/nodejs-engine")),
+            BuildpackReference::Other(String::from("heroku/nodejs-npm-install")),
+            BuildpackReference::CurrentCrate,
+        ]),
+        |context| {
+            println!("{}", context.pack_stdout);
+            assert_contains!(context.pack_stdout, "Compiled assets");
+            assert_contains!(
+                context
+                    .run_shell_command("cat app/assets/builds/application.js")
+                    .stdout,
+                "// built"
+            );
+        },
+    );
+}
+
 #[test]
 #[ignore = "integration test"]
 fn test_barnes_app() {
@@ -491,19 +589,3 @@ fn chmod_plus_x(path: &Path) -> Result<(), std::io::Error> {
 
     fs_err::set_permissions(path, perms)
 }
-
-fn copy_dir_all(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> Result<(), std::io::Error> {
-    let src = src.as_ref();
-    let dst = dst.as_ref();
-    fs_err::create_dir_all(dst)?;
-    for entry in fs_err::read_dir(src)? {
-        let entry = entry?;
-        let ty = entry.file_type()?;
-        if ty.is_dir() {
-            copy_dir_all(entry.path(), dst.join(entry.file_name()))?;
-        } else {
-            fs_err::copy(entry.path(), dst.join(entry.file_name()))?;
-        }
-    }
-    Ok(())
-}
@@ -0,0 +1,409 @@
+//! Asset compilation shared by `heroku/ruby` and `heroku/ruby-assets`
+//!
+//! Both buildpacks detect the asset pipeline, cache the same directories, and check the
+//! manifest the same way. Only running the rake task is left to the caller, see [`with_caches`].
+use crate::cache::{
+    mib, AppCache, CacheConfig, CacheError, CacheState, Eviction, KeepPath, PathState,
+};
+use bullet_stream::state::SubBullet;
+use bullet_stream::{style, Print};
+use libcnb::build::BuildContext;
+use std::io::Stdout;
+use std::path::{Path, PathBuf};
+
+/// The Rails asset pipeline decides which directories hold build state worth caching
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssetPipeline {
+    /// Sprockets caches compiled fragments in `tmp/cache/assets`
+    Sprockets,
+    /// Propshaft only copies and digests files, it keeps no compilation cache
+    Propshaft,
+}
+
+impl AssetPipeline {
+    /// An application with both gems installed is usually migrating, Sprockets still compiles
+    pub fn detect(has_gem: impl Fn(&str) -> bool) -> Option<Self> {
+        if has_gem("sprockets") {
+            Some(AssetPipeline::Sprockets)
+        } else if has_gem("propshaft") {
+            Some(AssetPipeline::Propshaft)
+        } else {
+            None
+        }
+    }
+
+    /// Caches for state that's only needed while compiling
+    #[must_use]
+    pub fn build_caches(self, app_dir: &Path) -> Vec<CacheConfig> {
+        match self {
+            AssetPipeline::Sprockets => vec![CacheConfig {
+                path: app_dir.join("tmp").join("cache").join("assets"),
+                limit: mib(100),
+                keep_path: KeepPath::BuildOnly,
+                eviction: Eviction::Lru,
+            }],
+            AssetPipeline::Propshaft => Vec::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            AssetPipeline::Sprockets => "sprockets",
+            AssetPipeline::Propshaft => "propshaft",
+        }
+    }
+
+    /// Prints the asset pipeline in use, nothing when there is none
+    #[must_use]
+    pub fn print(
+        pipeline: Option<Self>,
+        bullet: Print<SubBullet<Stdout>>,
+    ) -> Print<SubBullet<Stdout>> {
+        match pipeline {
+            Some(AssetPipeline::Sprockets) => bullet.sub_bullet(format!(
+                "Using asset pipeline {} (gem found)",
+                style::value("sprockets")
+            )),
+            Some(AssetPipeline::Propshaft) => bullet.sub_bullet(format!(
+                "Using asset pipeline {} (gem found), {} is not cached because propshaft does not use it",
+                style::value("propshaft"),
+                style::value("tmp/cache/assets")
+            )),
+            None => bullet,
+        }
+    }
+
+    /// Finds the manifest in `public/assets` and counts the assets it lists
+    ///
+    /// Sprockets writes `.sprockets-manifest-<digest>.json`, or `manifest-<digest>.json` before
+    /// version 3, with assets under the `assets` key. Propshaft writes `.manifest.json` with
+    /// assets at the top level.
+    ///
+    /// # Errors
+    ///
+    /// Errors when the manifest is missing, cannot be read or parsed, or lists no assets.
+    pub fn manifest(self, public_assets: &Path) -> Result<AssetManifest, AssetManifestError> {
+        let missing = || AssetManifestError::Missing {
+            name: self.name(),
+            dir: public_assets.to_path_buf(),
+        };
+        let path = match self {
+            AssetPipeline::Sprockets => fs_err::read_dir(public_assets)
+                .map_err(|_| missing())?
+                .filter_map(Result::ok)
+                .filter(|entry| {
+                    let name = entry.file_name().to_string_lossy().to_string();
+                    (name.starts_with(".sprockets-manifest-") || name.starts_with("manifest-"))
+                        && Path::new(&name)
+                            .extension()
+                            .is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+                })
+                // The newest manifest is the one the last precompile wrote
+                .max_by_key(|entry| entry.metadata().and_then(|meta| meta.modified()).ok())
+                .map(|entry| entry.path())
+                .ok_or_else(missing)?,
+            AssetPipeline::Propshaft => {
+                let path = public_assets.join(".manifest.json");
+                if !path.is_file() {
+                    return Err(missing());
+                }
+                path
+            }
+        };
+
+        let contents =
+            fs_err::read_to_string(&path).map_err(|source| AssetManifestError::Read {
+                path: path.clone(),
+                source,
+            })?;
+        let json = serde_json::from_str::<serde_json::Value>(&contents).map_err(|source| {
+            AssetManifestError::Parse {
+                path: path.clone(),
+                source,
+            }
+        })?;
+        let assets = match self {
+            AssetPipeline::Sprockets => json.get("assets"),
+            AssetPipeline::Propshaft => Some(&json),
+        };
+        let count = assets
+            .and_then(serde_json::Value::as_object)
+            .map_or(0, serde_json::Map::len);
+
+        if count == 0 {
+            Err(AssetManifestError::Empty(path))
+        } else {
+            Ok(AssetManifest { path, count })
+        }
+    }
+}
+
+/// The manifest that maps logical asset paths to fingerprinted files
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssetManifest {
+    pub path: PathBuf,
+    pub count: usize,
+}
+
+impl AssetManifest {
+    /// Prints the manifest path, relative to the application, and the number of assets
+    #[must_use]
+    pub fn print(
+        &self,
+        bullet: Print<SubBullet<Stdout>>,
+        app_dir: &Path,
+    ) -> Print<SubBullet<Stdout>> {
+        bullet.sub_bullet(format!(
+            "Found asset manifest {path} ({count} {assets})",
+            path = style::value(
+                self.path
+                    .strip_prefix(app_dir)
+                    .unwrap_or(&self.path)
+                    .to_string_lossy()
+            ),
+            count = self.count,
+            assets = if self.count == 1 { "asset" } else { "assets" }
+        ))
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum AssetManifestError {
+    #[error("No {name} asset manifest found in {}", dir.display())]
+    Missing { name: &'static str, dir: PathBuf },
+
+    #[error("Cannot read asset manifest {}: {source}", path.display())]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Cannot parse asset manifest {}: {source}", path.display())]
+    Parse {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+
+    #[error("Asset manifest {} lists no assets", .0.display())]
+    Empty(PathBuf),
+}
+
+/// Directories, relative to the application, that asset tasks compile into and the gems
+/// that write to them
+///
+/// `public/assets` is written by sprockets and propshaft, `public/packs` by webpacker and
+/// shakapacker, `public/vite` by vite, and `app/assets/builds` by the jsbundling and
+/// cssbundling gems. It's inside of `app/assets` but is not an asset source.
+pub const OUTPUT_DIRS: [(&str, &[&str]); 4] = [
+    ("public/assets", &[]),
+    ("public/packs", &["webpacker", "shakapacker"]),
+    ("public/vite", &["vite_rails", "vite_ruby"]),
+    (
+        "app/assets/builds",
+        &[
+            "jsbundling-rails",
+            "cssbundling-rails",
+            "tailwindcss-rails",
+            "dartsass-rails",
+        ],
+    ),
+];
+
+/// Caches for the compiled output, they ship in the image
+///
+/// Only use them when `assets:clean` runs, otherwise the cached output grows forever.
+/// `public/assets` is always cached, other output directories when a gem that writes to
+/// them is installed.
+pub fn output_cache_configs(app_dir: &Path, has_gem: impl Fn(&str) -> bool) -> Vec<CacheConfig> {
+    OUTPUT_DIRS
+        .into_iter()
+        .filter(|(_, gems)| gems.is_empty() || gems.iter().any(|gem| has_gem(gem)))
+        .map(|(dir, _)| CacheConfig {
+            path: app_dir.join(dir),
+            limit: mib(100),
+            keep_path: KeepPath::Runtime,
+            eviction: Eviction::Lru,
+        })
+        .collect()
+}
+
+/// Loads the caches, runs `compile`, then stores and cleans the caches
+///
+/// Caches are only stored when `compile` succeeds.
+///
+/// # Errors
+///
+/// Errors when a cache cannot be loaded or stored, or `compile` errors.
+pub fn with_caches<B, E>(
+    mut bullet: Print<SubBullet<Stdout>>,
+    context: &BuildContext<B>,
+    configs: impl IntoIterator<Item = CacheConfig>,
+    cache_error: impl Fn(CacheError) -> E,
+    compile: impl FnOnce(Print<SubBullet<Stdout>>) -> Result<Print<SubBullet<Stdout>>, E>,
+) -> Result<Print<SubBullet<Stdout>>, E>
+where
+    B: libcnb::Buildpack,
+{
+    let caches = configs
+        .into_iter()
+        .map(|config| AppCache::new_and_load(context, config))
+        .collect::<Result<Vec<AppCache>, CacheError>>()
+        .map_err(&cache_error)?;
+    for store in &caches {
+        bullet = load_message(bullet, store);
+    }
+
+    bullet = compile(bullet)?;
+
+    for store in &caches {
+        bullet = save_and_clean(bullet, store).map_err(&cache_error)?;
+    }
+    Ok(bullet)
+}
+
+/// Prints the state of a cache after it's loaded
+#[must_use]
+pub fn load_message(
+    bullet: Print<SubBullet<Stdout>>,
+    store: &AppCache,
+) -> Print<SubBullet<Stdout>> {
+    let path = store.path().display();
+    bullet.sub_bullet(match store.cache_state() {
+        CacheState::NewEmpty => format!("Creating cache for {path}"),
+        CacheState::ExistsEmpty => format!("Loading (empty) cache for {path}"),
+        CacheState::ExistsWithContents => format!("Loading cache for {path}"),
+        CacheState::Cleared(reason) => format!("Clearing cache for {path} ({reason})"),
+    })
+}
+
+/// Stores a cache and prints what was removed to keep it under its limit
+///
+/// A cache with [`Eviction::All`] is cleared as a whole, so no removed files are listed.
+///
+/// # Errors
+///
+/// Errors when the cache cannot be stored or cleaned.
+pub fn save_and_clean(
+    mut bullet: Print<SubBullet<Stdout>>,
+    store: &AppCache,
+) -> Result<Print<SubBullet<Stdout>>, CacheError> {
+    let path = store.path().display();
+
+    bullet = bullet.sub_bullet(match store.path_state() {
+        PathState::Empty => format!("Storing cache for (empty) {path}"),
+        PathState::HasFiles => format!("Storing cache for {path}"),
+    });
+
+    if let Some(removed) = store.save_and_clean()? {
+        let limit = store.limit();
+        let removed_size = removed.adjusted_bytes();
+        if store.eviction() == Eviction::All {
+            return Ok(bullet.sub_bullet(format!(
+                "Cleared cache for {path} (over {limit} limit by {removed_size}), it will be rebuilt on the next build"
+            )));
+        }
+
+        bullet = bullet.sub_bullet(format!(
+            "Detected cache size exceeded (over {limit} limit by {removed_size}) for {path}"
+        ));
+        for group in store.removed_by_dir(&removed) {
+            bullet = bullet.sub_bullet(format!("Removed {group}"));
+        }
+    }
+
+    Ok(bullet)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_asset_pipeline() {
+        let has = |gems: &'static [&'static str]| move |gem: &str| gems.contains(&gem);
+        assert_eq!(AssetPipeline::detect(has(&["rails"])), None);
+        assert_eq!(
+            AssetPipeline::detect(has(&["propshaft"])),
+            Some(AssetPipeline::Propshaft)
+        );
+        assert_eq!(
+            AssetPipeline::detect(has(&["propshaft", "sprockets"])),
+            Some(AssetPipeline::Sprockets)
+        );
+        assert!(AssetPipeline::Propshaft
+            .build_caches(Path::new("/app"))
+            .is_empty());
+        assert_eq!(
+            AssetPipeline::Sprockets
+                .build_caches(Path::new("/app"))
+                .into_iter()
+                .map(|config| config.path)
+                .collect::<Vec<_>>(),
+            vec![Path::new("/app/tmp/cache/assets").to_path_buf()]
+        );
+    }
+
+    #[test]
+    fn test_output_cache_configs() {
+        assert_eq!(
+            output_cache_configs(Path::new("/app"), |gem| {
+                ["shakapacker", "cssbundling-rails"].contains(&gem)
+            })
+            .into_iter()
+            .map(|config| config.path)
+            .collect::<Vec<_>>(),
+            vec![
+                Path::new("/app/public/assets").to_path_buf(),
+                Path::new("/app/public/packs").to_path_buf(),
+                Path::new("/app/app/assets/builds").to_path_buf()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_asset_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        let assets = dir.path();
+        assert!(matches!(
+            AssetPipeline::Sprockets.manifest(&assets.join("missing")),
+            Err(AssetManifestError::Missing { .. })
+        ));
+        assert!(matches!(
+            AssetPipeline::Propshaft.manifest(assets),
+            Err(AssetManifestError::Missing { .. })
+        ));
+
+        let sprockets = assets.join(".sprockets-manifest-abc123.json");
+        fs_err::write(&sprockets, r#"{"files":{},"assets":{}}"#).unwrap();
+        assert!(matches!(
+            AssetPipeline::Sprockets.manifest(assets),
+            Err(AssetManifestError::Empty(path)) if path == sprockets
+        ));
+        fs_err::write(
+            &sprockets,
+            r#"{"files":{},"assets":{"application.css":"application-abc123.css"}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            AssetPipeline::Sprockets.manifest(assets).unwrap(),
+            AssetManifest {
+                path: sprockets,
+                count: 1
+            }
+        );
+
+        let propshaft = assets.join(".manifest.json");
+        fs_err::write(&propshaft, "not json").unwrap();
+        assert!(matches!(
+            AssetPipeline::Propshaft.manifest(assets),
+            Err(AssetManifestError::Parse { .. })
+        ));
+        fs_err::write(
+            &propshaft,
+            r#"{"application.css":"application-abc123.css","app.js":"app-def456.js"}"#,
+        )
+        .unwrap();
+        assert_eq!(AssetPipeline::Propshaft.manifest(assets).unwrap().count, 2);
+    }
+}
@@ -1,9 +1,11 @@
 mod app_cache;
+mod app_cache_collection;
 mod clean;
 mod config;
 mod error;
 
 pub use self::app_cache::{build, AppCache, CacheState, PathState};
-pub use self::clean::FilesWithSize;
-pub use self::config::{mib, CacheConfig, KeepPath};
+pub use self::app_cache_collection::AppCacheCollection;
+pub use self::clean::{FilesInDir, FilesWithSize};
+pub use self::config::{mib, CacheConfig, Eviction, KeepPath};
 pub use self::error::CacheError;
@@ -1,13 +1,15 @@
-use crate::cache::clean::{lru_clean, FilesWithSize};
-use crate::cache::{CacheConfig, CacheError, KeepPath};
+use crate::cache::clean::{clean, FilesInDir, FilesWithSize};
+use crate::cache::{CacheConfig, CacheError, Eviction, KeepPath};
 use byte_unit::{AdjustedByte, Byte, UnitType};
-use fs_extra::dir::CopyOptions;
 use libcnb::build::BuildContext;
 use libcnb::data::layer::LayerName;
-use libcnb::layer::{CachedLayerDefinition, InvalidMetadataAction, RestoredLayerAction};
+use libcnb::layer::{
+    CachedLayerDefinition, EmptyLayerCause, InvalidMetadataAction, LayerState, RestoredLayerAction,
+};
+use magic_migrate::{try_migrate_deserializer_chain, TryMigrate};
 use serde::{Deserialize, Serialize};
-use std::path::Path;
 use std::path::PathBuf;
+use std::path::{Component, Path};
 use walkdir::WalkDir;
 
 use tempfile as _;
@@ -32,7 +34,7 @@ use tempfile as _;
 ///
 ///# pub(crate) struct HelloWorldBuildpack;
 ///
-///  use commons::cache::{AppCache, CacheConfig, KeepPath, mib};
+///  use commons::cache::{AppCache, CacheConfig, Eviction, KeepPath, mib};
 ///
 ///# impl Buildpack for HelloWorldBuildpack {
 ///#     type Platform = GenericPlatform;
@@ -47,7 +49,8 @@ use tempfile as _;
 ///         let config = CacheConfig {
 ///             path: context.app_dir.join("public").join("assets"),
 ///             limit: mib(100),
-///             keep_path: KeepPath::Runtime
+///             keep_path: KeepPath::Runtime,
+///             eviction: Eviction::Lru,
 ///         };
 ///
 ///         let store = AppCache::new_and_load(&context, config).unwrap();
@@ -66,7 +69,7 @@ pub struct AppCache {
     path: PathBuf,
 
     /// Prevent cache size from growing unbounded. Files over the limit
-    /// will be removed in order of least recently modified
+    /// will be removed in the order given by `eviction`
     limit: Byte,
 
     /// Directory where files will be cached
@@ -75,6 +78,9 @@ pub struct AppCache {
     /// Specify what happens to the application path while it's being stored
     keep_path: KeepPath,
 
+    /// Which files are removed first when the cache is over its limit
+    eviction: Eviction,
+
     /// Status of the cache directory when struct was instantiated
     cache_state: CacheState,
 }
@@ -111,6 +117,12 @@ impl AppCache {
         self.limit.get_appropriate_unit(UnitType::Binary)
     }
 
+    /// Which files are removed first when the cache is over its limit
+    #[must_use]
+    pub fn eviction(&self) -> Eviction {
+        self.eviction
+    }
+
     /// The state of the cache directory when the object was created
     #[must_use]
     pub fn cache_state(&self) -> &CacheState {
@@ -134,7 +146,7 @@ impl AppCache {
             KeepPath::BuildOnly => {
                 fs_err::remove_dir_all(&self.path).map_err(CacheError::IoError)?;
             }
-        };
+        }
 
         Ok(self)
     }
@@ -145,6 +157,9 @@ impl AppCache {
     /// cache.
     /// Ensures that both cache and path exist an disk.
     ///
+    /// The cache is left in place so that `save` only needs to
+    /// copy files that changed.
+    ///
     /// # Errors
     ///
     /// - If files cannot be moved from the cache to the path
@@ -153,25 +168,14 @@ impl AppCache {
         fs_err::create_dir_all(&self.path).map_err(CacheError::IoError)?;
         fs_err::create_dir_all(&self.cache).map_err(CacheError::IoError)?;
 
-        fs_extra::dir::copy(
-            &self.cache,
-            &self.path,
-            &CopyOptions {
-                overwrite: false,
-                skip_exist: true,
-                copy_inside: true,
-                content_only: true,
-                ..CopyOptions::default()
-            },
-        )
-        .map_err(|error| CacheError::CopyCacheToAppError {
-            path: self.path.clone(),
-            cache: self.cache.clone(),
-            error,
+        let copied = copy_missing(&self.cache, &self.path).map_err(|error| {
+            CacheError::SyncCacheToAppError {
+                path: self.path.clone(),
+                cache: self.cache.clone(),
+                error,
+            }
         })?;
-        copy_mtime_r(&self.cache, &self.path)?;
-
-        fs_err::remove_dir_all(&self.cache).map_err(CacheError::IoError)?;
+        copy_mtimes(&self.cache, &self.path, &copied)?;
 
         Ok(self)
     }
@@ -183,7 +187,8 @@ impl AppCache {
     ///
     /// Afterwards, files in the cache will be cleaned:
     /// If the cache directory is above the given `limit` then
-    /// files will be deleted in LRU order based on disk mtime.
+    /// files will be deleted in the order given by the configured
+    /// `eviction` strategy.
     ///
     /// If any files were removed in this process then they will
     /// be returned via `Some`. If no files were removed, `None`
@@ -198,7 +203,27 @@ impl AppCache {
     /// - If metadata of a file cannot be read, an error will be raised
     pub fn save_and_clean(&self) -> Result<Option<FilesWithSize>, CacheError> {
         self.save()?;
-        lru_clean(&self.cache, self.limit)
+        clean(&self.cache, self.limit, self.eviction)
+    }
+
+    /// Files removed by `save_and_clean` grouped by their top level directory
+    ///
+    /// Directories are reported inside of the application path rather than the
+    /// cache layer so they match what the user sees in their application.
+    #[must_use]
+    pub fn removed_by_dir(&self, removed: &FilesWithSize) -> Vec<FilesInDir> {
+        removed
+            .group_by_dir(&self.cache)
+            .into_iter()
+            .map(|mut group| {
+                group.dir = match group.dir.strip_prefix(&self.cache) {
+                    Ok(relative) if relative.as_os_str().is_empty() => self.path.clone(),
+                    Ok(relative) => self.path.join(relative),
+                    Err(_) => group.dir,
+                };
+                group
+            })
+            .collect()
     }
 
     /// Returns an enum representing the state
@@ -225,6 +250,13 @@ pub enum CacheState {
 
     /// Cache was previously created, it is non-empty
     ExistsWithContents,
+
+    /// Cache was previously created but its contents were removed, holds the reason
+    ///
+    /// Files such as compiled assets or bootsnap caches built for one distribution
+    /// or CPU architecture can be invalid on another, so the cache is cleared when
+    /// the build target changes.
+    Cleared(String),
 }
 
 /// Current state of the path
@@ -250,12 +282,90 @@ pub enum PathState {
 ///  Historically, sprockets will keep 3 versions of old files on disk. This
 ///  allows for emails, that might live a long time, to reference a specific SHA of an
 ///  asset.
+pub(crate) type Metadata = MetadataV2;
+try_migrate_deserializer_chain!(
+    deserializer: toml::Deserializer::new,
+    error: MetadataError,
+    chain: [MetadataV1, MetadataV2],
+);
+
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
 #[serde(deny_unknown_fields)]
-pub(crate) struct Metadata {
+pub(crate) struct MetadataV1 {
     pub(crate) app_dir_path: PathBuf,
 }
 
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct MetadataV2 {
+    pub(crate) app_dir_path: PathBuf,
+    pub(crate) distro_name: String,
+    pub(crate) distro_version: String,
+    pub(crate) arch: String,
+}
+
+/// V1 did not record a target, it's left empty and filled in with the current target
+/// when the layer is restored, see [`build`]
+impl From<MetadataV1> for MetadataV2 {
+    fn from(v1: MetadataV1) -> Self {
+        Self {
+            app_dir_path: v1.app_dir_path,
+            distro_name: String::new(),
+            distro_version: String::new(),
+            arch: String::new(),
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, thiserror::Error)]
+pub(crate) enum MetadataError {
+    // Update if migrating between a metadata version can error
+}
+
+impl MetadataV2 {
+    /// Description of the build target the cache was created for
+    fn target(&self) -> String {
+        format!(
+            "{} {} ({})",
+            self.distro_name, self.distro_version, self.arch
+        )
+    }
+}
+
+/// Why a restored cache must be cleared
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ClearReason {
+    /// Files built for one distribution or CPU architecture may not work on another
+    TargetChanged { from: String, to: String },
+    /// A different directory is being cached, it's not shown to the user
+    PathChanged,
+}
+
+impl std::fmt::Display for ClearReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ClearReason::TargetChanged { from, to } => {
+                write!(f, "target changed from {from} to {to}")
+            }
+            ClearReason::PathChanged => write!(f, "application path changed"),
+        }
+    }
+}
+
+/// Returns `None` when the restored cache can be kept
+fn clear_reason(old: &Metadata, now: &Metadata) -> Option<ClearReason> {
+    if old == now {
+        None
+    } else if old.app_dir_path == now.app_dir_path {
+        Some(ClearReason::TargetChanged {
+            from: old.target(),
+            to: now.target(),
+        })
+    } else {
+        Some(ClearReason::PathChanged)
+    }
+}
+
 /// Converts a `CacheConfig` into an `AppCache`
 ///
 /// Same as `AppCache::new_and_load` without loading
@@ -272,6 +382,7 @@ pub fn build<B: libcnb::Buildpack>(
         path,
         limit,
         keep_path,
+        eviction,
     } = config;
 
     let layer_name = create_layer_name(&context.app_dir, &path)?;
@@ -279,32 +390,58 @@ pub fn build<B: libcnb::Buildpack>(
 
     let metadata = Metadata {
         app_dir_path: path.clone(),
+        distro_name: context.target.distro_name.clone(),
+        distro_version: context.target.distro_version.clone(),
+        arch: context.target.arch.clone(),
     };
-    let cache = context
+    let layer_ref = context
         .cached_layer(
             layer_name,
             CachedLayerDefinition {
                 build: true,
                 launch: true,
-                invalid_metadata_action: &|_| InvalidMetadataAction::DeleteLayer,
-                restored_layer_action: &|old: &Metadata, _| {
-                    if old == &metadata {
-                        RestoredLayerAction::KeepLayer
-                    } else {
-                        RestoredLayerAction::DeleteLayer
+                invalid_metadata_action: &|invalid| {
+                    let migrated = toml::to_string(invalid)
+                        .ok()
+                        .and_then(|toml| Metadata::try_from_str_migrations(&toml))
+                        .and_then(Result::ok);
+                    match migrated {
+                        // Caches from before the target was recorded are kept, as they were
+                        Some(old) if old.app_dir_path == metadata.app_dir_path => (
+                            InvalidMetadataAction::ReplaceMetadata(metadata.clone()),
+                            None,
+                        ),
+                        _ => (InvalidMetadataAction::DeleteLayer, None::<ClearReason>),
                     }
                 },
+                restored_layer_action: &|old: &Metadata, _| match clear_reason(old, &metadata) {
+                    None => (RestoredLayerAction::KeepLayer, None),
+                    Some(reason) => (RestoredLayerAction::DeleteLayer, Some(reason)),
+                },
             },
         )
-        .map_err(|error| CacheError::InternalLayerError(format!("{error:?}")))
-        .map(|layer_ref| layer_ref.path())?;
+        .map_err(|error| CacheError::InternalLayerError(format!("{error:?}")))?;
+    layer_ref
+        .write_metadata(metadata)
+        .map_err(|error| CacheError::InternalLayerError(format!("{error:?}")))?;
+
+    let cache_state = match &layer_ref.state {
+        LayerState::Empty {
+            cause:
+                EmptyLayerCause::RestoredLayerAction {
+                    cause: Some(reason @ ClearReason::TargetChanged { .. }),
+                },
+        } => CacheState::Cleared(reason.to_string()),
+        _ => create_state,
+    };
 
     Ok(AppCache {
         path,
         limit,
-        cache,
+        cache: layer_ref.path(),
         keep_path,
-        cache_state: create_state,
+        eviction,
+        cache_state,
     })
 }
 
@@ -313,56 +450,136 @@ pub fn build<B: libcnb::Buildpack>(
 /// This action preserves the contents in the application path.
 /// Files from the application path are considered
 /// cannonical and will overwrite files with the same name in the
-/// cache.
+/// cache. Files in the cache that are no longer in the application
+/// path are removed.
 ///
 /// # Errors
 ///
-/// - If the copy command fails an `IoExtraError` will be raised.
+/// - If a file cannot be copied or removed an `SyncAppToCacheError` will be raised.
 fn save(store: &AppCache) -> Result<&AppCache, CacheError> {
-    fs_extra::dir::copy(
-        &store.path,
-        &store.cache,
-        &CopyOptions {
-            overwrite: true,
-            copy_inside: true,  // Recursive
-            content_only: true, // Don't copy top level directory name
-            ..CopyOptions::default()
-        },
-    )
-    .map_err(|error| CacheError::CopyAppToCacheError {
+    sync_dir(&store.path, &store.cache).map_err(|error| CacheError::SyncAppToCacheError {
         path: store.path.clone(),
         cache: store.cache.clone(),
         error,
     })?;
 
-    copy_mtime_r(&store.path, &store.cache)?;
-
     Ok(store)
 }
 
-/// Copies the mtime information from a path to another path
+/// Mirror the contents of one directory into another, only copying files that changed
 ///
-/// This is information used for the LRU cleaner so that older files are removed first.
-fn copy_mtime_r(from: &Path, to_path: &Path) -> Result<(), CacheError> {
-    for entry in WalkDir::new(from).into_iter().filter_map(Result::ok) {
+/// Like `rsync --archive --delete`, a file is unchanged when the destination has the same size
+/// and mtime. Unchanged files are skipped, which saves a lot of IO for large directories where
+/// most files stay the same between builds (such as compiled assets).
+///
+/// Files are copied with `std::fs::copy`, which uses `copy_file_range` on Linux so filesystems
+/// that support reflinks (such as btrfs and XFS) share data until it's written. They are never
+/// hardlinked, the application path is writable and a shared inode would let a write into a
+/// loaded file change the cache too.
+fn sync_dir(from: &Path, to: &Path) -> Result<(), std::io::Error> {
+    fs_err::create_dir_all(to)?;
+
+    for entry in WalkDir::new(from).min_depth(1) {
+        let entry = entry?;
+        let target = to.join(
+            entry
+                .path()
+                .strip_prefix(from)
+                .expect("Walkdir path should return path with prefix of called root"),
+        );
+        let metadata = fs_err::metadata(entry.path())?;
+
+        if metadata.is_dir() {
+            if target.is_file() {
+                fs_err::remove_file(&target)?;
+            }
+            fs_err::create_dir_all(&target)?;
+        } else if !is_unchanged(&metadata, &target) {
+            if target.is_dir() {
+                fs_err::remove_dir_all(&target)?;
+            } else if fs_err::symlink_metadata(&target).is_ok() {
+                fs_err::remove_file(&target)?;
+            }
+            fs_err::copy(entry.path(), &target)?;
+            filetime::set_file_mtime(
+                &target,
+                filetime::FileTime::from_last_modification_time(&metadata),
+            )?;
+        }
+    }
+
+    // Children are visited before their parents so emptied directories can be removed
+    for entry in WalkDir::new(to).min_depth(1).contents_first(true) {
+        let entry = entry?;
         let relative = entry
             .path()
-            .strip_prefix(from)
+            .strip_prefix(to)
             .expect("Walkdir path should return path with prefix of called root");
+        if fs_err::symlink_metadata(from.join(relative)).is_err() {
+            if entry.file_type().is_dir() {
+                fs_err::remove_dir(entry.path())?;
+            } else {
+                fs_err::remove_file(entry.path())?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Copy files from one directory into another, skipping files that already exist
+///
+/// Returns the copied files relative to both directories. See [`sync_dir`] for why files are
+/// copied and not hardlinked.
+fn copy_missing(from: &Path, to: &Path) -> Result<Vec<PathBuf>, std::io::Error> {
+    let mut copied = Vec::new();
+    for entry in WalkDir::new(from).min_depth(1) {
+        let entry = entry?;
+        let target = to.join(
+            entry
+                .path()
+                .strip_prefix(from)
+                .expect("Walkdir path should return path with prefix of called root"),
+        );
 
-        let mtime = entry
-            .metadata()
-            .map_err(|error| std::io::Error::new(std::io::ErrorKind::Other, error))
+        if entry.file_type().is_dir() {
+            fs_err::create_dir_all(&target)?;
+        } else if fs_err::symlink_metadata(&target).is_err() {
+            fs_err::copy(entry.path(), &target)?;
+            copied.push(target.strip_prefix(to).unwrap_or(&target).to_path_buf());
+        }
+    }
+    Ok(copied)
+}
+
+/// A destination file is unchanged when it has the same size and mtime as the source
+fn is_unchanged(source: &std::fs::Metadata, target: &Path) -> bool {
+    fs_err::metadata(target).is_ok_and(|existing| {
+        existing.is_file()
+            && existing.len() == source.len()
+            && filetime::FileTime::from_last_modification_time(&existing)
+                == filetime::FileTime::from_last_modification_time(source)
+    })
+}
+
+/// Copies the mtime of each relative path from one directory to another
+///
+/// This is information used for the LRU cleaner so that older files are removed first. Only
+/// files that were copied from the cache get its mtime. Files the application already had keep
+/// their own, otherwise `save` would take a changed file of the same size as unchanged.
+fn copy_mtimes(from: &Path, to_path: &Path, relative: &[PathBuf]) -> Result<(), CacheError> {
+    for relative in relative {
+        let mtime = fs_err::metadata(from.join(relative))
             .map(|metadata| filetime::FileTime::from_last_modification_time(&metadata))
             .map_err(|error| CacheError::Mtime {
-                from: entry.path().to_path_buf(),
+                from: from.join(relative),
                 to_path: to_path.join(relative),
                 error,
             })?;
 
         filetime::set_file_mtime(to_path.join(relative), mtime).map_err(|error| {
             CacheError::Mtime {
-                from: entry.path().to_path_buf(),
+                from: from.join(relative),
                 to_path: to_path.join(relative),
                 error,
             }
@@ -372,18 +589,37 @@ fn copy_mtime_r(from: &Path, to_path: &Path) -> Result<(), CacheError> {
 }
 
 /// Converts a path inside of an app to a valid layer name for libcnb.
+///
+/// Paths that escape the app with `..` or that are the app itself are rejected.
+///
+/// Components are joined with `_`. A `_` or `%` inside of a component is percent encoded
+/// so different paths never share a layer, for example `a/b` is `cache_a_b` and `a_b`
+/// is `cache_a%5Fb`.
 fn create_layer_name(app_root: &Path, path: &Path) -> Result<LayerName, CacheError> {
-    let name = path
-        .strip_prefix(app_root)
-        .map_err(|_| {
-            CacheError::CachedPathNotInAppPath(format!(
-                "Expected cached app path {} to be in {} but it was not",
-                path.display(),
-                app_root.display(),
-            ))
-        })?
+    let not_in_app = || {
+        CacheError::CachedPathNotInAppPath(format!(
+            "Expected cached app path {} to be in {} but it was not",
+            path.display(),
+            app_root.display(),
+        ))
+    };
+    let relative = path.strip_prefix(app_root).map_err(|_| not_in_app())?;
+    if relative.as_os_str().is_empty()
+        || !relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+    {
+        return Err(not_in_app());
+    }
+
+    let name = relative
         .iter()
-        .map(std::ffi::OsStr::to_string_lossy)
+        .map(|component| {
+            component
+                .to_string_lossy()
+                .replace('%', "%25")
+                .replace('_', "%5F")
+        })
         .collect::<Vec<_>>()
         .join("_");
 
@@ -430,6 +666,62 @@ mod tests {
         let dir = PathBuf::from_str("muh_base").unwrap();
         let layer = create_layer_name(&dir, &dir.join("my").join("input")).unwrap();
         assert_eq!(layer_name!("cache_my_input"), layer);
+
+        let layer = create_layer_name(&dir, &dir.join("my_input")).unwrap();
+        assert_eq!(layer_name!("cache_my%5Finput"), layer);
+        assert_ne!(
+            create_layer_name(&dir, &dir.join("a%5Fb")).unwrap(),
+            create_layer_name(&dir, &dir.join("a_b")).unwrap()
+        );
+
+        assert!(create_layer_name(&dir, &dir.join("..").join("etc")).is_err());
+        assert!(create_layer_name(&dir, &dir).is_err());
+    }
+
+    #[test]
+    fn test_clear_reason() {
+        let old = Metadata {
+            app_dir_path: PathBuf::from("/workspace/public/assets"),
+            distro_name: String::from("ubuntu"),
+            distro_version: String::from("22.04"),
+            arch: String::from("amd64"),
+        };
+        assert_eq!(None, clear_reason(&old, &old.clone()));
+
+        let now = Metadata {
+            distro_version: String::from("24.04"),
+            arch: String::from("arm64"),
+            ..old.clone()
+        };
+        assert_eq!(
+            Some(String::from(
+                "target changed from ubuntu 22.04 (amd64) to ubuntu 24.04 (arm64)"
+            )),
+            clear_reason(&old, &now).map(|reason| reason.to_string())
+        );
+
+        let moved = Metadata {
+            app_dir_path: PathBuf::from("/workspace/tmp/cache"),
+            ..old.clone()
+        };
+        assert_eq!(Some(ClearReason::PathChanged), clear_reason(&old, &moved));
+    }
+
+    #[test]
+    fn test_metadata_migrate_v1() {
+        let migrated =
+            Metadata::try_from_str_migrations(r#"app_dir_path = "/workspace/public/assets""#)
+                .unwrap()
+                .unwrap();
+        assert_eq!(
+            Metadata {
+                app_dir_path: PathBuf::from("/workspace/public/assets"),
+                distro_name: String::new(),
+                distro_version: String::new(),
+                arch: String::new(),
+            },
+            migrated
+        );
     }
 
     #[test]
@@ -469,6 +761,7 @@ mod tests {
             cache: cache_path,
             limit: Byte::from_u64(512),
             keep_path: KeepPath::Runtime,
+            eviction: Eviction::Lru,
             cache_state: CacheState::NewEmpty,
         };
 
@@ -488,6 +781,7 @@ mod tests {
             cache: cache_path,
             limit: Byte::from_u64(512),
             keep_path: KeepPath::Runtime,
+            eviction: Eviction::Lru,
             cache_state: CacheState::NewEmpty,
         };
 
@@ -519,6 +813,7 @@ mod tests {
             cache: cache_path,
             limit: Byte::from_u64(512),
             keep_path: KeepPath::BuildOnly,
+            eviction: Eviction::Lru,
             cache_state: CacheState::NewEmpty,
         };
 
@@ -556,6 +851,7 @@ mod tests {
             cache: cache_path.clone(),
             limit: Byte::from_u64(512),
             keep_path: KeepPath::BuildOnly,
+            eviction: Eviction::Lru,
             cache_state: CacheState::NewEmpty,
         };
 
@@ -598,6 +894,7 @@ mod tests {
             cache: cache_path.clone(),
             limit: Byte::from_u64(512),
             keep_path: KeepPath::Runtime,
+            eviction: Eviction::Lru,
             cache_state: CacheState::NewEmpty,
         };
 
@@ -624,4 +921,108 @@ mod tests {
             .unwrap();
         assert_eq!(mtime, actual);
     }
+
+    #[test]
+    fn test_save_only_copies_changed_files() {
+        let mtime = FileTime::from_unix_time(1000, 0);
+        let tmpdir = tempfile::tempdir().unwrap();
+        let app_path = tmpdir.path().join("app");
+        let cache_path = tmpdir.path().join("cache");
+        fs_err::create_dir_all(app_path.join("nested")).unwrap();
+
+        let store = AppCache {
+            path: app_path.clone(),
+            cache: cache_path.clone(),
+            limit: Byte::from_u64(512),
+            keep_path: KeepPath::Runtime,
+            eviction: Eviction::Lru,
+            cache_state: CacheState::NewEmpty,
+        };
+
+        fs_err::write(app_path.join("unchanged.txt"), "same").unwrap();
+        filetime::set_file_mtime(app_path.join("unchanged.txt"), mtime).unwrap();
+        fs_err::write(app_path.join("nested").join("removed.txt"), "gone").unwrap();
+        store.save().unwrap();
+
+        // Same size and mtime, only possible to detect if the file was not copied again
+        fs_err::write(cache_path.join("unchanged.txt"), "SAME").unwrap();
+        filetime::set_file_mtime(cache_path.join("unchanged.txt"), mtime).unwrap();
+        fs_err::remove_dir_all(app_path.join("nested")).unwrap();
+        fs_err::write(app_path.join("added.txt"), "new").unwrap();
+        store.save().unwrap();
+
+        assert_eq!(
+            "SAME",
+            fs_err::read_to_string(cache_path.join("unchanged.txt")).unwrap()
+        );
+        assert!(cache_path.join("added.txt").exists());
+        assert!(!cache_path.join("nested").exists());
+    }
+
+    #[test]
+    fn test_load_keeps_changed_app_files() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let app_path = tmpdir.path().join("app");
+        let cache_path = tmpdir.path().join("cache");
+        fs_err::create_dir_all(&app_path).unwrap();
+        fs_err::create_dir_all(&cache_path).unwrap();
+        fs_err::write(cache_path.join("a.txt"), "aaaa").unwrap();
+        filetime::set_file_mtime(
+            cache_path.join("a.txt"),
+            filetime::FileTime::from_unix_time(1_000_000, 0),
+        )
+        .unwrap();
+        fs_err::write(app_path.join("a.txt"), "bbbb").unwrap();
+
+        let store = AppCache {
+            path: app_path.clone(),
+            cache: cache_path.clone(),
+            limit: Byte::from_u64(512),
+            keep_path: KeepPath::Runtime,
+            eviction: Eviction::Lru,
+            cache_state: CacheState::ExistsWithContents,
+        };
+        store.load().unwrap();
+        store.save().unwrap();
+
+        assert_eq!(
+            "bbbb",
+            fs_err::read_to_string(cache_path.join("a.txt")).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_copy_missing() {
+        use std::io::Write;
+
+        let tmpdir = tempfile::tempdir().unwrap();
+        let from = tmpdir.path().join("from");
+        let to = tmpdir.path().join("to");
+        fs_err::create_dir_all(&from).unwrap();
+        fs_err::create_dir_all(&to).unwrap();
+        fs_err::write(from.join("cached.txt"), "data").unwrap();
+        fs_err::write(from.join("existing.txt"), "old").unwrap();
+        fs_err::write(to.join("existing.txt"), "new").unwrap();
+
+        assert_eq!(
+            copy_missing(&from, &to).unwrap(),
+            vec![PathBuf::from("cached.txt")]
+        );
+        assert_eq!(
+            "new",
+            fs_err::read_to_string(to.join("existing.txt")).unwrap()
+        );
+
+        // Writing into the loaded file in place must not change the cache
+        fs_err::OpenOptions::new()
+            .append(true)
+            .open(to.join("cached.txt"))
+            .unwrap()
+            .write_all(b" appended")
+            .unwrap();
+        assert_eq!(
+            "data",
+            fs_err::read_to_string(from.join("cached.txt")).unwrap()
+        );
+    }
 }
@@ -0,0 +1,70 @@
+use crate::cache::{AppCache, CacheConfig, CacheError, FilesWithSize};
+use libcnb::build::BuildContext;
+
+/// Store multiple directories from the `<app_dir>` between builds
+///
+/// Each directory is stored in its own `AppCache` with its own limit.
+/// Every path is validated before any cache is loaded, so an invalid
+/// entry does not leave the application with some directories restored.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct AppCacheCollection {
+    caches: Vec<AppCache>,
+}
+
+impl AppCacheCollection {
+    /// Create an `AppCache` for each config and load them into the application
+    ///
+    /// # Errors
+    ///
+    /// - If a path is not inside of the application directory.
+    /// - If the same path is configured more than once.
+    /// - Any error from `AppCache::new_and_load`.
+    pub fn new_and_load<B: libcnb::Buildpack>(
+        context: &BuildContext<B>,
+        configs: impl IntoIterator<Item = CacheConfig>,
+    ) -> Result<Self, CacheError> {
+        let configs = configs.into_iter().collect::<Vec<_>>();
+        for (index, config) in configs.iter().enumerate() {
+            if !config.path.starts_with(&context.app_dir) {
+                return Err(CacheError::CachedPathNotInAppPath(format!(
+                    "Expected cached app path {} to be in {} but it was not",
+                    config.path.display(),
+                    context.app_dir.display(),
+                )));
+            }
+            if configs[..index]
+                .iter()
+                .any(|other| other.path == config.path)
+            {
+                return Err(CacheError::DuplicatePath(config.path.clone()));
+            }
+        }
+
+        let caches = configs
+            .into_iter()
+            .map(|config| AppCache::new_and_load(context, config))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self { caches })
+    }
+
+    /// The caches in the order they were configured
+    #[must_use]
+    pub fn caches(&self) -> &[AppCache] {
+        &self.caches
+    }
+
+    /// Calls `AppCache::save_and_clean` on every cache
+    ///
+    /// Returns each cache with the files that were removed because it was over its limit.
+    ///
+    /// # Errors
+    ///
+    /// - Any error from `AppCache::save_and_clean`, remaining caches are not saved.
+    pub fn save_and_clean(&self) -> Result<Vec<(&AppCache, Option<FilesWithSize>)>, CacheError> {
+        self.caches
+            .iter()
+            .map(|cache| cache.save_and_clean().map(|removed| (cache, removed)))
+            .collect()
+    }
+}
@@ -1,15 +1,16 @@
-use crate::cache::CacheError;
+use crate::cache::{CacheError, Eviction};
 use byte_unit::{AdjustedByte, Byte, UnitType};
 use std::os::unix::fs::MetadataExt;
 use std::path::{Path, PathBuf};
 use std::time::SystemTime;
 
-/// Remove Least Recently Used (LRU) files in cache above a byte limit
+/// Remove files in cache above a byte limit
 ///
 /// The cache directory may grow unbounded. This function will limit
 /// the size of the directory to the given input. When the directory
-/// grows larger than the limit, then files will be deleted to
-/// bring the directory size under the given limit.
+/// grows larger than the limit, then files will be deleted in the order
+/// given by the `eviction` strategy to bring the directory size under
+/// the given limit.
 ///
 /// # Errors
 ///
@@ -19,8 +20,12 @@ use std::time::SystemTime;
 /// - If there's an OS error while deleting a file.
 /// - If an internal glob pattern is incorrect
 /// - If the OS does not support mtime operation on files.
-pub(crate) fn lru_clean(path: &Path, limit: Byte) -> Result<Option<FilesWithSize>, CacheError> {
-    let overage = lru_files_above_limit(path, limit)?;
+pub(crate) fn clean(
+    path: &Path,
+    limit: Byte,
+    eviction: Eviction,
+) -> Result<Option<FilesWithSize>, CacheError> {
+    let overage = files_above_limit(path, limit, eviction)?;
 
     if overage.files.is_empty() {
         Ok(None)
@@ -46,28 +51,42 @@ fn files(cache_path: &Path) -> Result<Vec<MiniPathModSize>, CacheError> {
 }
 
 /// Calculate total size of files in a directory. If that size is above the given limit, then a list
-/// of files (ordered by modified time so the last touched will come first) will be returned via a
-/// `FilesWithSize`. If later deleted, those values will reduce the total size of the directory
-/// below the limit.
-fn lru_files_above_limit(cache_path: &Path, limit: Byte) -> Result<FilesWithSize, CacheError> {
+/// of files (ordered by the eviction strategy so the first to remove will come first) will be
+/// returned via a `FilesWithSize`. If later deleted, those values will reduce the total size of the
+/// directory below the limit.
+fn files_above_limit(
+    cache_path: &Path,
+    limit: Byte,
+    eviction: Eviction,
+) -> Result<FilesWithSize, CacheError> {
     let max_bytes = limit.as_u128();
     let mut files = files(cache_path)?;
     let bytes = files.iter().map(|p| u128::from(p.size)).sum::<u128>();
 
     if bytes >= max_bytes {
         let mut current_bytes = bytes;
-        files.sort_by(|a, b| a.modified.cmp(&b.modified));
+        match eviction {
+            Eviction::Lru => files.sort_by_key(|file| file.modified),
+            Eviction::LargestFirst => files.sort_by(|a, b| {
+                b.size
+                    .cmp(&a.size)
+                    .then_with(|| a.modified.cmp(&b.modified))
+            }),
+            Eviction::All => {}
+        }
+
+        let removed = files
+            .iter()
+            .take_while(|m| {
+                current_bytes -= u128::from(m.size);
+                eviction == Eviction::All || current_bytes >= max_bytes
+            })
+            .collect::<Vec<_>>();
 
         Ok(FilesWithSize {
             bytes,
-            files: files
-                .iter()
-                .take_while(|m| {
-                    current_bytes -= u128::from(m.size);
-                    current_bytes >= max_bytes
-                })
-                .map(|p| p.path.clone())
-                .collect::<Vec<PathBuf>>(),
+            sizes: removed.iter().map(|p| p.size).collect(),
+            files: removed.into_iter().map(|p| p.path.clone()).collect(),
         })
     } else {
         Ok(FilesWithSize::default())
@@ -82,6 +101,9 @@ pub struct FilesWithSize {
 
     /// Paths to files
     pub files: Vec<PathBuf>,
+
+    /// Size of each file in `files`, in the same order
+    sizes: Vec<u64>,
 }
 
 impl FilesWithSize {
@@ -97,6 +119,67 @@ impl FilesWithSize {
     pub fn adjusted_bytes(&self) -> AdjustedByte {
         self.to_byte().get_appropriate_unit(UnitType::Binary)
     }
+
+    /// Files grouped by their top level directory relative to `root`
+    ///
+    /// Files directly inside of `root` are grouped under `root`. Groups
+    /// are sorted by directory name.
+    #[must_use]
+    pub fn group_by_dir(&self, root: &Path) -> Vec<FilesInDir> {
+        let mut groups = std::collections::BTreeMap::<PathBuf, FilesInDir>::new();
+        for (file, size) in self.files.iter().zip(&self.sizes) {
+            let relative = file.strip_prefix(root).unwrap_or(file);
+            let dir = match relative.components().next() {
+                Some(first) if relative.components().count() > 1 => root.join(first),
+                _ => root.to_path_buf(),
+            };
+            let group = groups.entry(dir.clone()).or_insert_with(|| FilesInDir {
+                dir,
+                count: 0,
+                bytes: 0,
+            });
+            group.count += 1;
+            group.bytes += u128::from(*size);
+        }
+        groups.into_values().collect()
+    }
+}
+
+/// Number and size of files in a directory, for reporting what was removed from a cache
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct FilesInDir {
+    /// Directory the files are in
+    pub dir: PathBuf,
+
+    /// Number of files
+    pub count: usize,
+
+    /// Combined size of the files on disk
+    bytes: u128,
+}
+
+impl FilesInDir {
+    /// Return byte value with adjusted units.
+    #[must_use]
+    pub fn adjusted_bytes(&self) -> AdjustedByte {
+        Byte::from_u128(self.bytes)
+            .unwrap_or(Byte::MAX)
+            .get_appropriate_unit(UnitType::Binary)
+    }
+}
+
+/// Formats as `312 files (48.2 MiB) from <dir>`
+impl std::fmt::Display for FilesInDir {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{count} {files} ({bytes:.1}) from {dir}",
+            count = self.count,
+            files = if self.count == 1 { "file" } else { "files" },
+            bytes = self.adjusted_bytes(),
+            dir = self.dir.display()
+        )
+    }
 }
 
 /// Internal helper for representing a file and it's metadata
@@ -164,13 +247,14 @@ mod tests {
 
         fs_err::create_dir_all(&dir).unwrap();
 
-        assert_eq!(lru_files_above_limit(&dir, mib(0),).unwrap().files.len(), 0);
+        let overage = files_above_limit(&dir, mib(0), Eviction::Lru).unwrap();
+        assert_eq!(overage.files.len(), 0);
 
         touch_file(&dir.join("a"), |file| {
-            let overage = lru_files_above_limit(&dir, mib(0)).unwrap();
+            let overage = files_above_limit(&dir, mib(0), Eviction::Lru).unwrap();
             assert_eq!(overage.files, vec![file.clone()]);
 
-            let overage = lru_files_above_limit(&dir, mib(10)).unwrap();
+            let overage = files_above_limit(&dir, mib(10), Eviction::Lru).unwrap();
             assert_eq!(overage.files.len(), 0);
         });
     }
@@ -185,7 +269,7 @@ mod tests {
                 filetime::set_file_mtime(a, filetime::FileTime::from_unix_time(0, 0)).unwrap();
                 filetime::set_file_mtime(b, filetime::FileTime::from_unix_time(1, 0)).unwrap();
 
-                let overage = lru_files_above_limit(&dir, mib(0)).unwrap();
+                let overage = files_above_limit(&dir, mib(0), Eviction::Lru).unwrap();
                 assert_eq!(overage.files, vec![a.clone(), b.clone()]);
             });
         });
@@ -196,7 +280,67 @@ mod tests {
         let tmpdir = tempfile::tempdir().unwrap();
         let dir = tmpdir.path().join("");
         fs_err::create_dir_all(dir.join("preservation_society")).unwrap();
-        let overage = lru_files_above_limit(&dir, mib(0)).unwrap();
+        let overage = files_above_limit(&dir, mib(0), Eviction::Lru).unwrap();
         assert_eq!(overage.files, Vec::<PathBuf>::new());
     }
+
+    #[test]
+    fn test_largest_first_returns_larger_files_first() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let dir = tmpdir.path().join("");
+
+        fs_err::write(dir.join("small"), "a").unwrap();
+        fs_err::write(dir.join("large"), "aaaaaaaaaa").unwrap();
+        filetime::set_file_mtime(dir.join("large"), filetime::FileTime::from_unix_time(1, 0))
+            .unwrap();
+        filetime::set_file_mtime(dir.join("small"), filetime::FileTime::from_unix_time(0, 0))
+            .unwrap();
+
+        let overage = files_above_limit(&dir, Byte::from_u64(1), Eviction::LargestFirst).unwrap();
+        assert_eq!(overage.files, vec![dir.join("large")]);
+
+        let overage = files_above_limit(&dir, Byte::from_u64(1), Eviction::Lru).unwrap();
+        assert_eq!(overage.files, vec![dir.join("small")]);
+    }
+
+    #[test]
+    fn test_all_returns_every_file() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let dir = tmpdir.path().join("");
+
+        fs_err::write(dir.join("small"), "a").unwrap();
+        fs_err::write(dir.join("large"), "aaaaaaaaaa").unwrap();
+
+        let overage = files_above_limit(&dir, Byte::from_u64(10), Eviction::All).unwrap();
+        assert_eq!(overage.files.len(), 2);
+
+        let overage = files_above_limit(&dir, Byte::from_u64(100), Eviction::All).unwrap();
+        assert_eq!(overage.files, Vec::<PathBuf>::new());
+    }
+
+    #[test]
+    fn test_group_by_dir() {
+        let root = PathBuf::from("/cache");
+        let removed = FilesWithSize {
+            bytes: 0,
+            files: vec![
+                root.join("sprockets").join("v4.0").join("a"),
+                root.join("sprockets").join("b"),
+                root.join("manifest.json"),
+            ],
+            sizes: vec![1024 * 1024, 512 * 1024, 10],
+        };
+
+        let groups = removed.group_by_dir(&root);
+        assert_eq!(
+            groups
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<String>>(),
+            vec![
+                "1 file (10 B) from /cache".to_string(),
+                "2 files (1.5 MiB) from /cache/sprockets".to_string(),
+            ]
+        );
+    }
 }
@@ -8,11 +8,35 @@ pub struct CacheConfig {
     pub path: PathBuf,
 
     /// Prevent cache size from growing unbounded. Files over the limit
-    /// will be removed in order of least recently modified
+    /// will be removed in the order given by `eviction`
     pub limit: Byte,
 
     /// Specify what happens to the application path while it's being stored
     pub keep_path: KeepPath,
+
+    /// Which files are removed first when the cache is over its limit
+    pub eviction: Eviction,
+}
+
+/// Order in which files are removed from a cache that is over its limit
+///
+/// There's no first in, first out strategy. The cache is kept between builds, but files are
+/// copied into it with the mtime of the application file, and a restored cache layer doesn't
+/// keep creation times. Nothing records when a file entered the cache, the closest proxy is
+/// the mtime that [`Eviction::Lru`] already sorts by.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Eviction {
+    /// Remove the least recently modified files first (by mtime)
+    #[default]
+    Lru,
+
+    /// Remove the largest files first, useful for caches where a few large
+    /// files (such as compiled bundles) make up most of the size
+    LargestFirst,
+
+    /// Remove every file, for caches where a partial copy is worse than none
+    /// (such as installed gems, where a gem missing a file still looks installed)
+    All,
 }
 
 /// Indicates whether we want the cache to be available at runtime or not.
@@ -5,6 +5,9 @@ pub enum CacheError {
     #[error("Cached path not in application directory: {0}")]
     CachedPathNotInAppPath(String),
 
+    #[error("Cached path configured more than once: {}", .0.display())]
+    DuplicatePath(PathBuf),
+
     #[error("Invalid layer name: {0}")]
     InvalidLayerName(libcnb::data::layer::LayerNameError),
 
@@ -24,6 +27,22 @@ pub enum CacheError {
         error: fs_extra::error::Error,
     },
 
+    #[error(
+        "Could not load cached files into application.\nFrom: {cache} to {path}\nError: {error}"
+    )]
+    SyncCacheToAppError {
+        path: PathBuf,
+        cache: PathBuf,
+        error: std::io::Error,
+    },
+
+    #[error("Could not sync files from the application to cache.\nFrom: {path} To: {cache}\nError: {error}")]
+    SyncAppToCacheError {
+        path: PathBuf,
+        cache: PathBuf,
+        error: std::io::Error,
+    },
+
     #[error("Could not move files out of the application to the cache.\nFrom: {path} To: {cache}\nError: {error}")]
     DestructiveMoveAppToCacheError {
         path: PathBuf,
@@ -0,0 +1,621 @@
+//! Parse a `Gemfile` without executing Ruby
+//!
+//! A `Gemfile` is Ruby code, so it can only be fully understood by running it. Most are
+//! declarative though, and the information the buildpack needs (the `ruby` directive, sources,
+//! groups, and gems installed from a local path or git) can be read from the text. Anything
+//! dynamic, such as a gem name built from a variable, is ignored.
+//!
+//! ```rust
+//! use commons::gemfile::{GemSource, Gemfile};
+//!
+//! let gemfile = Gemfile::parse(r#"
+//! source "https://rubygems.org"
+//! ruby "~> 3.3.0"
+//!
+//! gem "rails", "~> 8.0"
+//! gem "engine", path: "engines/engine"
+//!
+//! group :development, :test do
+//!   gem "rspec"
+//! end
+//! "#);
+//!
+//! assert_eq!(gemfile.sources, vec!["https://rubygems.org".to_string()]);
+//! assert_eq!(gemfile.ruby.as_ref().unwrap().requirements, vec!["~> 3.3.0".to_string()]);
+//! assert_eq!(gemfile.groups, vec!["development".to_string(), "test".to_string()]);
+//! assert_eq!(
+//!     gemfile.gem("engine").unwrap().source,
+//!     GemSource::Path("engines/engine".to_string())
+//! );
+//! ```
+use crate::requirements::{parse_requirements, Requirement, RequirementError};
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Gemfile {
+    /// Top level `source` declarations, in order
+    pub sources: Vec<String>,
+    /// The `ruby` directive, if any
+    pub ruby: Option<RubyDirective>,
+    /// Every group name used by a `group` block or a `group:` option, in order of first use
+    pub groups: Vec<String>,
+    /// Every `gem` declaration with a literal name, in order
+    pub gems: Vec<GemDeclaration>,
+}
+
+/// A `ruby` line such as `ruby "3.3.5", engine: "jruby", engine_version: "9.4.8.0"`
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RubyDirective {
+    pub requirements: Vec<String>,
+    pub engine: Option<String>,
+    pub engine_version: Option<String>,
+    /// Set when the version is read from a file i.e. `ruby file: ".ruby-version"`
+    pub file: Option<String>,
+}
+
+impl RubyDirective {
+    /// Parse the version requirements, such as `~> 3.3.0`
+    ///
+    /// # Errors
+    ///
+    /// Errors if any requirement is empty or contains an invalid version.
+    pub fn parsed_requirements(&self) -> Result<Vec<Requirement>, RequirementError> {
+        self.requirements
+            .iter()
+            .map(|requirement| parse_requirements(requirement))
+            .collect::<Result<Vec<_>, _>>()
+            .map(|requirements| requirements.into_iter().flatten().collect())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GemDeclaration {
+    pub name: String,
+    pub requirements: Vec<String>,
+    /// Groups from enclosing `group` blocks and `group:`/`groups:` options
+    pub groups: Vec<String>,
+    pub source: GemSource,
+}
+
+/// Where a gem is installed from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GemSource {
+    /// The top level source(s)
+    Default,
+    /// A `source "url" do` block or `source:` option
+    Remote(String),
+    /// A `path "dir" do` block or `path:` option
+    Path(String),
+    /// A `git "url" do` block or a `git:`/`github:` option
+    Git {
+        url: String,
+        reference: Option<String>,
+    },
+}
+
+impl GemSource {
+    /// True for gems that Bundler reads from the app directory instead of a remote
+    #[must_use]
+    pub fn is_local(&self) -> bool {
+        matches!(self, GemSource::Path(_))
+    }
+}
+
+impl Gemfile {
+    /// Parse the contents of a `Gemfile`
+    ///
+    /// Parsing never fails, lines that cannot be understood are skipped.
+    #[must_use]
+    pub fn parse(contents: &str) -> Self {
+        let mut gemfile = Gemfile::default();
+        let mut blocks: Vec<Block> = Vec::new();
+
+        let mut continued = String::new();
+        for line in contents.lines() {
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            // Arguments can span lines when a line ends with a comma or an open bracket
+            if line.ends_with([',', '(', '[']) {
+                continued.push_str(line);
+                continued.push(' ');
+                continue;
+            }
+            let joined = std::mem::take(&mut continued) + line;
+            let line = joined.as_str();
+            let (word, rest) = line
+                .split_once(|c: char| c.is_whitespace() || c == '(')
+                .unwrap_or((line, ""));
+            let (rest, opens_block) = strip_do(rest.trim());
+            let args = parse_args(rest.trim().trim_start_matches('(').trim_end_matches(')'));
+
+            match word {
+                "end" => {
+                    blocks.pop();
+                }
+                "if" | "unless" | "case" | "begin" | "while" | "until" | "def" | "class"
+                | "module" => blocks.push(Block::Other),
+                "source" if opens_block => {
+                    blocks.push(first_string(&args).map_or(Block::Other, Block::Source));
+                }
+                "source" => gemfile.sources.extend(first_string(&args)),
+                "path" if opens_block => {
+                    blocks.push(first_string(&args).map_or(Block::Other, Block::Path));
+                }
+                "git" | "github" if opens_block => {
+                    blocks.push(first_string(&args).map_or(Block::Other, |url| {
+                        Block::Git(GemSource::Git {
+                            url: git_url(word, &url),
+                            reference: git_reference(&args),
+                        })
+                    }));
+                }
+                "group" if opens_block => {
+                    let groups = symbols(&args);
+                    add_groups(&mut gemfile.groups, &groups);
+                    blocks.push(Block::Group(groups));
+                }
+                "ruby" => gemfile.ruby = Some(ruby_directive(&args)),
+                "gem" => {
+                    if let Some(gem) = gem_declaration(&args, &blocks) {
+                        add_groups(&mut gemfile.groups, &gem.groups);
+                        gemfile.gems.push(gem);
+                    }
+                    if opens_block {
+                        blocks.push(Block::Other);
+                    }
+                }
+                _ if opens_block => blocks.push(Block::Other),
+                _ => {}
+            }
+        }
+
+        gemfile
+    }
+
+    /// Returns the first declaration of the named gem
+    #[must_use]
+    pub fn gem(&self, name: &str) -> Option<&GemDeclaration> {
+        self.gems.iter().find(|gem| gem.name == name)
+    }
+
+    /// Gems installed from a directory, such as engines in a monorepo
+    pub fn local_gems(&self) -> impl Iterator<Item = &GemDeclaration> {
+        self.gems.iter().filter(|gem| gem.source.is_local())
+    }
+}
+
+#[derive(Debug)]
+enum Block {
+    Source(String),
+    Path(String),
+    Git(GemSource),
+    Group(Vec<String>),
+    Other,
+}
+
+/// A single argument, either positional or a `key: value` option
+#[derive(Debug, PartialEq)]
+enum Arg {
+    Positional(Value),
+    Option(String, Value),
+}
+
+#[derive(Debug, PartialEq)]
+enum Value {
+    Str(String),
+    Symbol(String),
+    List(Vec<Value>),
+    /// Anything else, such as a method call or variable
+    Expression,
+}
+
+impl Value {
+    fn as_string(&self) -> Option<String> {
+        match self {
+            Value::Str(value) => Some(value.clone()),
+            _ => None,
+        }
+    }
+
+    fn names(&self) -> Vec<String> {
+        match self {
+            Value::Str(name) | Value::Symbol(name) => vec![name.clone()],
+            Value::List(values) => values.iter().flat_map(Value::names).collect(),
+            Value::Expression => Vec::new(),
+        }
+    }
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    for (index, c) in line.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if q == c => quote = None,
+            (None, '#') => return &line[..index],
+            _ => {}
+        }
+    }
+    line
+}
+
+/// Removes a trailing `do` (or `do |arg|`) that opens a block
+fn strip_do(rest: &str) -> (&str, bool) {
+    let without_params = match rest.strip_suffix('|') {
+        Some(start) => start
+            .rfind('|')
+            .map_or(rest, |index| start[..index].trim_end()),
+        None => rest,
+    };
+    match without_params.strip_suffix("do") {
+        Some(start) if start.is_empty() || start.ends_with([' ', ')']) => (start, true),
+        _ => (rest, false),
+    }
+}
+
+/// Splits on commas that are not inside quotes or brackets
+fn split_top_level(input: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0_i32;
+    let mut quote = None;
+    let mut start = 0;
+    for (index, c) in input.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if q == c => quote = None,
+            (None, '[' | '{' | '(') => depth += 1,
+            (None, ']' | '}' | ')') => depth -= 1,
+            (None, ',') if depth == 0 => {
+                parts.push(input[start..index].trim());
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(input[start..].trim());
+    parts.retain(|part| !part.is_empty());
+    parts
+}
+
+fn parse_args(input: &str) -> Vec<Arg> {
+    // A trailing `if`/`unless` modifier makes the line conditional, the declaration still counts
+    let input = [" if ", " unless "]
+        .iter()
+        .filter_map(|modifier| input.find(modifier))
+        .min()
+        .map_or(input, |index| &input[..index]);
+
+    split_top_level(input)
+        .into_iter()
+        .map(|part| {
+            if let Some((key, value)) = part.split_once("=>") {
+                let key = key.trim().trim_start_matches(':');
+                Arg::Option(unquote(key).unwrap_or(key).to_string(), parse_value(value))
+            } else if let Some((key, value)) = part
+                .split_once(": ")
+                .filter(|(key, _)| key.chars().all(|c| c.is_alphanumeric() || c == '_'))
+            {
+                Arg::Option(key.to_string(), parse_value(value))
+            } else {
+                Arg::Positional(parse_value(part))
+            }
+        })
+        .collect()
+}
+
+fn unquote(value: &str) -> Option<&str> {
+    ['"', '\'']
+        .iter()
+        .find_map(|quote| value.strip_prefix(*quote)?.strip_suffix(*quote))
+}
+
+fn parse_value(value: &str) -> Value {
+    let value = value.trim();
+    if let Some(string) = unquote(value) {
+        if value.starts_with('"') && string.contains("#{") {
+            Value::Expression
+        } else {
+            Value::Str(string.to_string())
+        }
+    } else if let Some(symbol) = value.strip_prefix(':') {
+        Value::Symbol(unquote(symbol).unwrap_or(symbol).to_string())
+    } else if let Some(list) = value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+        Value::List(split_top_level(list).into_iter().map(parse_value).collect())
+    } else if let Some(words) = value
+        .strip_prefix("%i[")
+        .or_else(|| value.strip_prefix("%w["))
+        .and_then(|v| v.strip_suffix(']'))
+    {
+        Value::List(
+            words
+                .split_whitespace()
+                .map(|word| Value::Symbol(word.to_string()))
+                .collect(),
+        )
+    } else {
+        Value::Expression
+    }
+}
+
+fn first_string(args: &[Arg]) -> Option<String> {
+    match args.first() {
+        Some(Arg::Positional(value)) => value.as_string(),
+        _ => None,
+    }
+}
+
+fn option<'a>(args: &'a [Arg], name: &str) -> Option<&'a Value> {
+    args.iter().find_map(|arg| match arg {
+        Arg::Option(key, value) if key == name => Some(value),
+        _ => None,
+    })
+}
+
+fn symbols(args: &[Arg]) -> Vec<String> {
+    args.iter()
+        .flat_map(|arg| match arg {
+            Arg::Positional(value) => value.names(),
+            Arg::Option(..) => Vec::new(),
+        })
+        .collect()
+}
+
+fn add_groups(groups: &mut Vec<String>, new: &[String]) {
+    for group in new {
+        if !groups.contains(group) {
+            groups.push(group.clone());
+        }
+    }
+}
+
+fn git_url(kind: &str, url: &str) -> String {
+    if kind == "github" && !url.contains("://") {
+        format!("https://github.com/{url}.git")
+    } else {
+        url.to_string()
+    }
+}
+
+fn git_reference(args: &[Arg]) -> Option<String> {
+    ["ref", "branch", "tag"]
+        .iter()
+        .find_map(|key| option(args, key).and_then(Value::as_string))
+}
+
+fn ruby_directive(args: &[Arg]) -> RubyDirective {
+    RubyDirective {
+        requirements: args
+            .iter()
+            .filter_map(|arg| match arg {
+                Arg::Positional(value) => value.as_string(),
+                Arg::Option(..) => None,
+            })
+            .collect(),
+        engine: option(args, "engine").and_then(Value::as_string),
+        engine_version: option(args, "engine_version").and_then(Value::as_string),
+        file: option(args, "file").and_then(Value::as_string),
+    }
+}
+
+fn gem_declaration(args: &[Arg], blocks: &[Block]) -> Option<GemDeclaration> {
+    let name = first_string(args)?;
+    let requirements = args
+        .iter()
+        .skip(1)
+        .filter_map(|arg| match arg {
+            Arg::Positional(value) => value.as_string(),
+            Arg::Option(..) => None,
+        })
+        .collect();
+
+    let mut groups = Vec::new();
+    for block in blocks {
+        if let Block::Group(names) = block {
+            add_groups(&mut groups, names);
+        }
+    }
+    for key in ["group", "groups"] {
+        if let Some(value) = option(args, key) {
+            add_groups(&mut groups, &value.names());
+        }
+    }
+
+    let option_source = if let Some(path) = option(args, "path").and_then(Value::as_string) {
+        Some(GemSource::Path(path))
+    } else if let Some((kind, url)) = ["git", "github"].iter().find_map(|kind| {
+        option(args, kind)
+            .and_then(Value::as_string)
+            .map(|url| (kind, url))
+    }) {
+        Some(GemSource::Git {
+            url: git_url(kind, &url),
+            reference: git_reference(args),
+        })
+    } else {
+        option(args, "source")
+            .and_then(Value::as_string)
+            .map(GemSource::Remote)
+    };
+    let source = option_source
+        .or_else(|| {
+            blocks.iter().rev().find_map(|block| match block {
+                Block::Source(url) => Some(GemSource::Remote(url.clone())),
+                Block::Path(path) => Some(GemSource::Path(path.clone())),
+                Block::Git(source) => Some(source.clone()),
+                Block::Group(_) | Block::Other => None,
+            })
+        })
+        .unwrap_or(GemSource::Default);
+
+    Some(GemDeclaration {
+        name,
+        requirements,
+        groups,
+        source,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ruby_directive() {
+        let gemfile = Gemfile::parse(r#"ruby "3.1.4", engine: "jruby", engine_version: "9.4.5.0""#);
+        assert_eq!(
+            gemfile.ruby,
+            Some(RubyDirective {
+                requirements: vec!["3.1.4".to_string()],
+                engine: Some("jruby".to_string()),
+                engine_version: Some("9.4.5.0".to_string()),
+                file: None,
+            })
+        );
+
+        let gemfile = Gemfile::parse("ruby '>= 3.2', '< 3.4' # comment");
+        assert_eq!(
+            gemfile
+                .ruby
+                .unwrap()
+                .parsed_requirements()
+                .unwrap()
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec![">= 3.2", "< 3.4"]
+        );
+
+        let gemfile = Gemfile::parse(r#"ruby file: ".ruby-version""#);
+        assert_eq!(
+            gemfile.ruby.unwrap().file,
+            Some(".ruby-version".to_string())
+        );
+    }
+
+    #[test]
+    fn test_groups() {
+        let gemfile = Gemfile::parse(
+            r#"
+            gem "puma"
+            gem "debug", group: :development
+            gem "rspec-rails", groups: [:development, :test]
+
+            group :test do
+              gem "capybara", require: false
+              gem "webmock" if ENV["WEBMOCK"]
+            end
+
+            group(:production, :staging) do
+              gem "lograge"
+            end
+        "#,
+        );
+
+        assert_eq!(
+            gemfile.groups,
+            vec!["development", "test", "production", "staging"]
+        );
+        assert!(gemfile.gem("puma").unwrap().groups.is_empty());
+        assert_eq!(gemfile.gem("capybara").unwrap().groups, vec!["test"]);
+        assert_eq!(gemfile.gem("webmock").unwrap().groups, vec!["test"]);
+        assert_eq!(
+            gemfile.gem("lograge").unwrap().groups,
+            vec!["production", "staging"]
+        );
+    }
+
+    #[test]
+    fn test_sources() {
+        let gemfile = Gemfile::parse(
+            r#"
+            source "https://rubygems.org"
+            git_source(:github) { |repo| "https://github.com/#{repo}.git" }
+
+            gem "rails", "~> 8.0", ">= 8.0.1"
+            gem "engine", path: "engines/engine"
+            gem "rack", github: "rack/rack", branch: "main"
+            gem :"not_literal" if false
+
+            source "https://gems.example.com" do
+              gem "private_gem"
+            end
+
+            path "components" do
+              gem "billing"
+            end
+
+            git "https://example.com/tools.git", tag: "v1" do
+              gem "tool_a"
+            end
+
+            gem "after_blocks"
+        "#,
+        );
+
+        assert_eq!(gemfile.sources, vec!["https://rubygems.org"]);
+        assert_eq!(
+            gemfile.gem("rails").unwrap().requirements,
+            vec!["~> 8.0", ">= 8.0.1"]
+        );
+        assert_eq!(
+            gemfile.gem("rack").unwrap().source,
+            GemSource::Git {
+                url: "https://github.com/rack/rack.git".to_string(),
+                reference: Some("main".to_string())
+            }
+        );
+        assert_eq!(
+            gemfile.gem("private_gem").unwrap().source,
+            GemSource::Remote("https://gems.example.com".to_string())
+        );
+        assert_eq!(
+            gemfile.gem("tool_a").unwrap().source,
+            GemSource::Git {
+                url: "https://example.com/tools.git".to_string(),
+                reference: Some("v1".to_string())
+            }
+        );
+        assert_eq!(
+            gemfile.gem("after_blocks").unwrap().source,
+            GemSource::Default
+        );
+        assert_eq!(
+            gemfile
+                .local_gems()
+                .map(|gem| gem.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["engine", "billing"]
+        );
+    }
+
+    #[test]
+    fn test_hash_rockets_and_dynamic_values() {
+        let gemfile = Gemfile::parse(
+            r##"
+            gem 'old', :path => 'vendor/old', :group => [:test]
+            gem "dynamic", path: File.expand_path("..", __dir__)
+            gem "interpolated", "#{VERSION}"
+            if RUBY_VERSION >= "3.0"
+              gem "modern"
+            end
+            gem "multiline",
+              path: "vendor/multiline",
+              require: false
+            gem "last"
+        "##,
+        );
+
+        let old = gemfile.gem("old").unwrap();
+        assert_eq!(old.source, GemSource::Path("vendor/old".to_string()));
+        assert_eq!(old.groups, vec!["test"]);
+        assert_eq!(gemfile.gem("dynamic").unwrap().source, GemSource::Default);
+        assert!(gemfile.gem("interpolated").unwrap().requirements.is_empty());
+        assert!(gemfile.gem("modern").is_some());
+        assert_eq!(
+            gemfile.gem("multiline").unwrap().source,
+            GemSource::Path("vendor/multiline".to_string())
+        );
+        assert!(gemfile.gem("last").unwrap().groups.is_empty());
+    }
+}
@@ -1,6 +1,7 @@
 use core::str::FromStr;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
 use std::fmt::Display;
 
 /// # Parse and store contents of Gemfile.lock
@@ -44,6 +45,9 @@ use std::fmt::Display;
 pub struct GemfileLock {
     pub bundler_version: BundlerVersion,
     pub ruby_version: RubyVersion,
+    /// Entries of the `PLATFORMS` section, for example `ruby` or `x86_64-linux`
+    pub platforms: Vec<String>,
+    pub engine: Engine,
 }
 
 impl GemfileLock {
@@ -63,10 +67,29 @@ impl GemfileLock {
         }
     }
 
+    /// The Ruby implementation from the `RUBY VERSION` section
+    #[must_use]
+    pub fn engine(&self) -> &Engine {
+        &self.engine
+    }
+
+    /// True when the lockfile can be installed on the given platform without re-resolving
+    ///
+    /// Lockfiles with the generic `ruby` platform, or without a `PLATFORMS` section, are
+    /// installable everywhere. A `-gnu` suffix is ignored, so `x86_64-linux` also matches
+    /// `x86_64-linux-gnu`.
+    #[must_use]
+    pub fn supports_platform(&self, platform: &str) -> bool {
+        self.platforms.is_empty()
+            || self.platforms.iter().any(|entry| {
+                entry == "ruby" || entry == platform || entry.strip_prefix(platform) == Some("-gnu")
+            })
+    }
+
     #[must_use]
     pub fn resolve_ruby(&self, default: &str) -> ResolvedRubyVersion {
         match &self.ruby_version {
-            RubyVersion::Explicit(version) => ResolvedRubyVersion(version.to_string()),
+            RubyVersion::Explicit(version) => ResolvedRubyVersion(version.clone()),
             RubyVersion::Default => ResolvedRubyVersion(default.to_string()),
         }
     }
@@ -74,7 +97,7 @@ impl GemfileLock {
     #[must_use]
     pub fn resolve_bundler(&self, default: &str) -> ResolvedBundlerVersion {
         match &self.bundler_version {
-            BundlerVersion::Explicit(version) => ResolvedBundlerVersion(version.to_string()),
+            BundlerVersion::Explicit(version) => ResolvedBundlerVersion(version.clone()),
             BundlerVersion::Default => ResolvedBundlerVersion(default.to_string()),
         }
     }
@@ -84,6 +107,25 @@ impl GemfileLock {
 #[serde(deny_unknown_fields)]
 pub struct ResolvedRubyVersion(pub String);
 
+impl ResolvedRubyVersion {
+    /// The ABI version native extensions are compiled against, for example `3.3.0` for `3.3.6`
+    ///
+    /// Ruby keeps the ABI stable across patch releases. Versions that can't be parsed are
+    /// returned unchanged.
+    #[must_use]
+    pub fn abi(&self) -> String {
+        let mut parts = self.0.split(['.', '-']);
+        match (parts.next(), parts.next()) {
+            (Some(major), Some(minor))
+                if major.parse::<u64>().is_ok() && minor.parse::<u64>().is_ok() =>
+            {
+                format!("{major}.{minor}.0")
+            }
+            _ => self.0.clone(),
+        }
+    }
+}
+
 impl Display for ResolvedRubyVersion {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_str(&self.0)
@@ -94,6 +136,17 @@ impl Display for ResolvedRubyVersion {
 #[serde(deny_unknown_fields)]
 pub struct ResolvedBundlerVersion(pub String);
 
+impl ResolvedBundlerVersion {
+    /// The major version, for example `4` for `4.0.1`
+    #[must_use]
+    pub fn major(&self) -> Option<u64> {
+        self.0
+            .split('.')
+            .next()
+            .and_then(|major| major.parse().ok())
+    }
+}
+
 impl Display for ResolvedBundlerVersion {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_str(&self.0)
@@ -106,6 +159,60 @@ pub enum RubyVersion {
     Default,
 }
 
+/// The Ruby implementation an application runs on
+///
+/// Read from the parenthesized engine in the `RUBY VERSION` section of the lockfile, for
+/// example `ruby 3.1.4p001 (jruby 9.4.8.0)`. Lockfiles without an engine, or without a
+/// `RUBY VERSION` section, use MRI.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Engine {
+    Mri,
+    Jruby { version: String },
+    TruffleRuby { version: String },
+}
+
+impl Engine {
+    /// True when the engine runs on the JVM and needs a JDK
+    #[must_use]
+    pub fn needs_java(&self) -> bool {
+        matches!(self, Engine::Jruby { .. })
+    }
+}
+
+impl Display for Engine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Engine::Mri => f.write_str("ruby"),
+            Engine::Jruby { version } => write!(f, "jruby {version}"),
+            Engine::TruffleRuby { version } => write!(f, "truffleruby {version}"),
+        }
+    }
+}
+
+/// Parses the engine from the line following `RUBY VERSION`
+fn parse_engine(lockfile: &str) -> Engine {
+    let Some(line) = lockfile
+        .lines()
+        .skip_while(|line| line.trim_end() != "RUBY VERSION")
+        .nth(1)
+    else {
+        return Engine::Mri;
+    };
+    let Some((name, version)) = line
+        .split_once('(')
+        .and_then(|(_, rest)| rest.split_once(')'))
+        .and_then(|(engine, _)| engine.trim().split_once(' '))
+    else {
+        return Engine::Mri;
+    };
+    let version = version.trim().to_string();
+    match name {
+        "jruby" => Engine::Jruby { version },
+        "truffleruby" => Engine::TruffleRuby { version },
+        _ => Engine::Mri,
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum BundlerVersion {
     Explicit(String),
@@ -121,37 +228,156 @@ impl FromStr for GemfileLock {
         let main_ruby_version_re =
             Regex::new("RUBY VERSION\\s   ruby (\\d+\\.\\d+\\.\\d+((-|\\.)\\S*\\d+)?)")
                 .expect("Clippy checked");
-        let jruby_version_re = Regex::new("\\(jruby ((\\d+|\\.)+)\\)").expect("Clippy checked");
 
         let bundler_version = match bundled_with_re.captures(string).and_then(|c| c.get(1)) {
             Some(result) => BundlerVersion::Explicit(result.as_str().to_string()),
             None => BundlerVersion::Default,
         };
 
+        let engine = parse_engine(string);
         let ruby_version = match main_ruby_version_re.captures(string).and_then(|c| c.get(1)) {
-            Some(main_ruby_match) => match jruby_version_re.captures(string).and_then(|c| c.get(1))
-            {
-                Some(jruby_match) => RubyVersion::Explicit(format!(
-                    "{}-jruby-{}",
-                    main_ruby_match.as_str(),
-                    jruby_match.as_str()
-                )),
-                None => RubyVersion::Explicit(main_ruby_match.as_str().to_string()),
+            Some(main_ruby_match) => match &engine {
+                Engine::Jruby { version } => {
+                    RubyVersion::Explicit(format!("{}-jruby-{version}", main_ruby_match.as_str()))
+                }
+                Engine::Mri | Engine::TruffleRuby { .. } => {
+                    RubyVersion::Explicit(main_ruby_match.as_str().to_string())
+                }
             },
             None => RubyVersion::Default,
         };
 
+        let platforms = string
+            .lines()
+            .skip_while(|line| line.trim_end() != "PLATFORMS")
+            .skip(1)
+            .take_while(|line| line.starts_with("  "))
+            .map(|line| line.trim().to_string())
+            .collect();
+
         Ok(Self {
             bundler_version,
             ruby_version,
+            platforms,
+            engine,
         })
     }
 }
 
+/// Gems and versions from the specs of every source in a `Gemfile.lock`
+///
+/// Specs are indented four spaces, their dependencies are indented six.
+#[must_use]
+pub fn locked_gems(lockfile: &str) -> BTreeMap<String, String> {
+    let mut gems = BTreeMap::<String, String>::new();
+    for line in lockfile.lines() {
+        let Some(spec) = line
+            .strip_prefix("    ")
+            .filter(|spec| !spec.starts_with(' '))
+        else {
+            continue;
+        };
+        let Some((name, version)) = spec
+            .strip_suffix(')')
+            .and_then(|spec| spec.split_once(" ("))
+        else {
+            continue;
+        };
+        gems.entry(name.to_string())
+            .and_modify(|versions| {
+                versions.push_str(", ");
+                versions.push_str(version);
+            })
+            .or_insert_with(|| version.to_string());
+    }
+    gems
+}
+
+/// Gems listed in the `DEPENDENCIES` section of a `Gemfile.lock`, the gems in the `Gemfile`
+///
+/// Gems that are only required by other gems are not included. A trailing `!` (a gem from a
+/// git or path source) is removed.
+#[must_use]
+pub fn direct_dependencies(lockfile: &str) -> BTreeSet<String> {
+    lockfile
+        .lines()
+        .skip_while(|line| *line != "DEPENDENCIES")
+        .skip(1)
+        .take_while(|line| !line.trim().is_empty())
+        .filter_map(|line| line.split_whitespace().next())
+        .map(|name| name.trim_end_matches('!').to_string())
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_direct_dependencies() {
+        let dependencies = direct_dependencies(
+            "GEM
+  remote: https://rubygems.org/
+  specs:
+    dotenv (3.1.2)
+    dotenv-rails (3.1.2)
+      dotenv (= 3.1.2)
+
+PLATFORMS
+  x86_64-linux
+
+DEPENDENCIES
+  dotenv-rails
+  my_gem!
+  rails (~> 7.1)
+
+BUNDLED WITH
+   2.5.6
+",
+        );
+        assert_eq!(
+            dependencies.into_iter().collect::<Vec<_>>(),
+            vec![
+                String::from("dotenv-rails"),
+                String::from("my_gem"),
+                String::from("rails")
+            ]
+        );
+        assert!(direct_dependencies("GEM\n").is_empty());
+    }
+
+    #[test]
+    fn test_locked_gems() {
+        let gems = locked_gems(
+            "GEM
+  remote: https://rubygems.org/
+  specs:
+    nokogiri (1.16.7-aarch64-linux)
+      racc (~> 1.4)
+    nokogiri (1.16.7-x86_64-linux)
+      racc (~> 1.4)
+    racc (1.8.1)
+
+PLATFORMS
+  aarch64-linux
+  x86_64-linux
+
+BUNDLED WITH
+   2.5.6
+",
+        );
+        assert_eq!(
+            gems.into_iter().collect::<Vec<_>>(),
+            vec![
+                (
+                    String::from("nokogiri"),
+                    String::from("1.16.7-aarch64-linux, 1.16.7-x86_64-linux")
+                ),
+                (String::from("racc"), String::from("1.8.1")),
+            ]
+        );
+    }
+
     #[test]
     fn test_does_not_capture_patch_version() {
         let info = GemfileLock::from_str(
@@ -255,6 +481,99 @@ BUNDLED WITH
             info.ruby_version,
             RubyVersion::Explicit("3.1.0".to_string())
         );
+        assert_eq!(
+            info.platforms,
+            vec![
+                String::from("ruby"),
+                String::from("x86_64-darwin-20"),
+                String::from("x86_64-linux")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_supports_platform() {
+        let lockfile = |platforms: &[&str]| GemfileLock {
+            bundler_version: BundlerVersion::Default,
+            ruby_version: RubyVersion::Default,
+            platforms: platforms.iter().map(ToString::to_string).collect(),
+            engine: Engine::Mri,
+        };
+
+        assert!(lockfile(&[]).supports_platform("x86_64-linux"));
+        assert!(lockfile(&["ruby", "arm64-darwin-23"]).supports_platform("x86_64-linux"));
+        assert!(lockfile(&["x86_64-linux-gnu"]).supports_platform("x86_64-linux"));
+        assert!(!lockfile(&["arm64-darwin-23"]).supports_platform("x86_64-linux"));
+        assert!(!lockfile(&["x86_64-linux-musl"]).supports_platform("x86_64-linux"));
+        assert!(!lockfile(&["x86_64-linux"]).supports_platform("aarch64-linux"));
+    }
+
+    #[test]
+    fn test_ruby_abi() {
+        assert_eq!(ResolvedRubyVersion(String::from("3.3.6")).abi(), "3.3.0");
+        assert_eq!(
+            ResolvedRubyVersion(String::from("3.4.0-preview1")).abi(),
+            "3.4.0"
+        );
+        assert_eq!(ResolvedRubyVersion(String::from("3")).abi(), "3");
+    }
+
+    #[test]
+    fn test_bundler_major() {
+        assert_eq!(
+            ResolvedBundlerVersion(String::from("4.0.1")).major(),
+            Some(4)
+        );
+        assert_eq!(
+            ResolvedBundlerVersion(String::from("2.5.6")).major(),
+            Some(2)
+        );
+        assert_eq!(ResolvedBundlerVersion(String::new()).major(), None);
+    }
+
+    /// Bundler 4 writes a `CHECKSUMS` section to new lockfiles, its entries are indented like
+    /// `PLATFORMS` and `DEPENDENCIES` but look like specs
+    #[test]
+    fn test_bundler_4_checksums() {
+        let lockfile = r"GEM
+  remote: https://rubygems.org/
+  specs:
+    rack (3.1.8)
+
+PLATFORMS
+  ruby
+  x86_64-linux
+
+DEPENDENCIES
+  rack
+
+CHECKSUMS
+  rack (3.1.8) sha256=0000000000000000000000000000000000000000000000000000000000000000
+
+RUBY VERSION
+   ruby 3.4.1p0
+
+BUNDLED WITH
+   4.0.1
+";
+        let info = GemfileLock::from_str(lockfile).unwrap();
+        assert_eq!(
+            info.bundler_version,
+            BundlerVersion::Explicit(String::from("4.0.1"))
+        );
+        assert_eq!(
+            info.ruby_version,
+            RubyVersion::Explicit(String::from("3.4.1"))
+        );
+        assert_eq!(info.platforms, vec!["ruby", "x86_64-linux"]);
+        assert_eq!(
+            locked_gems(lockfile).into_iter().collect::<Vec<_>>(),
+            vec![(String::from("rack"), String::from("3.1.8"))]
+        );
+        assert_eq!(
+            direct_dependencies(lockfile),
+            BTreeSet::from([String::from("rack")])
+        );
     }
 
     #[test]
@@ -284,5 +603,41 @@ DEPENDENCIES
             info.ruby_version,
             RubyVersion::Explicit(String::from("2.5.7-jruby-9.2.13.0"))
         );
+        assert_eq!(
+            info.engine(),
+            &Engine::Jruby {
+                version: String::from("9.2.13.0")
+            }
+        );
+        assert!(info.engine().needs_java());
+    }
+
+    #[test]
+    fn test_engine() {
+        let info = GemfileLock::from_str(
+            r"
+GEM
+  specs:
+    # (jruby 9.4.8.0) mentioned in a comment
+    truffleruby-gem (1.0.0)
+
+RUBY VERSION
+   ruby 3.2.2p0 (truffleruby 24.1.1)
+",
+        )
+        .unwrap();
+        assert_eq!(
+            info.engine(),
+            &Engine::TruffleRuby {
+                version: String::from("24.1.1")
+            }
+        );
+        assert_eq!(
+            info.ruby_version,
+            RubyVersion::Explicit(String::from("3.2.2"))
+        );
+        assert!(!info.engine().needs_java());
+
+        assert_eq!(GemfileLock::from_str("").unwrap().engine(), &Engine::Mri);
     }
 }
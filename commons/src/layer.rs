@@ -1 +1,2 @@
 pub mod diff_migrate;
+pub mod env_layer;
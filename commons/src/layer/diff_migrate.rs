@@ -140,6 +140,11 @@ impl DiffMigrateLayer {
     ///
     /// After that this function callse [`cached_layer`] on the new layer.
     ///
+    /// Moving a layer changes its path. Files that recorded an absolute path into the prior
+    /// layer, such as executables generated by `gem install` or a bootsnap cache, will point
+    /// to a path that no longer exists. Layers whose path is public (for example `GEM_PATH`)
+    /// should keep their name.
+    ///
     /// # Panics
     ///
     /// This function should not panic unless there's an internal bug.
@@ -0,0 +1,111 @@
+//! Write environment variables to a layer
+//!
+//! Many layers only exist to set environment variables. These helpers create an
+//! uncached layer that is available at build and launch, write the environment to it,
+//! and return the [`LayerEnv`] so it can be applied to the current build environment.
+//!
+//! ```no_run
+//! use commons::layer::env_layer::default_env_layer;
+//! # use libcnb::build::BuildContext;
+//! use libcnb::data::layer_name;
+//! use libcnb::layer_env::Scope;
+//! use libcnb::Env;
+//!
+//! # fn build<B: libcnb::Buildpack>(context: &BuildContext<B>) -> libcnb::Result<(), B::Error> {
+//! let env = default_env_layer(
+//!     context,
+//!     layer_name!("env_defaults"),
+//!     [("RACK_ENV", "production"), ("RAILS_ENV", "production")],
+//! )?
+//! .apply(Scope::Build, &Env::from_current());
+//! # Ok(())
+//! # }
+//! ```
+use libcnb::build::BuildContext;
+use libcnb::data::layer::LayerName;
+use libcnb::layer::UncachedLayerDefinition;
+use libcnb::layer_env::{LayerEnv, ModificationBehavior, Scope};
+use std::ffi::OsString;
+
+/// Write a [`LayerEnv`] to a new layer available at build and launch
+///
+/// Returns the environment read back from the layer.
+///
+/// # Errors
+///
+/// Errors if the layer cannot be created or its environment cannot be written or read.
+pub fn write_env_layer<B: libcnb::Buildpack>(
+    context: &BuildContext<B>,
+    layer_name: LayerName,
+    layer_env: LayerEnv,
+) -> libcnb::Result<LayerEnv, B::Error> {
+    let layer_ref = context.uncached_layer(
+        layer_name,
+        UncachedLayerDefinition {
+            build: true,
+            launch: true,
+        },
+    )?;
+    layer_ref.write_env(layer_env)?;
+    layer_ref.read_env()
+}
+
+/// Write default environment variables to a new layer available at build and launch
+///
+/// Values are only used when the variable is not already set, for example by the user.
+///
+/// # Errors
+///
+/// Errors if the layer cannot be created or its environment cannot be written or read.
+pub fn default_env_layer<B, I, K, V>(
+    context: &BuildContext<B>,
+    layer_name: LayerName,
+    defaults: I,
+) -> libcnb::Result<LayerEnv, B::Error>
+where
+    B: libcnb::Buildpack,
+    I: IntoIterator<Item = (K, V)>,
+    K: Into<OsString>,
+    V: Into<OsString>,
+{
+    write_env_layer(context, layer_name, default_layer_env(defaults))
+}
+
+/// A [`LayerEnv`] that sets each value for all scopes, unless it's already set
+fn default_layer_env<I, K, V>(defaults: I) -> LayerEnv
+where
+    I: IntoIterator<Item = (K, V)>,
+    K: Into<OsString>,
+    V: Into<OsString>,
+{
+    defaults
+        .into_iter()
+        .fold(LayerEnv::new(), |layer_env, (name, value)| {
+            layer_env.chainable_insert(Scope::All, ModificationBehavior::Default, name, value)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libcnb::Env;
+
+    #[test]
+    fn test_default_layer_env_does_not_override() {
+        let mut env = Env::new();
+        env.insert("RAILS_ENV", "staging");
+
+        let env = default_layer_env([("RAILS_ENV", "production"), ("RACK_ENV", "production")])
+            .apply(Scope::Build, &env);
+
+        assert_eq!(env.get("RAILS_ENV"), Some(&OsString::from("staging")));
+        assert_eq!(env.get("RACK_ENV"), Some(&OsString::from("production")));
+    }
+
+    #[test]
+    fn test_default_layer_env_applies_at_launch() {
+        let env = default_layer_env([("MALLOC_ARENA_MAX", "2")]).apply(Scope::Launch, &Env::new());
+
+        assert_eq!(env.get("MALLOC_ARENA_MAX"), Some(&OsString::from("2")));
+    }
+}
@@ -1,6 +1,11 @@
+pub mod assets;
 pub mod cache;
 pub mod display;
 pub mod gem_version;
+pub mod gemfile;
 pub mod gemfile_lock;
 pub mod layer;
 pub mod metadata_digest;
+pub mod output_snapshot;
+pub mod rake_task_detect;
+pub mod requirements;
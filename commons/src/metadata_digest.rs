@@ -267,6 +267,20 @@ pub struct Changed {
     platform_env: bool,
 }
 
+impl Changed {
+    /// True when a tracked file was added, removed, or modified
+    #[must_use]
+    pub fn files_changed(&self) -> bool {
+        self.files.is_some()
+    }
+
+    /// True when the user configured environment variables changed
+    #[must_use]
+    pub fn platform_env_changed(&self) -> bool {
+        self.platform_env
+    }
+}
+
 impl Display for Changed {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let Changed {
@@ -422,6 +436,97 @@ impl PathsDigest {
     }
 }
 
+/// A single hash of many files, and one of the platform environment
+///
+/// [`MetadataDigest`] stores a hash per file so it can report which file changed, its
+/// size in layer metadata grows with the number of files. Use this for inputs such as
+/// asset directories with thousands of files, where knowing that something changed is enough.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Default)]
+#[serde(deny_unknown_fields)]
+pub struct CombinedDigest {
+    platform_env: String,
+    files: String,
+}
+
+impl CombinedDigest {
+    /// Create new from inputs
+    ///
+    /// # Errors
+    ///
+    /// Errors if one of the files cannot be read from disk.
+    pub fn new_env_files(platform: &impl Platform, files: &[&Path]) -> Result<Self, DigestError> {
+        Ok(Self {
+            platform_env: sha_from_env(platform.env()).0,
+            files: sha_from_files(files)?,
+        })
+    }
+
+    /// Which parts changed since `old`, `None` when nothing changed
+    #[must_use]
+    pub fn changed(&self, old: &CombinedDigest) -> Option<CombinedChanged> {
+        let changed = CombinedChanged {
+            files: self.files != old.files,
+            platform_env: self.platform_env != old.platform_env,
+        };
+        (changed.files || changed.platform_env).then_some(changed)
+    }
+}
+
+/// Which parts of a [`CombinedDigest`] changed, displays as a reason for the user
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CombinedChanged {
+    files: bool,
+    platform_env: bool,
+}
+
+impl CombinedChanged {
+    /// True when the hash of the files changed
+    #[must_use]
+    pub fn files_changed(&self) -> bool {
+        self.files
+    }
+
+    /// True when the hash of the platform environment changed
+    #[must_use]
+    pub fn platform_env_changed(&self) -> bool {
+        self.platform_env
+    }
+}
+
+impl Display for CombinedChanged {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.files, self.platform_env) {
+            (true, true) => write!(
+                f,
+                "changes detected in tracked files and {PLATFORM_ENV_VAR}"
+            ),
+            (true, false) => write!(f, "change detected in tracked files"),
+            (false, true) => write!(f, "change detected in {PLATFORM_ENV_VAR}"),
+            (false, false) => Ok(()),
+        }
+    }
+}
+
+/// One SHA256 hash of the names and contents of every file, in the order given
+///
+/// Contents are read as bytes, so binary files such as images can be hashed.
+///
+/// # Errors
+///
+/// Errors if one of the files cannot be read from disk.
+pub fn sha_from_files(paths: &[&Path]) -> Result<String, DigestError> {
+    let mut hasher = sha2::Sha256::new();
+    for path in paths {
+        let contents = fs_err::read(path)
+            .map_err(|error| DigestError::CannotReadFile(path.to_path_buf(), error))?;
+        hasher.update(path.as_os_str().as_encoded_bytes());
+        hasher.update([0]);
+        hasher.update((contents.len() as u64).to_le_bytes());
+        hasher.update(&contents);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum DigestError {
     #[error("Attempted to read file for digest but cannot: {1}")]
@@ -534,6 +639,35 @@ mod test {
         );
     }
 
+    #[test]
+    fn combined_digest_changed() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let logo = tempdir.path().join("logo.png");
+        fs_err::write(&logo, [0x89, 0x50, 0x4e, 0x47, 0xff]).unwrap();
+        let mut context = FakeContext::default();
+
+        let one = CombinedDigest::new_env_files(&context.platform, &[&logo]).unwrap();
+        assert_eq!(one.changed(&one.clone()), None);
+        assert_eq!(
+            one.changed(&CombinedDigest::default()).unwrap().to_string(),
+            "changes detected in tracked files and user configured environment variables"
+        );
+
+        fs_err::write(&logo, [0x89, 0x50, 0x4e, 0x47]).unwrap();
+        let two = CombinedDigest::new_env_files(&context.platform, &[&logo]).unwrap();
+        assert_eq!(
+            two.changed(&one).unwrap().to_string(),
+            "change detected in tracked files"
+        );
+
+        context.platform.env.insert("RAILS_ENV", "staging");
+        let three = CombinedDigest::new_env_files(&context.platform, &[&logo]).unwrap();
+        assert_eq!(
+            three.changed(&two).unwrap().to_string(),
+            "change detected in user configured environment variables"
+        );
+    }
+
     #[test]
     fn metadata_digest_files_changed() {
         let tempdir = tempfile::tempdir().unwrap();
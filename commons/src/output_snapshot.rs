@@ -0,0 +1,200 @@
+//! Compare build output against a reviewed snapshot
+//!
+//! Asserting on fragments of build output with `assert_contains!` breaks whenever formatting
+//! changes, and the fix is a hand-edited string. A snapshot stores the whole output instead,
+//! so a change to it is reviewed as a diff:
+//!
+//! ```ignore
+//! commons::assert_snapshot!("default_app_build", context.pack_stdout);
+//! ```
+//!
+//! Snapshots are stored in `tests/snapshots/<name>.snap` of the crate running the test. Output
+//! is [`scrub`]bed before it's compared, so colors and timings don't cause failures. When the
+//! output does not match, it's written next to the snapshot as `<name>.snap.new` and the test
+//! fails with a line diff. Review the diff, then run the test again with `UPDATE_SNAPSHOTS=1`
+//! to accept it. A missing snapshot fails the same way, so every snapshot is reviewed before
+//! it's committed.
+use regex::Regex;
+use std::path::Path;
+
+/// When set to `1`, mismatched and missing snapshots are overwritten instead of failing
+pub const UPDATE_SNAPSHOTS_ENV_KEY: &str = "UPDATE_SNAPSHOTS";
+
+/// Stands in for timings that change from build to build
+pub const DURATION_PLACEHOLDER: &str = "<duration>";
+
+/// Compares output against the snapshot `tests/snapshots/<name>.snap` of the calling crate
+///
+/// Panics with a diff when they don't match, see the [module docs](crate::output_snapshot).
+#[macro_export]
+macro_rules! assert_snapshot {
+    ($name:expr, $output:expr) => {
+        if let Err(message) = $crate::output_snapshot::check_snapshot(
+            &::std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("tests")
+                .join("snapshots")
+                .join(format!("{}.snap", $name)),
+            &$output,
+        ) {
+            panic!("{message}");
+        }
+    };
+}
+
+/// Removes output that changes between runs of the same build
+///
+/// - ANSI escape codes are removed.
+/// - Timings such as `(2.3s)`, `(< 0.1s)`, `1m 05s`, and `12ms` are replaced with
+///   [`DURATION_PLACEHOLDER`]. The dots a timer prints while a step runs are replaced with
+///   ` ... `.
+/// - Trailing whitespace is removed from every line.
+///
+/// # Panics
+///
+/// The regular expressions are hard coded and will not panic.
+#[must_use]
+pub fn scrub(output: &str) -> String {
+    let ansi = Regex::new(r"\x1b\[[0-9;?]*[A-Za-z]").expect("Clippy checked");
+    let durations =
+        Regex::new(r"(< )?\b(\d+h )?(\d+m )?\d+(\.\d+)?(ms|s)\b|\b\d+h \d+m\b|\b\d+m \d+s\b")
+            .expect("Clippy checked");
+    let timer_dots = Regex::new(&format!(
+        r"[ .]+\({}\)",
+        regex::escape(DURATION_PLACEHOLDER)
+    ))
+    .expect("Clippy checked");
+
+    let output = ansi.replace_all(output, "");
+    let output = durations.replace_all(&output, DURATION_PLACEHOLDER);
+    let output = timer_dots.replace_all(&output, format!(" ... ({DURATION_PLACEHOLDER})"));
+    output
+        .lines()
+        .map(str::trim_end)
+        .collect::<Vec<_>>()
+        .join("\n")
+        + "\n"
+}
+
+/// Compares scrubbed output against a snapshot file
+///
+/// # Errors
+///
+/// Errors with a message and a line diff when the snapshot is missing or does not match,
+/// after writing the output to `<snapshot>.new`. With `UPDATE_SNAPSHOTS=1` the snapshot is
+/// overwritten instead.
+pub fn check_snapshot(snapshot: &Path, output: &str) -> Result<(), String> {
+    let actual = scrub(output);
+    let expected = fs_err::read_to_string(snapshot).ok();
+    if expected.as_deref() == Some(actual.as_str()) {
+        let _ = fs_err::remove_file(pending(snapshot));
+        return Ok(());
+    }
+
+    let update = std::env::var(UPDATE_SNAPSHOTS_ENV_KEY).is_ok_and(|value| value.trim() == "1");
+    let (path, verb) = if update {
+        (snapshot.to_path_buf(), "Updated")
+    } else {
+        (pending(snapshot), "Wrote")
+    };
+    if let Some(parent) = path.parent() {
+        fs_err::create_dir_all(parent).map_err(|error| error.to_string())?;
+    }
+    fs_err::write(&path, &actual).map_err(|error| error.to_string())?;
+    if update {
+        let _ = fs_err::remove_file(pending(snapshot));
+        return Ok(());
+    }
+
+    let heading = match expected {
+        Some(_) => format!("Output does not match snapshot {}", snapshot.display()),
+        None => format!("Snapshot {} does not exist", snapshot.display()),
+    };
+    Err(format!(
+        "{heading}\n\n{diff}\n{verb} the output to {path}. Review it and run again with \
+         {UPDATE_SNAPSHOTS_ENV_KEY}=1 to accept it.",
+        diff = diff(expected.as_deref().unwrap_or_default(), &actual),
+        path = path.display(),
+    ))
+}
+
+fn pending(snapshot: &Path) -> std::path::PathBuf {
+    let mut name = snapshot.as_os_str().to_owned();
+    name.push(".new");
+    name.into()
+}
+
+/// A line diff with `-` for expected lines and `+` for actual lines
+fn diff(expected: &str, actual: &str) -> String {
+    let old = expected.lines().collect::<Vec<_>>();
+    let new = actual.lines().collect::<Vec<_>>();
+
+    // Longest common subsequence lengths of every pair of suffixes
+    let mut lengths = vec![vec![0_usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i][j] = if old[i] == new[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push(format!("  {}", old[i]));
+            i += 1;
+            j += 1;
+        } else if j < new.len() && (i == old.len() || lengths[i][j + 1] >= lengths[i + 1][j]) {
+            lines.push(format!("+ {}", new[j]));
+            j += 1;
+        } else {
+            lines.push(format!("- {}", old[i]));
+            i += 1;
+        }
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scrub() {
+        let output = "\x1b[1;35m# Heroku Ruby Buildpack\x1b[0m\n\n- Installing ... .... (2.3s)   \n- Done (< 0.1s)\n- Finished in 1m 05s\n- Ruby install  12ms\n- Ruby version `3.3.6`\n";
+        assert_eq!(
+            scrub(output),
+            "# Heroku Ruby Buildpack\n\n- Installing ... (<duration>)\n- Done ... (<duration>)\n- Finished in <duration>\n- Ruby install  <duration>\n- Ruby version `3.3.6`\n"
+        );
+    }
+
+    #[test]
+    fn test_diff() {
+        assert_eq!(diff("a\nb\nc\n", "a\nc\nd\n"), "  a\n- b\n  c\n+ d");
+    }
+
+    #[test]
+    fn test_check_snapshot() {
+        let dir = tempfile::tempdir().unwrap();
+        let snapshot = dir.path().join("snapshots").join("build.snap");
+
+        let error = check_snapshot(&snapshot, "- Installing (1.2s)\n").unwrap_err();
+        assert!(error.contains("does not exist"), "{error}");
+        assert!(error.contains("+ - Installing ... (<duration>)"), "{error}");
+        assert_eq!(
+            fs_err::read_to_string(pending(&snapshot)).unwrap(),
+            "- Installing ... (<duration>)\n"
+        );
+
+        fs_err::rename(pending(&snapshot), &snapshot).unwrap();
+        assert_eq!(check_snapshot(&snapshot, "- Installing (4.5s)\n"), Ok(()));
+
+        let error = check_snapshot(&snapshot, "- Downloading (1.2s)\n").unwrap_err();
+        assert!(error.contains("does not match"), "{error}");
+        assert!(error.contains("- - Installing"), "{error}");
+        assert!(error.contains("+ - Downloading"), "{error}");
+    }
+}
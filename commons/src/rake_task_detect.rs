@@ -0,0 +1,120 @@
+use std::collections::HashSet;
+use std::convert::Infallible;
+use std::str::FromStr;
+
+/// Tasks an application declares, parsed from `rake -P` output
+///
+/// Lines starting with `rake ` declare a task, indented lines below them are that task's
+/// prerequisites and are not tasks themselves. Task names are matched exactly.
+///
+/// ```rust
+/// use commons::rake_task_detect::RakeDetect;
+///
+/// let rake_detect = RakeDetect::from_rake_output("rake assets:precompile\n    environment\n");
+/// assert!(rake_detect.has_task("assets:precompile"));
+/// assert!(!rake_detect.has_task("environment"));
+/// assert_eq!(rake_detect.tasks(), ["assets:precompile"]);
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RakeDetect {
+    tasks: Vec<String>,
+    lookup: HashSet<String>,
+}
+
+impl RakeDetect {
+    #[must_use]
+    pub fn from_rake_output(output: &str) -> Self {
+        let tasks = output
+            .lines()
+            .filter_map(|line| line.strip_prefix("rake "))
+            .map(|task| task.trim().to_string())
+            .collect::<Vec<_>>();
+        let lookup = tasks.iter().cloned().collect();
+
+        Self { tasks, lookup }
+    }
+
+    /// True when the application declares a task with exactly this name
+    #[must_use]
+    pub fn has_task(&self, task: &str) -> bool {
+        self.lookup.contains(task)
+    }
+
+    /// Task names in the order rake printed them
+    #[must_use]
+    pub fn tasks(&self) -> &[String] {
+        &self.tasks
+    }
+}
+
+impl FromStr for RakeDetect {
+    type Err = Infallible;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        Ok(Self::from_rake_output(string))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parsing_rake_dash_p() {
+        let rake_detect = RakeDetect::from_rake_output(
+            r"
+rake about
+    environment
+rake action_mailbox:ingress:environment
+rake action_mailbox:ingress:exim
+    action_mailbox:ingress:environment
+rake action_mailbox:ingress:postfix
+    action_mailbox:ingress:environment
+rake action_mailbox:ingress:qmail
+    action_mailbox:ingress:environment
+rake action_mailbox:install
+rake action_mailbox:install:migrations
+rake action_text:install
+rake action_text:install:migrations
+rake active_storage:install
+    environment
+rake active_storage:install:migrations
+rake active_storage:update
+    environment
+rake app:binstub:yarn
+rake app:template
+    environment
+rake app:templates:copy
+rake app:update
+    update:configs
+    update:bin
+    update:active_storage
+    update:upgrade_guide_info
+rake app:update:active_storage
+rake app:update:bin
+rake app:update:configs
+rake app:update:upgrade_guide_info
+rake assets:bench
+rake assets:clean
+    environment
+rake assets:clobber
+    environment
+rake assets:environment
+rake assets:precompile
+    environment
+    yarn:install
+        ",
+        );
+
+        assert!(rake_detect.has_task("assets:precompile"));
+        assert!(rake_detect.has_task("app:update:bin"));
+        assert!(!rake_detect.has_task("environment"));
+        assert!(!rake_detect.has_task("assets"));
+        assert!(!rake_detect.has_task("precompile"));
+        assert_eq!(
+            rake_detect.tasks().first().map(String::as_str),
+            Some("about")
+        );
+        assert_eq!(rake_detect.tasks().len(), 25);
+    }
+}
@@ -0,0 +1,276 @@
+//! Resolve version requirements against a list of available versions
+//!
+//! Requirements use the same syntax as a `Gemfile`:
+//!
+//! - `3.3` or `3.3.5` matches that version, or any version it's a prefix of (whole segments only,
+//!   so `3.3` matches `3.3.5` but not `3.30.0`)
+//! - `= 3.3.5`, `!= 3.3.5`, `> 3.2`, `>= 3.2`, `< 3.4`, `<= 3.4` compare versions
+//! - `~> 3.3.0` is pessimistic, it matches `>= 3.3.0` and `< 3.4`
+//!
+//! Multiple requirements can be separated by commas, all of them must match.
+//!
+//! ```rust
+//! use commons::requirements::{parse_requirements, resolve};
+//!
+//! let requirements = parse_requirements(">= 3.2, < 3.4").unwrap();
+//! let available = ["3.1.6", "3.2.6", "3.3.5", "3.4.1"];
+//!
+//! assert_eq!(resolve(&requirements, &available), Some("3.3.5"));
+//! ```
+use crate::gem_version::GemVersion;
+use std::fmt;
+use std::str::FromStr;
+
+/// A single version requirement such as `>= 3.2` or `~> 3.3.0`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Requirement {
+    op: Op,
+    version: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Prefix,
+    Eq,
+    NotEq,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+    Pessimistic,
+}
+
+impl Op {
+    fn as_str(self) -> &'static str {
+        match self {
+            Op::Prefix => "",
+            Op::Eq => "=",
+            Op::NotEq => "!=",
+            Op::Gt => ">",
+            Op::Gte => ">=",
+            Op::Lt => "<",
+            Op::Lte => "<=",
+            Op::Pessimistic => "~>",
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum RequirementError {
+    #[error("Empty version requirement")]
+    Empty,
+
+    #[error("Invalid version `{version}` in requirement `{requirement}`")]
+    InvalidVersion {
+        requirement: String,
+        version: String,
+    },
+}
+
+impl Requirement {
+    /// Returns true if the version satisfies this requirement
+    #[must_use]
+    pub fn matches(&self, version: &str) -> bool {
+        if self.op == Op::Prefix {
+            return version == self.version || version.starts_with(&format!("{}.", self.version));
+        }
+
+        let (Ok(version), Ok(required)) = (
+            GemVersion::from_str(version),
+            GemVersion::from_str(&self.version),
+        ) else {
+            return false;
+        };
+
+        match self.op {
+            Op::Prefix => unreachable!("handled above"),
+            Op::Eq => version == required,
+            Op::NotEq => version != required,
+            Op::Gt => version > required,
+            Op::Gte => version >= required,
+            Op::Lt => version < required,
+            Op::Lte => version <= required,
+            Op::Pessimistic => {
+                version >= required
+                    && pessimistic_upper_bound(&self.version).is_some_and(|upper| version < upper)
+            }
+        }
+    }
+}
+
+/// The exclusive upper bound of a `~>` requirement, `3.3.0` becomes `3.4` and `3.3` becomes `4`
+fn pessimistic_upper_bound(version: &str) -> Option<GemVersion> {
+    let mut segments = version
+        .split('.')
+        .map_while(|segment| segment.parse::<u32>().ok())
+        .collect::<Vec<u32>>();
+    if segments.len() > 1 {
+        segments.pop();
+    }
+    let last = segments.last_mut()?;
+    *last += 1;
+
+    GemVersion::from_str(
+        &segments
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("."),
+    )
+    .ok()
+}
+
+impl FromStr for Requirement {
+    type Err = RequirementError;
+
+    fn from_str(requirement: &str) -> Result<Self, Self::Err> {
+        let trimmed = requirement.trim();
+        let (op, version) = [
+            Op::Pessimistic,
+            Op::NotEq,
+            Op::Gte,
+            Op::Lte,
+            Op::Eq,
+            Op::Gt,
+            Op::Lt,
+        ]
+        .into_iter()
+        .find_map(|op| {
+            trimmed
+                .strip_prefix(op.as_str())
+                .map(|version| (op, version.trim()))
+        })
+        .unwrap_or((Op::Prefix, trimmed));
+
+        if version.is_empty() {
+            return Err(RequirementError::Empty);
+        }
+        if GemVersion::from_str(version).is_err() {
+            return Err(RequirementError::InvalidVersion {
+                requirement: requirement.to_string(),
+                version: version.to_string(),
+            });
+        }
+
+        Ok(Requirement {
+            op,
+            version: version.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for Requirement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.op {
+            Op::Prefix => write!(f, "{}", self.version),
+            op => write!(f, "{} {}", op.as_str(), self.version),
+        }
+    }
+}
+
+/// Parse comma separated requirements such as `>= 3.2, < 3.4`
+///
+/// # Errors
+///
+/// Errors if any requirement is empty or contains an invalid version.
+pub fn parse_requirements(requirements: &str) -> Result<Vec<Requirement>, RequirementError> {
+    requirements.split(',').map(Requirement::from_str).collect()
+}
+
+/// Returns true if the version satisfies every requirement
+#[must_use]
+pub fn matches_all(requirements: &[Requirement], version: &str) -> bool {
+    requirements
+        .iter()
+        .all(|requirement| requirement.matches(version))
+}
+
+/// Returns the highest candidate that satisfies every requirement
+///
+/// Candidates that are not valid versions are ignored.
+#[must_use]
+pub fn resolve<'a, S: AsRef<str>>(
+    requirements: &[Requirement],
+    candidates: &'a [S],
+) -> Option<&'a str> {
+    candidates
+        .iter()
+        .map(AsRef::as_ref)
+        .filter(|candidate| matches_all(requirements, candidate))
+        .filter_map(|candidate| {
+            GemVersion::from_str(candidate)
+                .ok()
+                .map(|version| (version, candidate))
+        })
+        .max_by(|(a, _), (b, _)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+        .map(|(_, candidate)| candidate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn req(requirement: &str) -> Requirement {
+        Requirement::from_str(requirement).unwrap()
+    }
+
+    #[test]
+    fn test_prefix() {
+        assert!(req("3.3").matches("3.3.5"));
+        assert!(req("3.3.5").matches("3.3.5"));
+        assert!(!req("3.3").matches("3.30.0"));
+        assert!(!req("3.3.5").matches("3.3"));
+    }
+
+    #[test]
+    fn test_comparisons() {
+        assert!(req("= 3.3.5").matches("3.3.5"));
+        assert!(!req("=3.3.5").matches("3.3.6"));
+        assert!(req("!= 3.3.5").matches("3.3.6"));
+        assert!(req("> 3.2").matches("3.2.1"));
+        assert!(req(">= 3.2").matches("3.2"));
+        assert!(req("< 3.4").matches("3.3.9"));
+        assert!(!req("<= 3.3").matches("3.3.1"));
+    }
+
+    #[test]
+    fn test_pessimistic() {
+        assert!(req("~> 3.3.0").matches("3.3.7"));
+        assert!(!req("~> 3.3.0").matches("3.4.0"));
+        assert!(req("~> 3.3").matches("3.9.0"));
+        assert!(!req("~> 3.3").matches("4.0.0"));
+        assert!(!req("~> 3.3").matches("3.2.9"));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(Requirement::from_str(">="), Err(RequirementError::Empty));
+        assert!(matches!(
+            Requirement::from_str(">= lol!"),
+            Err(RequirementError::InvalidVersion { .. })
+        ));
+        assert!(parse_requirements(">= 3.2,").is_err());
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(req("~>3.3.0").to_string(), "~> 3.3.0");
+        assert_eq!(req(" 3.3 ").to_string(), "3.3");
+    }
+
+    #[test]
+    fn test_resolve() {
+        let available = ["3.1.6", "3.3.5", "3.2.6", "3.4.1", "not-a-version"];
+
+        assert_eq!(resolve(&[], &available), Some("3.4.1"));
+        assert_eq!(
+            resolve(&parse_requirements("~> 3.2.0").unwrap(), &available),
+            Some("3.2.6")
+        );
+        assert_eq!(
+            resolve(&parse_requirements(">= 3.2, < 3.4").unwrap(), &available),
+            Some("3.3.5")
+        );
+        assert_eq!(resolve(&[req("2.7")], &available), None);
+    }
+}